@@ -0,0 +1,12 @@
+#![no_main]
+use cfdp_core::pdu::{Dissection, PDUEncode, PDUView, PDU};
+use libfuzzer_sys::fuzz_target;
+
+// decoding untrusted bytes may fail but never panic.
+fuzz_target!(|data: &[u8]| {
+    let _ = PDU::decode(&mut &data[..]);
+    if let Ok((_, len)) = PDUView::decode(data) {
+        assert!(len <= data.len());
+    }
+    let _ = Dissection::decode(data);
+});
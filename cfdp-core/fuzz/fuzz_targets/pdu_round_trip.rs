@@ -0,0 +1,23 @@
+#![no_main]
+use cfdp_core::pdu::{PDUEncode, PDUError, PDUView, PDU};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|expected: PDU| {
+    // fields too long for their length are refused rather than encoded wrapped around.
+    if let Err(error) = expected.checked_len() {
+        assert!(matches!(
+            error,
+            PDUError::LengthValueTooLong(..) | PDUError::DataFieldTooLarge(_)
+        ));
+        assert!(expected.encode_into(&mut vec![0_u8; 1 << 17]).is_err());
+        return;
+    }
+
+    let buffer = expected.clone().encode();
+    let recovered = PDU::decode(&mut buffer.as_slice()).expect("an encoded PDU decodes.");
+    assert_eq!(expected, recovered);
+
+    let (view, len) = PDUView::decode(&buffer).expect("an encoded PDU decodes in place.");
+    assert_eq!(buffer.len(), len);
+    assert_eq!(expected, PDU::from(view));
+});
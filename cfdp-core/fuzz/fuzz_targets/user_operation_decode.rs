@@ -0,0 +1,15 @@
+#![no_main]
+use cfdp_core::pdu::{PDUEncode, UserOperation};
+use libfuzzer_sys::fuzz_target;
+
+// user operations arrive as the text of a message to the user, prefixed by "cfdp".
+fuzz_target!(|data: &[u8]| {
+    let message = [b"cfdp", data].concat();
+    if let Ok(operation) = UserOperation::decode(&mut message.as_slice()) {
+        let buffer = operation.clone().encode();
+        assert_eq!(
+            operation,
+            UserOperation::decode(&mut buffer.as_slice()).expect("an encoded operation decodes.")
+        );
+    }
+});
@@ -1,20 +1,30 @@
-use std::{collections::HashMap, io::Read, time::Duration};
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::SystemTime;
 
-use camino::Utf8PathBuf;
 use num_traits::FromPrimitive;
+use thiserror::Error;
+#[cfg(feature = "std")]
 use tokio::sync::oneshot;
 
+#[cfg(feature = "std")]
+use crate::pdu::NakOrKeepAlive;
+
 use crate::{
     filestore::ChecksumType,
     pdu::{
         error::{PDUError, PDUResult},
+        io::Read,
         CRCFlag, Condition, DeliveryCode, EntityID, FaultHandlerAction, FileStatusCode,
-        FileStoreRequest, FileStoreResponse, MessageToUser, NakOrKeepAlive, PDUEncode,
+        FileStoreRequest, FileStoreResponse, MessageToUser, PDUEncode, ProtocolVersion,
         TransactionSeqNum, TransactionStatus, TransmissionMode,
     },
     transaction::{TransactionID, TransactionState},
+    Utf8PathBuf,
 };
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
 /// Necessary Configuration for a Put.Request operation
 pub struct PutRequest {
@@ -30,31 +40,150 @@ pub struct PutRequest {
     pub filestore_requests: Vec<FileStoreRequest>,
     /// Any Messages to user received either from the metadataPDU or as input
     pub message_to_user: Vec<MessageToUser>,
+    /// Whether the receiver must answer with a Finished PDU in unacknowledged mode,
+    /// the [default](EntityConfig::closure_requested) of the destination if not given.
+    pub closure_requested: Option<bool>,
+    /// Access to the transport relative to other transactions with the same destination,
+    /// the [default](EntityConfig::default_priority) of the destination if not given.
+    pub priority: Option<Priority>,
+}
+
+pub type PrimitiveResult<T> = Result<T, PrimitiveError>;
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Why the Daemon did not carry out a [UserPrimitive].
+pub enum PrimitiveError {
+    #[error("No transaction {0} is known to the Daemon.")]
+    /// The transaction never existed or its record is no longer kept.
+    UnknownTransaction(TransactionID),
+
+    #[error("Transaction {0} has already finished.")]
+    /// The transaction is no longer running so cannot be commanded.
+    Finished(TransactionID),
+
+    #[error("Transaction {0} is not a Send transaction.")]
+    /// Prompts can only be issued by the sender of a file.
+    NotSender(TransactionID),
+
+    #[error("No Transport available for EntityID: {0}.")]
+    /// A Put request named a destination no transport reaches.
+    NoRoute(EntityID),
+
+    #[error("FileStore error: {0}.")]
+    /// The source file of a Put request could not be read.
+    FileStore(String),
+
+    #[error("{1} is not supported by the protocol version spoken with entity {0}.")]
+    /// The remote entity speaks the legacy protocol, which cannot carry out the request.
+    Unsupported(EntityID, String),
+
+    #[error("The Metadata PDU to entity {0} would be {1} bytes but PDUs are limited to {2}.")]
+    /// The filenames, filestore requests and messages to user of a Put request
+    /// do not fit in a single Metadata PDU.
+    MetadataTooLarge(EntityID, usize, usize),
+
+    #[error("{1} of {2} bytes for entity {0} is longer than the {3} bytes it may hold.")]
+    /// A filename, filestore request or message to user of a Put request
+    /// is longer than its length field can express.
+    FieldTooLong(EntityID, String, usize, usize),
+
+    #[error("The Daemon is shutting down.")]
+    /// No new transactions are started, nor further shutdowns requested, once a shutdown begins.
+    ShuttingDown,
+
+    #[error("No store is configured to save suspended transactions in.")]
+    /// A [SuspendAll](ShutdownMode::SuspendAll) shutdown needs somewhere
+    /// to keep the transactions until they are resumed.
+    NoSuspendStore,
 }
 
+#[cfg(feature = "std")]
 #[derive(Debug)]
 /// Possible User Primitives sent from a end user application via the user primitive channel
+///
+/// Every primitive concerning a transaction carries a channel on which the Daemon replies
+/// with the outcome, or the [PrimitiveError] explaining why it was not carried out.
 pub enum UserPrimitive {
     /// Initiate a Put transaction with the specified [PutRequest] configuration.
     /// The channel is for the requesting entity to receive the unique transaction ID
     /// from the Daemon.
-    Put(PutRequest, oneshot::Sender<TransactionID>),
+    Put(PutRequest, oneshot::Sender<PrimitiveResult<TransactionID>>),
     /// Cancel the give transaction.
-    Cancel(TransactionID),
+    Cancel(TransactionID, oneshot::Sender<PrimitiveResult<()>>),
     /// Suspend operations of the given transaction.
-    Suspend(TransactionID),
+    Suspend(TransactionID, oneshot::Sender<PrimitiveResult<()>>),
     /// Resume operations of the given transaction.
-    Resume(TransactionID),
+    Resume(TransactionID, oneshot::Sender<PrimitiveResult<()>>),
     /// Report progress of the given transaction.
-    Report(TransactionID, oneshot::Sender<Report>),
+    Report(TransactionID, oneshot::Sender<PrimitiveResult<Report>>),
     /// Send the designated PromptPDU from the given transaction.
     /// This primitive is only valid for Send transactions
-    Prompt(TransactionID, NakOrKeepAlive),
+    Prompt(
+        TransactionID,
+        NakOrKeepAlive,
+        oneshot::Sender<PrimitiveResult<()>>,
+    ),
+    /// Replace the contact plan for the given remote entity.
+    /// An empty plan means the link is available at all times.
+    SetContactPlan(EntityID, Vec<ContactWindow>),
+    /// Force the link to the given remote entity up or down, overriding any contact plan.
+    SetLinkState(EntityID, LinkState),
+    /// Remove a previous [SetLinkState](UserPrimitive::SetLinkState) override
+    /// and follow the contact plan again.
+    ClearLinkState(EntityID),
+    /// Stop the Daemon according to the given [ShutdownMode].
+    /// The channel receives a [Report] of the final state of every transaction
+    /// which was running when the shutdown was requested.
+    Shutdown(ShutdownMode, oneshot::Sender<PrimitiveResult<Vec<Report>>>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// How the Daemon treats running transactions when asked to shut down.
+///
+/// In every mode no new transactions are started once the shutdown is requested.
+pub enum ShutdownMode {
+    /// Let active transactions finish on their own.
+    /// Any transaction still running once the deadline passes is abandoned.
+    Drain(Duration),
+    /// Suspend every transaction and save it to the Daemon's store of suspended transactions,
+    /// then stop. The next Daemon using the same store restores them, still suspended,
+    /// so the user may resume them. Refused if the Daemon has no such store.
+    SuspendAll,
+    /// Abandon every transaction immediately.
+    Abort,
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A period of time during which a remote entity can be reached.
+pub struct ContactWindow {
+    /// Beginning of the pass.
+    pub start: SystemTime,
+    /// End of the pass.
+    pub end: SystemTime,
+}
+#[cfg(feature = "std")]
+impl ContactWindow {
+    pub fn contains(&self, time: SystemTime) -> bool {
+        self.start <= time && time < self.end
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Availability of the link to a remote entity.
+///
+/// While a link is [Down](LinkState::Down) transactions with the entity hold their
+/// outgoing PDUs and their timers are frozen so no spurious faults are raised.
+pub enum LinkState {
+    Up,
+    Down,
 }
 
 /// Simple Status Report
 #[derive(Debug, Clone)]
 #[cfg_attr(test, derive(PartialEq))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Transaction status report
 pub struct Report {
     /// The unique ID of the transaction.
@@ -114,6 +243,7 @@ impl Report {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone)]
 /// Indication sent from a Transaction when [Metadata](crate::transaction::Metadata) has been received
 pub struct MetadataRecvIndication {
@@ -130,6 +260,7 @@ pub struct MetadataRecvIndication {
     pub user_messages: Vec<MessageToUser>,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone)]
 /// Indication of the amount of data received from a [FileDataPDU](crate::pdu::FileDataPDU)
 pub struct FileSegmentIndication {
@@ -141,6 +272,7 @@ pub struct FileSegmentIndication {
     pub length: u64,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone)]
 /// Indication sent when a transaction has finished.
 pub struct FinishedIndication {
@@ -156,6 +288,7 @@ pub struct FinishedIndication {
     pub filestore_responses: Vec<FileStoreResponse>,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone)]
 /// Indication that a transaction has been suspended
 pub struct SuspendIndication {
@@ -165,6 +298,7 @@ pub struct SuspendIndication {
     pub condition: Condition,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone)]
 /// Indication that a transaction has been resumed.
 pub struct ResumeIndication {
@@ -174,6 +308,7 @@ pub struct ResumeIndication {
     pub progress: u64,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone)]
 /// Indication a fault has occurred.
 pub struct FaultIndication {
@@ -185,6 +320,20 @@ pub struct FaultIndication {
     pub progress: u64,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Indication of a change in availability of the link to a remote entity.
+///
+/// Issued when a contact window opens or closes, the link state is set by the user,
+/// or the transport serving the entity fails or recovers.
+pub struct LinkIndication {
+    /// The remote entity whose link changed.
+    pub entity_id: EntityID,
+    /// The new state of the link.
+    pub state: LinkState,
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone)]
 /// Indications how the Daemon and Transactions relay information back to the User application.
 /// Indications are issued at necessary points in each Transaction's lifetime.
@@ -212,6 +361,60 @@ pub enum Indication {
     Fault(FaultIndication),
     /// An Abandon Fault has been initiated for the given transaction
     Abandon(FaultIndication),
+    /// The link to a remote entity has gone up or down.
+    Link(LinkIndication),
+}
+impl Indication {
+    /// The kind of this indication, without its contents.
+    pub fn kind(&self) -> IndicationKind {
+        match self {
+            Self::Transaction(_) => IndicationKind::Transaction,
+            Self::EoFSent(_) => IndicationKind::EoFSent,
+            Self::EoFRecv(_) => IndicationKind::EoFRecv,
+            Self::Finished(_) => IndicationKind::Finished,
+            Self::MetadataRecv(_) => IndicationKind::MetadataRecv,
+            Self::FileSegmentRecv(_) => IndicationKind::FileSegmentRecv,
+            Self::Suspended(_) => IndicationKind::Suspended,
+            Self::Resumed(_) => IndicationKind::Resumed,
+            Self::Report(_) => IndicationKind::Report,
+            Self::Fault(_) => IndicationKind::Fault,
+            Self::Abandon(_) => IndicationKind::Abandon,
+            Self::Link(_) => IndicationKind::Link,
+        }
+    }
+
+    /// The transaction this indication was issued for, if any.
+    pub fn transaction_id(&self) -> Option<TransactionID> {
+        match self {
+            Self::Transaction(id) | Self::EoFSent(id) | Self::EoFRecv(id) => Some(*id),
+            Self::Finished(finished) => Some(finished.id),
+            Self::MetadataRecv(metadata) => Some(metadata.id),
+            Self::FileSegmentRecv(segment) => Some(segment.id),
+            Self::Suspended(suspend) => Some(suspend.id),
+            Self::Resumed(resume) => Some(resume.id),
+            Self::Report(report) => Some(report.id),
+            Self::Fault(fault) | Self::Abandon(fault) => Some(fault.id),
+            Self::Link(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// The variants of an [Indication], used to select the indications of interest.
+pub enum IndicationKind {
+    Transaction,
+    EoFSent,
+    EoFRecv,
+    Finished,
+    MetadataRecv,
+    FileSegmentRecv,
+    Suspended,
+    Resumed,
+    Report,
+    Fault,
+    Abandon,
+    Link,
 }
 
 /// The way the Nak procedure is implemented is the following:
@@ -237,19 +440,93 @@ pub enum Indication {
 ///   the timer counter is reset to 0.
 /// - If the NAK timer expired more than the predefined limit (without any new data being received), the NakLimitReached
 ///   fault will be raised.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum NakProcedure {
     Immediate(Duration /* delay*/),
     Deferred(Duration /* delay */),
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// Token bucket parameters used to pace outgoing PDUs.
+///
+/// Up to `burst` bytes may be sent back to back,
+/// afterwards PDUs are released at the sustained `bytes_per_second` rate.
+pub struct RateLimit {
+    /// Sustained throughput in bytes per second.
+    pub bytes_per_second: u64,
+    /// Maximum number of bytes which may be sent in a single burst.
+    pub burst: u64,
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+/// How urgently a transaction is given the transport when other transactions
+/// with the same remote entity also have PDUs to send.
+pub struct Priority {
+    /// Classes are served strictly in order, the highest first.
+    pub class: u8,
+    /// Share of the transport given to the transaction relative to the others in its class.
+    pub weight: u16,
+}
+impl Default for Priority {
+    fn default() -> Self {
+        Self {
+            class: 0,
+            weight: 1,
+        }
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+/// How the transactions with a remote entity share the transport.
+pub enum Scheduling {
+    /// Transactions take turns sending PDUs in [Priority] order.
+    #[default]
+    Priority,
+    /// Every transaction sends as soon as it has a PDU ready.
+    Unscheduled,
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+/// Which revision of the protocol is spoken with a remote entity.
+pub enum VersionNegotiation {
+    /// Answer in the version the entity was last heard using,
+    /// [ProtocolVersion::Current] until it has been heard from.
+    #[default]
+    Auto,
+    /// Always speak [ProtocolVersion::Legacy].
+    Legacy,
+    /// Always speak [ProtocolVersion::Current].
+    Current,
+}
+impl VersionNegotiation {
+    /// The version to speak given the one the entity was last heard using.
+    pub fn resolve(self, heard: Option<ProtocolVersion>) -> ProtocolVersion {
+        match self {
+            Self::Auto => heard.unwrap_or_default(),
+            Self::Legacy => ProtocolVersion::Legacy,
+            Self::Current => ProtocolVersion::Current,
+        }
+    }
+}
+
 #[derive(Clone)]
 /// Configuration parameters for transactions which may change based on the receiving entity.
 pub struct EntityConfig {
     /// Mapping to decide how each fault type should be handled
-    pub fault_handler_override: HashMap<Condition, FaultHandlerAction>,
+    pub fault_handler_override: BTreeMap<Condition, FaultHandlerAction>,
     /// Maximum file size fragment this entity can receive
     pub file_size_segment: u16,
+    /// Longest PDU, header and CRC included, exchanged with this entity.
+    /// Without one PDUs are only limited by their encoding and the transport.
+    pub max_pdu_len: Option<usize>,
     // The number of timeouts before a fault is issued on a transaction
     pub default_transaction_max_count: u32,
     // number of seconds for inactivity timers to wait
@@ -266,4 +543,82 @@ pub struct EntityConfig {
     pub checksum_type: ChecksumType,
     // for recv transactions - when to send the NAKs (immediately when detected or after EOF)
     pub nak_procedure: NakProcedure,
+    /// Action taken on transactions with this entity when the transport serving it fails.
+    pub link_failure_action: FaultHandlerAction,
+    /// Bandwidth limit shared by all transactions with this entity.
+    pub rate_limit: Option<RateLimit>,
+    /// Bandwidth limit applied to each individual transaction with this entity.
+    pub transaction_rate_limit: Option<RateLimit>,
+    /// How transactions sending to this entity share the transport.
+    pub scheduling: Scheduling,
+    /// Priority of transactions sending to this entity which do not request one.
+    pub default_priority: Priority,
+    /// The protocol revision spoken with this entity.
+    pub protocol_version: VersionNegotiation,
+    /// Number of bytes entity IDs are encoded with in PDUs exchanged with this entity.
+    /// IDs are never narrowed below their own width.
+    pub id_width: Option<u8>,
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+    use super::*;
+
+    use crate::pdu::{CreateFileStatus, FileStoreAction, FileStoreStatus, VariableID};
+
+    #[test]
+    fn put_request_serde() {
+        let expected = PutRequest {
+            source_filename: "local/file.txt".into(),
+            destination_filename: "remote/file.txt".into(),
+            destination_entity_id: VariableID::new(2, 3).unwrap(),
+            transmission_mode: TransmissionMode::Acknowledged,
+            filestore_requests: vec![FileStoreRequest {
+                action_code: FileStoreAction::CreateDirectory,
+                first_filename: "remote".into(),
+                second_filename: "".into(),
+            }],
+            message_to_user: vec![MessageToUser {
+                message_text: "hello".as_bytes().to_vec(),
+            }],
+            closure_requested: Some(true),
+            priority: None,
+        };
+        let json = serde_json::to_value(&expected).unwrap();
+        assert_eq!("local/file.txt", json["source_filename"]);
+        assert_eq!(expected, serde_json::from_value(json).unwrap());
+    }
+
+    #[test]
+    fn indication_serde() {
+        let id = TransactionID(VariableID::from(1_u16), VariableID::from(7_u32));
+        let report = Report {
+            id,
+            state: TransactionState::Terminated,
+            status: TransactionStatus::Terminated,
+            condition: Condition::NoError,
+        };
+        let indication = Indication::Finished(FinishedIndication {
+            id,
+            report: report.clone(),
+            file_status: FileStatusCode::Retained,
+            delivery_code: DeliveryCode::Complete,
+            filestore_responses: vec![FileStoreResponse {
+                action_and_status: FileStoreStatus::CreateFile(CreateFileStatus::Successful),
+                first_filename: "file.txt".into(),
+                second_filename: "".into(),
+                filestore_message: "".into(),
+            }],
+        });
+
+        let json = serde_json::to_string(&indication).unwrap();
+        let recovered: Indication = serde_json::from_str(&json).unwrap();
+        assert_eq!(IndicationKind::Finished, recovered.kind());
+        assert_eq!(json, serde_json::to_string(&recovered).unwrap());
+
+        let Indication::Finished(finished) = recovered else {
+            unreachable!()
+        };
+        assert_eq!(report, finished.report);
+    }
 }
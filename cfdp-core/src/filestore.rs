@@ -1,3 +1,4 @@
+#[cfg(feature = "std")]
 use std::{
     fmt::Write as _Write,
     fs::{self, File, OpenOptions},
@@ -6,10 +7,14 @@ use std::{
     time::{SystemTime, SystemTimeError},
 };
 
+#[cfg(feature = "std")]
 use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
 use num_derive::FromPrimitive;
+#[cfg(feature = "std")]
 use pathdiff::diff_paths;
+#[cfg(feature = "std")]
 use tempfile::tempfile;
+#[cfg(feature = "std")]
 use thiserror::Error;
 
 pub use crate::pdu::{
@@ -23,6 +28,7 @@ pub use crate::pdu::{
 // This has been modified as follows:
 //   -  Does accept root dir `/` as the first entry.
 //   - Operators on Utf8Paths from the camino crate
+#[cfg(feature = "std")]
 fn normalize_path(path: &Utf8Path) -> Utf8PathBuf {
     let mut components = path.components().peekable();
     let mut ret = if let Some(c @ Utf8Component::Prefix(..)) = components.peek().cloned() {
@@ -54,7 +60,9 @@ fn normalize_path(path: &Utf8Path) -> Utf8PathBuf {
     ret
 }
 
+#[cfg(feature = "std")]
 pub type FileStoreResult<T> = Result<T, FileStoreError>;
+#[cfg(feature = "std")]
 #[derive(Error, Debug)]
 pub enum FileStoreError {
     #[error("File data storage error: {0}")]
@@ -71,6 +79,7 @@ pub enum FileStoreError {
 
 /// Defines any necessary actions a CFDP File Store implementation
 /// must perform. Assumes any FileStore has a root path it operates relative to.
+#[cfg(feature = "std")]
 pub trait FileStore {
     /// Returns the path to the target with the root path prepended.
     /// Used when manipulating the filesystem relative to the root path.
@@ -228,9 +237,11 @@ pub trait FileStore {
 
 /// Store the root path information for a FileStore implementation
 /// using built in rust [std::fs] interface.
+#[cfg(feature = "std")]
 pub struct NativeFileStore {
     root_path: Utf8PathBuf,
 }
+#[cfg(feature = "std")]
 impl NativeFileStore {
     pub fn new<P: AsRef<Utf8Path>>(root_path: P) -> Self {
         Self {
@@ -238,6 +249,7 @@ impl NativeFileStore {
         }
     }
 }
+#[cfg(feature = "std")]
 impl FileStore for NativeFileStore {
     fn get_native_path<P: AsRef<Utf8Path>>(&self, path: P) -> Utf8PathBuf {
         let path = path.as_ref();
@@ -326,8 +338,7 @@ impl FileStore for NativeFileStore {
 
     fn list_directory<P: AsRef<Utf8Path>>(&self, path: P) -> FileStoreResult<String> {
         let directory = self.get_native_path(path);
-        let mut directory_listing =
-            format!("isDirectory,name,size,modified\n",);
+        let mut directory_listing = "isDirectory,name,size,modified\n".to_string();
         let (mut dirs, mut files): (Vec<_>, Vec<_>) = fs::read_dir(&directory)?
             .filter_map(|entry| entry.ok())
             .partition(|entry| entry.path().is_dir());
@@ -396,6 +407,8 @@ impl FileStore for NativeFileStore {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Debug, Clone, Copy, FromPrimitive, PartialEq, Eq)]
 /// CCSDS enumerated checksum types
 pub enum ChecksumType {
@@ -405,63 +418,101 @@ pub enum ChecksumType {
     Null = 15,
 }
 
+/// Incremental computation of a [ChecksumType] over file data received in any order.
+///
+/// Each piece of file data is accounted for at its offset in the file,
+/// so segments may be added as they arrive rather than from the start of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checksum {
+    checksum_type: ChecksumType,
+    value: u32,
+}
+impl Checksum {
+    pub fn new(checksum_type: ChecksumType) -> Self {
+        Self {
+            checksum_type,
+            value: 0,
+        }
+    }
+
+    /// Account for file data beginning at the given offset into the file.
+    pub fn update(&mut self, offset: u64, data: &[u8]) {
+        match self.checksum_type {
+            ChecksumType::Null => {}
+            ChecksumType::Modular => {
+                // bytes before the next 4 byte boundary of the file.
+                let leading = ((4 - offset % 4) % 4) as usize;
+                let (unaligned, aligned) = data.split_at(leading.min(data.len()));
+                self.add_bytes(offset, unaligned);
+
+                // Take 4 bytes at a time from the buffer, convert to u32 and add
+                let mut iter = aligned.chunks_exact(4);
+                (&mut iter).for_each(|chunk| {
+                    // we can unwrap because we are guaranteed to have a length 4 slice
+                    self.value = self
+                        .value
+                        .wrapping_add(u32::from_be_bytes(chunk.try_into().unwrap()));
+                });
+                // a remainder starts on a boundary, it is padded with zeros.
+                self.add_bytes(0, iter.remainder());
+            }
+        }
+    }
+
+    /// The checksum of all the data added so far.
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+
+    // add each byte in its position within the 4 byte word it falls in.
+    fn add_bytes(&mut self, offset: u64, data: &[u8]) {
+        for (index, byte) in data.iter().enumerate() {
+            let shift = 8 * (3 - (offset + index as u64) % 4);
+            self.value = self.value.wrapping_add((*byte as u32) << shift);
+        }
+    }
+}
+
 /// Computes all pre-defined CCSDS checksums
+#[cfg(feature = "std")]
 pub trait FileChecksum {
     /// Given the input [ChecksumType] compute the appropriate algorithm.
     fn checksum(&mut self, checksum_type: ChecksumType) -> FileStoreResult<u32>;
 }
 
+#[cfg(feature = "std")]
 impl<R: Read + Seek + ?Sized> FileChecksum for R {
     fn checksum(&mut self, checksum_type: ChecksumType) -> FileStoreResult<u32> {
-        match checksum_type {
-            ChecksumType::Null => Ok(0_u32),
-            ChecksumType::Modular => {
-                let mut reader = BufReader::new(self);
-                // reset the file pointer to the beginning
-                reader.rewind()?;
-
-                let mut checksum: u32 = 0;
-                'outer: loop {
-                    // fill_buffer will return an empty slice when EoF is reached
-                    // on the internal Read instance
-                    let buffer = reader.fill_buf()?;
-
-                    if buffer.is_empty() {
-                        // if nothing was read break from the loop
-
-                        break 'outer;
-                    }
-
-                    // chunks_exact can some times be more efficient than chunks
-                    // we'll have to deal with the remainder anyway.
-                    // Take 4 bytes at a time from the buffer, convert to u32 and add
-                    let mut iter = buffer.chunks_exact(4);
-                    (&mut iter).for_each(|chunk| {
-                        // we can unwrap because we are guaranteed to have a length 4 slice
-                        checksum =
-                            checksum.wrapping_add(u32::from_be_bytes(chunk.try_into().unwrap()));
-                    });
-                    // handle any remainder by resizing to 4-bytes then adding
-                    if !iter.remainder().is_empty() {
-                        let mut remainder = iter.remainder().to_vec();
-                        remainder.resize(4, 0_u8);
-                        // we can unwrap because we are guaranteed to have a length 4 vector
-                        checksum = checksum
-                            .wrapping_add(u32::from_be_bytes(remainder.try_into().unwrap()));
-                    }
+        let mut checksum = Checksum::new(checksum_type);
+        if checksum_type == ChecksumType::Null {
+            return Ok(checksum.value());
+        }
 
-                    let len = buffer.len();
-                    // update the internal buffer to let it know
-                    // len bytes were consumed
-                    reader.consume(len);
-                }
-                Ok(checksum)
+        let mut reader = BufReader::new(self);
+        // reset the file pointer to the beginning
+        reader.rewind()?;
+
+        let mut offset = 0_u64;
+        loop {
+            // fill_buffer will return an empty slice when EoF is reached
+            // on the internal Read instance
+            let buffer = reader.fill_buf()?;
+            if buffer.is_empty() {
+                break;
             }
+            checksum.update(offset, buffer);
+
+            let len = buffer.len();
+            offset += len as u64;
+            // update the internal buffer to let it know
+            // len bytes were consumed
+            reader.consume(len);
         }
+        Ok(checksum.value())
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod test {
     use super::*;
 
@@ -687,14 +738,13 @@ mod test {
         }
 
         let expected_listing = format!(
-            "Listing for directory: {dir}\ntype,path,size,timestamp
+            "isDirectory,name,size,modified
 d,one,{s1},{t1}
 d,three,{s2},{t2}
 d,two,{s3},{t3}
 f,new.dat,{s4},{t4}
 f,test.txt,{s5},{t5}
 ",
-            dir = test_filestore.get_native_path(basepath),
             t1 = timings.get("one").unwrap(),
             t2 = timings.get("three").unwrap(),
             t3 = timings.get("two").unwrap(),
@@ -728,6 +778,24 @@ f,test.txt,{s5},{t5}
         Ok(())
     }
 
+    #[rstest]
+    fn checksum_out_of_order(
+        #[values(ChecksumType::Null, ChecksumType::Modular)] checksum_type: ChecksumType,
+        #[values(1, 3, 4, 7)] split: usize,
+    ) {
+        let file_data: Vec<u8> = vec![0x8a, 0x1b, 0x37, 0x44, 0x78, 0x91, 0xab, 0x03, 0x46, 0x12];
+
+        let expected_checksum = match &checksum_type {
+            ChecksumType::Null => 0_u32,
+            ChecksumType::Modular => 0x48BEE247_u32,
+        };
+
+        let mut checksum = Checksum::new(checksum_type);
+        checksum.update(split as u64, &file_data[split..]);
+        checksum.update(0, &file_data[..split]);
+        assert_eq!(expected_checksum, checksum.value());
+    }
+
     #[rstest]
     fn checksum_file(
         test_filestore: &NativeFileStore,
@@ -1,3 +1,14 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+// filenames are converted with `as_str` to work with both representations of a path.
+#![cfg_attr(not(feature = "std"), allow(clippy::redundant_as_str))]
+//! Protocol logic of the CCSDS File Delivery Protocol.
+//!
+//! The `std` feature, enabled by default, provides the native filestore and the channels
+//! the daemon primitives reply on. Without it the PDU codec, checksums and core types
+//! build for `no_std` targets with an allocator.
+
+extern crate alloc;
+
 /// Daemon related configurations and all [UserPrimitives](crate::daemon::UserPrimitive)
 pub mod daemon;
 
@@ -7,12 +18,22 @@ pub mod filestore;
 /// All pdu definitions.
 pub mod pdu;
 
+/// Bookkeeping of the received parts of a file.
+pub mod segments;
+
 /// Transaction related configurations and [Metadata](crate::transaction::Metadata)
 pub mod transaction;
 
 // re-exported for convenience and compatibility with the Primitives.
+#[cfg(feature = "std")]
 pub use tokio::sync::oneshot;
 
+#[cfg(feature = "std")]
+pub(crate) use camino::Utf8PathBuf;
+// without std, filenames are carried as plain UTF-8 strings.
+#[cfg(not(feature = "std"))]
+pub(crate) type Utf8PathBuf = alloc::string::String;
+
 // this import is necessary for the template macro in rstest_reuse as of v0.5.0
 #[cfg(test)]
 #[cfg_attr(test, allow(clippy::single_component_path_imports))]
@@ -1,22 +1,33 @@
-use log::error;
-use std::io::Read;
-
+mod dissect;
 pub(crate) mod error;
 mod fault_handler;
 mod filestore;
+#[cfg(any(test, feature = "arbitrary"))]
+mod generate;
 mod header;
+/// The byte sources PDUs are decoded from.
+pub mod io;
 mod ops;
 mod user_ops;
+mod view;
 
+pub use dissect::*;
 pub use fault_handler::*;
 pub use filestore::*;
 pub use header::*;
 pub use ops::*;
 pub use user_ops::*;
+pub use view::*;
 
 #[doc(inline)]
 pub use error::{PDUError, PDUResult};
 
+use alloc::{vec, vec::Vec};
+
+use self::io::Read;
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 /// All possible payloads of a cfdp PDU.
 pub enum PDUPayload {
@@ -43,7 +54,7 @@ impl PDUPayload {
     }
 
     /// Decodes from an input bytestream
-    pub fn decode<T: std::io::Read>(
+    pub fn decode<T: Read>(
         buffer: &mut T,
         pdu_type: PDUType,
         file_size_flag: FileSizeFlag,
@@ -62,6 +73,7 @@ impl PDUPayload {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 /// The Protocol Data Unit (PDU).
 ///
@@ -75,8 +87,11 @@ pub struct PDU {
 impl PDUEncode for PDU {
     type PDUType = Self;
 
+    /// Saturates when the PDU is too large to encode, see [PDU::checked_len].
     fn encoded_len(&self) -> u16 {
-        self.header.encoded_len() + self.payload.encoded_len(self.header.large_file_flag)
+        self.header
+            .encoded_len()
+            .saturating_add(self.payload.encoded_len(self.header.large_file_flag))
     }
 
     fn encode(self) -> Vec<u8> {
@@ -93,54 +108,21 @@ impl PDUEncode for PDU {
     }
 
     fn decode<T: Read>(buffer: &mut T) -> PDUResult<Self::PDUType> {
-        let header = PDUHeader::decode(buffer)?;
-
-        let mut remaining_msg = vec![0_u8; header.pdu_data_field_length as usize];
-
-        buffer.read_exact(remaining_msg.as_mut_slice())?;
-        let remaining_buffer = &mut remaining_msg.as_slice();
-
-        let payload = PDUPayload::decode(
-            remaining_buffer,
-            header.pdu_type.clone(),
-            header.large_file_flag,
-            header.segment_metadata_flag,
-        )?;
+        // collect the raw PDU as it arrives so the CRC is verified over the received bytes.
+        let mut message = vec![0_u8; FIXED_HEADER_LEN];
+        buffer.read_exact(&mut message)?;
+        let entity_id_len = usize::from((message[3] & 0x70) >> 4) + 1;
+        let sequence_num_len = usize::from(message[3] & 0x7) + 1;
+        // the data field length already counts the CRC.
+        let data_field_len = usize::from(u16::from_be_bytes([message[1], message[2]]));
+        message.resize(
+            FIXED_HEADER_LEN + 2 * entity_id_len + sequence_num_len + data_field_len,
+            0,
+        );
+        buffer.read_exact(&mut message[FIXED_HEADER_LEN..])?;
 
-        let received_pdu = Self { header, payload };
-
-        // TODO! Make this CRC check first for a faster failure mode
-        match &received_pdu.header.crc_flag {
-            CRCFlag::NotPresent => {}
-            CRCFlag::Present => {
-                let mut u16_buffer = [0_u8; 2];
-                buffer.read_exact(&mut u16_buffer)?;
-                let crc16 = u16::from_be_bytes(u16_buffer);
-                let tmp_buffer = {
-                    let input_pdu = received_pdu.clone();
-
-                    let mut temp = input_pdu.encode();
-                    // remove the crc from the temporary buffer
-                    temp.truncate(temp.len() - 2);
-                    temp
-                };
-                let crc = crc16_ibm_3740(tmp_buffer.as_slice());
-                match crc == crc16 {
-                    true => {}
-                    false => {
-                        error!(
-                            "CRC FAILURE, {}, {}, {}",
-                            crc,
-                            crc16,
-                            crc.overflowing_add(crc16).0
-                        );
-                        return Err(PDUError::CRCFailure(crc16, crc));
-                    }
-                }
-            }
-        }
-
-        Ok(received_pdu)
+        let (view, _) = PDUView::decode(&message)?;
+        Ok(view.into())
     }
 }
 
@@ -167,8 +149,12 @@ fn crc16(in_char: u16, crc: u16) -> u16 {
 mod test {
     use super::*;
 
-    use crate::{filestore::ChecksumType, pdu::header::NakOrKeepAlive};
+    use crate::{
+        filestore::ChecksumType,
+        pdu::{generate::from_raw, header::NakOrKeepAlive},
+    };
 
+    use proptest::{collection::vec, prelude::*};
     use rstest::rstest;
 
     #[rstest]
@@ -394,4 +380,117 @@ mod test {
         assert_eq!(expected, recovered);
         Ok(())
     }
+
+    #[cfg(feature = "serde")]
+    #[rstest]
+    #[case(PDUPayload::Directive(Operations::EoF(EndOfFile {
+        condition: Condition::FileChecksumFailure,
+        checksum: 123749_u32,
+        file_size: 7738949_u64,
+        fault_location: Some(VariableID::new(7, 3).unwrap()),
+    })))]
+    #[case(PDUPayload::Directive(Operations::Metadata(MetadataPDU {
+        closure_requested: false,
+        checksum_type: ChecksumType::Modular,
+        file_size: 55_u64,
+        source_filename: "the input filename".into(),
+        destination_filename: "the output filename".into(),
+        options: vec![
+            MetadataTLV::FlowLabel(FlowLabel { value: vec![1, 2, 3] }),
+            MetadataTLV::MessageToUser(MessageToUser::from(UserOperation::ProxyOperation(
+                ProxyOperation::ProxyPutRequest(ProxyPutRequest {
+                    destination_entity_id: VariableID::from(3_u16),
+                    source_filename: "remote/file".into(),
+                    destination_filename: "local/file".into(),
+                }),
+            ))),
+        ],
+    })))]
+    #[case(PDUPayload::FileData(FileDataPDU::Unsegmented(UnsegmentedFileData {
+        offset: 16_u64,
+        file_data: "test some information".as_bytes().to_vec(),
+    })))]
+    fn serde_round_trip(#[case] payload: PDUPayload) {
+        let expected = PDU {
+            header: PDUHeader {
+                version: U3::One,
+                pdu_type: match &payload {
+                    PDUPayload::Directive(_) => PDUType::FileDirective,
+                    PDUPayload::FileData(_) => PDUType::FileData,
+                },
+                direction: Direction::ToReceiver,
+                transmission_mode: TransmissionMode::Unacknowledged,
+                crc_flag: CRCFlag::Present,
+                large_file_flag: FileSizeFlag::Small,
+                pdu_data_field_length: payload.encoded_len(FileSizeFlag::Small),
+                segmentation_control: SegmentationControl::NotPreserved,
+                segment_metadata_flag: SegmentedData::NotPresent,
+                source_entity_id: VariableID::from(18_u16),
+                transaction_sequence_number: VariableID::new(7533, 5).unwrap(),
+                destination_entity_id: VariableID::from(23_u8),
+            },
+            payload,
+        };
+        let json = serde_json::to_string(&expected).unwrap();
+        let recovered: PDU = serde_json::from_str(&json).unwrap();
+        assert_eq!(expected, recovered);
+        // the width of an ID is kept so the PDU encodes to the same bytes.
+        assert_eq!(expected.encode(), recovered.encode());
+    }
+
+    proptest! {
+        #[test]
+        fn pdu_round_trip(raw in vec(any::<u8>(), 0..4096)) {
+            let expected: PDU = from_raw(&raw);
+            let mut written = vec![0_u8; 1 << 17];
+            let len = match expected.checked_len() {
+                Ok(len) => len,
+                Err(error) => {
+                    // fields too long for their length are refused, never encoded wrapped around.
+                    prop_assert!(
+                        matches!(error, PDUError::LengthValueTooLong(..) | PDUError::DataFieldTooLarge(_)),
+                        "{:?}", error
+                    );
+                    let refused = expected.encode_into(&mut written).unwrap_err();
+                    prop_assert_eq!(error.to_string(), refused.to_string());
+                    return Ok(());
+                }
+            };
+            let buffer = expected.clone().encode();
+            prop_assert_eq!(len, buffer.len());
+            prop_assert_eq!(expected.encoded_len() as usize + expected.header.crc_flag.encoded_len() as usize, buffer.len(), "{:?}", expected);
+
+            let recovered = PDU::decode(&mut buffer.as_slice()).unwrap();
+            prop_assert_eq!(&expected, &recovered);
+
+            prop_assert_eq!(buffer.len(), expected.encode_into(&mut written).unwrap());
+            prop_assert_eq!(buffer.as_slice(), &written[..len]);
+        }
+
+        #[test]
+        fn decode_arbitrary_bytes(raw in vec(any::<u8>(), 0..512)) {
+            let _ = PDU::decode(&mut raw.as_slice());
+            let _ = PDUView::decode(&raw);
+            let _ = Dissection::decode(&raw);
+        }
+
+        #[test]
+        fn decode_corrupted_pdu(
+            raw in vec(any::<u8>(), 0..4096),
+            corruption in vec((any::<usize>(), any::<u8>()), 1..4),
+            len in any::<usize>(),
+        ) {
+            let pdu: PDU = from_raw(&raw);
+            let mut buffer = pdu.encode();
+            for (index, byte) in corruption {
+                let index = index % buffer.len();
+                buffer[index] = byte;
+            }
+            buffer.truncate(len % (buffer.len() + 1));
+
+            let _ = PDU::decode(&mut buffer.as_slice());
+            let _ = PDUView::decode(&buffer);
+            let _ = Dissection::decode(&buffer);
+        }
+    }
 }
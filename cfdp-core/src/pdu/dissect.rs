@@ -0,0 +1,402 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt::{self, Debug, Display};
+
+use super::{
+    error::PDUError, FileDataPDU, MessageToUser, MetadataTLV, Operations, PDUEncode, PDUHeader,
+    PDUPayload, PDUView, UserOperation, VariableID, PDU,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// A labelled value of a dissected [PDU], compound values hold their parts as children.
+pub struct Field {
+    pub name: String,
+    /// Human readable rendering of the value, empty for pure groups.
+    pub value: String,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub children: Vec<Field>,
+}
+impl Field {
+    fn new(name: &str, value: impl Display) -> Self {
+        Self {
+            name: name.to_string(),
+            value: value.to_string(),
+            children: vec![],
+        }
+    }
+
+    fn debug(name: &str, value: impl Debug) -> Self {
+        Self::new(name, format!("{value:?}"))
+    }
+
+    fn group(name: &str, value: impl Display, children: Vec<Field>) -> Self {
+        Self {
+            children,
+            ..Self::new(name, value)
+        }
+    }
+
+    fn id(name: &str, id: &VariableID) -> Self {
+        Self::new(name, format!("{id} ({} byte(s))", id.width()))
+    }
+
+    fn bytes(name: &str, bytes: &[u8]) -> Self {
+        Self::new(name, format!("[{}] {}", bytes.len(), hex(bytes)))
+    }
+
+    fn message_to_user(msg: &MessageToUser) -> Self {
+        match UserOperation::decode(&mut msg.message_text.as_slice()) {
+            Ok(operation) => Self::debug("message_to_user", operation),
+            Err(_) => match core::str::from_utf8(&msg.message_text) {
+                Ok(text) => Self::debug("message_to_user", text),
+                Err(_) => Self::bytes("message_to_user", &msg.message_text),
+            },
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        write!(f, "{:indent$}{}:", "", self.name, indent = 2 * depth)?;
+        if !self.value.is_empty() {
+            write!(f, " {}", self.value)?;
+        }
+        for child in &self.children {
+            writeln!(f)?;
+            child.write(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+impl Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn optional_id(fields: &mut Vec<Field>, name: &str, id: &Option<VariableID>) {
+    if let Some(id) = id {
+        fields.push(Field::id(name, id))
+    }
+}
+
+impl PDUHeader {
+    /// Break the header down into its fields.
+    pub fn dissect(&self) -> Field {
+        Field::group(
+            "header",
+            "",
+            vec![
+                Field::new(
+                    "version",
+                    format!(
+                        "{} ({:?})",
+                        self.version.clone() as u8,
+                        self.protocol_version()
+                    ),
+                ),
+                Field::debug("pdu_type", &self.pdu_type),
+                Field::debug("direction", &self.direction),
+                Field::debug("transmission_mode", self.transmission_mode),
+                Field::debug("crc_flag", self.crc_flag),
+                Field::debug("large_file_flag", self.large_file_flag),
+                Field::new("pdu_data_field_length", self.pdu_data_field_length),
+                Field::debug("segmentation_control", self.segmentation_control),
+                Field::debug("segment_metadata_flag", self.segment_metadata_flag),
+                Field::id("source_entity_id", &self.source_entity_id),
+                Field::id(
+                    "transaction_sequence_number",
+                    &self.transaction_sequence_number,
+                ),
+                Field::id("destination_entity_id", &self.destination_entity_id),
+            ],
+        )
+    }
+}
+
+impl MetadataTLV {
+    fn dissect(&self) -> Field {
+        match self {
+            Self::FileStoreRequest(request) => Field::debug("filestore_request", request),
+            Self::FileStoreResponse(response) => Field::debug("filestore_response", response),
+            Self::MessageToUser(msg) => Field::message_to_user(msg),
+            Self::FaultHandlerOverride(handler) => Field::debug("fault_handler_override", handler),
+            Self::FlowLabel(label) => Field::bytes("flow_label", &label.value),
+            Self::EntityID(id) => Field::id("entity_id", id),
+        }
+    }
+}
+
+impl Operations {
+    fn dissect(&self) -> Field {
+        let (name, fields) = match self {
+            Self::EoF(eof) => {
+                let mut fields = vec![
+                    Field::debug("condition", eof.condition),
+                    Field::new("checksum", format!("0x{:08x}", eof.checksum)),
+                    Field::new("file_size", eof.file_size),
+                ];
+                optional_id(&mut fields, "fault_location", &eof.fault_location);
+                ("EoF", fields)
+            }
+            Self::Finished(finished) => {
+                let mut fields = vec![
+                    Field::debug("condition", finished.condition),
+                    Field::debug("delivery_code", finished.delivery_code),
+                    Field::debug("file_status", finished.file_status),
+                ];
+                fields.extend(
+                    finished
+                        .filestore_response
+                        .iter()
+                        .map(|response| Field::debug("filestore_response", response)),
+                );
+                optional_id(&mut fields, "fault_location", &finished.fault_location);
+                ("Finished", fields)
+            }
+            Self::Ack(ack) => (
+                "Ack",
+                vec![
+                    Field::debug("directive", &ack.directive),
+                    Field::debug("directive_subtype_code", &ack.directive_subtype_code),
+                    Field::debug("condition", ack.condition),
+                    Field::debug("transaction_status", ack.transaction_status),
+                ],
+            ),
+            Self::Metadata(metadata) => (
+                "Metadata",
+                vec![
+                    Field::new("closure_requested", metadata.closure_requested),
+                    Field::debug("checksum_type", metadata.checksum_type),
+                    Field::new("file_size", metadata.file_size),
+                    Field::debug("source_filename", &metadata.source_filename),
+                    Field::debug("destination_filename", &metadata.destination_filename),
+                    Field::group(
+                        "options",
+                        metadata.options.len(),
+                        metadata.options.iter().map(MetadataTLV::dissect).collect(),
+                    ),
+                ],
+            ),
+            Self::Nak(nak) => (
+                "Nak",
+                vec![
+                    Field::new("start_of_scope", nak.start_of_scope),
+                    Field::new("end_of_scope", nak.end_of_scope),
+                    Field::group(
+                        "segment_requests",
+                        nak.segment_requests.len(),
+                        nak.segment_requests
+                            .iter()
+                            .map(|form| {
+                                Field::new(
+                                    "segment",
+                                    format!("{}..{}", form.start_offset, form.end_offset),
+                                )
+                            })
+                            .collect(),
+                    ),
+                ],
+            ),
+            Self::Prompt(prompt) => (
+                "Prompt",
+                vec![Field::debug("nak_or_keep_alive", prompt.nak_or_keep_alive)],
+            ),
+            Self::KeepAlive(keep_alive) => (
+                "KeepAlive",
+                vec![Field::new("progress", keep_alive.progress)],
+            ),
+        };
+        Field::group("directive", name, fields)
+    }
+}
+
+impl FileDataPDU {
+    fn dissect(&self) -> Field {
+        match self {
+            Self::Unsegmented(data) => Field::group(
+                "file_data",
+                "Unsegmented",
+                vec![
+                    Field::new("offset", data.offset),
+                    Field::new("length", data.file_data.len()),
+                ],
+            ),
+            Self::Segmented(data) => Field::group(
+                "file_data",
+                "Segmented",
+                vec![
+                    Field::debug("record_continuation_state", &data.record_continuation_state),
+                    Field::bytes("segment_metadata", &data.segment_metadata),
+                    Field::new("offset", data.offset),
+                    Field::new("length", data.file_data.len()),
+                ],
+            ),
+        }
+    }
+}
+
+impl PDU {
+    /// Break the PDU down into labelled fields for inspection.
+    ///
+    /// Messages to the user holding a [UserOperation] are shown decoded.
+    pub fn dissect(&self) -> Vec<Field> {
+        let payload = match &self.payload {
+            PDUPayload::Directive(operation) => operation.dissect(),
+            PDUPayload::FileData(data) => data.dissect(),
+        };
+        vec![self.header.dissect(), payload]
+    }
+}
+impl Display for PDU {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, field) in self.dissect().iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{field}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+/// The outcome of dissecting one PDU from raw bytes.
+pub struct Dissection {
+    /// Bytes the PDU occupies according to its header, never more than were given.
+    pub len: usize,
+    /// The fields of the PDU, only the header if the remainder could not be decoded.
+    pub fields: Vec<Field>,
+    /// Why the PDU could not be decoded in full,
+    /// e.g. [PDUError::CRCFailure] holds the expected and received checksums.
+    pub error: Option<PDUError>,
+}
+impl Dissection {
+    /// Dissect the PDU at the start of the buffer.
+    ///
+    /// Unlike [PDUView::decode] the header is still reported when the payload
+    /// or the CRC is invalid, and the length lets a caller skip to the next PDU.
+    pub fn decode(buffer: &[u8]) -> Self {
+        let header = match PDUHeader::decode(&mut &buffer[..]) {
+            Ok(header) => header,
+            Err(error) => {
+                return Self {
+                    len: buffer.len(),
+                    fields: vec![],
+                    error: Some(error),
+                }
+            }
+        };
+        match PDUView::decode(buffer) {
+            Ok((view, len)) => Self {
+                len,
+                fields: PDU::from(view).dissect(),
+                error: None,
+            },
+            Err(error) => {
+                let len = header.encoded_len() as usize
+                    + header.pdu_data_field_length as usize
+                    + header.crc_flag.encoded_len() as usize;
+                Self {
+                    len: len.min(buffer.len()),
+                    fields: vec![header.dissect()],
+                    error: Some(error),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{
+        filestore::ChecksumType,
+        pdu::{
+            CRCFlag, Direction, FileSizeFlag, MetadataPDU, PDUType, ProxyOperation,
+            ProxyPutRequest, SegmentationControl, SegmentedData, TransmissionMode, U3,
+        },
+    };
+
+    fn metadata_pdu(crc_flag: CRCFlag) -> PDU {
+        let payload = PDUPayload::Directive(Operations::Metadata(MetadataPDU {
+            closure_requested: true,
+            checksum_type: ChecksumType::Modular,
+            file_size: 55,
+            source_filename: "local/file.txt".into(),
+            destination_filename: "remote/file.txt".into(),
+            options: vec![MetadataTLV::MessageToUser(MessageToUser::from(
+                UserOperation::ProxyOperation(ProxyOperation::ProxyPutRequest(ProxyPutRequest {
+                    destination_entity_id: VariableID::from(7_u8),
+                    source_filename: "a".into(),
+                    destination_filename: "b".into(),
+                })),
+            ))],
+        }));
+        PDU {
+            header: PDUHeader {
+                version: U3::One,
+                pdu_type: PDUType::FileDirective,
+                direction: Direction::ToReceiver,
+                transmission_mode: TransmissionMode::Acknowledged,
+                crc_flag,
+                large_file_flag: FileSizeFlag::Small,
+                pdu_data_field_length: payload.encoded_len(FileSizeFlag::Small),
+                segmentation_control: SegmentationControl::NotPreserved,
+                segment_metadata_flag: SegmentedData::NotPresent,
+                source_entity_id: VariableID::from(1_u16),
+                transaction_sequence_number: VariableID::from(3_u8),
+                destination_entity_id: VariableID::from(2_u16),
+            },
+            payload,
+        }
+    }
+
+    #[test]
+    fn display() {
+        let text = metadata_pdu(CRCFlag::NotPresent).to_string();
+        assert!(text.starts_with("header:\n  version: 1 (Current)\n"));
+        assert!(text.contains("\n  source_entity_id: 1 (2 byte(s))\n"));
+        assert!(text.contains("\ndirective: Metadata\n  closure_requested: true\n"));
+        assert!(
+            text.contains("\n  options: 1\n    message_to_user: ProxyOperation(ProxyPutRequest(")
+        );
+    }
+
+    #[test]
+    fn dissect_bytes() {
+        let pdu = metadata_pdu(CRCFlag::Present);
+        let mut buffer = pdu.clone().encode();
+        let len = buffer.len();
+        buffer.extend([0xde, 0xad]);
+
+        let dissection = Dissection::decode(&buffer);
+        assert_eq!(len, dissection.len);
+        assert!(dissection.error.is_none());
+        assert_eq!(pdu.dissect(), dissection.fields);
+    }
+
+    #[test]
+    fn crc_failure() {
+        let mut buffer = metadata_pdu(CRCFlag::Present).encode();
+        let len = buffer.len();
+        buffer[len - 1] ^= 0xff;
+
+        let dissection = Dissection::decode(&buffer);
+        assert_eq!(len, dissection.len);
+        assert!(matches!(dissection.error, Some(PDUError::CRCFailure(_, _))));
+        assert_eq!(1, dissection.fields.len());
+        assert_eq!("header", dissection.fields[0].name);
+    }
+}
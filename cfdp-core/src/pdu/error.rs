@@ -1,7 +1,13 @@
-use std::string::FromUtf8Error;
+use alloc::{
+    string::{FromUtf8Error, String},
+    vec::Vec,
+};
 use thiserror::Error;
 
-use super::filestore::FileStoreAction;
+use super::{
+    filestore::FileStoreAction,
+    ops::{MetadataTLVFieldCode, PDUDirective},
+};
 
 pub type PDUResult<T> = Result<T, PDUError>;
 #[derive(Error, Debug)]
@@ -88,6 +94,14 @@ pub enum PDUError {
     /// Unkonwn CCSDS packet version.
     InvalidVersion(u8),
 
+    #[error("{0:?} PDUs are not part of the legacy CFDP protocol.")]
+    /// A legacy (version `000`) PDU carried a directive introduced by later revisions.
+    LegacyDirective(PDUDirective),
+
+    #[error("{0} TLVs are not allowed in legacy Metadata.")]
+    /// A legacy (version `000`) Metadata PDU carried an option introduced by later revisions.
+    LegacyTLV(MetadataTLVFieldCode),
+
     #[error("Invalid PDU Type {0:}.")]
     /// Received an unkown [PDUType](crate::pdu::PDUType) value.
     InvalidPDUType(u8),
@@ -104,18 +118,44 @@ pub enum PDUError {
     /// Unkonwn value for [SegmentedData](crate::pdu::SegmentedData) flag.
     InvalidSegmentMetadataFlag(u8),
 
+    #[error("PDU data field length {0:} is too short to hold the CRC.")]
+    /// The header announced a CRC but a data field too short to contain it.
+    InvalidDataFieldLength(u16),
+
+    #[error("Invalid Record Continuation State {0:}.")]
+    /// Unknown [RecordContinuationState](crate::pdu::RecordContinuationState) value.
+    InvalidRecordContinuationState(u8),
+
     #[error("CRC Failure on PDU. Expected 0x{0:X} Receieved 0x{1:X}")]
     /// PDU crc16 failure
     CRCFailure(u16, u16),
 
+    #[error("PDU requires {0:} bytes but the buffer holds {1:}.")]
+    /// The buffer is too short to hold the PDU being decoded or encoded.
+    BufferTooShort(usize, usize),
+
+    #[error("{0} of {1:} bytes is longer than the {2:} bytes its length field can express.")]
+    /// A length-value field, or the segment metadata of file data,
+    /// is too long to be encoded without corrupting its length.
+    LengthValueTooLong(&'static str, usize, usize),
+
+    #[error("PDU data field of {0:} bytes does not fit its 16 bit length.")]
+    /// The payload, and CRC if present, is longer than the header can announce.
+    DataFieldTooLarge(usize),
+
+    #[cfg(feature = "std")]
     #[error("Error Reading PDU Buffer. {0:}")]
     /// [std::io::Error] occurred when reading PDU from input byte stream.
     ReadError(#[from] std::io::Error),
 
-    #[error("Bad length for Variable Identifier (not a power a of 2) {0:}.")]
-    /// Length of the ID variable identifier was not a power of 2.
+    #[error("Bad length for Variable Identifier (not between 1 and 8 bytes) {0:}.")]
+    /// Length of the ID variable identifier was not between 1 and 8 bytes.
     UnknownIDLength(u8),
 
+    #[error("Variable Identifier {0:} does not fit in {1:} byte(s).")]
+    /// The value of an ID is too large for the requested width.
+    IDOutOfRange(u64, u8),
+
     #[error("Unable to decode filename. {0}")]
     /// Error occurred converting bytes to UTF-8 compliant filename.
     InvalidFileName(#[from] FromUtf8Error),
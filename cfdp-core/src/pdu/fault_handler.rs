@@ -1,4 +1,5 @@
-use std::{io::Read, str::FromStr};
+use alloc::{vec, vec::Vec};
+use core::str::FromStr;
 
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
@@ -6,9 +7,11 @@ use num_traits::FromPrimitive;
 use super::{
     error::{PDUError, PDUResult},
     header::PDUEncode,
+    io::Read,
 };
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 /// Available actions which can be taken when a fault has occurred.
 pub enum FaultHandlerAction {
@@ -38,6 +41,8 @@ impl FromStr for FaultHandlerAction {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Debug, Clone, PartialEq, Eq, FromPrimitive)]
 /// Fault Handler Codes defined by CCSDS
 /// Values of 0b0000 and 0b0101-0b1111 are reserved as of 2022.
@@ -47,6 +52,8 @@ pub enum HandlerCode {
     IgnoreError = 0b0011,
     AbandonTransaction = 0b0100,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct FaultHandlerOverride {
     pub fault_handler_code: HandlerCode,
@@ -1,15 +1,17 @@
-use camino::Utf8PathBuf;
+use alloc::{string::String, vec, vec::Vec};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
-use std::io::Read;
-
 use super::{
     error::{PDUError, PDUResult},
-    header::{read_length_value_pair, PDUEncode},
+    header::{check_length_value, read_length_value_pair, PDUEncode, MAX_LV_LEN},
+    io::Read,
 };
+use crate::Utf8PathBuf;
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Debug, Clone, PartialEq, Eq, FromPrimitive)]
 /// Actions which can be take via a FileStore Request to a CFDP entity.
 pub enum FileStoreAction {
@@ -34,6 +36,8 @@ pub enum FileStoreAction {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
 /// Resulting status from the creating a file.
 pub enum CreateFileStatus {
@@ -43,6 +47,8 @@ pub enum CreateFileStatus {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
 /// Resulting status from the deleting a file.
 pub enum DeleteFileStatus {
@@ -53,6 +59,8 @@ pub enum DeleteFileStatus {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
 /// Resulting status from the renaming a file.
 pub enum RenameStatus {
@@ -64,6 +72,8 @@ pub enum RenameStatus {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
 /// Resulting status from the appending file B onto file A.
 pub enum AppendStatus {
@@ -74,6 +84,8 @@ pub enum AppendStatus {
     NotPerformed = 0b1111,
 }
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
 /// Resulting status from the Replacing the contents of file A with those of file B.
 pub enum ReplaceStatus {
@@ -85,6 +97,8 @@ pub enum ReplaceStatus {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
 /// Resulting status from the creating a directory.
 pub enum CreateDirectoryStatus {
@@ -94,6 +108,8 @@ pub enum CreateDirectoryStatus {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
 /// Resulting status from the removing a directory.
 pub enum RemoveDirectoryStatus {
@@ -104,6 +120,8 @@ pub enum RemoveDirectoryStatus {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
 /// Resulting status from performing a Deny action.
 pub enum DenyStatus {
@@ -113,6 +131,8 @@ pub enum DenyStatus {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 /// All possible results of filestore actions and their associated status codes.
 pub enum FileStoreStatus {
@@ -249,16 +269,36 @@ impl FileStoreStatus {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Debug, Clone, PartialEq, Eq)]
 /// A Request to perform the designated action on the given file(s).
 pub struct FileStoreRequest {
     pub action_code: FileStoreAction,
     /// LV type field, omitted when length 0.
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::filename))]
     pub first_filename: Utf8PathBuf,
     /// LV type field, omitted when length 0.
     /// Only has non-zero length for rename, append, and replace actions.
+    #[cfg_attr(feature = "serde", serde(default))]
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::filename))]
     pub second_filename: Utf8PathBuf,
 }
+impl FileStoreRequest {
+    /// Fail if a filename is longer than its length field can express.
+    pub(crate) fn check_lengths(&self) -> PDUResult<()> {
+        check_length_value(
+            "Filestore request first filename",
+            self.first_filename.as_str().len(),
+            MAX_LV_LEN,
+        )?;
+        check_length_value(
+            "Filestore request second filename",
+            self.second_filename.as_str().len(),
+            MAX_LV_LEN,
+        )
+    }
+}
 impl PDUEncode for FileStoreRequest {
     type PDUType = Self;
 
@@ -309,16 +349,21 @@ impl PDUEncode for FileStoreRequest {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Debug, Clone, PartialEq, Eq)]
 /// Results of performing the action in a [FileStoreRequest].
 pub struct FileStoreResponse {
     pub action_and_status: FileStoreStatus,
     /// LV type field, omitted when length 0
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::filename))]
     pub first_filename: Utf8PathBuf,
     /// LV type field, omitted when length 0
     /// Only has non-zero length for rename, append, and replace actions.
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::filename))]
     pub second_filename: Utf8PathBuf,
     /// LV type field, omitted when length 0
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::filestore_message))]
     pub filestore_message: Vec<u8>,
 }
 impl FileStoreResponse {
@@ -330,6 +375,25 @@ impl FileStoreResponse {
             filestore_message: vec![],
         }
     }
+
+    /// Fail if a filename or the message is longer than its length field can express.
+    pub(crate) fn check_lengths(&self) -> PDUResult<()> {
+        check_length_value(
+            "Filestore response first filename",
+            self.first_filename.as_str().len(),
+            MAX_LV_LEN,
+        )?;
+        check_length_value(
+            "Filestore response second filename",
+            self.second_filename.as_str().len(),
+            MAX_LV_LEN,
+        )?;
+        check_length_value(
+            "Filestore message",
+            self.filestore_message.len(),
+            MAX_LV_LEN,
+        )
+    }
 }
 impl PDUEncode for FileStoreResponse {
     type PDUType = Self;
@@ -0,0 +1,212 @@
+//! Generators of arbitrary PDUs for property tests and fuzzing.
+//!
+//! Fields the encoding ties together are kept consistent, but variable length fields
+//! may run past the length their encoding can express: such values must be refused
+//! when encoding, every other generated value encodes and decodes back to itself.
+use alloc::{string::String, vec::Vec};
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use super::{
+    header::{MAX_LV_LEN, MAX_SEGMENT_METADATA_LEN},
+    ACKSubDirective, CRCFlag, Condition, DeliveryCode, Direction, EndOfFile, FileDataPDU,
+    FileSizeFlag, FileStatusCode, Finished, Operations, PDUDirective, PDUHeader, PDUPayload,
+    PDUType, PositiveAcknowledgePDU, SegmentationControl, SegmentedData, TransactionStatus,
+    TransmissionMode, VariableID, PDU, U3,
+};
+use crate::Utf8PathBuf;
+
+/// How far a variable length field may run past the longest length it can express.
+const OVERRUN_LEN: usize = 8;
+
+/// Most elements generated for a repeated field.
+const REPEATED_LEN: usize = 16;
+
+/// Longest file data generated, one byte more than the data field length can express.
+const FILE_DATA_LEN: usize = u16::MAX as usize + 1;
+
+fn bytes(u: &mut Unstructured, max_len: usize) -> Result<Vec<u8>> {
+    let len = u.int_in_range(0..=max_len)?.min(u.len());
+    Ok(u.bytes(len)?.to_vec())
+}
+
+/// A UTF-8 string of at most `max_len` bytes.
+fn string(u: &mut Unstructured, max_len: usize) -> Result<String> {
+    let mut string = String::arbitrary(u)?;
+    let mut len = string.len().min(max_len);
+    while !string.is_char_boundary(len) {
+        len -= 1;
+    }
+    string.truncate(len);
+    Ok(string)
+}
+
+/// A filename, which may be too long for the single byte length it is encoded with.
+pub(crate) fn filename(u: &mut Unstructured) -> Result<Utf8PathBuf> {
+    Ok(string(u, MAX_LV_LEN + OVERRUN_LEN)?.into())
+}
+
+pub(crate) fn filestore_message(u: &mut Unstructured) -> Result<Vec<u8>> {
+    bytes(u, MAX_LV_LEN + OVERRUN_LEN)
+}
+
+/// The value of a length-value field, possibly too long for its length.
+pub(crate) fn lv_value(u: &mut Unstructured) -> Result<Vec<u8>> {
+    bytes(u, MAX_LV_LEN + OVERRUN_LEN)
+}
+
+/// Segment metadata, possibly too long for the six bits of its length.
+pub(crate) fn segment_metadata(u: &mut Unstructured) -> Result<Vec<u8>> {
+    bytes(u, MAX_SEGMENT_METADATA_LEN + OVERRUN_LEN)
+}
+
+pub(crate) fn file_data(u: &mut Unstructured) -> Result<Vec<u8>> {
+    bytes(u, FILE_DATA_LEN)
+}
+
+pub(crate) fn few<'a, T: Arbitrary<'a>>(u: &mut Unstructured<'a>) -> Result<Vec<T>> {
+    let len = u.int_in_range(0..=REPEATED_LEN)?;
+    (0..len).map(|_| T::arbitrary(u)).collect()
+}
+
+/// Build a value from raw bytes, as handed out by proptest or a fuzzer.
+#[cfg(test)]
+pub(crate) fn from_raw<T: for<'a> Arbitrary<'a>>(raw: &[u8]) -> T {
+    T::arbitrary(&mut Unstructured::new(raw)).expect("generators accept any input.")
+}
+
+impl<'a> Arbitrary<'a> for VariableID {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let width = u.int_in_range(1..=8)?;
+        let value = u64::arbitrary(u)? >> (64 - 8 * u32::from(width));
+        Ok(Self::new(value, width).expect("the value is masked to the width."))
+    }
+}
+
+/// A fault location is only carried when the condition is an error.
+fn fault_location(u: &mut Unstructured, condition: &Condition) -> Result<Option<VariableID>> {
+    match condition {
+        Condition::NoError => Ok(None),
+        _ => Ok(Some(VariableID::arbitrary(u)?)),
+    }
+}
+
+impl<'a> Arbitrary<'a> for EndOfFile {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let condition = Condition::arbitrary(u)?;
+        Ok(Self {
+            fault_location: fault_location(u, &condition)?,
+            condition,
+            checksum: u.arbitrary()?,
+            file_size: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Finished {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let condition = Condition::arbitrary(u)?;
+        // the fault location is optional in a Finished PDU.
+        let fault_location = match bool::arbitrary(u)? {
+            true => fault_location(u, &condition)?,
+            false => None,
+        };
+        Ok(Self {
+            condition,
+            delivery_code: DeliveryCode::arbitrary(u)?,
+            file_status: FileStatusCode::arbitrary(u)?,
+            filestore_response: few(u)?,
+            fault_location,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for PositiveAcknowledgePDU {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        // only EoF and Finished PDUs are acknowledged.
+        let (directive, directive_subtype_code) = match bool::arbitrary(u)? {
+            true => (PDUDirective::EoF, ACKSubDirective::Other),
+            false => (PDUDirective::Finished, ACKSubDirective::Finished),
+        };
+        Ok(Self {
+            directive,
+            directive_subtype_code,
+            condition: Condition::arbitrary(u)?,
+            transaction_status: TransactionStatus::arbitrary(u)?,
+        })
+    }
+}
+
+/// The largest file offset or size in the payload.
+fn largest_offset(payload: &PDUPayload) -> u64 {
+    match payload {
+        PDUPayload::Directive(Operations::EoF(eof)) => eof.file_size,
+        PDUPayload::Directive(Operations::Metadata(metadata)) => metadata.file_size,
+        PDUPayload::Directive(Operations::Nak(nak)) => nak
+            .segment_requests
+            .iter()
+            .flat_map(|request| [request.start_offset, request.end_offset])
+            .chain([nak.start_of_scope, nak.end_of_scope])
+            .max()
+            .unwrap_or_default(),
+        PDUPayload::Directive(Operations::KeepAlive(keep_alive)) => keep_alive.progress,
+        PDUPayload::Directive(_) => 0,
+        PDUPayload::FileData(FileDataPDU::Unsegmented(data)) => data.offset,
+        PDUPayload::FileData(FileDataPDU::Segmented(data)) => data.offset,
+    }
+}
+
+impl<'a> Arbitrary<'a> for PDU {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let payload = PDUPayload::arbitrary(u)?;
+        // small files only have room for 32 bit offsets.
+        let large_file_flag = match largest_offset(&payload) > u32::MAX.into() {
+            true => FileSizeFlag::Large,
+            false => FileSizeFlag::arbitrary(u)?,
+        };
+        // legacy PDUs must follow the rules of the earlier revision to read back the same.
+        let version = match U3::arbitrary(u)? {
+            U3::Zero if large_file_flag == FileSizeFlag::Small => match &payload {
+                PDUPayload::Directive(operation) => match operation.clone().to_legacy() {
+                    Ok(legacy) if &legacy == operation => U3::Zero,
+                    _ => U3::One,
+                },
+                PDUPayload::FileData(_) => U3::Zero,
+            },
+            U3::Zero => U3::One,
+            version => version,
+        };
+        let (pdu_type, segment_metadata_flag) = match &payload {
+            PDUPayload::Directive(_) => (PDUType::FileDirective, SegmentedData::arbitrary(u)?),
+            PDUPayload::FileData(FileDataPDU::Unsegmented(_)) => {
+                (PDUType::FileData, SegmentedData::NotPresent)
+            }
+            PDUPayload::FileData(FileDataPDU::Segmented(_)) => {
+                (PDUType::FileData, SegmentedData::Present)
+            }
+        };
+
+        // both entity IDs are read back with the width of the wider one.
+        let source_entity_id = VariableID::arbitrary(u)?;
+        let destination_entity_id = VariableID::arbitrary(u)?;
+        let id_width = source_entity_id.width().max(destination_entity_id.width());
+
+        Ok(Self {
+            header: PDUHeader {
+                version,
+                pdu_type,
+                direction: Direction::arbitrary(u)?,
+                transmission_mode: TransmissionMode::arbitrary(u)?,
+                crc_flag: CRCFlag::arbitrary(u)?,
+                large_file_flag,
+                pdu_data_field_length: payload.encoded_len(large_file_flag),
+                segmentation_control: SegmentationControl::arbitrary(u)?,
+                segment_metadata_flag,
+                source_entity_id: source_entity_id.widen(id_width),
+                transaction_sequence_number: u.arbitrary()?,
+                destination_entity_id: destination_entity_id.widen(id_width),
+            },
+            payload,
+        })
+    }
+}
@@ -1,15 +1,18 @@
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
-use std::io::Read;
+use alloc::{vec, vec::Vec};
 
 use super::{
     error::{PDUError, PDUResult},
+    io::Read,
     VariableID,
 };
 
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, FromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, FromPrimitive)]
 /// All possible conditions a transaction may be in
 pub enum Condition {
     /// No errors occurred yet during the transaction.
@@ -44,6 +47,8 @@ pub enum Condition {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq, FromPrimitive)]
 /// A 3 bit integer used in versioning to limit possible values.
 pub enum U3 {
@@ -57,7 +62,32 @@ pub enum U3 {
     Seven = 0b111,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+/// The revision of the CFDP protocol a PDU is written in.
+pub enum ProtocolVersion {
+    /// CFDP version 1 (CCSDS 727.0-B-4 and earlier), version number `000` in the header.
+    ///
+    /// It knows no Keep Alive or Prompt PDUs, the Metadata carries neither a closure
+    /// request nor a checksum type, and files are limited to 32 bit sizes.
+    Legacy,
+    /// CFDP version 2 (CCSDS 727.0-B-5), version number `001` in the header.
+    #[default]
+    Current,
+}
+impl From<ProtocolVersion> for U3 {
+    fn from(version: ProtocolVersion) -> Self {
+        match version {
+            ProtocolVersion::Legacy => U3::Zero,
+            ProtocolVersion::Current => U3::One,
+        }
+    }
+}
+
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq, FromPrimitive)]
 /// A flag to differentiate the payalod type of the PDU.
 pub enum PDUType {
@@ -68,6 +98,8 @@ pub enum PDUType {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq, FromPrimitive)]
 /// The direction in which this PDU is heading.
 pub enum Direction {
@@ -76,6 +108,8 @@ pub enum Direction {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
 /// The transmission mode of this transaction.
 pub enum TransmissionMode {
@@ -101,6 +135,8 @@ impl PDUEncode for TransmissionMode {
     }
 }
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq, FromPrimitive)]
 /// Store and Forward trace control level.
 pub enum TraceControl {
@@ -111,6 +147,8 @@ pub enum TraceControl {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
 /// Flag determining the presence of a CRC appended to a PDU.
 pub enum CRCFlag {
@@ -118,7 +156,19 @@ pub enum CRCFlag {
     Present = 1,
 }
 
+impl CRCFlag {
+    /// returns the size in bytes of the CRC appended to the PDU (i.e. [CRC_LEN] when present and 0 otherwise)
+    pub fn encoded_len(&self) -> u16 {
+        match self {
+            CRCFlag::NotPresent => 0,
+            CRCFlag::Present => CRC_LEN as u16,
+        }
+    }
+}
+
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
 /// Flag indicating if the file size is less than the maximum u32 value.
 pub enum FileSizeFlag {
@@ -136,6 +186,8 @@ impl FileSizeFlag {
     }
 }
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
 /// Whether segmentation control is implemented on this transaction.
 pub enum SegmentationControl {
@@ -144,6 +196,8 @@ pub enum SegmentationControl {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
 /// Flag to determine if the file data in this PDU is segmented.
 pub enum SegmentedData {
@@ -152,6 +206,8 @@ pub enum SegmentedData {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
 /// The type of Prompt PDU being sent.
 pub enum NakOrKeepAlive {
@@ -160,6 +216,8 @@ pub enum NakOrKeepAlive {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
 /// Final transaction delivery code.
 pub enum DeliveryCode {
@@ -168,6 +226,8 @@ pub enum DeliveryCode {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
 /// The resulting code of a file being written during a transaction.
 pub enum FileStatusCode {
@@ -178,6 +238,8 @@ pub enum FileStatusCode {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
 /// The current status of a running transaction
 pub enum TransactionStatus {
@@ -188,6 +250,7 @@ pub enum TransactionStatus {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
 /// All possible User Messages which can be sent as file directives.
 pub enum MessageType {
@@ -267,6 +330,7 @@ pub trait SegmentEncode {
     ) -> PDUResult<Self::PDUType>;
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 /// The standard header attached to all CFDP PDUs.
 pub struct PDUHeader {
@@ -309,6 +373,69 @@ pub struct PDUHeader {
     /// Destination entity identification number.
     pub destination_entity_id: VariableID,
 }
+impl PDUHeader {
+    /// The protocol revision of this PDU.
+    ///
+    /// Version numbers from future revisions are treated as the current one.
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        match self.version {
+            U3::Zero => ProtocolVersion::Legacy,
+            _ => ProtocolVersion::Current,
+        }
+    }
+
+    /// The number of bytes both entity IDs are encoded with.
+    ///
+    /// The header carries a single length for the source and destination IDs,
+    /// the narrower one is widened to match.
+    pub fn entity_id_width(&self) -> u8 {
+        self.source_entity_id
+            .width()
+            .max(self.destination_entity_id.width())
+    }
+
+    /// The entity IDs and transaction sequence number as they are encoded.
+    pub(super) fn encoded_ids(&self) -> [VariableID; 3] {
+        let width = self.entity_id_width();
+        [
+            self.source_entity_id.widen(width),
+            self.transaction_sequence_number,
+            self.destination_entity_id.widen(width),
+        ]
+    }
+
+    /// The data field length as written on the wire, which counts the CRC when present.
+    pub(super) fn wire_data_field_length(&self) -> PDUResult<u16> {
+        let crc_len = self.crc_flag.encoded_len();
+        self.pdu_data_field_length
+            .checked_add(crc_len)
+            .ok_or(PDUError::DataFieldTooLarge(
+                usize::from(self.pdu_data_field_length) + usize::from(crc_len),
+            ))
+    }
+
+    /// The fixed length fields preceding the entity IDs and sequence number,
+    /// announcing a data field of `data_field_length` bytes including the CRC.
+    pub(super) fn fixed_fields(&self, data_field_length: u16) -> [u8; 4] {
+        let first_byte = ((self.version.clone() as u8) << 5)
+            | ((self.pdu_type.clone() as u8) << 4)
+            | ((self.direction.clone() as u8) << 3)
+            | ((self.transmission_mode as u8) << 2)
+            | ((self.crc_flag as u8) << 1)
+            | self.large_file_flag as u8;
+        let data_field_length = data_field_length.to_be_bytes();
+        let last_byte = ((self.segmentation_control as u8) << 7)
+            | ((self.entity_id_width() - 1) << 4)
+            | ((self.segment_metadata_flag as u8) << 3)
+            | (self.transaction_sequence_number.encoded_len() as u8 - 1);
+        [
+            first_byte,
+            data_field_length[0],
+            data_field_length[1],
+            last_byte,
+        ]
+    }
+}
 impl PDUEncode for PDUHeader {
     type PDUType = Self;
 
@@ -319,33 +446,17 @@ impl PDUEncode for PDUHeader {
             2
             // segmentation control, entity ID len, segment metadata flag, sequence_number len
             + 1
-            + self.source_entity_id.encoded_len()
+            + 2 * u16::from(self.entity_id_width())
             + self.transaction_sequence_number.encoded_len()
-            + self.destination_entity_id.encoded_len()
     }
 
     fn encode(self) -> Vec<u8> {
-        let first_byte = ((self.version as u8) << 5)
-            | ((self.pdu_type as u8) << 4)
-            | ((self.direction as u8) << 3)
-            | ((self.transmission_mode as u8) << 2)
-            | ((self.crc_flag as u8) << 1)
-            | self.large_file_flag as u8;
-        let mut buffer = vec![first_byte];
-        // if the CRC is expected add 2 to the length of the "data" field
-        buffer.extend(match &self.crc_flag {
-            CRCFlag::NotPresent => self.pdu_data_field_length.to_be_bytes(),
-            CRCFlag::Present => (self.pdu_data_field_length + 2).to_be_bytes(),
-        });
-        buffer.push(
-            ((self.segmentation_control as u8) << 7)
-                | ((self.source_entity_id.encoded_len() as u8 - 1) << 4)
-                | ((self.segment_metadata_flag as u8) << 3)
-                | (self.transaction_sequence_number.encoded_len() as u8 - 1),
-        );
-        buffer.extend(self.source_entity_id.to_be_bytes());
-        buffer.extend(self.transaction_sequence_number.to_be_bytes());
-        buffer.extend(self.destination_entity_id.to_be_bytes());
+        // saturates like the length of the PDU, encode_into refuses such headers.
+        let data_field_length = self.wire_data_field_length().unwrap_or(u16::MAX);
+        let mut buffer = self.fixed_fields(data_field_length).to_vec();
+        self.encoded_ids()
+            .into_iter()
+            .for_each(|id| buffer.extend(id.to_be_bytes()));
         buffer
     }
 
@@ -390,10 +501,10 @@ impl PDUEncode for PDUHeader {
             // CRC length is _included_ in the data_field_length
             // but it is not actually part of the message.
             // strip the crc length to preserve the original message
-            match &crc_flag {
-                CRCFlag::NotPresent => u16::from_be_bytes(u16_buff),
-                CRCFlag::Present => u16::from_be_bytes(u16_buff) - 2,
-            }
+            let length = u16::from_be_bytes(u16_buff);
+            length
+                .checked_sub(crc_flag.encoded_len())
+                .ok_or(PDUError::InvalidDataFieldLength(length))?
         };
 
         buffer.read_exact(&mut u8_buff)?;
@@ -414,23 +525,24 @@ impl PDUEncode for PDUHeader {
         // add one back to get actual value.
         let entity_id_length = ((u8_buff[0] & 0x70) >> 4) + 1;
         let transaction_sequence_length = (u8_buff[0] & 0x7) + 1;
+        let mut id_buff = [0_u8; 8];
 
         let source_entity_id = {
-            let mut buff = vec![0_u8; entity_id_length as usize];
-            buffer.read_exact(buff.as_mut_slice())?;
-            VariableID::try_from(buff.to_vec())?
+            let buff = &mut id_buff[..entity_id_length as usize];
+            buffer.read_exact(buff)?;
+            VariableID::try_from(&*buff)?
         };
 
         let transaction_sequence_number = {
-            let mut buff = vec![0_u8; transaction_sequence_length as usize];
-            buffer.read_exact(buff.as_mut_slice())?;
-            VariableID::try_from(buff.to_vec())?
+            let buff = &mut id_buff[..transaction_sequence_length as usize];
+            buffer.read_exact(buff)?;
+            VariableID::try_from(&*buff)?
         };
 
         let destination_entity_id = {
-            let mut buff = vec![0_u8; entity_id_length as usize];
-            buffer.read_exact(buff.as_mut_slice())?;
-            VariableID::try_from(buff.to_vec())?
+            let buff = &mut id_buff[..entity_id_length as usize];
+            buffer.read_exact(buff)?;
+            VariableID::try_from(&*buff)?
         };
 
         Ok(Self {
@@ -460,6 +572,26 @@ pub fn read_length_value_pair<T: Read>(buffer: &mut T) -> PDUResult<Vec<u8>> {
     Ok(vector)
 }
 
+/// The version, flags, data field length and ID lengths starting every PDU header.
+pub const FIXED_HEADER_LEN: usize = 4;
+
+/// The CRC appended to a PDU when its [CRCFlag] is [Present](CRCFlag::Present).
+pub const CRC_LEN: usize = 2;
+
+/// The longest value a length-value (LV) pair can hold.
+pub const MAX_LV_LEN: usize = u8::MAX as usize;
+
+/// The longest segment metadata file data can carry, its length is held in 6 bits.
+pub const MAX_SEGMENT_METADATA_LEN: usize = 0x3F;
+
+/// Make sure a value of `len` bytes fits the length field of at most `max` preceding it.
+pub(crate) fn check_length_value(field: &'static str, len: usize, max: usize) -> PDUResult<()> {
+    match len <= max {
+        true => Ok(()),
+        false => Err(PDUError::LengthValueTooLong(field, len, max)),
+    }
+}
+
 /// Read a type field (u8) from a byte stream.
 pub fn read_type<T: Read>(buffer: &mut T) -> PDUResult<u8> {
     let mut u8_buff = [0u8];
@@ -583,4 +715,38 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn mixed_id_widths() -> PDUResult<()> {
+        let expected = PDUHeader {
+            version: U3::One,
+            pdu_type: PDUType::FileDirective,
+            direction: Direction::ToReceiver,
+            transmission_mode: TransmissionMode::Acknowledged,
+            crc_flag: CRCFlag::NotPresent,
+            large_file_flag: FileSizeFlag::Small,
+            pdu_data_field_length: 12,
+            segmentation_control: SegmentationControl::NotPreserved,
+            segment_metadata_flag: SegmentedData::NotPresent,
+            source_entity_id: VariableID::from(3_u8),
+            transaction_sequence_number: VariableID::new(0x10_0000, 3)?,
+            destination_entity_id: VariableID::from(77_000_u32),
+        };
+        let buffer = expected.clone().encode();
+        // the source ID is widened to the 4 bytes of the destination.
+        assert_eq!(4 + 2 * 4 + 3, buffer.len());
+        assert_eq!(expected.encoded_len() as usize, buffer.len());
+
+        let recovered = PDUHeader::decode(&mut buffer.as_slice())?;
+        assert_eq!(
+            PDUHeader {
+                source_entity_id: VariableID::new(3, 4)?,
+                ..expected
+            },
+            recovered
+        );
+        assert_eq!(4, recovered.source_entity_id.width());
+        assert_eq!(3, recovered.transaction_sequence_number.width());
+        Ok(())
+    }
 }
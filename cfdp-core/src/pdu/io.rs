@@ -0,0 +1,72 @@
+use alloc::vec::Vec;
+
+use byteorder::ByteOrder;
+
+use super::error::PDUResult;
+
+/// The byte source PDUs are decoded from.
+///
+/// With the `std` feature every [std::io::Read] is a source,
+/// without it PDUs are decoded from byte slices.
+pub trait Read {
+    /// Fill the whole buffer from the source.
+    fn read_exact(&mut self, buf: &mut [u8]) -> PDUResult<()>;
+
+    /// Append everything remaining in the source to the buffer.
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> PDUResult<usize>;
+
+    fn read_u8(&mut self) -> PDUResult<u8> {
+        let mut buf = [0_u8; 1];
+        self.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn read_u16<B: ByteOrder>(&mut self) -> PDUResult<u16> {
+        let mut buf = [0_u8; 2];
+        self.read_exact(&mut buf)?;
+        Ok(B::read_u16(&buf))
+    }
+
+    fn read_u32<B: ByteOrder>(&mut self) -> PDUResult<u32> {
+        let mut buf = [0_u8; 4];
+        self.read_exact(&mut buf)?;
+        Ok(B::read_u32(&buf))
+    }
+
+    fn read_u64<B: ByteOrder>(&mut self) -> PDUResult<u64> {
+        let mut buf = [0_u8; 8];
+        self.read_exact(&mut buf)?;
+        Ok(B::read_u64(&buf))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read + ?Sized> Read for R {
+    fn read_exact(&mut self, buf: &mut [u8]) -> PDUResult<()> {
+        Ok(std::io::Read::read_exact(self, buf)?)
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> PDUResult<usize> {
+        Ok(std::io::Read::read_to_end(self, buf)?)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Read for &[u8] {
+    fn read_exact(&mut self, buf: &mut [u8]) -> PDUResult<()> {
+        if self.len() < buf.len() {
+            return Err(super::PDUError::BufferTooShort(buf.len(), self.len()));
+        }
+        let (read, remaining) = self.split_at(buf.len());
+        buf.copy_from_slice(read);
+        *self = remaining;
+        Ok(())
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> PDUResult<usize> {
+        let length = self.len();
+        buf.extend_from_slice(self);
+        *self = &[];
+        Ok(length)
+    }
+}
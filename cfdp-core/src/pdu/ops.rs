@@ -1,86 +1,134 @@
-use byteorder::{BigEndian, ReadBytesExt};
-use camino::Utf8PathBuf;
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use byteorder::BigEndian;
+use core::fmt::{self, Display};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
-use std::{
-    fmt::{self, Display},
-    io::Read,
-};
 
 use super::{
     error::{PDUError, PDUResult},
     fault_handler::FaultHandlerOverride,
     filestore::{FileStoreRequest, FileStoreResponse},
     header::{
-        read_length_value_pair, Condition, DeliveryCode, FSSEncode, FileSizeFlag, FileStatusCode,
-        NakOrKeepAlive, PDUEncode, SegmentEncode, SegmentedData, TransactionStatus,
+        check_length_value, read_length_value_pair, Condition, DeliveryCode, FSSEncode,
+        FileSizeFlag, FileStatusCode, NakOrKeepAlive, PDUEncode, SegmentEncode, SegmentedData,
+        TransactionStatus, MAX_LV_LEN,
     },
+    io::Read,
     UserOperation,
 };
-use crate::filestore::ChecksumType;
+use crate::{filestore::ChecksumType, Utf8PathBuf};
 
 macro_rules! impl_id {
-    ( $prim:ty, $id:expr ) => {
+    ( $prim:ty ) => {
         impl From<$prim> for EntityID {
             fn from(val: $prim) -> Self {
-                $id(val)
+                Self {
+                    value: val as u64,
+                    width: core::mem::size_of::<$prim>() as u8,
+                }
             }
         }
     };
 }
 pub type EntityID = VariableID;
 pub type TransactionSeqNum = VariableID;
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Copy)]
-/// All possible variable length IDs as defined in CCSDS standard.
-pub enum VariableID {
-    U8(u8),
-    U16(u16),
-    U32(u32),
-    U64(u64),
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(into = "SerializedID", try_from = "SerializedID")
+)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
+/// A variable length ID as defined in CCSDS standard, 1 to 8 bytes wide.
+///
+/// IDs are only equal when they encode to the same bytes,
+/// use [same_value](Self::same_value) to compare IDs of any width.
+pub struct VariableID {
+    value: u64,
+    width: u8,
+}
+impl_id!(u8);
+impl_id!(u16);
+impl_id!(u32);
+impl_id!(u64);
+impl TryFrom<&[u8]> for VariableID {
+    type Error = PDUError;
+
+    /// attempt to construct an ID from a slice of big endian bytes.
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        match value.len() {
+            width @ 1..=8 => {
+                let mut bytes = [0_u8; 8];
+                bytes[8 - width..].copy_from_slice(value);
+                Ok(Self {
+                    value: u64::from_be_bytes(bytes),
+                    width: width as u8,
+                })
+            }
+            other => Err(PDUError::UnknownIDLength(other as u8)),
+        }
+    }
 }
-impl_id!(u8, VariableID::U8);
-impl_id!(u16, VariableID::U16);
-impl_id!(u32, VariableID::U32);
-impl_id!(u64, VariableID::U64);
 impl TryFrom<Vec<u8>> for VariableID {
     type Error = PDUError;
 
     /// attempt to construct an ID from a Vec of big endian bytes.
     fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
-        let length = value.len();
-        match length {
-            1 => Ok(Self::from(u8::from_be_bytes(
-                value
-                    .try_into()
-                    .expect("Unable to coerce vec into same sized array."),
-            ))),
-            2 => Ok(Self::from(u16::from_be_bytes(
-                value
-                    .try_into()
-                    .expect("Unable to coerce vec into same sized array."),
-            ))),
-            4 => Ok(Self::from(u32::from_be_bytes(
-                value
-                    .try_into()
-                    .expect("Unable to coerce vec into same sized array."),
-            ))),
-            8 => Ok(Self::from(u64::from_be_bytes(
-                value
-                    .try_into()
-                    .expect("Unable to coerce vec into same sized array."),
-            ))),
-            other => Err(PDUError::UnknownIDLength(other as u8)),
-        }
+        Self::try_from(value.as_slice())
     }
 }
 impl VariableID {
+    /// Construct an ID encoded with `width` bytes.
+    ///
+    /// Fails if the width is not between 1 and 8 or the value does not fit.
+    pub fn new(value: u64, width: u8) -> PDUResult<Self> {
+        if !(1..=8).contains(&width) {
+            return Err(PDUError::UnknownIDLength(width));
+        }
+        let id = Self { value, width };
+        match value <= id.max_value() {
+            true => Ok(id),
+            false => Err(PDUError::IDOutOfRange(value, width)),
+        }
+    }
+
+    /// The number of bytes used to encode the ID.
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    /// Whether both IDs hold the same value, whatever their widths.
+    ///
+    /// Peers may widen IDs when encoding them, the same entity can then be
+    /// received with a different width than it is known by locally.
+    pub fn same_value(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+
+    /// The same ID encoded with at least `width` bytes.
+    ///
+    /// IDs are never narrowed and at most 8 bytes wide.
+    pub fn widen(self, width: u8) -> Self {
+        Self {
+            width: width.clamp(self.width, 8),
+            ..self
+        }
+    }
+
+    fn max_value(&self) -> u64 {
+        u64::MAX >> (64 - 8 * u32::from(self.width))
+    }
+
     /// Incerement the internal counter of the ID. This is useful for sequence numbers.
+    ///
+    /// The counter wraps around within the width of the ID.
     pub fn increment(&mut self) {
-        match self {
-            Self::U8(val) => *val = val.overflowing_add(1).0,
-            Self::U16(val) => *val = val.overflowing_add(1).0,
-            Self::U32(val) => *val = val.overflowing_add(1).0,
-            Self::U64(val) => *val = val.overflowing_add(1).0,
+        self.value = match self.value == self.max_value() {
+            true => 0,
+            false => self.value + 1,
         };
     }
 
@@ -93,21 +141,51 @@ impl VariableID {
 
     /// Convert the internal counter to Big endian bytes.
     pub fn to_be_bytes(self) -> Vec<u8> {
-        match self {
-            VariableID::U8(val) => val.to_be_bytes().to_vec(),
-            VariableID::U16(val) => val.to_be_bytes().to_vec(),
-            VariableID::U32(val) => val.to_be_bytes().to_vec(),
-            VariableID::U64(val) => val.to_be_bytes().to_vec(),
-        }
+        self.value.to_be_bytes()[8 - self.width as usize..].to_vec()
     }
 
     /// convert underlying ID to u64
     pub fn to_u64(&self) -> u64 {
-        match self {
-            VariableID::U8(val) => *val as u64,
-            VariableID::U16(val) => *val as u64,
-            VariableID::U32(val) => *val as u64,
-            VariableID::U64(val) => *val,
+        self.value
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+/// Serialized form of a [VariableID], the common widths keep their own variant.
+enum SerializedID {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    Sized { value: u64, width: u8 },
+}
+#[cfg(feature = "serde")]
+impl From<VariableID> for SerializedID {
+    fn from(id: VariableID) -> Self {
+        match id.width {
+            1 => Self::U8(id.value as u8),
+            2 => Self::U16(id.value as u16),
+            4 => Self::U32(id.value as u32),
+            8 => Self::U64(id.value),
+            width => Self::Sized {
+                value: id.value,
+                width,
+            },
+        }
+    }
+}
+#[cfg(feature = "serde")]
+impl TryFrom<SerializedID> for VariableID {
+    type Error = PDUError;
+
+    fn try_from(id: SerializedID) -> Result<Self, Self::Error> {
+        match id {
+            SerializedID::U8(value) => Ok(Self::from(value)),
+            SerializedID::U16(value) => Ok(Self::from(value)),
+            SerializedID::U32(value) => Ok(Self::from(value)),
+            SerializedID::U64(value) => Ok(Self::from(value)),
+            SerializedID::Sized { value, width } => Self::new(value, width),
         }
     }
 }
@@ -115,14 +193,7 @@ impl PDUEncode for VariableID {
     type PDUType = Self;
 
     fn encoded_len(&self) -> u16 {
-        // largest values supported are u64s so this should always
-        // be castable down to a u8 even.
-        match self {
-            Self::U8(_) => 1,
-            Self::U16(_) => 2,
-            Self::U32(_) => 4,
-            Self::U64(_) => 8,
-        }
+        self.width.into()
     }
 
     fn encode(self) -> Vec<u8> {
@@ -136,8 +207,9 @@ impl PDUEncode for VariableID {
         let mut u8_buff = [0u8; 1];
         buffer.read_exact(&mut u8_buff)?;
 
-        let length: u8 = u8_buff[0] + 1;
-        let mut id = vec![0u8; length as usize];
+        // a width up to 256 bytes can be expressed, anything beyond 8 fails to convert below.
+        let length = usize::from(u8_buff[0]) + 1;
+        let mut id = vec![0u8; length];
         buffer.read_exact(id.as_mut_slice())?;
         Self::try_from(id)
     }
@@ -150,10 +222,18 @@ impl fmt::Display for VariableID {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct FlowLabel {
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::lv_value))]
     pub value: Vec<u8>,
 }
+impl FlowLabel {
+    pub(crate) fn check_lengths(&self) -> PDUResult<()> {
+        check_length_value("Flow label", self.value.len(), MAX_LV_LEN)
+    }
+}
 impl PDUEncode for FlowLabel {
     type PDUType = Self;
 
@@ -175,14 +255,26 @@ impl PDUEncode for FlowLabel {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 /// A holder for all possible Messages to the User.
 ///
 /// An implementation my have a unique message definiton or include
 /// human readable text.
 pub struct MessageToUser {
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::lv_value))]
     pub message_text: Vec<u8>,
 }
+impl MessageToUser {
+    /// Fail if the message is longer than its length field can express.
+    ///
+    /// Encoded [user operations](UserOperation) are only checked as a whole,
+    /// any of their fields being too long makes the message too long as well.
+    pub(crate) fn check_lengths(&self) -> PDUResult<()> {
+        check_length_value("Message to user", self.message_text.len(), MAX_LV_LEN)
+    }
+}
 impl PDUEncode for MessageToUser {
     type PDUType = Self;
 
@@ -212,6 +304,7 @@ impl From<UserOperation> for MessageToUser {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, FromPrimitive)]
 /// The possible field codes for a Metadata TLV field.
 pub enum MetadataTLVFieldCode {
@@ -223,11 +316,13 @@ pub enum MetadataTLVFieldCode {
     EntityID = 0x06,
 }
 impl Display for MetadataTLVFieldCode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", &self)
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 /// All TLV fields related to Metadata
 pub enum MetadataTLV {
@@ -249,6 +344,16 @@ impl MetadataTLV {
             Self::EntityID(_) => MetadataTLVFieldCode::EntityID,
         }
     }
+
+    pub(crate) fn check_lengths(&self) -> PDUResult<()> {
+        match self {
+            Self::FileStoreRequest(request) => request.check_lengths(),
+            Self::FileStoreResponse(response) => response.check_lengths(),
+            Self::MessageToUser(message) => message.check_lengths(),
+            Self::FlowLabel(label) => label.check_lengths(),
+            Self::FaultHandlerOverride(_) | Self::EntityID(_) => Ok(()),
+        }
+    }
 }
 impl PDUEncode for MetadataTLV {
     type PDUType = Self;
@@ -260,7 +365,8 @@ impl PDUEncode for MetadataTLV {
             Self::MessageToUser(inner) => inner.encoded_len(),
             Self::FaultHandlerOverride(inner) => inner.encoded_len(),
             Self::FlowLabel(inner) => inner.encoded_len(),
-            Self::EntityID(inner) => inner.encoded_len(),
+            // the length of the ID precedes it.
+            Self::EntityID(inner) => 1 + inner.encoded_len(),
         }
     }
 
@@ -301,6 +407,7 @@ impl PDUEncode for MetadataTLV {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, FromPrimitive)]
 /// The possible directive types of a PDU, used to distinguish the PDUs.
 pub enum PDUDirective {
@@ -321,6 +428,7 @@ pub enum PDUDirective {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, FromPrimitive)]
 /// Subdirective codes for Positive acknowledgement PDUs.
 pub enum ACKSubDirective {
@@ -328,6 +436,8 @@ pub enum ACKSubDirective {
     Finished = 0b0001,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq, FromPrimitive)]
 /// Continuation state of a record.
 pub enum RecordContinuationState {
@@ -337,11 +447,14 @@ pub enum RecordContinuationState {
     Interim = 0b00,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 /// Holds File data beginning at the given offset.
 pub struct UnsegmentedFileData {
     /// Byte offset into the file where this data begins.
     pub offset: u64,
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::file_data))]
     pub file_data: Vec<u8>,
 }
 impl FSSEncode for UnsegmentedFileData {
@@ -375,12 +488,16 @@ impl FSSEncode for UnsegmentedFileData {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 /// Holds segmented file data. Segmentation is implementation dependent.
 pub struct SegmentedFileData {
     pub record_continuation_state: RecordContinuationState,
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::segment_metadata))]
     pub segment_metadata: Vec<u8>,
     pub offset: u64,
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::file_data))]
     pub file_data: Vec<u8>,
 }
 impl FSSEncode for SegmentedFileData {
@@ -410,8 +527,11 @@ impl FSSEncode for SegmentedFileData {
     fn decode<T: Read>(buffer: &mut T, file_size_flag: FileSizeFlag) -> PDUResult<Self::PDUType> {
         let mut u8_buff = [0_u8; 1];
         buffer.read_exact(&mut u8_buff)?;
-        let record_continuation_state =
-            RecordContinuationState::from_u8((u8_buff[0] & 0xC0) >> 6).unwrap();
+        let record_continuation_state = {
+            let possible = (u8_buff[0] & 0xC0) >> 6;
+            RecordContinuationState::from_u8(possible)
+                .ok_or(PDUError::InvalidRecordContinuationState(possible))?
+        };
         let segment_metadata = {
             let metadata_len = u8_buff[0] & 0x3F;
             let mut data = vec![0_u8; metadata_len as usize];
@@ -437,6 +557,8 @@ impl FSSEncode for SegmentedFileData {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 /// A holder for both possible types of File data PDUs.
 pub enum FileDataPDU {
@@ -478,6 +600,8 @@ impl SegmentEncode for FileDataPDU {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 /// All operations PDUs
 pub enum Operations {
@@ -501,12 +625,54 @@ impl Operations {
             Self::KeepAlive(_) => PDUDirective::KeepAlive,
         }
     }
+
+    /// Restrict the directive to what the legacy protocol (version `000`) can express.
+    ///
+    /// Keep Alive and Prompt PDUs are refused, as are Metadata options other than
+    /// filestore requests, messages to user, fault handler overrides and flow labels.
+    /// Legacy Metadata has no room for a closure request or checksum type,
+    /// so closure is never requested and the modular checksum is always used.
+    pub fn to_legacy(self) -> PDUResult<Self> {
+        match self {
+            Self::Prompt(_) | Self::KeepAlive(_) => {
+                Err(PDUError::LegacyDirective(self.get_directive()))
+            }
+            Self::Metadata(metadata) => {
+                if let Some(option) = metadata.options.iter().find(|option| {
+                    matches!(
+                        option,
+                        MetadataTLV::FileStoreResponse(_) | MetadataTLV::EntityID(_)
+                    )
+                }) {
+                    return Err(PDUError::LegacyTLV(option.get_field_code()));
+                }
+                Ok(Self::Metadata(MetadataPDU {
+                    closure_requested: false,
+                    checksum_type: ChecksumType::Modular,
+                    ..metadata
+                }))
+            }
+            operation => Ok(operation),
+        }
+    }
+
+    /// Fail if any length-value field is longer than its length field can express,
+    /// rather than encoding a length which wrapped around.
+    pub fn check_lengths(&self) -> PDUResult<()> {
+        match self {
+            Self::Metadata(metadata) => metadata.check_lengths(),
+            Self::Finished(finished) => finished.check_lengths(),
+            Self::EoF(_) | Self::Ack(_) | Self::Nak(_) | Self::Prompt(_) | Self::KeepAlive(_) => {
+                Ok(())
+            }
+        }
+    }
 }
 impl FSSEncode for Operations {
     type PDUType = Self;
 
     fn encoded_len(&self, file_size_flag: FileSizeFlag) -> u16 {
-        1 + match self {
+        match self {
             Self::EoF(eof) => eof.encoded_len(file_size_flag),
             Self::Finished(finished) => finished.encoded_len(),
             Self::Ack(ack) => ack.encoded_len(),
@@ -515,6 +681,7 @@ impl FSSEncode for Operations {
             Self::Prompt(prompt) => prompt.encoded_len(),
             Self::KeepAlive(keepalive) => keepalive.encoded_len(file_size_flag),
         }
+        .saturating_add(1)
     }
 
     fn encode(self, file_size_flag: FileSizeFlag) -> Vec<u8> {
@@ -553,6 +720,7 @@ impl FSSEncode for Operations {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct EndOfFile {
     pub condition: Condition,
@@ -641,6 +809,7 @@ impl FSSEncode for EndOfFile {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Finished {
     pub condition: Condition,
@@ -649,6 +818,18 @@ pub struct Finished {
     pub filestore_response: Vec<FileStoreResponse>,
     pub fault_location: Option<VariableID>,
 }
+impl Finished {
+    fn check_lengths(&self) -> PDUResult<()> {
+        self.filestore_response.iter().try_for_each(|response| {
+            response.check_lengths()?;
+            check_length_value(
+                "Filestore response",
+                usize::from(response.encoded_len()),
+                MAX_LV_LEN,
+            )
+        })
+    }
+}
 impl PDUEncode for Finished {
     type PDUType = Self;
 
@@ -656,23 +837,23 @@ impl PDUEncode for Finished {
         //  Condition + delivery coe + status (1 byte)
         //  Filestore Responses: TLV of each response
         //  Fault Location (0 or TLV)
-        1 + self
-            .filestore_response
+        self.filestore_response
             .iter()
-            .fold(0_u16, |acc, file_response| {
-                acc
-                        // TLV code
-                        + 1
-                        // TLV Length
-                        + 1
-                        + file_response.encoded_len()
+            .fold(1_u16, |acc, file_response| {
+                acc.saturating_add(
+                    // TLV code
+                    1
+                    // TLV Length
+                    + 1
+                    + file_response.encoded_len(),
+                )
             })
-            + if let Some(fault) = self.fault_location {
+            .saturating_add(if let Some(fault) = self.fault_location {
                 // Space for TLV code and the length field
                 2 + fault.encoded_len()
             } else {
                 0
-            }
+            })
     }
 
     fn encode(self) -> Vec<u8> {
@@ -774,6 +955,7 @@ impl PDUEncode for Finished {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct PositiveAcknowledgePDU {
     /// Only valid for EoF and Finished Directives
@@ -848,15 +1030,35 @@ impl PDUEncode for PositiveAcknowledgePDU {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct MetadataPDU {
     pub closure_requested: bool,
     pub checksum_type: ChecksumType,
     pub file_size: u64,
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::filename))]
     pub source_filename: Utf8PathBuf,
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::filename))]
     pub destination_filename: Utf8PathBuf,
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::few))]
     pub options: Vec<MetadataTLV>,
 }
+impl MetadataPDU {
+    fn check_lengths(&self) -> PDUResult<()> {
+        check_length_value(
+            "Source filename",
+            self.source_filename.as_str().len(),
+            MAX_LV_LEN,
+        )?;
+        check_length_value(
+            "Destination filename",
+            self.destination_filename.as_str().len(),
+            MAX_LV_LEN,
+        )?;
+        self.options.iter().try_for_each(MetadataTLV::check_lengths)
+    }
+}
 impl FSSEncode for MetadataPDU {
     type PDUType = Self;
 
@@ -866,15 +1068,14 @@ impl FSSEncode for MetadataPDU {
         // source filename (1 + len )
         // destination filename (1 + len)
         // options (TLV per option)
-        1 + file_size_flag.encoded_len()
-            + 1
-            + self.source_filename.as_str().len() as u16
-            + 1
-            + self.destination_filename.as_str().len() as u16
-            + self
-                .options
-                .iter()
-                .fold(0_u16, |acc, opt| acc + opt.encoded_len())
+        self.options.iter().fold(
+            1 + file_size_flag.encoded_len()
+                + 1
+                + self.source_filename.as_str().len() as u16
+                + 1
+                + self.destination_filename.as_str().len() as u16,
+            |acc, opt| acc.saturating_add(opt.encoded_len()),
+        )
     }
 
     fn encode(self, file_size_flag: FileSizeFlag) -> Vec<u8> {
@@ -942,6 +1143,8 @@ impl FSSEncode for MetadataPDU {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct SegmentRequestForm {
     pub start_offset: u64,
@@ -1001,11 +1204,14 @@ impl FSSEncode for SegmentRequestForm {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct NegativeAcknowledgmentPDU {
     pub start_of_scope: u64,
     pub end_of_scope: u64,
     // 2 x FileSizeSensitive x N length for N requests.
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::few))]
     pub segment_requests: Vec<SegmentRequestForm>,
 }
 pub(crate) type NakPDU = NegativeAcknowledgmentPDU;
@@ -1015,8 +1221,10 @@ impl FSSEncode for NegativeAcknowledgmentPDU {
     fn encoded_len(&self, file_size_flag: FileSizeFlag) -> u16 {
         self.segment_requests
             .iter()
-            .fold(0, |acc, seg| acc + seg.encoded_len(file_size_flag))
-            + 2 * file_size_flag.encoded_len()
+            .fold(0_u16, |acc, seg| {
+                acc.saturating_add(seg.encoded_len(file_size_flag))
+            })
+            .saturating_add(2 * file_size_flag.encoded_len())
     }
 
     fn encode(self, file_size_flag: FileSizeFlag) -> Vec<u8> {
@@ -1073,11 +1281,13 @@ impl FSSEncode for NegativeAcknowledgmentPDU {
 impl NegativeAcknowledgmentPDU {
     /// returns the maximum number of nak segments which can fit into one PDU given the payload length
     pub fn max_nak_num(file_size_flag: FileSizeFlag, payload_len: u32) -> u32 {
-        (payload_len - 2 * file_size_flag.encoded_len() as u32)
+        payload_len.saturating_sub(2 * file_size_flag.encoded_len() as u32)
             / (2 * file_size_flag.encoded_len() as u32)
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct PromptPDU {
     pub nak_or_keep_alive: NakOrKeepAlive,
@@ -1103,6 +1313,8 @@ impl PDUEncode for PromptPDU {
         Ok(Self { nak_or_keep_alive })
     }
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct KeepAlivePDU {
     pub progress: u64,
@@ -1147,6 +1359,8 @@ mod test {
     #[case(VariableID::from(300_u16), VariableID::from(301_u16))]
     #[case(VariableID::from(867381_u32), VariableID::from(867382_u32))]
     #[case(VariableID::from(857198297_u64), VariableID::from(857198298_u64))]
+    #[case(VariableID::from(u8::MAX), VariableID::from(0_u8))]
+    #[case(VariableID::new(0xFF_FFFF, 3).unwrap(), VariableID::new(0, 3).unwrap())]
     fn increment_varible_id(#[case] id: VariableID, #[case] expected: VariableID) {
         let mut id = id;
         id.increment();
@@ -1167,7 +1381,10 @@ mod test {
             VariableID::from(1_u8),
             VariableID::from(300_u16),
             VariableID::from(867381_u32),
-            VariableID::from(857198297_u64)
+            VariableID::from(857198297_u64),
+            VariableID::new(0x12_3456, 3).unwrap(),
+            VariableID::new(0x12_3456_789A, 5).unwrap(),
+            VariableID::new(0x12_3456_789A_BCDE, 7).unwrap()
         )]
         id: VariableID,
     ) {
@@ -1175,7 +1392,32 @@ mod test {
         let recovered =
             VariableID::decode(&mut buff.as_slice()).expect("Unable to decode VariableID");
 
-        assert_eq!(id, recovered)
+        assert_eq!(id, recovered);
+        assert_eq!(id.width(), recovered.width())
+    }
+
+    #[rstest]
+    #[case(300, 0, PDUError::UnknownIDLength(0))]
+    #[case(300, 9, PDUError::UnknownIDLength(9))]
+    #[case(300, 1, PDUError::IDOutOfRange(300, 1))]
+    #[case(0x1_0000_0000, 4, PDUError::IDOutOfRange(0x1_0000_0000, 4))]
+    fn variableid_invalid(#[case] value: u64, #[case] width: u8, #[case] expected: PDUError) {
+        let error = VariableID::new(value, width).unwrap_err();
+        assert_eq!(expected.to_string(), error.to_string())
+    }
+
+    #[test]
+    fn variableid_widths() {
+        let narrow = VariableID::from(7_u8);
+        let wide = narrow.widen(6);
+        assert_eq!(6, wide.width());
+        // equal IDs encode to the same bytes, only their values are the same.
+        assert_ne!(narrow, wide);
+        assert!(narrow.same_value(&wide));
+        assert!(!narrow.same_value(&VariableID::from(8_u8)));
+        // never narrowed and never beyond 8 bytes.
+        assert_eq!(6, wide.widen(2).width());
+        assert_eq!(8, wide.widen(12).width());
     }
 
     #[rstest]
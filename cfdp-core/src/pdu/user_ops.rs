@@ -1,6 +1,4 @@
-use std::io::Read;
-
-use camino::Utf8PathBuf;
+use alloc::{string::String, vec, vec::Vec};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
@@ -12,11 +10,15 @@ use super::{
         read_length_value_pair, Condition, DeliveryCode, Direction, FileStatusCode, MessageType,
         PDUEncode, SegmentationControl, TraceControl, TransactionStatus, TransmissionMode,
     },
+    io::Read,
     ops::{EntityID, FlowLabel, MessageToUser, TransactionSeqNum},
 };
+use crate::Utf8PathBuf;
 
 const USER_OPS_IDENTIFIER: &[u8] = "cfdp".as_bytes();
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ProxyOperation {
     ProxyPutRequest(ProxyPutRequest),
@@ -27,6 +29,7 @@ pub enum ProxyOperation {
     ProxyFlowLabel(FlowLabel),
     ProxySegmentationControl(ProxySegmentationControl),
     ProxyPutCancel,
+    ProxyClosureRequest(ProxyClosureRequest),
 }
 impl ProxyOperation {
     pub fn get_message_type(&self) -> MessageType {
@@ -39,6 +42,7 @@ impl ProxyOperation {
             Self::ProxyFlowLabel(_) => MessageType::ProxyFlowLabel,
             Self::ProxySegmentationControl(_) => MessageType::ProxySegmentationControl,
             Self::ProxyPutCancel => MessageType::ProxyPutCancel,
+            Self::ProxyClosureRequest(_) => MessageType::ProxyClosureRequest,
         }
     }
 
@@ -53,6 +57,7 @@ impl ProxyOperation {
             Self::ProxyFlowLabel(inner) => inner.encoded_len(),
             Self::ProxySegmentationControl(inner) => inner.encoded_len(),
             Self::ProxyPutCancel => 0,
+            Self::ProxyClosureRequest(inner) => inner.encoded_len(),
         }
     }
     fn encode(self) -> Vec<u8> {
@@ -69,10 +74,13 @@ impl ProxyOperation {
             Self::ProxyFlowLabel(msg) => msg.encode(),
             Self::ProxySegmentationControl(msg) => msg.encode(),
             Self::ProxyPutCancel => vec![],
+            Self::ProxyClosureRequest(msg) => msg.encode(),
         }
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum UserResponse {
     ProxyPut(ProxyPutResponse),
@@ -119,6 +127,8 @@ impl UserResponse {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum UserRequest {
     DirectoryListing(DirectoryListingRequest),
@@ -154,6 +164,8 @@ impl UserRequest {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 /// User Operations are transported as the paylod of a [MessageToUser]
 /// in a metadataPDU. A reserved "CFDP" identifier is used to delinate User Operations.
@@ -299,7 +311,9 @@ impl PDUEncode for UserOperation {
                     OriginatingTransactionIDMessage::decode(buffer)?,
                 ))
             }
-            MessageType::ProxyClosureRequest => Err(PDUError::MessageType(u8_buff[0])),
+            MessageType::ProxyClosureRequest => Ok(Self::ProxyOperation(
+                ProxyOperation::ProxyClosureRequest(ProxyClosureRequest::decode(buffer)?),
+            )),
             MessageType::DirectoryListingRequest => Ok(Self::Request(
                 UserRequest::DirectoryListing(DirectoryListingRequest::decode(buffer)?),
             )),
@@ -349,6 +363,7 @@ impl PDUEncode for UserOperation {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ReservedMessageHeader {
     message_type: MessageType,
@@ -357,6 +372,8 @@ pub struct ReservedMessageHeader {
     value: Vec<u8>,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct OriginatingTransactionIDMessage {
     pub source_entity_id: EntityID,
@@ -372,8 +389,8 @@ impl PDUEncode for OriginatingTransactionIDMessage {
     fn encode(self) -> Vec<u8> {
         let mut buffer: Vec<u8> = vec![];
 
-        let first_byte = (((self.source_entity_id.encoded_len() as u8 - 1u8) & 0x3) << 4)
-            | ((self.transaction_sequence_number.encoded_len() as u8 - 1u8) & 0x3);
+        let first_byte = (((self.source_entity_id.encoded_len() as u8 - 1u8) & 0x7) << 4)
+            | ((self.transaction_sequence_number.encoded_len() as u8 - 1u8) & 0x7);
         buffer.push(first_byte);
 
         buffer.extend(self.source_entity_id.to_be_bytes());
@@ -407,10 +424,14 @@ impl PDUEncode for OriginatingTransactionIDMessage {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ProxyPutRequest {
     pub destination_entity_id: EntityID,
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::filename))]
     pub source_filename: Utf8PathBuf,
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::filename))]
     pub destination_filename: Utf8PathBuf,
 }
 impl PDUEncode for ProxyPutRequest {
@@ -453,6 +474,8 @@ impl PDUEncode for ProxyPutRequest {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ProxyPutResponse {
     pub condition: Condition,
@@ -503,6 +526,8 @@ impl PDUEncode for ProxyPutResponse {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ProxySegmentationControl {
     control: SegmentationControl,
@@ -531,9 +556,40 @@ impl PDUEncode for ProxySegmentationControl {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProxyClosureRequest {
+    pub closure_requested: bool,
+}
+impl PDUEncode for ProxyClosureRequest {
+    type PDUType = Self;
+
+    fn encoded_len(&self) -> u16 {
+        1
+    }
+
+    fn encode(self) -> Vec<u8> {
+        // the seven most significant bits are spare
+        vec![self.closure_requested as u8]
+    }
+
+    fn decode<T: Read>(buffer: &mut T) -> PDUResult<Self::PDUType> {
+        let mut u8_buff = [0u8; 1];
+        buffer.read_exact(&mut u8_buff)?;
+        Ok(Self {
+            closure_requested: u8_buff[0] & 0x1 == 1,
+        })
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct DirectoryListingRequest {
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::filename))]
     pub directory_name: Utf8PathBuf,
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::filename))]
     pub directory_filename: Utf8PathBuf,
 }
 impl PDUEncode for DirectoryListingRequest {
@@ -568,6 +624,8 @@ impl PDUEncode for DirectoryListingRequest {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq, FromPrimitive)]
 #[repr(u8)]
 pub enum ListingResponseCode {
@@ -575,10 +633,14 @@ pub enum ListingResponseCode {
     Unsuccessful = 0x80,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct DirectoryListingResponse {
     pub response_code: ListingResponseCode,
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::filename))]
     pub directory_name: Utf8PathBuf,
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::filename))]
     pub directory_filename: Utf8PathBuf,
 }
 impl PDUEncode for DirectoryListingResponse {
@@ -626,10 +688,13 @@ impl PDUEncode for DirectoryListingResponse {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct RemoteStatusReportRequest {
     pub source_entity_id: EntityID,
     pub transaction_sequence_number: TransactionSeqNum,
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::filename))]
     pub report_filename: Utf8PathBuf,
 }
 impl PDUEncode for RemoteStatusReportRequest {
@@ -645,8 +710,8 @@ impl PDUEncode for RemoteStatusReportRequest {
     fn encode(self) -> Vec<u8> {
         let mut buffer: Vec<u8> = vec![];
 
-        let first_byte = (((self.source_entity_id.encoded_len() as u8 - 1u8) & 0x3) << 4)
-            | ((self.transaction_sequence_number.encoded_len() as u8 - 1u8) & 0x3);
+        let first_byte = (((self.source_entity_id.encoded_len() as u8 - 1u8) & 0x7) << 4)
+            | ((self.transaction_sequence_number.encoded_len() as u8 - 1u8) & 0x7);
         buffer.push(first_byte);
 
         buffer.extend(self.source_entity_id.to_be_bytes());
@@ -689,6 +754,8 @@ impl PDUEncode for RemoteStatusReportRequest {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct RemoteStatusReportResponse {
     pub transaction_status: TransactionStatus,
@@ -709,8 +776,8 @@ impl PDUEncode for RemoteStatusReportResponse {
         let first_byte: u8 = ((self.transaction_status as u8) << 6) | (self.response_code as u8);
         buffer.push(first_byte);
 
-        let second_byte = (((self.source_entity_id.encoded_len() as u8 - 1u8) & 0x3) << 4)
-            | ((self.transaction_sequence_number.encoded_len() as u8 - 1u8) & 0x3);
+        let second_byte = (((self.source_entity_id.encoded_len() as u8 - 1u8) & 0x7) << 4)
+            | ((self.transaction_sequence_number.encoded_len() as u8 - 1u8) & 0x7);
         buffer.push(second_byte);
 
         buffer.extend(self.source_entity_id.to_be_bytes());
@@ -758,6 +825,8 @@ impl PDUEncode for RemoteStatusReportResponse {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct RemoteSuspendRequest {
     pub source_entity_id: EntityID,
@@ -773,8 +842,8 @@ impl PDUEncode for RemoteSuspendRequest {
     fn encode(self) -> Vec<u8> {
         let mut buffer: Vec<u8> = vec![];
 
-        let first_byte = (((self.source_entity_id.encoded_len() as u8 - 1u8) & 0x3) << 4)
-            | ((self.transaction_sequence_number.encoded_len() as u8 - 1u8) & 0x3);
+        let first_byte = (((self.source_entity_id.encoded_len() as u8 - 1u8) & 0x7) << 4)
+            | ((self.transaction_sequence_number.encoded_len() as u8 - 1u8) & 0x7);
         buffer.push(first_byte);
 
         buffer.extend(self.source_entity_id.to_be_bytes());
@@ -810,6 +879,8 @@ impl PDUEncode for RemoteSuspendRequest {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct RemoteSuspendResponse {
     pub suspend_indication: bool,
@@ -831,8 +902,8 @@ impl PDUEncode for RemoteSuspendResponse {
             ((self.suspend_indication as u8) << 7) | ((self.transaction_status as u8) << 5);
         buffer.push(first_byte);
 
-        let second_byte = (((self.source_entity_id.encoded_len() as u8 - 1u8) & 0x3) << 4)
-            | ((self.transaction_sequence_number.encoded_len() as u8 - 1u8) & 0x3);
+        let second_byte = (((self.source_entity_id.encoded_len() as u8 - 1u8) & 0x7) << 4)
+            | ((self.transaction_sequence_number.encoded_len() as u8 - 1u8) & 0x7);
         buffer.push(second_byte);
 
         buffer.extend(self.source_entity_id.to_be_bytes());
@@ -879,6 +950,8 @@ impl PDUEncode for RemoteSuspendResponse {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct RemoteResumeRequest {
     pub source_entity_id: EntityID,
@@ -894,8 +967,8 @@ impl PDUEncode for RemoteResumeRequest {
     fn encode(self) -> Vec<u8> {
         let mut buffer: Vec<u8> = vec![];
 
-        let first_byte = (((self.source_entity_id.encoded_len() as u8 - 1u8) & 0x3) << 4)
-            | ((self.transaction_sequence_number.encoded_len() as u8 - 1u8) & 0x3);
+        let first_byte = (((self.source_entity_id.encoded_len() as u8 - 1u8) & 0x7) << 4)
+            | ((self.transaction_sequence_number.encoded_len() as u8 - 1u8) & 0x7);
         buffer.push(first_byte);
 
         buffer.extend(self.source_entity_id.to_be_bytes());
@@ -930,6 +1003,8 @@ impl PDUEncode for RemoteResumeRequest {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct RemoteResumeResponse {
     pub suspend_indication: bool,
@@ -951,8 +1026,8 @@ impl PDUEncode for RemoteResumeResponse {
             ((self.suspend_indication as u8) << 7) | ((self.transaction_status as u8) << 5);
         buffer.push(first_byte);
 
-        let second_byte = (((self.source_entity_id.encoded_len() as u8 - 1u8) & 0x3) << 4)
-            | ((self.transaction_sequence_number.encoded_len() as u8 - 1u8) & 0x3);
+        let second_byte = (((self.source_entity_id.encoded_len() as u8 - 1u8) & 0x7) << 4)
+            | ((self.transaction_sequence_number.encoded_len() as u8 - 1u8) & 0x7);
         buffer.push(second_byte);
 
         buffer.extend(self.source_entity_id.to_be_bytes());
@@ -968,7 +1043,7 @@ impl PDUEncode for RemoteResumeResponse {
 
         let suspend_indication = ((first_byte & 0x80) >> 7) != 0;
         let transaction_status = {
-            let status = (first_byte & 0x30) >> 5;
+            let status = (first_byte & 0x60) >> 5;
             TransactionStatus::from_u8(status).ok_or(PDUError::InvalidTransactionStatus(status))?
         };
 
@@ -998,6 +1073,8 @@ impl PDUEncode for RemoteResumeResponse {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct SFORequest {
     trace_control: TraceControl,
@@ -1005,10 +1082,13 @@ pub struct SFORequest {
     segment_control: SegmentationControl,
     closure_request: bool,
     prior_waypoints_count: u8,
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::lv_value))]
     request_label: Vec<u8>,
     source_entity_id: EntityID,
     destination_entity_id: EntityID,
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::filename))]
     source_filename: Utf8PathBuf,
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::filename))]
     destination_filename: Utf8PathBuf,
 }
 impl PDUEncode for SFORequest {
@@ -1111,8 +1191,11 @@ impl PDUEncode for SFORequest {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct SFOReport {
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = crate::pdu::generate::lv_value))]
     request_label: Vec<u8>,
     source_entity_id: EntityID,
     destination_entity_id: EntityID,
@@ -1226,9 +1309,11 @@ mod test {
         pdu::{
             fault_handler::HandlerCode,
             filestore::{DenyStatus, FileStoreAction, FileStoreStatus, RenameStatus},
+            generate::from_raw,
         },
     };
 
+    use proptest::{collection::vec, prelude::*};
     use rstest::rstest;
     use rstest_reuse::{self, template, *};
 
@@ -1363,6 +1448,11 @@ mod test {
         }
     )))]
     #[case::proxy_put_cancel(UserOperation::ProxyOperation(ProxyOperation::ProxyPutCancel))]
+    #[case::closure_request(UserOperation::ProxyOperation(ProxyOperation::ProxyClosureRequest(
+        ProxyClosureRequest{
+            closure_requested: true
+        }
+    )))]
     #[case::directory_listing_request(UserOperation::Request(UserRequest::DirectoryListing(
         DirectoryListingRequest{
             directory_name: "/home/user/help".into(),
@@ -1499,4 +1589,25 @@ mod test {
     fn user_ops_len(expected: UserOperation) {
         assert_eq!(expected.encoded_len(), expected.encode().len() as u16)
     }
+
+    proptest! {
+        #[test]
+        fn user_operation_round_trip(raw in vec(any::<u8>(), 0..1024)) {
+            let expected: UserOperation = from_raw(&raw);
+            // a field too long for its length makes the message carrying it too long as well.
+            if let Err(error) = MessageToUser::from(expected.clone()).check_lengths() {
+                prop_assert!(matches!(error, PDUError::LengthValueTooLong("Message to user", ..)), "{:?}", error);
+                return Ok(());
+            }
+            let buffer = expected.clone().encode();
+            prop_assert_eq!(expected.encoded_len() as usize, buffer.len());
+            prop_assert_eq!(expected, UserOperation::decode(&mut buffer.as_slice()).unwrap());
+        }
+
+        #[test]
+        fn decode_arbitrary_user_operation(raw in vec(any::<u8>(), 0..512)) {
+            let buffer = [USER_OPS_IDENTIFIER, &raw].concat();
+            let _ = UserOperation::decode(&mut buffer.as_slice());
+        }
+    }
 }
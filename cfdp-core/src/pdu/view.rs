@@ -0,0 +1,747 @@
+use alloc::{vec, vec::Vec};
+
+use byteorder::{BigEndian, ByteOrder};
+use num_traits::FromPrimitive;
+
+use super::{
+    crc16_ibm_3740,
+    error::{PDUError, PDUResult},
+    header::{check_length_value, CRC_LEN, MAX_SEGMENT_METADATA_LEN},
+    CRCFlag, FSSEncode, FileDataPDU, FileSizeFlag, Operations, PDUEncode, PDUHeader, PDUPayload,
+    PDUType, ProtocolVersion, RecordContinuationState, SegmentedData, SegmentedFileData,
+    UnsegmentedFileData, VariableID, PDU,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// File data borrowed from the buffer the PDU was decoded from.
+pub enum FileDataView<'a> {
+    Unsegmented {
+        /// Byte offset into the file where this data begins.
+        offset: u64,
+        file_data: &'a [u8],
+    },
+    Segmented {
+        record_continuation_state: RecordContinuationState,
+        segment_metadata: &'a [u8],
+        /// Byte offset into the file where this data begins.
+        offset: u64,
+        file_data: &'a [u8],
+    },
+}
+impl<'a> FileDataView<'a> {
+    /// Byte offset into the file where this data begins.
+    pub fn offset(&self) -> u64 {
+        match self {
+            Self::Unsegmented { offset, .. } | Self::Segmented { offset, .. } => *offset,
+        }
+    }
+
+    /// The file data carried by the PDU.
+    pub fn file_data(&self) -> &'a [u8] {
+        match self {
+            Self::Unsegmented { file_data, .. } | Self::Segmented { file_data, .. } => file_data,
+        }
+    }
+
+    fn encoded_len(&self, file_size_flag: FileSizeFlag) -> usize {
+        let offset_len = file_size_flag.encoded_len() as usize;
+        match self {
+            Self::Unsegmented { file_data, .. } => offset_len + file_data.len(),
+            Self::Segmented {
+                segment_metadata,
+                file_data,
+                ..
+            } => 1 + segment_metadata.len() + offset_len + file_data.len(),
+        }
+    }
+
+    fn check_lengths(&self) -> PDUResult<()> {
+        match self {
+            Self::Unsegmented { .. } => Ok(()),
+            Self::Segmented {
+                segment_metadata, ..
+            } => check_length_value(
+                "Segment metadata",
+                segment_metadata.len(),
+                MAX_SEGMENT_METADATA_LEN,
+            ),
+        }
+    }
+
+    fn decode(
+        data: &'a [u8],
+        segmentation_flag: SegmentedData,
+        file_size_flag: FileSizeFlag,
+    ) -> PDUResult<Self> {
+        match segmentation_flag {
+            SegmentedData::NotPresent => {
+                let (offset, file_data) = split_offset(data, file_size_flag)?;
+                Ok(Self::Unsegmented { offset, file_data })
+            }
+            SegmentedData::Present => {
+                let (first_byte, remaining) = split(data, 1)?;
+                let record_continuation_state = {
+                    let possible = (first_byte[0] & 0xC0) >> 6;
+                    RecordContinuationState::from_u8(possible)
+                        .ok_or(PDUError::InvalidRecordContinuationState(possible))?
+                };
+                let (segment_metadata, remaining) =
+                    split(remaining, (first_byte[0] & 0x3F) as usize)?;
+                let (offset, file_data) = split_offset(remaining, file_size_flag)?;
+                Ok(Self::Segmented {
+                    record_continuation_state,
+                    segment_metadata,
+                    offset,
+                    file_data,
+                })
+            }
+        }
+    }
+
+    fn encode_into(
+        &self,
+        file_size_flag: FileSizeFlag,
+        writer: &mut SliceWriter<'_>,
+    ) -> PDUResult<()> {
+        match self {
+            Self::Unsegmented { offset, file_data } => {
+                writer.put_offset(*offset, file_size_flag)?;
+                writer.put(file_data)
+            }
+            Self::Segmented {
+                record_continuation_state,
+                segment_metadata,
+                offset,
+                file_data,
+            } => {
+                writer
+                    .put(&[((record_continuation_state.clone() as u8) << 6)
+                        | segment_metadata.len() as u8])?;
+                writer.put(segment_metadata)?;
+                writer.put_offset(*offset, file_size_flag)?;
+                writer.put(file_data)
+            }
+        }
+    }
+}
+impl<'a> From<&'a FileDataPDU> for FileDataView<'a> {
+    fn from(pdu: &'a FileDataPDU) -> Self {
+        match pdu {
+            FileDataPDU::Unsegmented(data) => Self::Unsegmented {
+                offset: data.offset,
+                file_data: &data.file_data,
+            },
+            FileDataPDU::Segmented(data) => Self::Segmented {
+                record_continuation_state: data.record_continuation_state.clone(),
+                segment_metadata: &data.segment_metadata,
+                offset: data.offset,
+                file_data: &data.file_data,
+            },
+        }
+    }
+}
+impl From<FileDataView<'_>> for FileDataPDU {
+    fn from(view: FileDataView<'_>) -> Self {
+        match view {
+            FileDataView::Unsegmented { offset, file_data } => {
+                Self::Unsegmented(UnsegmentedFileData {
+                    offset,
+                    file_data: file_data.to_vec(),
+                })
+            }
+            FileDataView::Segmented {
+                record_continuation_state,
+                segment_metadata,
+                offset,
+                file_data,
+            } => Self::Segmented(SegmentedFileData {
+                record_continuation_state,
+                segment_metadata: segment_metadata.to_vec(),
+                offset,
+                file_data: file_data.to_vec(),
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// The payload of a [PDUView].
+pub enum PayloadView<'a> {
+    /// Any non file data related PDU, decoded into its owned form.
+    Directive(Operations),
+    /// File data borrowed from the decoded buffer.
+    FileData(FileDataView<'a>),
+}
+impl From<PayloadView<'_>> for PDUPayload {
+    fn from(view: PayloadView<'_>) -> Self {
+        match view {
+            PayloadView::Directive(operation) => Self::Directive(operation),
+            PayloadView::FileData(data) => Self::FileData(data.into()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A [PDU] decoded in place from a byte slice.
+///
+/// File data is borrowed from the input rather than copied,
+/// so a received PDU can be inspected, forwarded or written to a file
+/// without allocating. Convert it into a [PDU] to keep it beyond the buffer.
+pub struct PDUView<'a> {
+    /// Header information used to decode the rest of the packet.
+    pub header: PDUHeader,
+    /// Packet payload containing filedata or a directive.
+    pub payload: PayloadView<'a>,
+}
+impl<'a> PDUView<'a> {
+    /// Decode the PDU at the start of the buffer.
+    ///
+    /// Returns the PDU along with the number of bytes it occupied,
+    /// anything after the PDU is left untouched.
+    /// When the header announces a CRC it is verified over the raw bytes before the
+    /// payload is parsed, so corrupted PDUs are rejected with [PDUError::CRCFailure].
+    /// Legacy PDUs are held to the rules of [Operations::to_legacy].
+    pub fn decode(buffer: &'a [u8]) -> PDUResult<(Self, usize)> {
+        let header = PDUHeader::decode(&mut &buffer[..])?;
+        check_legacy_header(&header)?;
+
+        let message_len = header.encoded_len() as usize + header.pdu_data_field_length as usize;
+        let total_len = message_len + usize::from(header.crc_flag.encoded_len());
+        if buffer.len() < total_len {
+            return Err(too_short(total_len, buffer));
+        }
+        let (message, remaining) = buffer.split_at(message_len);
+        if header.crc_flag == CRCFlag::Present {
+            let crc = &remaining[..CRC_LEN];
+            let received = BigEndian::read_u16(crc);
+            let computed = crc16_ibm_3740(message);
+            if received != computed {
+                return Err(PDUError::CRCFailure(received, computed));
+            }
+        }
+
+        let data = &message[header.encoded_len() as usize..];
+        let payload = match header.pdu_type {
+            PDUType::FileDirective => {
+                let operation = Operations::decode(&mut &data[..], header.large_file_flag)?;
+                PayloadView::Directive(match header.protocol_version() {
+                    ProtocolVersion::Legacy => operation.to_legacy()?,
+                    ProtocolVersion::Current => operation,
+                })
+            }
+            PDUType::FileData => PayloadView::FileData(FileDataView::decode(
+                data,
+                header.segment_metadata_flag,
+                header.large_file_flag,
+            )?),
+        };
+        Ok((Self { header, payload }, total_len))
+    }
+
+    /// Encode the PDU into the start of the buffer and return the number of bytes written.
+    ///
+    /// File data is copied straight from the view into the buffer.
+    pub fn encode_into(&self, buffer: &mut [u8]) -> PDUResult<usize> {
+        let payload = match &self.payload {
+            PayloadView::Directive(operation) => PayloadRef::Directive(operation),
+            PayloadView::FileData(data) => PayloadRef::FileData(data.clone()),
+        };
+        encode_into(&self.header, payload, buffer)
+    }
+}
+impl From<PDUView<'_>> for PDU {
+    fn from(view: PDUView<'_>) -> Self {
+        Self {
+            header: view.header,
+            payload: view.payload.into(),
+        }
+    }
+}
+
+impl PDU {
+    /// Encode the PDU into the start of the buffer and return the number of bytes written.
+    ///
+    /// File data is written without intermediate allocations.
+    /// Fails with [PDUError::BufferTooShort] if the PDU does not fit,
+    /// and with [PDUError::LengthValueTooLong] if one of its fields does not fit its length.
+    /// Legacy PDUs are written following [Operations::to_legacy].
+    pub fn encode_into(&self, buffer: &mut [u8]) -> PDUResult<usize> {
+        let payload = match &self.payload {
+            PDUPayload::Directive(operation) => PayloadRef::Directive(operation),
+            PDUPayload::FileData(data) => PayloadRef::FileData(data.into()),
+        };
+        encode_into(&self.header, payload, buffer)
+    }
+
+    /// The number of bytes the PDU encodes to, including its CRC.
+    ///
+    /// Unlike [encoded_len](PDUEncode::encoded_len) this never overflows, it fails with
+    /// [PDUError::DataFieldTooLarge] when the payload does not fit the 16 bit data field length
+    /// and with [PDUError::LengthValueTooLong] when a field does not fit its length.
+    pub fn checked_len(&self) -> PDUResult<usize> {
+        check_legacy_header(&self.header)?;
+        let payload_len = match &self.payload {
+            PDUPayload::Directive(operation) => encode_directive(&self.header, operation)?.len(),
+            PDUPayload::FileData(data) => {
+                let data = FileDataView::from(data);
+                data.check_lengths()?;
+                data.encoded_len(self.header.large_file_flag)
+            }
+        };
+        total_len(&self.header, payload_len)
+    }
+}
+
+enum PayloadRef<'p, 'a> {
+    Directive(&'p Operations),
+    FileData(FileDataView<'a>),
+}
+
+fn encode_into(header: &PDUHeader, payload: PayloadRef, buffer: &mut [u8]) -> PDUResult<usize> {
+    check_legacy_header(header)?;
+    let file_size_flag = header.large_file_flag;
+    // directives are small and rare, they still go through their own encoding.
+    let directive = match &payload {
+        PayloadRef::Directive(operation) => encode_directive(header, operation)?,
+        PayloadRef::FileData(data) => {
+            data.check_lengths()?;
+            vec![]
+        }
+    };
+    let payload_len = match &payload {
+        PayloadRef::Directive(_) => directive.len(),
+        PayloadRef::FileData(data) => data.encoded_len(file_size_flag),
+    };
+    let total_len = total_len(header, payload_len)?;
+    if buffer.len() < total_len {
+        return Err(too_short(total_len, buffer));
+    }
+    // the length announced is that of the payload written, whatever the header holds.
+    let data_field_len = (payload_len + usize::from(header.crc_flag.encoded_len())) as u16;
+
+    let mut writer = SliceWriter {
+        buffer,
+        position: 0,
+    };
+    writer.put(&header.fixed_fields(data_field_len))?;
+    for id in header.encoded_ids() {
+        writer.put_id(id)?;
+    }
+    match payload {
+        PayloadRef::Directive(_) => writer.put(&directive)?,
+        PayloadRef::FileData(data) => data.encode_into(file_size_flag, &mut writer)?,
+    }
+    if header.crc_flag == CRCFlag::Present {
+        let crc = crc16_ibm_3740(&writer.buffer[..writer.position]);
+        writer.put(&crc.to_be_bytes())?;
+    }
+    Ok(writer.position)
+}
+
+/// Legacy PDUs carry their directive following [Operations::to_legacy].
+fn encode_directive(header: &PDUHeader, operation: &Operations) -> PDUResult<Vec<u8>> {
+    let operation = match header.protocol_version() {
+        ProtocolVersion::Legacy => operation.clone().to_legacy()?,
+        ProtocolVersion::Current => operation.clone(),
+    };
+    operation.check_lengths()?;
+    Ok(operation.encode(header.large_file_flag))
+}
+
+/// The length of the whole PDU, provided the data field length can express its payload.
+fn total_len(header: &PDUHeader, payload_len: usize) -> PDUResult<usize> {
+    let data_field_len = payload_len + usize::from(header.crc_flag.encoded_len());
+    match data_field_len <= usize::from(u16::MAX) {
+        true => Ok(usize::from(header.encoded_len()) + data_field_len),
+        false => Err(PDUError::DataFieldTooLarge(data_field_len)),
+    }
+}
+
+/// Sequentially fills a caller provided buffer.
+struct SliceWriter<'b> {
+    buffer: &'b mut [u8],
+    position: usize,
+}
+impl SliceWriter<'_> {
+    fn put(&mut self, bytes: &[u8]) -> PDUResult<()> {
+        let end = self.position + bytes.len();
+        let length = self.buffer.len();
+        self.buffer
+            .get_mut(self.position..end)
+            .ok_or(PDUError::BufferTooShort(end, length))?
+            .copy_from_slice(bytes);
+        self.position = end;
+        Ok(())
+    }
+
+    fn put_offset(&mut self, offset: u64, file_size_flag: FileSizeFlag) -> PDUResult<()> {
+        match file_size_flag {
+            FileSizeFlag::Small => self.put(&(offset as u32).to_be_bytes()),
+            FileSizeFlag::Large => self.put(&offset.to_be_bytes()),
+        }
+    }
+
+    fn put_id(&mut self, id: VariableID) -> PDUResult<()> {
+        let bytes = id.to_u64().to_be_bytes();
+        self.put(&bytes[bytes.len() - usize::from(id.width())..])
+    }
+}
+
+/// Legacy PDUs have no large file flag, the bit was reserved.
+fn check_legacy_header(header: &PDUHeader) -> PDUResult<()> {
+    match (header.protocol_version(), header.large_file_flag) {
+        (ProtocolVersion::Legacy, FileSizeFlag::Large) => {
+            Err(PDUError::InvalidFileSizeFlag(FileSizeFlag::Large as u8))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn too_short(required: usize, buffer: &[u8]) -> PDUError {
+    PDUError::BufferTooShort(required, buffer.len())
+}
+
+fn split(buffer: &[u8], at: usize) -> PDUResult<(&[u8], &[u8])> {
+    match at <= buffer.len() {
+        true => Ok(buffer.split_at(at)),
+        false => Err(too_short(at, buffer)),
+    }
+}
+
+fn split_offset(buffer: &[u8], file_size_flag: FileSizeFlag) -> PDUResult<(u64, &[u8])> {
+    let (offset, remaining) = split(buffer, file_size_flag.encoded_len() as usize)?;
+    let offset = match file_size_flag {
+        FileSizeFlag::Small => BigEndian::read_u32(offset) as u64,
+        FileSizeFlag::Large => BigEndian::read_u64(offset),
+    };
+    Ok((offset, remaining))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{
+        filestore::ChecksumType,
+        pdu::{
+            Condition, Direction, EndOfFile, KeepAlivePDU, MessageToUser, MetadataPDU, MetadataTLV,
+            MetadataTLVFieldCode, PDUDirective, SegmentationControl, TransmissionMode, U3,
+        },
+    };
+
+    use rstest::rstest;
+
+    fn pdu(payload: PDUPayload, crc_flag: CRCFlag, large_file_flag: FileSizeFlag) -> PDU {
+        let pdu_type = match &payload {
+            PDUPayload::Directive(_) => PDUType::FileDirective,
+            PDUPayload::FileData(_) => PDUType::FileData,
+        };
+        let segment_metadata_flag = match &payload {
+            PDUPayload::FileData(FileDataPDU::Segmented(_)) => SegmentedData::Present,
+            _ => SegmentedData::NotPresent,
+        };
+        PDU {
+            header: PDUHeader {
+                version: U3::One,
+                pdu_type,
+                direction: Direction::ToReceiver,
+                transmission_mode: TransmissionMode::Acknowledged,
+                crc_flag,
+                large_file_flag,
+                pdu_data_field_length: payload.encoded_len(large_file_flag),
+                segmentation_control: SegmentationControl::NotPreserved,
+                segment_metadata_flag,
+                source_entity_id: VariableID::from(18_u16),
+                transaction_sequence_number: VariableID::from(7533_u32),
+                destination_entity_id: VariableID::from(23_u16),
+            },
+            payload,
+        }
+    }
+
+    fn file_data() -> PDUPayload {
+        PDUPayload::FileData(FileDataPDU::Unsegmented(UnsegmentedFileData {
+            offset: 16_u64,
+            file_data: "test some information".as_bytes().to_vec(),
+        }))
+    }
+
+    #[rstest]
+    #[case(file_data())]
+    #[case(PDUPayload::FileData(FileDataPDU::Segmented(SegmentedFileData {
+        record_continuation_state: RecordContinuationState::Last,
+        segment_metadata: vec![1, 2, 3],
+        offset: 757,
+        file_data: (33..57).collect(),
+    })))]
+    #[case(PDUPayload::Directive(Operations::EoF(EndOfFile {
+        condition: Condition::NoError,
+        checksum: 123749_u32,
+        file_size: 7738949_u64,
+        fault_location: None,
+    })))]
+    fn round_trip(
+        #[case] payload: PDUPayload,
+        #[values(CRCFlag::NotPresent, CRCFlag::Present)] crc_flag: CRCFlag,
+        #[values(FileSizeFlag::Small, FileSizeFlag::Large)] large_file_flag: FileSizeFlag,
+    ) -> PDUResult<()> {
+        let expected = pdu(payload, crc_flag, large_file_flag);
+        let encoded = expected.clone().encode();
+
+        let mut buffer = [0_u8; 128];
+        let written = expected.encode_into(&mut buffer)?;
+        assert_eq!(encoded.as_slice(), &buffer[..written]);
+        assert_eq!(written, expected.checked_len()?);
+
+        // trailing bytes are not part of the PDU.
+        let (view, read) = PDUView::decode(&buffer)?;
+        assert_eq!(written, read);
+
+        let mut reencoded = [0_u8; 128];
+        assert_eq!(written, view.encode_into(&mut reencoded)?);
+        assert_eq!(&buffer[..written], &reencoded[..written]);
+
+        assert_eq!(expected, PDU::from(view));
+        Ok(())
+    }
+
+    #[test]
+    fn borrows_file_data() -> PDUResult<()> {
+        let encoded = pdu(file_data(), CRCFlag::Present, FileSizeFlag::Small).encode();
+        let (view, _) = PDUView::decode(&encoded)?;
+        let PayloadView::FileData(data) = view.payload else {
+            panic!("expected file data, got {:?}", view.payload)
+        };
+        assert_eq!(16, data.offset());
+        assert_eq!(b"test some information", data.file_data());
+        assert!(encoded.as_ptr_range().contains(&data.file_data().as_ptr()));
+        Ok(())
+    }
+
+    #[test]
+    fn crc_checked_before_parsing() {
+        let pdu = pdu(
+            PDUPayload::Directive(Operations::EoF(EndOfFile {
+                condition: Condition::NoError,
+                checksum: 1_u32,
+                file_size: 2_u64,
+                fault_location: None,
+            })),
+            CRCFlag::Present,
+            FileSizeFlag::Small,
+        );
+        let header_len = pdu.header.encoded_len() as usize;
+        let mut encoded = pdu.encode();
+        // an unknown directive code would fail to parse.
+        encoded[header_len] = 0xFF;
+        assert!(matches!(
+            PDUView::decode(&encoded),
+            Err(PDUError::CRCFailure(..))
+        ));
+    }
+
+    #[test]
+    fn short_buffers() {
+        let encoded = pdu(file_data(), CRCFlag::Present, FileSizeFlag::Large).encode();
+        let len = encoded.len();
+        assert!(matches!(
+            PDUView::decode(&encoded[..len - 1]),
+            Err(PDUError::BufferTooShort(required, available)) if required == len && available == len - 1
+        ));
+
+        let pdu = pdu(file_data(), CRCFlag::Present, FileSizeFlag::Large);
+        let mut buffer = vec![0_u8; len - 1];
+        assert!(matches!(
+            pdu.encode_into(&mut buffer),
+            Err(PDUError::BufferTooShort(required, available)) if required == len && available == len - 1
+        ));
+    }
+
+    #[test]
+    fn oversize_data_field() {
+        let message = MetadataTLV::MessageToUser(MessageToUser {
+            message_text: vec![0x5A; 250],
+        });
+        let pdu = pdu(
+            metadata(vec![message; 300]),
+            CRCFlag::Present,
+            FileSizeFlag::Small,
+        );
+        // lengths saturate instead of wrapping around.
+        assert_eq!(u16::MAX, pdu.encoded_len());
+        assert!(matches!(
+            pdu.checked_len(),
+            Err(PDUError::DataFieldTooLarge(len)) if len == 300 * 252 + 33 + 2
+        ));
+        assert!(matches!(
+            pdu.encode_into(&mut vec![0_u8; 100_000]),
+            Err(PDUError::DataFieldTooLarge(_))
+        ));
+    }
+
+    #[rstest]
+    #[case(0)]
+    #[case(5)]
+    #[case(u16::MAX)]
+    fn wrong_header_length(
+        #[case] pdu_data_field_length: u16,
+        #[values(CRCFlag::NotPresent, CRCFlag::Present)] crc_flag: CRCFlag,
+    ) -> PDUResult<()> {
+        let expected = pdu(file_data(), crc_flag, FileSizeFlag::Small);
+        let mut wrong = expected.clone();
+        wrong.header.pdu_data_field_length = pdu_data_field_length;
+
+        // the length written is that of the payload, not the one the header holds.
+        let mut buffer = [0_u8; 128];
+        let written = wrong.encode_into(&mut buffer)?;
+        assert_eq!(expected.clone().encode().as_slice(), &buffer[..written]);
+        assert_eq!(expected, PDU::from(PDUView::decode(&buffer)?.0));
+
+        // encoding the header alone neither panics nor wraps around.
+        let header = wrong.header.encode();
+        let expected_len = match (crc_flag, pdu_data_field_length) {
+            (CRCFlag::Present, u16::MAX) => u16::MAX,
+            (CRCFlag::Present, len) => len + 2,
+            (CRCFlag::NotPresent, len) => len,
+        };
+        assert_eq!(expected_len.to_be_bytes(), header[1..3]);
+        Ok(())
+    }
+
+    #[test]
+    fn decode_verifies_received_bytes() {
+        let mut encoded = pdu(file_data(), CRCFlag::Present, FileSizeFlag::Small).encode();
+        // corrupting the header is caught before anything is parsed.
+        encoded[4] ^= 0x01;
+        assert!(matches!(
+            PDU::decode(&mut encoded.as_slice()),
+            Err(PDUError::CRCFailure(..))
+        ));
+    }
+
+    #[rstest]
+    #[case(
+        PDUPayload::Directive(Operations::Metadata(MetadataPDU {
+            closure_requested: true,
+            checksum_type: ChecksumType::Null,
+            file_size: 55,
+            source_filename: "s".repeat(256).into(),
+            destination_filename: "the/destination".into(),
+            options: vec![],
+        })),
+        "Source filename",
+        256,
+        255
+    )]
+    #[case(
+        metadata(vec![MetadataTLV::MessageToUser(MessageToUser {
+            message_text: vec![0x5A; 300],
+        })]),
+        "Message to user",
+        300,
+        255
+    )]
+    #[case(
+        PDUPayload::FileData(FileDataPDU::Segmented(SegmentedFileData {
+            record_continuation_state: RecordContinuationState::Last,
+            segment_metadata: vec![0; 64],
+            offset: 0,
+            file_data: vec![1, 2, 3],
+        })),
+        "Segment metadata",
+        64,
+        63
+    )]
+    fn length_value_too_long(
+        #[case] payload: PDUPayload,
+        #[case] name: &str,
+        #[case] expected_len: usize,
+        #[case] expected_max: usize,
+    ) {
+        let pdu = pdu(payload, CRCFlag::Present, FileSizeFlag::Small);
+        let too_long = |result: PDUResult<usize>| {
+            matches!(
+                result,
+                Err(PDUError::LengthValueTooLong(field, len, max))
+                    if field == name && len == expected_len && max == expected_max
+            )
+        };
+        assert!(too_long(pdu.checked_len()));
+        assert!(too_long(pdu.encode_into(&mut [0_u8; 1024])));
+    }
+
+    fn legacy(mut pdu: PDU) -> PDU {
+        pdu.header.version = U3::Zero;
+        pdu
+    }
+
+    fn metadata(options: Vec<MetadataTLV>) -> PDUPayload {
+        PDUPayload::Directive(Operations::Metadata(MetadataPDU {
+            closure_requested: true,
+            checksum_type: ChecksumType::Null,
+            file_size: 55,
+            source_filename: "the/source".into(),
+            destination_filename: "the/destination".into(),
+            options,
+        }))
+    }
+
+    #[test]
+    fn legacy_metadata() -> PDUResult<()> {
+        let pdu = legacy(pdu(metadata(vec![]), CRCFlag::Present, FileSizeFlag::Small));
+        let mut buffer = [0_u8; 128];
+        let written = pdu.encode_into(&mut buffer)?;
+        // the closure request and checksum type are reserved bits in legacy Metadata.
+        assert_eq!(0, buffer[pdu.header.encoded_len() as usize + 1]);
+
+        let (view, _) = PDUView::decode(&buffer[..written])?;
+        assert_eq!(ProtocolVersion::Legacy, view.header.protocol_version());
+        let PayloadView::Directive(Operations::Metadata(metadata)) = view.payload else {
+            panic!("expected metadata, got {:?}", view.payload)
+        };
+        assert!(!metadata.closure_requested);
+        assert_eq!(ChecksumType::Modular, metadata.checksum_type);
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_rejects_newer_directives() {
+        let keep_alive = PDUPayload::Directive(Operations::KeepAlive(KeepAlivePDU { progress: 4 }));
+        let mut encoded =
+            pdu(keep_alive.clone(), CRCFlag::NotPresent, FileSizeFlag::Small).encode();
+        encoded[0] &= 0x1F;
+        assert!(matches!(
+            PDUView::decode(&encoded),
+            Err(PDUError::LegacyDirective(PDUDirective::KeepAlive))
+        ));
+
+        let pdu = legacy(pdu(keep_alive, CRCFlag::NotPresent, FileSizeFlag::Small));
+        assert!(matches!(
+            pdu.encode_into(&mut [0_u8; 128]),
+            Err(PDUError::LegacyDirective(PDUDirective::KeepAlive))
+        ));
+    }
+
+    #[test]
+    fn legacy_rejects_newer_options() {
+        let pdu = legacy(pdu(
+            metadata(vec![MetadataTLV::EntityID(VariableID::from(1_u8))]),
+            CRCFlag::NotPresent,
+            FileSizeFlag::Small,
+        ));
+        assert!(matches!(
+            pdu.encode_into(&mut [0_u8; 128]),
+            Err(PDUError::LegacyTLV(MetadataTLVFieldCode::EntityID))
+        ));
+    }
+
+    #[test]
+    fn legacy_rejects_large_files() {
+        let mut encoded = pdu(file_data(), CRCFlag::NotPresent, FileSizeFlag::Large).encode();
+        encoded[0] &= 0x1F;
+        assert!(matches!(
+            PDUView::decode(&encoded),
+            Err(PDUError::InvalidFileSizeFlag(1))
+        ));
+    }
+}
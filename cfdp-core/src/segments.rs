@@ -1,6 +1,9 @@
-use std::cmp::Ordering;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
 
 /// Holds a list of disjunctive [start, end) segments covering the received file
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Default)]
 pub struct Segments(Vec<(u64, u64)>);
 impl Segments {
     pub fn new() -> Self {
@@ -12,6 +15,11 @@ impl Segments {
         self.0.len()
     }
 
+    /// return true if nothing has been received yet
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     /// return the end of the last segment or None if there is no segment
     pub fn end(&self) -> Option<u64> {
         self.0.last().map(|x| x.1)
@@ -130,7 +138,7 @@ impl Segments {
                 if k == 0 {
                     start
                 } else {
-                    std::cmp::max(v[k - 1].1, start)
+                    core::cmp::max(v[k - 1].1, start)
                 },
             ),
         };
@@ -1,16 +1,19 @@
-use std::{collections::HashMap, fmt};
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::fmt;
 
-use camino::Utf8PathBuf;
 use num_derive::FromPrimitive;
 
 use crate::{
     filestore::ChecksumType,
     pdu::{
         CRCFlag, Condition, EntityID, FaultHandlerAction, FileSizeFlag, FileStoreRequest,
-        MessageToUser, SegmentedData, TransactionSeqNum, TransmissionMode, VariableID,
+        MessageToUser, ProtocolVersion, SegmentedData, TransactionSeqNum, TransmissionMode,
+        VariableID,
     },
+    Utf8PathBuf,
 };
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct TransactionID(pub EntityID, pub TransactionSeqNum);
 
@@ -32,6 +35,7 @@ impl TransactionID {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, FromPrimitive)]
 pub enum TransactionState {
     Active,
@@ -39,6 +43,7 @@ pub enum TransactionState {
     Terminated,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct Metadata {
     /// Bytes of the source filename, can be null if length is 0.
@@ -57,6 +62,7 @@ pub struct Metadata {
     pub checksum_type: ChecksumType,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone)]
 pub struct TransactionConfig {
     /// Identification number of the source (this) entity. See [EntityID]
@@ -71,10 +77,12 @@ pub struct TransactionConfig {
     pub file_size_flag: FileSizeFlag,
     /// A Mapping of actions to take when each condition is reached.
     /// See also [Condition] and [FaultHandlerAction].
-    pub fault_handler_override: HashMap<Condition, FaultHandlerAction>,
+    pub fault_handler_override: BTreeMap<Condition, FaultHandlerAction>,
     /// The maximum length a file segment sent to Destination can be.
     /// u16 will be larger than any possible CCSDS packet size.
     pub file_size_segment: u16,
+    /// The longest PDU, header and CRC included, exchanged with the remote entity.
+    pub max_pdu_len: Option<usize>,
     /// Flag indicating whether or not CRCs will be present on the PDUs
     pub crc_flag: CRCFlag,
     /// Flag indicating whether file metadata is included with FileData
@@ -87,6 +95,8 @@ pub struct TransactionConfig {
     pub nak_timeout: i64,
     /// Maximum amount timeof without activity before the ACK timer increments its count.
     pub ack_timeout: i64,
+    /// The protocol revision spoken with the remote entity.
+    pub protocol_version: ProtocolVersion,
 }
 
 #[cfg(test)]
@@ -97,7 +107,14 @@ mod test {
     fn id_from_u8() {
         let id = TransactionID::from(3_u8, 5_u8);
 
-        assert_eq!(TransactionID(VariableID::U8(3), VariableID::U8(5)), id)
+        assert_eq!(
+            TransactionID(
+                VariableID::new(3, 1).unwrap(),
+                VariableID::new(5, 1).unwrap()
+            ),
+            id
+        );
+        assert_eq!((1, 1), (id.0.width(), id.1.width()))
     }
 
     #[test]
@@ -105,9 +122,13 @@ mod test {
         let id = TransactionID::from(13_u16, 541_u16);
 
         assert_eq!(
-            TransactionID(VariableID::U16(13_u16), VariableID::U16(541_u16)),
+            TransactionID(
+                VariableID::new(13, 2).unwrap(),
+                VariableID::new(541, 2).unwrap()
+            ),
             id,
-        )
+        );
+        assert_eq!((2, 2), (id.0.width(), id.1.width()))
     }
 
     #[test]
@@ -115,22 +136,40 @@ mod test {
         let id = TransactionID::from(13_u32, 541_u32);
 
         assert_eq!(
-            TransactionID(VariableID::U32(13_u32), VariableID::U32(541_u32)),
+            TransactionID(
+                VariableID::new(13, 4).unwrap(),
+                VariableID::new(541, 4).unwrap()
+            ),
             id,
-        )
+        );
+        assert_eq!((4, 4), (id.0.width(), id.1.width()))
     }
 
     #[test]
     fn id_from_u64() {
         let id = TransactionID::from(13_u64, 541_u64);
 
-        assert_eq!(TransactionID(VariableID::U64(13), VariableID::U64(541)), id,)
+        assert_eq!(
+            TransactionID(
+                VariableID::new(13, 8).unwrap(),
+                VariableID::new(541, 8).unwrap()
+            ),
+            id,
+        );
+        assert_eq!((8, 8), (id.0.width(), id.1.width()))
     }
 
     #[test]
     fn id_from_mixed() {
         let id = TransactionID::from(13_u8, 541_u64);
 
-        assert_eq!(TransactionID(VariableID::U8(13), VariableID::U64(541)), id,)
+        assert_eq!(
+            TransactionID(
+                VariableID::new(13, 1).unwrap(),
+                VariableID::new(541, 8).unwrap()
+            ),
+            id,
+        );
+        assert_eq!((1, 8), (id.0.width(), id.1.width()))
     }
 }
@@ -0,0 +1,200 @@
+use std::{
+    fs,
+    io::{self, Error as IoError, ErrorKind, Read},
+    process::ExitCode,
+};
+
+use camino::Utf8PathBuf;
+use cfdp_daemon::{
+    capture::{read_pcap, CaptureDirection},
+    cfdp_core::pdu::{Dissection, PDUError},
+};
+use clap::{Parser, ValueEnum};
+use serde_json::json;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Decode captured CFDP PDUs and print their fields.")]
+struct Args {
+    /// File holding the PDUs, standard input if omitted.
+    input: Option<Utf8PathBuf>,
+
+    /// Encoding of the input.
+    #[arg(short, long, value_enum, default_value_t = Format::Auto)]
+    format: Format,
+
+    /// Print each PDU as a line of JSON.
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Detect pcap captures and hex dumps, anything else is read as raw.
+    Auto,
+    /// Hexadecimal text, each line holds one or more PDUs.
+    Hex,
+    /// Binary PDUs back to back.
+    Raw,
+    /// A libpcap capture of UDP datagrams or user link type frames.
+    Pcap,
+}
+impl Format {
+    fn detect(input: &[u8]) -> Self {
+        let magic = input
+            .get(..4)
+            .map(|magic| [magic[0], magic[1], magic[2], magic[3]]);
+        match magic {
+            Some([0xa1, 0xb2, 0xc3, 0xd4] | [0xd4, 0xc3, 0xb2, 0xa1])
+            | Some([0xa1, 0xb2, 0x3c, 0x4d] | [0x4d, 0x3c, 0xb2, 0xa1]) => Self::Pcap,
+            _ if !input.is_empty() && input.iter().all(|byte| is_hex_text(*byte)) => Self::Hex,
+            _ => Self::Raw,
+        }
+    }
+}
+
+fn is_hex_text(byte: u8) -> bool {
+    byte.is_ascii_hexdigit() || byte.is_ascii_whitespace() || matches!(byte, b'x' | b'X' | b':')
+}
+
+/// A contiguous run of bytes holding PDUs and where it came from.
+struct Chunk {
+    source: String,
+    bytes: Vec<u8>,
+}
+
+fn parse_hex(input: &[u8]) -> Result<Vec<Chunk>, IoError> {
+    let text =
+        std::str::from_utf8(input).map_err(|err| IoError::new(ErrorKind::InvalidData, err))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let digits: String = line
+                .split_whitespace()
+                .map(|word| word.trim_start_matches("0x").trim_start_matches("0X"))
+                .collect::<String>()
+                .replace(':', "");
+            if digits.len() % 2 != 0 {
+                return Err(IoError::new(
+                    ErrorKind::InvalidData,
+                    format!("line {} holds an odd number of hex digits.", index + 1),
+                ));
+            }
+            let bytes = (0..digits.len())
+                .step_by(2)
+                .map(|start| u8::from_str_radix(&digits[start..start + 2], 16))
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|err| {
+                    IoError::new(ErrorKind::InvalidData, format!("line {}: {err}", index + 1))
+                })?;
+            Ok(Chunk {
+                source: format!("line {}", index + 1),
+                bytes,
+            })
+        })
+        .collect()
+}
+
+fn read_chunks(input: Vec<u8>, format: Format) -> Result<Vec<Chunk>, IoError> {
+    match format {
+        Format::Auto => {
+            let format = Format::detect(&input);
+            read_chunks(input, format)
+        }
+        Format::Hex => parse_hex(&input),
+        Format::Raw => Ok(vec![Chunk {
+            source: "input".to_owned(),
+            bytes: input,
+        }]),
+        Format::Pcap => Ok(read_pcap(&input)
+            .map_err(|err| IoError::new(ErrorKind::InvalidData, err))?
+            .into_iter()
+            .map(|packet| Chunk {
+                source: format!(
+                    "record {}{} at {}.{:06}",
+                    packet.record,
+                    match packet.direction {
+                        Some(CaptureDirection::Sent) => " (sent)",
+                        Some(CaptureDirection::Received) => " (received)",
+                        None => "",
+                    },
+                    packet.timestamp.as_secs(),
+                    packet.timestamp.subsec_micros()
+                ),
+                bytes: packet.payload,
+            })
+            .collect()),
+    }
+}
+
+fn print(index: usize, source: &str, offset: usize, dissection: &Dissection, json: bool) {
+    if json {
+        let mut line = json!({
+            "index": index,
+            "source": source,
+            "offset": offset,
+            "len": dissection.len,
+            "fields": dissection.fields,
+        });
+        if let Some(error) = &dissection.error {
+            line["error"] = json!(error.to_string());
+        }
+        if let Some(PDUError::CRCFailure(expected, received)) = dissection.error {
+            line["crc_failure"] = json!({ "expected": expected, "received": received });
+        }
+        println!("{line}");
+        return;
+    }
+
+    println!(
+        "PDU {index}: {} bytes at {source}, offset {offset}",
+        dissection.len
+    );
+    for field in &dissection.fields {
+        println!("{field}");
+    }
+    match &dissection.error {
+        Some(error @ PDUError::CRCFailure(..)) => println!("CRC FAILURE: {error}"),
+        Some(error) => println!("INVALID: {error}"),
+        None => {}
+    }
+    println!();
+}
+
+fn run(args: Args) -> Result<bool, IoError> {
+    let input = match &args.input {
+        Some(path) => fs::read(path)?,
+        None => {
+            let mut input = vec![];
+            io::stdin().read_to_end(&mut input)?;
+            input
+        }
+    };
+
+    let mut index = 0;
+    let mut valid = true;
+    for chunk in read_chunks(input, args.format)? {
+        let mut offset = 0;
+        while offset < chunk.bytes.len() {
+            index += 1;
+            let dissection = Dissection::decode(&chunk.bytes[offset..]);
+            print(index, &chunk.source, offset, &dissection, args.json);
+            valid &= dissection.error.is_none();
+            // a PDU occupies at least its header, guard against looping on nothing.
+            offset += dissection.len.max(1);
+        }
+    }
+    Ok(valid)
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
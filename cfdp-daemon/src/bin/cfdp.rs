@@ -0,0 +1,432 @@
+use std::{io::Error as IoError, process::ExitCode};
+
+use camino::Utf8PathBuf;
+use cfdp_daemon::{
+    cfdp_core::{
+        daemon::{IndicationKind, Priority},
+        pdu::{Condition, NakOrKeepAlive, TransmissionMode},
+    },
+    control::{
+        default_socket, ControlClient, ControlRequest, ControlResponse, HistoryRecord,
+        TransactionEvent, TransactionRef, TransactionReport, PROTOCOL_VERSION,
+    },
+    entity::TransportKind,
+};
+use clap::{Parser, Subcommand, ValueEnum};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Issue CFDP primitives to a running cfdp-daemon.")]
+struct Args {
+    /// Unix socket of the daemon's control interface.
+    #[arg(short, long, global = true, default_value_t = default_socket())]
+    socket: Utf8PathBuf,
+
+    /// Print each response as a line of JSON.
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Send a file to a remote entity.
+    Put {
+        /// Source file relative to the local filestore.
+        source: Utf8PathBuf,
+        /// Destination file relative to the remote filestore.
+        destination: Utf8PathBuf,
+        /// Entity ID of the receiving entity.
+        #[arg(short, long)]
+        to: u64,
+        /// Send the file without acknowledgements or retransmission.
+        #[arg(short, long)]
+        unacknowledged: bool,
+        /// Priority class, higher classes are sent first [default: configured for the remote entity].
+        #[arg(short, long)]
+        priority: Option<u8>,
+        /// Share of the transport relative to other transactions in the same class.
+        #[arg(long, requires = "priority", default_value_t = 1)]
+        weight: u16,
+        /// Whether the receiver of an unacknowledged transaction sends a Finished PDU
+        /// [default: configured for the remote entity].
+        #[arg(long)]
+        closure: Option<bool>,
+        #[command(flatten)]
+        completion: Completion,
+    },
+    /// Cancel a transaction.
+    Cancel { transaction: TransactionRef },
+    /// Suspend a transaction.
+    Suspend { transaction: TransactionRef },
+    /// Resume a suspended transaction.
+    Resume { transaction: TransactionRef },
+    /// Print the current status of a transaction.
+    Report { transaction: TransactionRef },
+    /// Wait for a running transaction to finish.
+    Wait {
+        transaction: TransactionRef,
+        /// Print every event of the transaction while waiting.
+        #[arg(short, long)]
+        follow: bool,
+    },
+    /// Ask the remote entity of a transaction for a NAK or Keep Alive.
+    Prompt {
+        transaction: TransactionRef,
+        #[arg(value_enum)]
+        option: PromptOption,
+    },
+    /// List the remote entities known to the daemon.
+    ListRemote,
+    /// Print the indications issued by the daemon.
+    Monitor {
+        /// Only indications of the given transaction, may be repeated.
+        #[arg(short, long = "transaction")]
+        transactions: Vec<TransactionRef>,
+        /// Only indications concerning the given remote entity, may be repeated.
+        #[arg(short, long = "entity")]
+        entities: Vec<u64>,
+        /// Only indications of the given kind, e.g. Finished, may be repeated.
+        #[arg(short, long = "kind", value_parser = parse_kind)]
+        kinds: Vec<IndicationKind>,
+    },
+    /// List finished transactions.
+    History {
+        /// Only the given transaction, may be repeated.
+        #[arg(short, long = "transaction")]
+        transactions: Vec<TransactionRef>,
+        /// Only transactions with the given remote entity, may be repeated.
+        #[arg(short, long = "entity")]
+        entities: Vec<u64>,
+        /// Only transactions which finished at or after this Unix time, in seconds.
+        #[arg(long)]
+        since: Option<u64>,
+        /// Only transactions which finished before this Unix time, in seconds.
+        #[arg(long)]
+        until: Option<u64>,
+    },
+}
+
+fn parse_kind(kind: &str) -> Result<IndicationKind, String> {
+    serde_json::from_value(serde_json::Value::String(kind.to_owned()))
+        .map_err(|_| format!("unknown indication kind {kind}"))
+}
+
+#[derive(Debug, clap::Args)]
+struct Completion {
+    /// Wait until the transaction has finished.
+    #[arg(short, long)]
+    wait: bool,
+    /// Print every event of the transaction until it has finished.
+    #[arg(short, long)]
+    follow: bool,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum PromptOption {
+    Nak,
+    KeepAlive,
+}
+impl From<PromptOption> for NakOrKeepAlive {
+    fn from(option: PromptOption) -> Self {
+        match option {
+            PromptOption::Nak => NakOrKeepAlive::Nak,
+            PromptOption::KeepAlive => NakOrKeepAlive::KeepAlive,
+        }
+    }
+}
+
+/// Which of the responses streamed while following a transaction are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    /// A single response is expected.
+    Single,
+    /// Only the start and the outcome of the transaction.
+    Outcome,
+    /// Every response.
+    All,
+    /// Every indication until the daemon stops.
+    Indications,
+}
+
+fn describe_event(event: &TransactionEvent) -> String {
+    match event {
+        TransactionEvent::Initiated => "initiated".to_owned(),
+        TransactionEvent::EofSent => "EOF sent".to_owned(),
+        TransactionEvent::EofReceived => "EOF received".to_owned(),
+        TransactionEvent::MetadataReceived {
+            source_filename,
+            destination_filename,
+            file_size,
+            ..
+        } => format!(
+            "receiving {source_filename} as {destination_filename}, file size {file_size} bytes"
+        ),
+        TransactionEvent::FileSegmentReceived { offset, length } => {
+            format!("received {length} bytes at offset {offset}")
+        }
+        TransactionEvent::Suspended { condition } => format!("suspended ({condition:?})"),
+        TransactionEvent::Resumed { progress } => format!("resumed at {progress} bytes"),
+        TransactionEvent::Fault {
+            condition,
+            progress,
+        } => format!("fault {condition:?} at {progress} bytes"),
+        TransactionEvent::Abandoned {
+            condition,
+            progress,
+        } => format!("abandoned after {condition:?} at {progress} bytes"),
+    }
+}
+
+fn describe_report(report: &TransactionReport) -> String {
+    format!(
+        "{}: {:?}, status {:?}, condition {:?}",
+        report.transaction, report.state, report.status, report.condition
+    )
+}
+
+fn describe_record(record: &HistoryRecord) -> String {
+    format!(
+        "{}: {} -> {} with {}, {} bytes in {:.1}s, {:?}, file {:?}, condition {:?}",
+        record.report.transaction,
+        record.source_filename,
+        record.destination_filename,
+        record.remote_entity_id,
+        record.file_size,
+        record.finished_ms.saturating_sub(record.started_ms) as f64 / 1000.0,
+        record.delivery_code,
+        record.file_status,
+        record.report.condition
+    )
+}
+
+fn describe(response: &ControlResponse) -> String {
+    match response {
+        ControlResponse::Hello {
+            version,
+            entity_id,
+            ..
+        } => format!("Entity {entity_id}, protocol version {version}."),
+        ControlResponse::Started { transaction } => format!("Started transaction {transaction}."),
+        ControlResponse::Accepted => "Accepted.".to_owned(),
+        ControlResponse::Report(report) => describe_report(report),
+        ControlResponse::Event { transaction, event } => {
+            format!("{transaction}: {}", describe_event(event))
+        }
+        ControlResponse::Finished {
+            transaction,
+            condition,
+            file_status,
+            delivery_code,
+            ..
+        } => format!(
+            "{transaction}: finished {delivery_code:?}, file {file_status:?}, condition {condition:?}"
+        ),
+        ControlResponse::Link { entity_id, state } => format!("link to {entity_id} {state:?}"),
+        ControlResponse::Subscribed => "Subscribed to indications.".to_owned(),
+        ControlResponse::Lagged { missed } => {
+            format!("Fell behind, {missed} indications were dropped.")
+        }
+        ControlResponse::Stopped { reports } => reports
+            .iter()
+            .map(describe_report)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        ControlResponse::Remotes { remotes } => remotes
+            .iter()
+            .map(|remote| {
+                let transport = match remote.transport {
+                    TransportKind::Udp => "udp",
+                };
+                format!("{}\t{transport}\t{}", remote.entity_id, remote.address)
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        ControlResponse::History { records } => records
+            .iter()
+            .map(describe_record)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        ControlResponse::Refused { refusal } => refusal.to_string(),
+        ControlResponse::Error { message } => message.clone(),
+    }
+}
+
+fn print(response: &ControlResponse, json: bool) -> Result<(), IoError> {
+    let line = match json {
+        true => serde_json::to_string(response)?,
+        false => describe(response),
+    };
+    match response {
+        ControlResponse::Refused { .. } if !json => eprintln!("refused: {line}"),
+        ControlResponse::Error { .. } if !json => eprintln!("error: {line}"),
+        // nothing to list.
+        _ if line.is_empty() => {}
+        _ => println!("{line}"),
+    }
+    Ok(())
+}
+
+/// Whether the last response received indicates success.
+fn succeeded(response: &ControlResponse) -> bool {
+    match response {
+        ControlResponse::Error { .. }
+        | ControlResponse::Refused { .. }
+        | ControlResponse::Event {
+            event: TransactionEvent::Abandoned { .. },
+            ..
+        } => false,
+        // without closure the sender of an unacknowledged transaction never learns the delivery code.
+        ControlResponse::Finished { condition, .. } => *condition == Condition::NoError,
+        _ => true,
+    }
+}
+
+async fn run(args: Args) -> Result<bool, IoError> {
+    let (request, output) = match args.command {
+        Command::Put {
+            source,
+            destination,
+            to,
+            unacknowledged,
+            priority,
+            weight,
+            closure,
+            completion,
+        } => (
+            ControlRequest::Put {
+                source_filename: source,
+                destination_filename: destination,
+                destination_entity_id: to,
+                transmission_mode: match unacknowledged {
+                    true => TransmissionMode::Unacknowledged,
+                    false => TransmissionMode::Acknowledged,
+                },
+                filestore_requests: vec![],
+                messages_to_user: vec![],
+                priority: priority.map(|class| Priority { class, weight }),
+                closure_requested: closure,
+                follow: completion.wait || completion.follow,
+            },
+            match (completion.wait, completion.follow) {
+                (_, true) => Output::All,
+                (true, false) => Output::Outcome,
+                (false, false) => Output::Single,
+            },
+        ),
+        Command::Cancel { transaction } => (ControlRequest::Cancel { transaction }, Output::Single),
+        Command::Suspend { transaction } => {
+            (ControlRequest::Suspend { transaction }, Output::Single)
+        }
+        Command::Resume { transaction } => (ControlRequest::Resume { transaction }, Output::Single),
+        Command::Report { transaction } => (ControlRequest::Report { transaction }, Output::Single),
+        Command::Wait {
+            transaction,
+            follow,
+        } => (
+            ControlRequest::Watch { transaction },
+            match follow {
+                true => Output::All,
+                false => Output::Outcome,
+            },
+        ),
+        Command::Prompt {
+            transaction,
+            option,
+        } => (
+            ControlRequest::Prompt {
+                transaction,
+                option: option.into(),
+            },
+            Output::Single,
+        ),
+        Command::ListRemote => (ControlRequest::ListRemote, Output::Single),
+        Command::Monitor {
+            transactions,
+            entities,
+            kinds,
+        } => (
+            ControlRequest::Subscribe {
+                transactions,
+                entities,
+                kinds,
+            },
+            Output::Indications,
+        ),
+        Command::History {
+            transactions,
+            entities,
+            since,
+            until,
+        } => (
+            ControlRequest::History {
+                transactions,
+                entities,
+                since_ms: since.map(|seconds| seconds.saturating_mul(1000)),
+                until_ms: until.map(|seconds| seconds.saturating_mul(1000)),
+            },
+            Output::Single,
+        ),
+    };
+
+    let mut client = ControlClient::connect(&args.socket).await?;
+    match client
+        .request(&ControlRequest::Hello {
+            version: PROTOCOL_VERSION,
+        })
+        .await?
+    {
+        ControlResponse::Hello { .. } => {}
+        response => {
+            print(&response, args.json)?;
+            return Ok(false);
+        }
+    }
+
+    let mut response = client.request(&request).await?;
+    loop {
+        let last = match output {
+            Output::Single => true,
+            Output::Indications => matches!(response, ControlResponse::Error { .. }),
+            Output::Outcome | Output::All => response.is_final(),
+        };
+        let shown = match output {
+            Output::Outcome => {
+                last || matches!(
+                    response,
+                    ControlResponse::Started { .. } | ControlResponse::Error { .. }
+                )
+            }
+            _ => true,
+        };
+        if shown {
+            print(&response, args.json)?;
+        }
+        if last {
+            return Ok(succeeded(&response));
+        }
+        response = client.next_response().await?;
+    }
+}
+
+#[cfg(not(unix))]
+fn main() -> ExitCode {
+    eprintln!("The control interface requires Unix domain sockets.");
+    ExitCode::FAILURE
+}
+
+#[cfg(unix)]
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    let socket = args.socket.clone();
+    match run(args).await {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("error: unable to reach the daemon at {socket}: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,618 @@
+use std::{collections::HashMap, sync::Arc};
+
+use cfdp_core::{
+    daemon::{EntityConfig, Indication, UserPrimitive},
+    filestore::FileStore,
+    pdu::{EntityID, PDUEncode, TransactionSeqNum, FIXED_HEADER_LEN},
+};
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::{
+    capture::Recorder,
+    error::{ConfigError, ConfigResult},
+    handle::DaemonHandle,
+    history::{TransactionHistory, DEFAULT_HISTORY_CAPACITY},
+    indication::{IndicationBroadcast, DEFAULT_INDICATION_CAPACITY},
+    suspend::SuspendStore,
+    transport::PDUTransport,
+    Daemon,
+};
+
+// primitives queued for the Daemon before a DaemonHandle waits.
+const HANDLE_PRIMITIVE_CAPACITY: usize = 100;
+
+// offsets are 8 bytes wide when transferring files larger than 4 GiB.
+const LARGE_OFFSET_LEN: usize = 8;
+
+/// Everything a [Daemon] is constructed from.
+///
+/// The [DaemonBuilder] assembles and validates the parts,
+/// [Daemon::new] takes them as they are.
+pub struct DaemonParts<T: FileStore + Send + Sync + 'static> {
+    pub entity_id: EntityID,
+    /// The first sequence number used for transactions initiated by this entity.
+    pub sequence_num: TransactionSeqNum,
+    /// Each transport along with the remote entities it is used to communicate with.
+    pub transports: Vec<(Vec<EntityID>, Box<dyn PDUTransport + Send>)>,
+    pub filestore: Arc<T>,
+    /// Configuration specific to a single remote entity.
+    pub entity_configs: HashMap<EntityID, EntityConfig>,
+    /// Configuration used for any remote entity without its own [EntityConfig].
+    pub default_config: EntityConfig,
+    /// The channel the User sends primitives on.
+    pub primitive_rx: Receiver<UserPrimitive>,
+    /// Where indications are published to.
+    pub indications: IndicationBroadcast,
+    /// Where records of finished transactions are kept.
+    pub history: TransactionHistory,
+    /// Where a [SuspendAll](cfdp_core::daemon::ShutdownMode::SuspendAll) shutdown saves the running transactions.
+    pub suspend_store: Option<SuspendStore>,
+}
+
+/// Constructs a [Daemon] after validating its configuration.
+///
+/// Problems such as a zero timeout or an entity without a transport are reported
+/// as a [ConfigError] before any transport is started,
+/// instead of surfacing as stalled or malformed transactions at runtime.
+pub struct DaemonBuilder<T: FileStore + Send + Sync + 'static> {
+    entity_id: EntityID,
+    filestore: Arc<T>,
+    sequence_num: TransactionSeqNum,
+    transports: Vec<(Vec<EntityID>, Box<dyn PDUTransport + Send>)>,
+    entity_configs: HashMap<EntityID, EntityConfig>,
+    default_config: Option<EntityConfig>,
+    primitive_rx: Option<Receiver<UserPrimitive>>,
+    indication_tx: Option<Sender<Indication>>,
+    indication_capacity: usize,
+    history: Option<TransactionHistory>,
+    suspend_store: Option<SuspendStore>,
+    capture: Option<Recorder>,
+}
+impl<T: FileStore + Send + Sync + 'static> DaemonBuilder<T> {
+    pub fn new(entity_id: EntityID, filestore: Arc<T>) -> Self {
+        Self {
+            entity_id,
+            filestore,
+            sequence_num: TransactionSeqNum::from(0_u16),
+            transports: vec![],
+            entity_configs: HashMap::new(),
+            default_config: None,
+            primitive_rx: None,
+            indication_tx: None,
+            indication_capacity: DEFAULT_INDICATION_CAPACITY,
+            history: None,
+            suspend_store: None,
+            capture: None,
+        }
+    }
+
+    /// The first sequence number used for transactions initiated by this entity.
+    /// Defaults to a 16 bit zero.
+    pub fn sequence_num(mut self, sequence_num: TransactionSeqNum) -> Self {
+        self.sequence_num = sequence_num;
+        self
+    }
+
+    /// Add a transport used to communicate with the given remote entities.
+    pub fn transport(
+        mut self,
+        entities: Vec<EntityID>,
+        transport: Box<dyn PDUTransport + Send>,
+    ) -> Self {
+        self.transports.push((entities, transport));
+        self
+    }
+
+    /// Configuration specific to a single remote entity.
+    pub fn entity_config(mut self, entity: EntityID, config: EntityConfig) -> Self {
+        self.entity_configs.insert(entity, config);
+        self
+    }
+
+    /// Configuration used for any remote entity without its own [EntityConfig].
+    pub fn default_config(mut self, config: EntityConfig) -> Self {
+        self.default_config = Some(config);
+        self
+    }
+
+    /// The channels connecting the Daemon to the User.
+    /// Every indication is forwarded to `indication_tx`.
+    pub fn user(
+        mut self,
+        primitive_rx: Receiver<UserPrimitive>,
+        indication_tx: Sender<Indication>,
+    ) -> Self {
+        self.primitive_rx = Some(primitive_rx);
+        self.indication_tx = Some(indication_tx);
+        self
+    }
+
+    /// The channel the User sends primitives on,
+    /// for Users which only [subscribe](Daemon::subscribe) to indications.
+    pub fn primitives(mut self, primitive_rx: Receiver<UserPrimitive>) -> Self {
+        self.primitive_rx = Some(primitive_rx);
+        self
+    }
+
+    /// Number of indications retained for subscribers which fall behind.
+    /// Defaults to [DEFAULT_INDICATION_CAPACITY].
+    pub fn indication_capacity(mut self, capacity: usize) -> Self {
+        self.indication_capacity = capacity;
+        self
+    }
+
+    /// Where records of finished transactions are kept.
+    /// Defaults to the [DEFAULT_HISTORY_CAPACITY] most recent transactions held in memory.
+    pub fn history(mut self, history: TransactionHistory) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Where a [SuspendAll](cfdp_core::daemon::ShutdownMode::SuspendAll) shutdown saves
+    /// the running transactions. Any transactions already saved in the store are restored,
+    /// still suspended, once the Daemon starts managing transactions.
+    /// Without a store such shutdowns are refused.
+    pub fn suspend_store(mut self, store: SuspendStore) -> Self {
+        self.suspend_store = Some(store);
+        self
+    }
+
+    /// Record every PDU sent and received over any transport.
+    pub fn capture(mut self, recorder: Recorder) -> Self {
+        self.capture = Some(recorder);
+        self
+    }
+
+    /// Check the configuration without constructing the [Daemon].
+    pub fn validate(&self) -> ConfigResult<()> {
+        let default_config = self
+            .default_config
+            .as_ref()
+            .ok_or(ConfigError::Missing("default entity configuration"))?;
+        if self.primitive_rx.is_none() {
+            return Err(ConfigError::Missing("user primitive channel"));
+        }
+        if self.indication_capacity == 0 {
+            return Err(ConfigError::ZeroIndicationCapacity);
+        }
+
+        validate_entity_config(|| "the default configuration".to_owned(), default_config)?;
+        for (entity, config) in self.entity_configs.iter() {
+            validate_entity_config(|| format!("entity {entity}"), config)?;
+        }
+
+        let mut served: Vec<EntityID> = vec![];
+        for (entities, transport) in self.transports.iter() {
+            for entity in entities {
+                if served.contains(entity) {
+                    return Err(ConfigError::DuplicateTransport(*entity));
+                }
+                served.push(*entity);
+
+                let config = self.entity_configs.get(entity).unwrap_or(default_config);
+                // the tighter of the transport's and the configured limits applies.
+                let max_pdu_len = [transport.max_pdu_len(), config.max_pdu_len]
+                    .into_iter()
+                    .flatten()
+                    .min();
+                if let Some(max_pdu_len) = max_pdu_len {
+                    let header_len =
+                        max_header_len(&self.entity_id, entity, &self.sequence_num, config);
+                    let pdu_len = header_len
+                        + LARGE_OFFSET_LEN
+                        + usize::from(config.file_size_segment)
+                        + usize::from(config.crc_flag.encoded_len());
+                    if pdu_len > max_pdu_len {
+                        return Err(ConfigError::SegmentTooLarge {
+                            entity: *entity,
+                            pdu_len,
+                            max_pdu_len,
+                        });
+                    }
+                    // directive code, scope and a single segment request.
+                    let nak_len = header_len
+                        + 1
+                        + 4 * LARGE_OFFSET_LEN
+                        + usize::from(config.crc_flag.encoded_len());
+                    if nak_len > max_pdu_len {
+                        return Err(ConfigError::PDULengthTooShort {
+                            entity: *entity,
+                            nak_len,
+                            max_pdu_len,
+                        });
+                    }
+                }
+            }
+        }
+
+        // a configuration for the local entity itself does not need a transport.
+        match self
+            .entity_configs
+            .keys()
+            .find(|entity| **entity != self.entity_id && !served.contains(entity))
+        {
+            Some(entity) => Err(ConfigError::NoTransport(*entity)),
+            None => Ok(()),
+        }
+    }
+
+    /// Validate the configuration and construct the [Daemon].
+    ///
+    /// Transports are started immediately, so this must be called from within a tokio runtime.
+    pub fn build(self) -> ConfigResult<Daemon<T>> {
+        self.validate()?;
+
+        let (Some(default_config), Some(primitive_rx)) = (self.default_config, self.primitive_rx)
+        else {
+            unreachable!("presence checked during validation");
+        };
+
+        let indications = match self.indication_tx {
+            Some(indication_tx) => {
+                IndicationBroadcast::with_relay(self.indication_capacity, indication_tx)
+            }
+            None => IndicationBroadcast::new(self.indication_capacity),
+        };
+        Ok(Daemon::new(DaemonParts {
+            entity_id: self.entity_id,
+            sequence_num: self.sequence_num,
+            transports: self
+                .transports
+                .into_iter()
+                .map(|(entities, transport)| match &self.capture {
+                    Some(recorder) => (
+                        entities,
+                        Box::new(recorder.wrap(transport)) as Box<dyn PDUTransport + Send>,
+                    ),
+                    None => (entities, transport),
+                })
+                .collect(),
+            filestore: self.filestore,
+            entity_configs: self.entity_configs,
+            default_config,
+            primitive_rx,
+            indications,
+            history: self
+                .history
+                .unwrap_or_else(|| TransactionHistory::new(DEFAULT_HISTORY_CAPACITY)),
+            suspend_store: self.suspend_store,
+        }))
+    }
+
+    /// Validate the configuration and construct the [Daemon] along with a [DaemonHandle] to drive it.
+    ///
+    /// The handle replaces any primitive channel given to [user](Self::user) or [primitives](Self::primitives).
+    pub fn build_with_handle(mut self) -> ConfigResult<(Daemon<T>, DaemonHandle)> {
+        let (primitive_tx, primitive_rx) = channel(HANDLE_PRIMITIVE_CAPACITY);
+        self.primitive_rx = Some(primitive_rx);
+        let daemon = self.build()?;
+        let handle = DaemonHandle::new(primitive_tx, daemon.indications(), daemon.history());
+        Ok((daemon, handle))
+    }
+}
+
+pub(crate) fn validate_entity_config(
+    label: impl Fn() -> String,
+    config: &EntityConfig,
+) -> ConfigResult<()> {
+    for (timer, value) in [
+        ("inactivity", config.inactivity_timeout),
+        ("ACK", config.ack_timeout),
+        ("NAK", config.nak_timeout),
+    ] {
+        if value <= 0 {
+            return Err(ConfigError::InvalidTimeout {
+                entity: label(),
+                timer,
+                value,
+            });
+        }
+    }
+    if config.file_size_segment == 0 {
+        return Err(ConfigError::ZeroSegmentSize(label()));
+    }
+    // a bucket which never refills would hold every PDU forever.
+    for (limit, rate_limit) in [
+        ("entity", config.rate_limit),
+        ("transaction", config.transaction_rate_limit),
+    ] {
+        if rate_limit.is_some_and(|rate_limit| rate_limit.bytes_per_second == 0) {
+            return Err(ConfigError::ZeroRate {
+                entity: label(),
+                limit,
+            });
+        }
+    }
+    match config.id_width {
+        Some(width) if !(1..=8).contains(&width) => Err(ConfigError::InvalidIDWidth(width)),
+        _ => Ok(()),
+    }
+}
+
+/// The encoded length of the largest PDU header sent with the given configuration.
+fn max_header_len(
+    entity_id: &EntityID,
+    remote_id: &EntityID,
+    sequence_num: &TransactionSeqNum,
+    config: &EntityConfig,
+) -> usize {
+    // both IDs are widened to the widest of them.
+    let id_width = entity_id
+        .width()
+        .max(remote_id.width())
+        .max(config.id_width.unwrap_or_default());
+    FIXED_HEADER_LEN + 2 * usize::from(id_width) + usize::from(sequence_num.encoded_len())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::BTreeMap,
+        io::{Error as IoError, ErrorKind},
+        time::Duration,
+    };
+
+    use async_trait::async_trait;
+    use cfdp_core::{
+        daemon::{
+            NakProcedure, PrimitiveError, Priority, RateLimit, Scheduling, ShutdownMode,
+            VersionNegotiation,
+        },
+        filestore::{ChecksumType, NativeFileStore},
+        pdu::{CRCFlag, FaultHandlerAction, VariableID, PDU},
+        transaction::TransactionID,
+    };
+    use rstest::{fixture, rstest};
+    use tokio::sync::mpsc::channel;
+
+    use super::*;
+    use crate::error::HandleError;
+
+    struct LimitedTransport(Option<usize>);
+    #[async_trait]
+    impl PDUTransport for LimitedTransport {
+        async fn request(&mut self, _destination: VariableID, _pdu: PDU) -> Result<(), IoError> {
+            Ok(())
+        }
+
+        async fn receive(&mut self) -> Result<PDU, IoError> {
+            std::future::pending::<()>().await;
+            Err(IoError::from(ErrorKind::NotConnected))
+        }
+
+        fn max_pdu_len(&self) -> Option<usize> {
+            self.0
+        }
+    }
+
+    #[fixture]
+    fn config() -> EntityConfig {
+        EntityConfig {
+            fault_handler_override: BTreeMap::new(),
+            file_size_segment: 1024,
+            max_pdu_len: None,
+            default_transaction_max_count: 2,
+            inactivity_timeout: 1,
+            ack_timeout: 1,
+            nak_timeout: 1,
+            crc_flag: CRCFlag::Present,
+            closure_requested: false,
+            checksum_type: ChecksumType::Modular,
+            nak_procedure: NakProcedure::Deferred(Duration::ZERO),
+            link_failure_action: FaultHandlerAction::Ignore,
+            rate_limit: None,
+            transaction_rate_limit: None,
+            scheduling: Scheduling::Priority,
+            default_priority: Priority::default(),
+            protocol_version: VersionNegotiation::Auto,
+            id_width: None,
+        }
+    }
+
+    fn builder(config: EntityConfig, max_pdu_len: Option<usize>) -> DaemonBuilder<NativeFileStore> {
+        let (_primitive_tx, primitive_rx) = channel(1);
+        let (indication_tx, _indication_rx) = channel(1);
+        DaemonBuilder::new(EntityID::from(0_u16), Arc::new(NativeFileStore::new(".")))
+            .transport(
+                vec![EntityID::from(1_u16)],
+                Box::new(LimitedTransport(max_pdu_len)),
+            )
+            .default_config(config)
+            .user(primitive_rx, indication_tx)
+    }
+
+    #[rstest]
+    fn valid(config: EntityConfig) {
+        // 4 byte header + 2 byte IDs + 2 byte sequence number + 8 byte offset + CRC
+        let exact = 4 + 4 + 2 + 8 + 1024 + 2;
+        assert_eq!(Ok(()), builder(config.clone(), None).validate());
+        assert_eq!(Ok(()), builder(config, Some(exact)).validate());
+    }
+
+    #[rstest]
+    fn segment_too_large(config: EntityConfig) {
+        assert_eq!(
+            Err(ConfigError::SegmentTooLarge {
+                entity: EntityID::from(1_u16),
+                pdu_len: 1044,
+                max_pdu_len: 1000
+            }),
+            builder(config, Some(1000)).validate()
+        )
+    }
+
+    #[rstest]
+    fn configured_max_pdu_len(config: EntityConfig) {
+        let mut remote = config.clone();
+        remote.max_pdu_len = Some(1000);
+        // the configured limit applies even though the transport allows more.
+        assert_eq!(
+            Err(ConfigError::SegmentTooLarge {
+                entity: EntityID::from(1_u16),
+                pdu_len: 1044,
+                max_pdu_len: 1000
+            }),
+            builder(config.clone(), Some(1400))
+                .entity_config(EntityID::from(1_u16), remote.clone())
+                .validate()
+        );
+
+        // a NAK for a single segment must always fit.
+        remote.file_size_segment = 8;
+        remote.max_pdu_len = Some(40);
+        assert_eq!(
+            Err(ConfigError::PDULengthTooShort {
+                entity: EntityID::from(1_u16),
+                nak_len: 4 + 4 + 2 + 1 + 32 + 2,
+                max_pdu_len: 40
+            }),
+            builder(config, None)
+                .entity_config(EntityID::from(1_u16), remote)
+                .validate()
+        );
+    }
+
+    #[rstest]
+    #[case(0, 1, 1, "inactivity", 0)]
+    #[case(1, -2, 1, "ACK", -2)]
+    #[case(1, 1, 0, "NAK", 0)]
+    fn invalid_timeout(
+        mut config: EntityConfig,
+        #[case] inactivity: i64,
+        #[case] ack: i64,
+        #[case] nak: i64,
+        #[case] timer: &'static str,
+        #[case] value: i64,
+    ) {
+        config.inactivity_timeout = inactivity;
+        config.ack_timeout = ack;
+        config.nak_timeout = nak;
+        assert_eq!(
+            Err(ConfigError::InvalidTimeout {
+                entity: "the default configuration".to_owned(),
+                timer,
+                value
+            }),
+            builder(config, None).validate()
+        )
+    }
+
+    #[rstest]
+    fn zero_segment(config: EntityConfig) {
+        let mut remote = config.clone();
+        remote.file_size_segment = 0;
+        assert_eq!(
+            Err(ConfigError::ZeroSegmentSize("entity 1".to_owned())),
+            builder(config, None)
+                .entity_config(EntityID::from(1_u16), remote)
+                .validate()
+        )
+    }
+
+    #[rstest]
+    fn zero_rate(config: EntityConfig) {
+        let mut remote = config.clone();
+        remote.transaction_rate_limit = Some(RateLimit {
+            bytes_per_second: 0,
+            burst: 1024,
+        });
+        assert_eq!(
+            Err(ConfigError::ZeroRate {
+                entity: "entity 1".to_owned(),
+                limit: "transaction"
+            }),
+            builder(config, None)
+                .entity_config(EntityID::from(1_u16), remote)
+                .validate()
+        )
+    }
+
+    #[rstest]
+    fn entity_id_width(config: EntityConfig) {
+        // IDs of different widths are widened to a common length in the header.
+        assert_eq!(
+            Ok(()),
+            builder(config.clone(), None)
+                .transport(
+                    vec![EntityID::from(2_u32)],
+                    Box::new(LimitedTransport(None))
+                )
+                .validate()
+        );
+
+        let mut remote = config.clone();
+        remote.id_width = Some(9);
+        assert_eq!(
+            Err(ConfigError::InvalidIDWidth(9)),
+            builder(config, None)
+                .entity_config(EntityID::from(1_u16), remote)
+                .validate()
+        )
+    }
+
+    #[rstest]
+    fn transport_coverage(config: EntityConfig) {
+        assert_eq!(
+            Err(ConfigError::NoTransport(EntityID::from(2_u16))),
+            builder(config.clone(), None)
+                .entity_config(EntityID::from(2_u16), config.clone())
+                .validate()
+        );
+        assert_eq!(
+            Err(ConfigError::DuplicateTransport(EntityID::from(1_u16))),
+            builder(config, None)
+                .transport(
+                    vec![EntityID::from(1_u16)],
+                    Box::new(LimitedTransport(None))
+                )
+                .validate()
+        );
+    }
+
+    #[rstest]
+    fn missing_default() {
+        assert_eq!(
+            Err(ConfigError::Missing("default entity configuration")),
+            DaemonBuilder::new(EntityID::from(0_u16), Arc::new(NativeFileStore::new(".")))
+                .validate()
+        )
+    }
+
+    #[rstest]
+    fn indication_capacity(config: EntityConfig) {
+        assert_eq!(
+            Err(ConfigError::ZeroIndicationCapacity),
+            builder(config, None).indication_capacity(0).validate()
+        )
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn build(config: EntityConfig) {
+        assert!(builder(config, Some(u16::MAX.into())).build().is_ok());
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn build_with_handle(config: EntityConfig) {
+        // the handle replaces the primitive channel of the user.
+        let (mut daemon, handle) = builder(config, None).build_with_handle().unwrap();
+        let running = tokio::task::spawn(async move { daemon.manage_transactions().await });
+
+        let unknown = TransactionID(EntityID::from(0_u16), TransactionSeqNum::from(7_u16));
+        assert!(matches!(
+            handle.report(unknown).await,
+            Err(HandleError::Refused(PrimitiveError::UnknownTransaction(id))) if id == unknown
+        ));
+        assert!(handle
+            .shutdown(ShutdownMode::Abort)
+            .await
+            .unwrap()
+            .is_empty());
+        running.await.unwrap().unwrap();
+        assert!(matches!(
+            handle.cancel(unknown).await,
+            Err(HandleError::DaemonStopped)
+        ));
+    }
+}
@@ -0,0 +1,573 @@
+//! Packet captures of PDUs.
+//!
+//! Captures are read from the classic libpcap file format as written by tcpdump or Wireshark.
+//! PDUs carried in UDP datagrams over IPv4 or IPv6 are unwrapped from the link layer,
+//! frames of the other user defined link types (147 to 162) are taken to be bare PDUs.
+//!
+//! A [Recorder] captures every PDU a [Daemon](crate::Daemon) sends and receives,
+//! see [DaemonBuilder::capture](crate::DaemonBuilder::capture).
+//! Its captures use the link type 148 (USER1) where each frame starts with one byte
+//! giving the [CaptureDirection] followed by the PDU.
+//! A [Replay] later feeds the received PDUs of a capture back into a Daemon
+//! through a [ReplayTransport], or directly into a single transaction.
+use std::{
+    collections::VecDeque,
+    fs::File,
+    future::pending,
+    io::{BufWriter, Error as IoError, Write},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
+use camino::Utf8Path;
+use cfdp_core::pdu::{PDUEncode, PDUView, VariableID, PDU};
+use log::warn;
+use tokio::{sync::mpsc::Sender, time::Instant};
+
+use crate::{error::CaptureError, transport::PDUTransport};
+
+const MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+const MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+const FILE_HEADER_LEN: usize = 24;
+const RECORD_HEADER_LEN: usize = 16;
+const SNAPSHOT_LEN: u32 = 65_535;
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_USER0: u32 = 147;
+/// Frames written by a [Recorder], the PDU is preceded by its [CaptureDirection].
+const LINKTYPE_RECORDING: u32 = 148;
+const LINKTYPE_USER15: u32 = 162;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const IP_PROTOCOL_UDP: u8 = 17;
+const UDP_HEADER_LEN: usize = 8;
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Whether the recording entity sent or received a captured PDU.
+pub enum CaptureDirection {
+    Received = 0,
+    Sent = 1,
+}
+impl CaptureDirection {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Received),
+            1 => Some(Self::Sent),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The CFDP payload of a captured packet.
+pub struct CapturedPacket {
+    /// Position of the packet in the capture, starting at 1.
+    pub record: usize,
+    /// Time of capture since the Unix epoch.
+    pub timestamp: Duration,
+    /// Only known for captures written by a [Recorder].
+    pub direction: Option<CaptureDirection>,
+    pub payload: Vec<u8>,
+}
+
+/// Read every packet of a pcap capture that carries a UDP datagram or a user link type frame.
+/// Any other packet is skipped.
+pub fn read_pcap(capture: &[u8]) -> Result<Vec<CapturedPacket>, CaptureError> {
+    let header = capture
+        .get(..FILE_HEADER_LEN)
+        .ok_or(CaptureError::Truncated(capture.len()))?;
+    let magic = u32::from_le_bytes(header[..4].try_into().unwrap());
+    let (little_endian, nanos) = match (magic, magic.swap_bytes()) {
+        (MAGIC_MICROS, _) => (true, false),
+        (MAGIC_NANOS, _) => (true, true),
+        (_, MAGIC_MICROS) => (false, false),
+        (_, MAGIC_NANOS) => (false, true),
+        _ => return Err(CaptureError::Magic(magic)),
+    };
+    let read_u32 = |bytes: &[u8]| {
+        let bytes = bytes[..4].try_into().unwrap();
+        match little_endian {
+            true => u32::from_le_bytes(bytes),
+            false => u32::from_be_bytes(bytes),
+        }
+    };
+
+    let link_type = read_u32(&header[20..]) & 0xffff;
+    let link_payload: fn(&[u8]) -> Option<&[u8]> = match link_type {
+        LINKTYPE_NULL => |frame: &[u8]| frame.get(4..).and_then(ip_payload),
+        LINKTYPE_ETHERNET => ethernet_payload,
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => ip_payload,
+        LINKTYPE_LINUX_SLL => |frame: &[u8]| frame.get(16..).and_then(ip_payload),
+        LINKTYPE_LINUX_SLL2 => |frame: &[u8]| frame.get(20..).and_then(ip_payload),
+        LINKTYPE_RECORDING => |frame: &[u8]| frame.get(1..),
+        LINKTYPE_USER0..=LINKTYPE_USER15 => |frame: &[u8]| Some(frame),
+        _ => return Err(CaptureError::LinkType(link_type)),
+    };
+
+    let mut packets = vec![];
+    let mut offset = FILE_HEADER_LEN;
+    let mut records = 0;
+    while offset < capture.len() {
+        let record = capture
+            .get(offset..offset + RECORD_HEADER_LEN)
+            .ok_or(CaptureError::Truncated(offset))?;
+        let seconds = read_u32(record) as u64;
+        let fraction = read_u32(&record[4..]);
+        let captured_len = read_u32(&record[8..]) as usize;
+        let frame = capture
+            .get(offset + RECORD_HEADER_LEN..offset + RECORD_HEADER_LEN + captured_len)
+            .ok_or(CaptureError::Truncated(offset))?;
+        offset += RECORD_HEADER_LEN + captured_len;
+        records += 1;
+
+        let direction = match link_type {
+            LINKTYPE_RECORDING => frame.first().copied().and_then(CaptureDirection::from_u8),
+            _ => None,
+        };
+        if let Some(payload) = link_payload(frame) {
+            packets.push(CapturedPacket {
+                record: records,
+                timestamp: Duration::from_secs(seconds)
+                    + match nanos {
+                        true => Duration::from_nanos(fraction as u64),
+                        false => Duration::from_micros(fraction as u64),
+                    },
+                direction,
+                payload: payload.to_vec(),
+            });
+        }
+    }
+    Ok(packets)
+}
+
+fn ethernet_payload(frame: &[u8]) -> Option<&[u8]> {
+    let ethertype = u16::from_be_bytes(frame.get(12..14)?.try_into().ok()?);
+    match ethertype {
+        // the tag is followed by the actual ethertype.
+        ETHERTYPE_VLAN => ip_payload(frame.get(18..)?),
+        _ => ip_payload(frame.get(14..)?),
+    }
+}
+
+/// The payload of the UDP datagram in an IP packet, IP options and fragments are not followed.
+fn ip_payload(packet: &[u8]) -> Option<&[u8]> {
+    let datagram = match packet.first()? >> 4 {
+        4 if *packet.get(9)? == IP_PROTOCOL_UDP => {
+            let header_len = 4 * (packet[0] & 0x0f) as usize;
+            packet.get(header_len..)?
+        }
+        6 if *packet.get(6)? == IP_PROTOCOL_UDP => packet.get(40..)?,
+        _ => return None,
+    };
+    let length = u16::from_be_bytes(datagram.get(4..6)?.try_into().ok()?) as usize;
+    datagram.get(UDP_HEADER_LEN..length.clamp(UDP_HEADER_LEN, datagram.len()))
+}
+
+/// Writes PDUs to a pcap capture readable by [read_pcap] and Wireshark.
+pub struct CaptureWriter<W: Write> {
+    writer: W,
+}
+impl<W: Write> CaptureWriter<W> {
+    /// Start a new capture, the file header is written immediately.
+    pub fn new(mut writer: W) -> Result<Self, IoError> {
+        writer.write_all(&MAGIC_MICROS.to_le_bytes())?;
+        // pcap format version 2.4
+        writer.write_all(&2_u16.to_le_bytes())?;
+        writer.write_all(&4_u16.to_le_bytes())?;
+        // timezone offset and timestamp accuracy, both unused.
+        writer.write_all(&[0; 8])?;
+        writer.write_all(&SNAPSHOT_LEN.to_le_bytes())?;
+        writer.write_all(&LINKTYPE_RECORDING.to_le_bytes())?;
+        writer.flush()?;
+        Ok(Self { writer })
+    }
+
+    /// Append a PDU captured at `timestamp` since the Unix epoch.
+    pub fn write(
+        &mut self,
+        timestamp: Duration,
+        direction: CaptureDirection,
+        pdu: &[u8],
+    ) -> Result<(), IoError> {
+        let frame_len = (pdu.len() + 1) as u32;
+        self.writer
+            .write_all(&(timestamp.as_secs() as u32).to_le_bytes())?;
+        self.writer
+            .write_all(&timestamp.subsec_micros().to_le_bytes())?;
+        self.writer.write_all(&frame_len.to_le_bytes())?;
+        self.writer.write_all(&frame_len.to_le_bytes())?;
+        self.writer.write_all(&[direction as u8])?;
+        self.writer.write_all(pdu)?;
+        // a capture is most useful right after something went wrong, keep it complete.
+        self.writer.flush()
+    }
+}
+
+#[derive(Clone)]
+/// A capture shared by every transport recording into it.
+pub struct Recorder {
+    capture: Arc<Mutex<CaptureWriter<Box<dyn Write + Send>>>>,
+}
+impl Recorder {
+    pub fn new(writer: impl Write + Send + 'static) -> Result<Self, IoError> {
+        let writer: Box<dyn Write + Send> = Box::new(writer);
+        Ok(Self {
+            capture: Arc::new(Mutex::new(CaptureWriter::new(writer)?)),
+        })
+    }
+
+    /// Record into the file at `path`, replacing any previous capture.
+    pub fn create(path: &Utf8Path) -> Result<Self, IoError> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+
+    /// Append the PDU to the capture, stamped with the current time.
+    ///
+    /// Failures are logged rather than returned, the recording must not take down the link.
+    pub fn record(&self, direction: CaptureDirection, pdu: &PDU) {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut capture = self.capture.lock().unwrap();
+        if let Err(err) = capture.write(timestamp, direction, &pdu.clone().encode()) {
+            warn!("Unable to record {direction:?} PDU: {err}");
+        }
+    }
+
+    /// Wrap a transport so every PDU passing through it is recorded.
+    pub fn wrap(&self, transport: Box<dyn PDUTransport + Send>) -> RecordingTransport {
+        RecordingTransport {
+            transport,
+            recorder: self.clone(),
+        }
+    }
+}
+
+/// A transport recording every PDU it sends and receives, see [Recorder::wrap].
+pub struct RecordingTransport {
+    transport: Box<dyn PDUTransport + Send>,
+    recorder: Recorder,
+}
+
+#[async_trait]
+impl PDUTransport for RecordingTransport {
+    async fn request(&mut self, destination: VariableID, pdu: PDU) -> Result<(), IoError> {
+        self.recorder.record(CaptureDirection::Sent, &pdu);
+        self.transport.request(destination, pdu).await
+    }
+
+    async fn receive(&mut self) -> Result<PDU, IoError> {
+        let pdu = self.transport.receive().await?;
+        self.recorder.record(CaptureDirection::Received, &pdu);
+        Ok(pdu)
+    }
+
+    fn max_pdu_len(&self) -> Option<usize> {
+        self.transport.max_pdu_len()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// The pace at which a [Replay] delivers PDUs.
+pub enum ReplayTiming {
+    /// With the delays between PDUs as captured.
+    Original,
+    /// With the captured delays divided by a positive factor.
+    Accelerated(f64),
+    /// Every PDU without delay.
+    Immediate,
+}
+
+/// Delivers the received PDUs of a capture, paced by their timestamps.
+///
+/// PDUs the recording entity sent are skipped, packets of unknown direction are
+/// taken to be received. To reproduce a single transaction pass each PDU to its
+/// [process_pdu](crate::transaction::RecvTransaction::process_pdu).
+pub struct Replay {
+    packets: VecDeque<CapturedPacket>,
+    timing: ReplayTiming,
+    // when the first PDU was delivered and its captured timestamp.
+    start: Option<(Instant, Duration)>,
+}
+impl Replay {
+    pub fn new(packets: Vec<CapturedPacket>, timing: ReplayTiming) -> Self {
+        Self {
+            packets: packets
+                .into_iter()
+                .filter(|packet| packet.direction != Some(CaptureDirection::Sent))
+                .collect(),
+            timing,
+            start: None,
+        }
+    }
+
+    /// Number of captured PDUs not yet delivered.
+    pub fn remaining(&self) -> usize {
+        self.packets.len()
+    }
+
+    /// Wait until the next PDU is due and return it, [None] once the capture is exhausted.
+    /// Undecodable PDUs are logged and skipped.
+    ///
+    /// This method is cancel safe, a PDU is only consumed once it is returned.
+    pub async fn next(&mut self) -> Option<PDU> {
+        loop {
+            let packet = self.packets.front()?;
+            let (start, first) = *self
+                .start
+                .get_or_insert_with(|| (Instant::now(), packet.timestamp));
+            let offset = packet.timestamp.saturating_sub(first);
+            let delay = match self.timing {
+                ReplayTiming::Original => offset,
+                ReplayTiming::Accelerated(factor) if factor > 0.0 => offset.div_f64(factor),
+                ReplayTiming::Accelerated(_) | ReplayTiming::Immediate => Duration::ZERO,
+            };
+            tokio::time::sleep_until(start + delay).await;
+
+            let packet = self.packets.pop_front()?;
+            match PDUView::decode(&packet.payload) {
+                Ok((view, _)) => return Some(view.into()),
+                Err(err) => warn!(
+                    "Skipping undecodable PDU of record {}: {err}",
+                    packet.record
+                ),
+            }
+        }
+    }
+}
+
+/// Feeds a [Replay] to a [Daemon](crate::Daemon) in place of a live transport.
+///
+/// The capture holds no remote entity to answer, PDUs the Daemon sends are
+/// forwarded to the [outbound](Self::outbound) channel if one is attached and dropped otherwise.
+/// Once the capture is exhausted the transport stays silent.
+pub struct ReplayTransport {
+    replay: Replay,
+    outbound: Option<Sender<(VariableID, PDU)>>,
+}
+impl ReplayTransport {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            outbound: None,
+        }
+    }
+
+    /// Forward every PDU the Daemon sends to this channel.
+    pub fn outbound(mut self, outbound: Sender<(VariableID, PDU)>) -> Self {
+        self.outbound = Some(outbound);
+        self
+    }
+}
+
+#[async_trait]
+impl PDUTransport for ReplayTransport {
+    async fn request(&mut self, destination: VariableID, pdu: PDU) -> Result<(), IoError> {
+        if let Some(outbound) = &self.outbound {
+            // nobody inspecting the responses is no reason to fail the Daemon.
+            let _ = outbound.send((destination, pdu)).await;
+        }
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Result<PDU, IoError> {
+        match self.replay.next().await {
+            Some(pdu) => Ok(pdu),
+            None => pending().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use cfdp_core::pdu::{
+        CRCFlag, Condition, Direction, EndOfFile, FileSizeFlag, Operations, PDUHeader, PDUPayload,
+        PDUType, SegmentationControl, SegmentedData, TransmissionMode, U3,
+    };
+    use rstest::rstest;
+    use tokio::sync::mpsc::{channel, Receiver};
+
+    fn capture(link_type: u32, frames: &[&[u8]]) -> Vec<u8> {
+        let mut capture = vec![];
+        capture.extend(MAGIC_MICROS.to_be_bytes());
+        capture.extend(2_u16.to_be_bytes());
+        capture.extend(4_u16.to_be_bytes());
+        capture.extend([0; 8]);
+        capture.extend(65535_u32.to_be_bytes());
+        capture.extend(link_type.to_be_bytes());
+        for (index, frame) in frames.iter().enumerate() {
+            capture.extend((index as u32 + 10).to_be_bytes());
+            capture.extend(500_u32.to_be_bytes());
+            capture.extend((frame.len() as u32).to_be_bytes());
+            capture.extend((frame.len() as u32).to_be_bytes());
+            capture.extend(*frame);
+        }
+        capture
+    }
+
+    fn udp_ipv4(payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 64, IP_PROTOCOL_UDP];
+        packet.extend([0; 10]);
+        packet.extend([0x12, 0x34, 0x56, 0x78]);
+        packet.extend((payload.len() as u16 + 8).to_be_bytes());
+        packet.extend([0, 0]);
+        packet.extend(payload);
+        packet
+    }
+
+    fn eof(file_size: u64) -> PDU {
+        let payload = PDUPayload::Directive(Operations::EoF(EndOfFile {
+            condition: Condition::NoError,
+            checksum: 0,
+            file_size,
+            fault_location: None,
+        }));
+        PDU {
+            header: PDUHeader {
+                version: U3::One,
+                pdu_type: PDUType::FileDirective,
+                direction: Direction::ToReceiver,
+                transmission_mode: TransmissionMode::Acknowledged,
+                crc_flag: CRCFlag::NotPresent,
+                large_file_flag: FileSizeFlag::Small,
+                pdu_data_field_length: payload.encoded_len(FileSizeFlag::Small),
+                segmentation_control: SegmentationControl::NotPreserved,
+                segment_metadata_flag: SegmentedData::NotPresent,
+                source_entity_id: 0_u16.into(),
+                transaction_sequence_number: 3_u16.into(),
+                destination_entity_id: 1_u16.into(),
+            },
+            payload,
+        }
+    }
+
+    fn packet(millis: u64, direction: Option<CaptureDirection>, pdu: PDU) -> CapturedPacket {
+        CapturedPacket {
+            record: 1,
+            timestamp: Duration::from_millis(millis),
+            direction,
+            payload: pdu.encode(),
+        }
+    }
+
+    struct ChannelTransport {
+        outbound: Sender<(VariableID, PDU)>,
+        inbound: Receiver<PDU>,
+    }
+    #[async_trait]
+    impl PDUTransport for ChannelTransport {
+        async fn request(&mut self, destination: VariableID, pdu: PDU) -> Result<(), IoError> {
+            self.outbound.send((destination, pdu)).await.unwrap();
+            Ok(())
+        }
+
+        async fn receive(&mut self) -> Result<PDU, IoError> {
+            Ok(self.inbound.recv().await.unwrap())
+        }
+    }
+
+    #[test]
+    fn ethernet() {
+        let mut frame = vec![0; 12];
+        frame.extend(0x0800_u16.to_be_bytes());
+        frame.extend(udp_ipv4(&[1, 2, 3]));
+        // trailing ethernet padding is not part of the datagram.
+        frame.extend([0; 4]);
+        let mut tcp = frame.clone();
+        tcp[14 + 9] = 6;
+
+        let packets = read_pcap(&capture(LINKTYPE_ETHERNET, &[&tcp, &frame])).unwrap();
+        assert_eq!(
+            vec![CapturedPacket {
+                record: 2,
+                timestamp: Duration::from_secs(11) + Duration::from_micros(500),
+                direction: None,
+                payload: vec![1, 2, 3],
+            }],
+            packets
+        );
+    }
+
+    #[rstest]
+    #[case(LINKTYPE_USER0, vec![7, 8, 9])]
+    #[case(LINKTYPE_RAW, udp_ipv4(&[7, 8, 9]))]
+    fn bare_frames(#[case] link_type: u32, #[case] frame: Vec<u8>) {
+        let packets = read_pcap(&capture(link_type, &[&frame])).unwrap();
+        assert_eq!(vec![7, 8, 9], packets[0].payload);
+    }
+
+    #[rstest]
+    #[case(vec![0xde, 0xad], CaptureError::Truncated(2))]
+    #[case(vec![0; 24], CaptureError::Magic(0))]
+    #[case(capture(105, &[]), CaptureError::LinkType(105))]
+    #[case(capture(LINKTYPE_USER0, &[&[1, 2]])[..40].to_vec(), CaptureError::Truncated(24))]
+    fn invalid(#[case] capture: Vec<u8>, #[case] expected: CaptureError) {
+        assert_eq!(Err(expected), read_pcap(&capture))
+    }
+
+    #[tokio::test]
+    async fn record() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = Utf8Path::from_path(dir.path()).unwrap().join("record.pcap");
+        let (outbound, mut outbound_rx) = channel(1);
+        let (inbound_tx, inbound) = channel(1);
+        let mut transport = Recorder::create(&path)
+            .unwrap()
+            .wrap(Box::new(ChannelTransport { outbound, inbound }));
+
+        transport.request(1_u16.into(), eof(10)).await.unwrap();
+        assert_eq!(eof(10), outbound_rx.recv().await.unwrap().1);
+        inbound_tx.send(eof(20)).await.unwrap();
+        assert_eq!(eof(20), transport.receive().await.unwrap());
+
+        let packets = read_pcap(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(
+            vec![
+                (Some(CaptureDirection::Sent), eof(10).encode()),
+                (Some(CaptureDirection::Received), eof(20).encode())
+            ],
+            packets
+                .into_iter()
+                .map(|packet| (packet.direction, packet.payload))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[rstest]
+    #[case(ReplayTiming::Accelerated(10.0), Duration::from_millis(50))]
+    #[case(ReplayTiming::Immediate, Duration::ZERO)]
+    #[tokio::test]
+    async fn replay(#[case] timing: ReplayTiming, #[case] expected: Duration) {
+        let packets = vec![
+            packet(100_000, Some(CaptureDirection::Received), eof(10)),
+            packet(100_200, Some(CaptureDirection::Sent), eof(20)),
+            packet(100_500, None, eof(30)),
+        ];
+        let (outbound, mut outbound_rx) = channel(1);
+        let mut transport = ReplayTransport::new(Replay::new(packets, timing)).outbound(outbound);
+
+        let start = Instant::now();
+        assert_eq!(eof(10), transport.receive().await.unwrap());
+        assert_eq!(eof(30), transport.receive().await.unwrap());
+        let elapsed = start.elapsed();
+        assert!(elapsed >= expected && elapsed < expected + Duration::from_millis(150));
+
+        transport.request(1_u16.into(), eof(40)).await.unwrap();
+        assert_eq!(eof(40), outbound_rx.recv().await.unwrap().1);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), transport.receive())
+                .await
+                .is_err()
+        );
+    }
+}
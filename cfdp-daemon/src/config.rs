@@ -0,0 +1,684 @@
+//! File based configuration of a [Daemon](crate::Daemon), the CFDP Management Information Base.
+//!
+//! The configuration may be written in TOML or JSON, this module requires the `config` feature.
+//! Every remote entity setting is optional and falls back to the `defaults` table,
+//! then to the built-in default.
+//!
+//! ```toml
+//! [local]
+//! entity_id = 1
+//! id_width = 2
+//! filestore_root = "/srv/cfdp"
+//! udp_bind = "0.0.0.0:4560"
+//! history_file = "/var/lib/cfdp/history.jsonl"
+//! suspend_dir = "/var/lib/cfdp/suspended"
+//! capture_file = "/var/lib/cfdp/pdus.pcap"
+//!
+//! [defaults]
+//! ack_timeout = "5s"
+//! inactivity_timeout = "2m"
+//! checksum = "Modular"
+//! fault_handlers = { PositiveLimitReached = "Abandon" }
+//!
+//! [[remote]]
+//! entity_id = 2
+//! transport = "udp"
+//! address = "192.168.1.2:4560"
+//!
+//! [remote.settings]
+//! file_size_segment = 512
+//! max_pdu_len = 1400
+//! nak_procedure = "immediate"
+//! priority = { class = 2, weight = 1 }
+//! ```
+//!
+//! Each remote entity is reached over a single transport. Multi-path links and contact plans
+//! are only available through the API: build a [MultiPathTransport](crate::transport::MultiPathTransport)
+//! and add it with [DaemonBuilder::transport], and send
+//! [SetContactPlan](cfdp_core::daemon::UserPrimitive::SetContactPlan) once the Daemon runs.
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    net::SocketAddr,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use cfdp_core::{
+    daemon::{EntityConfig, NakProcedure, Priority, RateLimit, Scheduling, VersionNegotiation},
+    filestore::{ChecksumType, NativeFileStore},
+    pdu::{CRCFlag, Condition, EntityID, FaultHandlerAction},
+};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    builder::{validate_entity_config, DaemonBuilder},
+    capture::Recorder,
+    entity::{variable_id, TransportKind},
+    error::{ConfigError, ConfigResult},
+    history::{TransactionHistory, DEFAULT_HISTORY_CAPACITY},
+    suspend::SuspendStore,
+    transport::{PDUTransport, UdpTransport},
+};
+
+const DEFAULT_SEGMENT_SIZE: u16 = 1024;
+const DEFAULT_MAX_COUNT: u32 = 2;
+const DEFAULT_INACTIVITY_TIMEOUT: Timeout = Timeout(30);
+const DEFAULT_ACK_TIMEOUT: Timeout = Timeout(10);
+const DEFAULT_NAK_TIMEOUT: Timeout = Timeout(10);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+/// Complete configuration of a local entity and the remote entities it communicates with.
+pub struct DaemonConfig {
+    pub local: LocalConfig,
+    /// Settings applied to every remote entity unless overridden.
+    #[serde(default)]
+    pub defaults: EntitySettings,
+    #[serde(default, rename = "remote")]
+    pub remotes: Vec<RemoteConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LocalConfig {
+    pub entity_id: u64,
+    /// Number of bytes used to encode entity IDs and transaction sequence numbers.
+    #[serde(default = "default_id_width")]
+    pub id_width: u8,
+    /// Directory all file paths in a transaction are relative to.
+    pub filestore_root: Utf8PathBuf,
+    /// Address the UDP transport listens on.
+    /// Only required if a remote entity is reached over UDP.
+    #[serde(default)]
+    pub udp_bind: Option<SocketAddr>,
+    /// Number of finished transactions kept in memory.
+    #[serde(default)]
+    pub history_capacity: Option<usize>,
+    /// File every finished transaction is appended to, one JSON record per line.
+    #[serde(default)]
+    pub history_file: Option<Utf8PathBuf>,
+    /// Directory transactions are saved to by a suspending shutdown,
+    /// and restored from when the daemon starts.
+    #[serde(default)]
+    pub suspend_dir: Option<Utf8PathBuf>,
+    /// File every PDU sent and received is recorded to as a pcap capture,
+    /// replaced each time the daemon starts.
+    #[serde(default)]
+    pub capture_file: Option<Utf8PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteConfig {
+    pub entity_id: u64,
+    pub transport: TransportKind,
+    pub address: SocketAddr,
+    #[serde(default)]
+    pub settings: EntitySettings,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// When a receiving transaction sends NAKs, see [NakProcedure].
+pub enum NakMode {
+    Immediate,
+    Deferred,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+/// Optional overrides of each field in [EntityConfig].
+pub struct EntitySettings {
+    pub file_size_segment: Option<u16>,
+    /// Longest PDU in bytes, header and CRC included.
+    pub max_pdu_len: Option<usize>,
+    pub max_count: Option<u32>,
+    pub inactivity_timeout: Option<Timeout>,
+    pub ack_timeout: Option<Timeout>,
+    pub nak_timeout: Option<Timeout>,
+    pub crc: Option<bool>,
+    pub closure_requested: Option<bool>,
+    pub checksum: Option<ChecksumType>,
+    pub nak_procedure: Option<NakMode>,
+    /// Delay in milliseconds before NAKs are sent.
+    pub nak_delay_ms: Option<u64>,
+    pub fault_handlers: Option<BTreeMap<Condition, FaultHandlerAction>>,
+    pub link_failure_action: Option<FaultHandlerAction>,
+    pub rate_limit: Option<RateLimit>,
+    pub transaction_rate_limit: Option<RateLimit>,
+    pub scheduling: Option<Scheduling>,
+    /// Priority of transactions sending to the entity which do not request one.
+    pub priority: Option<Priority>,
+    /// Protocol revision spoken with the entity, `auto`, `legacy` or `current`.
+    pub protocol_version: Option<VersionNegotiation>,
+    /// Number of bytes entity IDs are encoded with in PDUs exchanged with the entity,
+    /// when wider than the `id_width` of the local entity.
+    pub id_width: Option<u8>,
+}
+impl EntitySettings {
+    /// Combine with a set of fallback settings, preferring any values in `self`.
+    fn or(&self, fallback: &Self) -> Self {
+        Self {
+            file_size_segment: self.file_size_segment.or(fallback.file_size_segment),
+            max_pdu_len: self.max_pdu_len.or(fallback.max_pdu_len),
+            max_count: self.max_count.or(fallback.max_count),
+            inactivity_timeout: self.inactivity_timeout.or(fallback.inactivity_timeout),
+            ack_timeout: self.ack_timeout.or(fallback.ack_timeout),
+            nak_timeout: self.nak_timeout.or(fallback.nak_timeout),
+            crc: self.crc.or(fallback.crc),
+            closure_requested: self.closure_requested.or(fallback.closure_requested),
+            checksum: self.checksum.or(fallback.checksum),
+            nak_procedure: self.nak_procedure.or(fallback.nak_procedure),
+            nak_delay_ms: self.nak_delay_ms.or(fallback.nak_delay_ms),
+            fault_handlers: self
+                .fault_handlers
+                .clone()
+                .or_else(|| fallback.fault_handlers.clone()),
+            link_failure_action: self
+                .link_failure_action
+                .clone()
+                .or_else(|| fallback.link_failure_action.clone()),
+            rate_limit: self.rate_limit.or(fallback.rate_limit),
+            transaction_rate_limit: self
+                .transaction_rate_limit
+                .or(fallback.transaction_rate_limit),
+            scheduling: self.scheduling.or(fallback.scheduling),
+            priority: self.priority.or(fallback.priority),
+            protocol_version: self.protocol_version.or(fallback.protocol_version),
+            id_width: self.id_width.or(fallback.id_width),
+        }
+    }
+
+    /// Fill any remaining gaps with the built-in defaults.
+    fn entity_config(&self) -> EntityConfig {
+        let nak_delay = Duration::from_millis(self.nak_delay_ms.unwrap_or(0));
+        EntityConfig {
+            fault_handler_override: self.fault_handlers.clone().unwrap_or_default(),
+            file_size_segment: self.file_size_segment.unwrap_or(DEFAULT_SEGMENT_SIZE),
+            max_pdu_len: self.max_pdu_len,
+            default_transaction_max_count: self.max_count.unwrap_or(DEFAULT_MAX_COUNT),
+            inactivity_timeout: self
+                .inactivity_timeout
+                .unwrap_or(DEFAULT_INACTIVITY_TIMEOUT)
+                .as_secs(),
+            ack_timeout: self.ack_timeout.unwrap_or(DEFAULT_ACK_TIMEOUT).as_secs(),
+            nak_timeout: self.nak_timeout.unwrap_or(DEFAULT_NAK_TIMEOUT).as_secs(),
+            crc_flag: match self.crc.unwrap_or(false) {
+                true => CRCFlag::Present,
+                false => CRCFlag::NotPresent,
+            },
+            closure_requested: self.closure_requested.unwrap_or(false),
+            checksum_type: self.checksum.unwrap_or(ChecksumType::Modular),
+            nak_procedure: match self.nak_procedure.unwrap_or(NakMode::Deferred) {
+                NakMode::Immediate => NakProcedure::Immediate(nak_delay),
+                NakMode::Deferred => NakProcedure::Deferred(nak_delay),
+            },
+            link_failure_action: self
+                .link_failure_action
+                .clone()
+                .unwrap_or(FaultHandlerAction::Ignore),
+            rate_limit: self.rate_limit,
+            transaction_rate_limit: self.transaction_rate_limit,
+            scheduling: self.scheduling.unwrap_or_default(),
+            default_priority: self.priority.unwrap_or_default(),
+            protocol_version: self.protocol_version.unwrap_or_default(),
+            id_width: self.id_width,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A timeout in whole seconds.
+///
+/// Written either as a number of seconds or as a string with a unit: `"90s"`, `"5m"` or `"1h"`.
+pub struct Timeout(i64);
+impl Timeout {
+    pub const fn from_secs(secs: i64) -> Self {
+        Self(secs)
+    }
+
+    pub const fn as_secs(self) -> i64 {
+        self.0
+    }
+}
+impl From<Timeout> for Duration {
+    /// Negative timeouts are clamped to zero.
+    fn from(timeout: Timeout) -> Self {
+        Duration::from_secs(timeout.0.max(0) as u64)
+    }
+}
+impl FromStr for Timeout {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+        let split = input
+            .find(|c: char| !(c.is_ascii_digit() || c == '-' || c == '+'))
+            .unwrap_or(input.len());
+        let (value, unit) = input.split_at(split);
+        let value: i64 = value
+            .parse()
+            .map_err(|_| format!("invalid timeout \"{input}\", expected e.g. \"30s\""))?;
+        let scale = match unit.trim() {
+            "" | "s" | "sec" | "secs" => 1,
+            "m" | "min" | "mins" => 60,
+            "h" | "hr" | "hrs" => 60 * 60,
+            unit => {
+                return Err(format!(
+                    "unknown unit \"{unit}\" in timeout \"{input}\", expected s, m or h"
+                ))
+            }
+        };
+        value
+            .checked_mul(scale)
+            .map(Self)
+            .ok_or_else(|| format!("timeout \"{input}\" is too long"))
+    }
+}
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}s", self.0)
+    }
+}
+impl Serialize for Timeout {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(self.0)
+    }
+}
+impl<'de> Deserialize<'de> for Timeout {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+        impl de::Visitor<'_> for Visitor {
+            type Value = Timeout;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a number of seconds or a duration such as \"30s\", \"5m\" or \"1h\"")
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Timeout, E> {
+                Ok(Timeout(value))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Timeout, E> {
+                i64::try_from(value)
+                    .map(Timeout)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(value), &self))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Timeout, E> {
+                value.parse().map_err(E::custom)
+            }
+        }
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+fn default_id_width() -> u8 {
+    2
+}
+
+impl DaemonConfig {
+    pub fn from_toml(input: &str) -> ConfigResult<Self> {
+        let config: Self =
+            toml::from_str(input).map_err(|err| ConfigError::Parse(err.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_json(input: &str) -> ConfigResult<Self> {
+        let config: Self =
+            serde_json::from_str(input).map_err(|err| ConfigError::Parse(err.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check every entity configuration the file describes, rejecting
+    /// timeouts which are not positive and rate limits which never send anything.
+    pub fn validate(&self) -> ConfigResult<()> {
+        self.entity_id()?;
+        let (default_config, entity_configs) = self.entity_configs()?;
+        validate_entity_config(|| "the default configuration".to_owned(), &default_config)?;
+        for (entity, config) in entity_configs.iter() {
+            validate_entity_config(|| format!("entity {entity}"), config)?;
+        }
+        Ok(())
+    }
+
+    /// Read a configuration file, files ending in `.json` are parsed as JSON and anything else as TOML.
+    pub fn load(path: &Utf8Path) -> ConfigResult<Self> {
+        let input = std::fs::read_to_string(path)
+            .map_err(|err| ConfigError::Read(path.to_string(), err.to_string()))?;
+        match path.extension() {
+            Some("json") => Self::from_json(&input),
+            _ => Self::from_toml(&input),
+        }
+    }
+
+    pub fn entity_id(&self) -> ConfigResult<EntityID> {
+        variable_id(self.local.entity_id, self.local.id_width)
+    }
+
+    /// The default [EntityConfig] followed by the configuration of each remote entity.
+    pub fn entity_configs(&self) -> ConfigResult<(EntityConfig, HashMap<EntityID, EntityConfig>)> {
+        let remotes = self
+            .remotes
+            .iter()
+            .map(|remote| {
+                Ok((
+                    variable_id(remote.entity_id, self.local.id_width)?,
+                    remote.settings.or(&self.defaults).entity_config(),
+                ))
+            })
+            .collect::<ConfigResult<_>>()?;
+        Ok((self.defaults.entity_config(), remotes))
+    }
+
+    /// Start the configured transports and prepare a [DaemonBuilder].
+    ///
+    /// The user channels still need to be attached before the Daemon is built.
+    pub async fn builder(&self) -> ConfigResult<DaemonBuilder<NativeFileStore>> {
+        // nothing is opened or bound until the whole configuration is known to be usable.
+        self.validate()?;
+        let entity_id = self.entity_id()?;
+        let (default_config, entity_configs) = self.entity_configs()?;
+        let udp_remotes = self
+            .remotes
+            .iter()
+            .filter(|remote| remote.transport == TransportKind::Udp)
+            .map(|remote| {
+                Ok((
+                    variable_id(remote.entity_id, self.local.id_width)?,
+                    remote.address,
+                ))
+            })
+            .collect::<ConfigResult<HashMap<EntityID, SocketAddr>>>()?;
+        let udp_bind = match udp_remotes.is_empty() {
+            true => None,
+            false => Some(
+                self.local
+                    .udp_bind
+                    .ok_or(ConfigError::Missing("udp_bind address of the local entity"))?,
+            ),
+        };
+
+        let mut builder = DaemonBuilder::new(
+            entity_id,
+            Arc::new(NativeFileStore::new(&self.local.filestore_root)),
+        )
+        .sequence_num(variable_id(0, self.local.id_width)?)
+        .default_config(default_config);
+        for (entity, config) in entity_configs {
+            builder = builder.entity_config(entity, config);
+        }
+
+        let capacity = self
+            .local
+            .history_capacity
+            .unwrap_or(DEFAULT_HISTORY_CAPACITY);
+        builder = builder.history(match self.local.history_file.as_ref() {
+            Some(path) => TransactionHistory::open(capacity, path)
+                .map_err(|err| ConfigError::History(err.to_string()))?,
+            None => TransactionHistory::new(capacity),
+        });
+        if let Some(path) = self.local.suspend_dir.as_ref() {
+            let store =
+                SuspendStore::open(path).map_err(|err| ConfigError::Suspend(err.to_string()))?;
+            builder = builder.suspend_store(store);
+        }
+        if let Some(path) = self.local.capture_file.as_ref() {
+            let recorder = Recorder::create(path)
+                .map_err(|err| ConfigError::Capture(format!("{path}: {err}")))?;
+            builder = builder.capture(recorder);
+        }
+
+        if let Some(bind) = udp_bind {
+            let entities = udp_remotes.keys().copied().collect();
+            let transport = UdpTransport::new(bind, udp_remotes)
+                .await
+                .map_err(|err| ConfigError::Transport(format!("UDP on {bind}: {err}")))?;
+            builder = builder.transport(
+                entities,
+                Box::new(transport) as Box<dyn PDUTransport + Send>,
+            );
+        }
+
+        Ok(builder)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::capture::read_pcap;
+    use rstest::rstest;
+    use tokio::sync::mpsc::channel;
+
+    const TOML: &str = r#"
+        [local]
+        entity_id = 1
+        filestore_root = "."
+        udp_bind = "127.0.0.1:0"
+
+        [defaults]
+        ack_timeout = 5
+        inactivity_timeout = "2m"
+        crc = true
+        fault_handlers = { PositiveLimitReached = "Abandon" }
+
+        [[remote]]
+        entity_id = 2
+        transport = "udp"
+        address = "127.0.0.1:4560"
+
+        [remote.settings]
+        ack_timeout = "3s"
+        file_size_segment = 512
+        max_pdu_len = 1400
+        nak_procedure = "immediate"
+        nak_delay_ms = 100
+        priority = { class = 2 }
+
+        [[remote]]
+        entity_id = 3
+        transport = "udp"
+        address = "127.0.0.1:4561"
+
+        [remote.settings]
+        scheduling = "unscheduled"
+        protocol_version = "legacy"
+    "#;
+
+    #[test]
+    fn overrides() {
+        let config = DaemonConfig::from_toml(TOML).unwrap();
+        let (default, remotes) = config.entity_configs().unwrap();
+        assert_eq!(5, default.ack_timeout);
+        assert_eq!(120, default.inactivity_timeout);
+        assert_eq!(DEFAULT_NAK_TIMEOUT.as_secs(), default.nak_timeout);
+
+        let two = &remotes[&EntityID::from(2_u16)];
+        assert_eq!(3, two.ack_timeout);
+        assert_eq!(512, two.file_size_segment);
+        assert_eq!(Some(1400), two.max_pdu_len);
+        assert_eq!(CRCFlag::Present, two.crc_flag);
+        assert_eq!(
+            NakProcedure::Immediate(Duration::from_millis(100)),
+            two.nak_procedure
+        );
+        assert_eq!(
+            Some(&FaultHandlerAction::Abandon),
+            two.fault_handler_override
+                .get(&Condition::PositiveLimitReached)
+        );
+
+        assert_eq!(
+            Priority {
+                class: 2,
+                weight: 1
+            },
+            two.default_priority
+        );
+
+        let three = &remotes[&EntityID::from(3_u16)];
+        assert_eq!(5, three.ack_timeout);
+        assert_eq!(DEFAULT_SEGMENT_SIZE, three.file_size_segment);
+        assert_eq!(None, three.max_pdu_len);
+        assert_eq!(Scheduling::Unscheduled, three.scheduling);
+        assert_eq!(Priority::default(), three.default_priority);
+        assert_eq!(VersionNegotiation::Legacy, three.protocol_version);
+        assert_eq!(VersionNegotiation::Auto, two.protocol_version);
+    }
+
+    #[test]
+    fn json() {
+        let config = DaemonConfig::from_json(
+            r#"{
+                "local": { "entity_id": 7, "id_width": 1, "filestore_root": "/tmp" },
+                "remote": [ { "entity_id": 8, "transport": "udp", "address": "127.0.0.1:4560" } ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(EntityID::from(7_u8), config.entity_id().unwrap());
+        assert_eq!(
+            config,
+            DaemonConfig::from_toml(&toml::to_string(&config).unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn unknown_field() {
+        assert!(matches!(
+            DaemonConfig::from_toml("[local]\nentity_id = 1\nfilestore_root = \".\"\nfoo = 1"),
+            Err(ConfigError::Parse(_))
+        ))
+    }
+
+    #[rstest]
+    #[case("45", Ok(Timeout(45)))]
+    #[case("45s", Ok(Timeout(45)))]
+    #[case(" 5 m", Ok(Timeout(300)))]
+    #[case("1h", Ok(Timeout(3600)))]
+    #[case("-3s", Ok(Timeout(-3)))]
+    #[case("5d", Err(()))]
+    #[case("s", Err(()))]
+    #[case("9223372036854775807h", Err(()))]
+    fn timeout(#[case] input: &str, #[case] expected: Result<Timeout, ()>) {
+        assert_eq!(expected, input.parse::<Timeout>().map_err(|_| ()));
+    }
+
+    #[rstest]
+    #[case("ack_timeout = 0", ConfigError::InvalidTimeout { entity: "the default configuration".to_owned(), timer: "ACK", value: 0 })]
+    #[case("nak_timeout = -5", ConfigError::InvalidTimeout { entity: "the default configuration".to_owned(), timer: "NAK", value: -5 })]
+    #[case("inactivity_timeout = \"0m\"", ConfigError::InvalidTimeout { entity: "the default configuration".to_owned(), timer: "inactivity", value: 0 })]
+    #[case("rate_limit = { bytes_per_second = 0, burst = 1024 }", ConfigError::ZeroRate { entity: "the default configuration".to_owned(), limit: "entity" })]
+    fn rejected_on_load(#[case] defaults: &str, #[case] expected: ConfigError) {
+        let input =
+            format!("[local]\nentity_id = 1\nfilestore_root = \".\"\n[defaults]\n{defaults}");
+        assert_eq!(Err(expected), DaemonConfig::from_toml(&input));
+    }
+
+    #[test]
+    fn rejected_remote_on_load() {
+        let input = TOML.replace(
+            "scheduling = \"unscheduled\"",
+            "transaction_rate_limit = { bytes_per_second = 0, burst = 1 }",
+        );
+        assert_eq!(
+            Err(ConfigError::ZeroRate {
+                entity: "entity 3".to_owned(),
+                limit: "transaction"
+            }),
+            DaemonConfig::from_toml(&input)
+        );
+    }
+
+    #[tokio::test]
+    async fn build() {
+        let config = DaemonConfig::from_toml(TOML).unwrap();
+        let (_primitive_tx, primitive_rx) = channel(1);
+        let (indication_tx, _indication_rx) = channel(1);
+        let daemon = config
+            .builder()
+            .await
+            .unwrap()
+            .user(primitive_rx, indication_tx)
+            .build();
+        assert!(daemon.is_ok());
+    }
+
+    #[tokio::test]
+    async fn history_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut config = DaemonConfig::from_toml(TOML).unwrap();
+        config.local.history_file = Some(Utf8PathBuf::try_from(dir.path().to_owned()).unwrap());
+        assert!(matches!(
+            config.builder().await,
+            Err(ConfigError::History(_))
+        ));
+
+        config.local.history_file =
+            Some(Utf8PathBuf::try_from(dir.path().join("history.jsonl")).unwrap());
+        assert!(config.builder().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn suspend_dir() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("file");
+        std::fs::write(&file, b"").unwrap();
+        let mut config = DaemonConfig::from_toml(TOML).unwrap();
+        config.local.suspend_dir = Some(Utf8PathBuf::try_from(file).unwrap());
+        assert!(matches!(
+            config.builder().await,
+            Err(ConfigError::Suspend(_))
+        ));
+
+        config.local.suspend_dir =
+            Some(Utf8PathBuf::try_from(dir.path().join("suspended")).unwrap());
+        assert!(config.builder().await.is_ok());
+        assert!(dir.path().join("suspended").is_dir());
+    }
+
+    #[tokio::test]
+    async fn capture_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut config = DaemonConfig::from_toml(TOML).unwrap();
+        config.local.capture_file = Some(Utf8PathBuf::try_from(dir.path().to_owned()).unwrap());
+        assert!(matches!(
+            config.builder().await,
+            Err(ConfigError::Capture(_))
+        ));
+
+        let path = Utf8PathBuf::try_from(dir.path().join("pdus.pcap")).unwrap();
+        config.local.capture_file = Some(path.clone());
+        assert!(config.builder().await.is_ok());
+        assert_eq!(Ok(vec![]), read_pcap(&std::fs::read(path).unwrap()));
+    }
+
+    #[tokio::test]
+    async fn invalid_before_bind() {
+        let mut config = DaemonConfig::from_toml(TOML).unwrap();
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        config.local.udp_bind = Some(socket.local_addr().unwrap());
+        config.defaults.ack_timeout = Some(Timeout::from_secs(0));
+        // the configuration is rejected rather than the address reported in use.
+        assert!(matches!(
+            config.builder().await,
+            Err(ConfigError::InvalidTimeout { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn missing_bind() {
+        let mut config = DaemonConfig::from_toml(TOML).unwrap();
+        config.local.udp_bind = None;
+        assert!(matches!(
+            config.builder().await,
+            Err(ConfigError::Missing(_))
+        ));
+    }
+}
@@ -0,0 +1,171 @@
+use std::time::{Duration, SystemTime};
+
+use cfdp_core::daemon::{ContactWindow, LinkState};
+use tokio::sync::watch;
+
+/// Tracks the availability of the link to a single remote entity.
+///
+/// The state is derived from the contact plan unless a manual override is set.
+/// A failed transport always brings the link down until it recovers.
+/// Every transaction with the remote entity holds a [watch::Receiver] to be notified of changes.
+#[derive(Debug)]
+pub struct LinkSchedule {
+    windows: Vec<ContactWindow>,
+    state_override: Option<LinkState>,
+    transport_state: LinkState,
+    state_tx: watch::Sender<LinkState>,
+}
+impl LinkSchedule {
+    pub fn new() -> Self {
+        let (state_tx, _) = watch::channel(LinkState::Up);
+        Self {
+            windows: vec![],
+            state_override: None,
+            transport_state: LinkState::Up,
+            state_tx,
+        }
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<LinkState> {
+        self.state_tx.subscribe()
+    }
+
+    pub fn set_plan(
+        &mut self,
+        mut windows: Vec<ContactWindow>,
+        now: SystemTime,
+    ) -> Option<LinkState> {
+        windows.sort_by_key(|window| window.start);
+        self.windows = windows;
+        self.update(now)
+    }
+
+    pub fn set_override(&mut self, state: Option<LinkState>, now: SystemTime) -> Option<LinkState> {
+        self.state_override = state;
+        self.update(now)
+    }
+
+    pub fn set_transport_state(&mut self, state: LinkState, now: SystemTime) -> Option<LinkState> {
+        self.transport_state = state;
+        self.update(now)
+    }
+
+    /// Whether the link should be considered available at the given time.
+    pub fn state_at(&self, now: SystemTime) -> LinkState {
+        if self.transport_state == LinkState::Down {
+            return LinkState::Down;
+        }
+        match self.state_override {
+            Some(state) => state,
+            None if self.windows.is_empty() => LinkState::Up,
+            None if self.windows.iter().any(|window| window.contains(now)) => LinkState::Up,
+            None => LinkState::Down,
+        }
+    }
+
+    /// The next point in time at which the link state may change.
+    /// Returns [None] if the state is fixed.
+    pub fn next_transition(&self, now: SystemTime) -> Option<SystemTime> {
+        if self.state_override.is_some() {
+            return None;
+        }
+        self.windows
+            .iter()
+            .flat_map(|window| [window.start, window.end])
+            .filter(|time| *time > now)
+            .min()
+    }
+
+    /// Recompute the link state, notifying any transactions if it changed.
+    /// Returns the new state when a change occurred.
+    pub fn update(&self, now: SystemTime) -> Option<LinkState> {
+        let state = self.state_at(now);
+        self.state_tx
+            .send_if_modified(|current| {
+                let changed = *current != state;
+                *current = state;
+                changed
+            })
+            .then_some(state)
+    }
+}
+
+/// Time remaining until the given instant, saturating at zero.
+pub fn until(time: SystemTime, now: SystemTime) -> Duration {
+    time.duration_since(now).unwrap_or(Duration::ZERO)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn window(start: u64, end: u64) -> ContactWindow {
+        ContactWindow {
+            start: SystemTime::UNIX_EPOCH + Duration::from_secs(start),
+            end: SystemTime::UNIX_EPOCH + Duration::from_secs(end),
+        }
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn no_plan() {
+        let schedule = LinkSchedule::new();
+        assert_eq!(LinkState::Up, schedule.state_at(at(10)));
+        assert_eq!(None, schedule.next_transition(at(10)));
+    }
+
+    #[test]
+    fn follow_plan() {
+        let mut schedule = LinkSchedule::new();
+        let rx = schedule.subscribe();
+
+        schedule.set_plan(vec![window(100, 200), window(10, 20)], at(0));
+        assert_eq!(LinkState::Down, *rx.borrow());
+        assert_eq!(Some(at(10)), schedule.next_transition(at(0)));
+
+        assert_eq!(Some(LinkState::Up), schedule.update(at(15)));
+        assert_eq!(LinkState::Up, *rx.borrow());
+        assert_eq!(Some(at(20)), schedule.next_transition(at(15)));
+        // no change reported when the state is the same
+        assert_eq!(None, schedule.update(at(16)));
+
+        assert_eq!(Some(LinkState::Down), schedule.update(at(20)));
+        assert_eq!(Some(at(100)), schedule.next_transition(at(20)));
+        assert_eq!(None, schedule.next_transition(at(200)));
+    }
+
+    #[test]
+    fn manual_override() {
+        let mut schedule = LinkSchedule::new();
+        let rx = schedule.subscribe();
+        schedule.set_plan(vec![window(10, 20)], at(15));
+        assert_eq!(LinkState::Up, *rx.borrow());
+
+        schedule.set_override(Some(LinkState::Down), at(15));
+        assert_eq!(LinkState::Down, *rx.borrow());
+        assert_eq!(None, schedule.next_transition(at(15)));
+
+        schedule.set_override(None, at(15));
+        assert_eq!(LinkState::Up, *rx.borrow());
+    }
+
+    #[test]
+    fn transport_failure() {
+        let mut schedule = LinkSchedule::new();
+        schedule.set_override(Some(LinkState::Up), at(0));
+
+        assert_eq!(
+            Some(LinkState::Down),
+            schedule.set_transport_state(LinkState::Down, at(0))
+        );
+        // a failed transport cannot be overridden
+        assert_eq!(None, schedule.set_override(Some(LinkState::Up), at(0)));
+        assert_eq!(
+            Some(LinkState::Up),
+            schedule.set_transport_state(LinkState::Up, at(0))
+        );
+    }
+}
@@ -0,0 +1,1988 @@
+//! Local control interface to a running [Daemon](crate::Daemon) over a Unix domain socket.
+//!
+//! The interface carries every [UserPrimitive] and streams [Indication]s to subscribed clients,
+//! so that tools written in any language can drive the Daemon.
+//!
+//! # Protocol
+//! This module implements version [PROTOCOL_VERSION] of the protocol.
+//!
+//! Messages are UTF-8 encoded JSON objects, each terminated by a newline.
+//! Requests are identified by their `request` field and responses by their `response` field,
+//! the remaining fields of each message are described by [ControlRequest] and [ControlResponse].
+//! Entity IDs and sequence numbers are plain integers and are encoded
+//! with the ID width of the local entity, unless a transaction gives their widths.
+//! Enumerations such as conditions and transaction states are written as their variant name.
+//!
+//! The socket is only accessible to the user running the Daemon, which carries out every request
+//! with its own filesystem rights. It is placed in `$XDG_RUNTIME_DIR` by default, see [default_socket].
+//! Requests longer than [MAX_REQUEST_LEN] bytes close the connection.
+//!
+//! A client should begin by exchanging a `hello` with the version it implements,
+//! the Daemon refuses versions it does not speak.
+//!
+//! ```text
+//! > {"request": "hello", "version": 1}
+//! < {"response": "hello", "version": 1, "entity_id": 1, "id_width": 2}
+//! > {"request": "put", "source_filename": "local.txt", "destination_filename": "remote.txt", "destination_entity_id": 2, "transmission_mode": "Acknowledged"}
+//! < {"response": "started", "transaction": {"entity_id": 1, "sequence_number": 0, "entity_id_width": 2, "sequence_number_width": 2}}
+//! > {"request": "report", "transaction": {"entity_id": 1, "sequence_number": 0}}
+//! < {"response": "report", "transaction": {"entity_id": 1, "sequence_number": 0, "entity_id_width": 2, "sequence_number_width": 2}, "state": "Active", "status": "Undefined", "condition": "NoError"}
+//! ```
+//!
+//! Every request is answered by exactly one response, with the exception of
+//! - a `put` with `"follow": true` and `watch`, which are followed by the events of the transaction
+//!   until a `finished` response or an `abandoned` event.
+//!   Watching a transaction which already finished yields only its `finished` response.
+//! - `subscribe`, which is answered by `subscribed` followed by the matching indications issued
+//!   by the Daemon for as long as the connection stays open.
+//!   No further requests are accepted on the connection.
+//!
+//! A request the Daemon does not carry out is answered by `refused` with the `reason`,
+//! such as `unknown_transaction`, `finished`, or `no_route`, see [Refusal].
+//!
+//! A client which reads a stream too slowly receives a `lagged` response with the number of
+//! indications it missed, then continues with the oldest indication still retained.
+//!
+//! New optional request fields, responses, and events may be added without changing the version,
+//! clients should ignore responses and events they do not recognize.
+//! Any other change increments [PROTOCOL_VERSION].
+use std::{
+    fmt,
+    fs::Permissions,
+    io::{Error as IoError, ErrorKind},
+    net::SocketAddr,
+    os::unix::{
+        fs::{FileTypeExt, PermissionsExt},
+        net::UnixStream as StdUnixStream,
+    },
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use cfdp_core::{
+    daemon::{
+        ContactWindow, Indication, IndicationKind, LinkState, PrimitiveError, PrimitiveResult,
+        Priority, PutRequest, Report, ShutdownMode, UserPrimitive,
+    },
+    pdu::{
+        Condition, DeliveryCode, FileStatusCode, FileStoreRequest, FileStoreResponse,
+        MessageToUser, NakOrKeepAlive, TransactionStatus, TransmissionMode,
+    },
+    transaction::{TransactionID, TransactionState},
+};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, Lines},
+    net::{
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+        UnixListener, UnixStream,
+    },
+    sync::{mpsc::Sender, oneshot},
+};
+
+use crate::{
+    entity::{variable_id, TransportKind},
+    error::{ConfigResult, SubscriptionError},
+    history::{HistoryQuery, TransactionHistory, TransactionRecord},
+    indication::{IndicationBroadcast, IndicationFilter, Subscription},
+};
+
+/// Name of the socket when none is configured.
+pub const DEFAULT_SOCKET_NAME: &str = "cfdp-daemon.sock";
+
+/// Longest request accepted from a client, newline included.
+pub const MAX_REQUEST_LEN: usize = 64 * 1024;
+
+/// Socket used when none is configured.
+///
+/// It is created in the per-user `$XDG_RUNTIME_DIR`,
+/// or the temporary directory of the system where that is not set.
+pub fn default_socket() -> Utf8PathBuf {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    Utf8PathBuf::from_path_buf(dir.join(DEFAULT_SOCKET_NAME))
+        .unwrap_or_else(|path| Utf8PathBuf::from(path.to_string_lossy().into_owned()))
+}
+
+/// Version of the protocol spoken over the control interface.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// A [TransactionID] as exchanged over the control interface.
+///
+/// Written as `<entity_id>:<sequence_number>` on the command line.
+/// The widths of the IDs are part of the transaction, those not given are
+/// taken to be the ID width of the local entity.
+pub struct TransactionRef {
+    pub entity_id: u64,
+    pub sequence_number: u64,
+    /// Number of bytes the entity ID is encoded with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity_id_width: Option<u8>,
+    /// Number of bytes the sequence number is encoded with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence_number_width: Option<u8>,
+}
+impl From<&TransactionID> for TransactionRef {
+    fn from(id: &TransactionID) -> Self {
+        Self {
+            entity_id: id.0.to_u64(),
+            sequence_number: id.1.to_u64(),
+            entity_id_width: Some(id.0.width()),
+            sequence_number_width: Some(id.1.width()),
+        }
+    }
+}
+impl TransactionRef {
+    /// A transaction whose IDs have the width of the local entity.
+    pub fn new(entity_id: u64, sequence_number: u64) -> Self {
+        Self {
+            entity_id,
+            sequence_number,
+            entity_id_width: None,
+            sequence_number_width: None,
+        }
+    }
+
+    pub fn to_id(self, id_width: u8) -> ConfigResult<TransactionID> {
+        Ok(TransactionID(
+            variable_id(self.entity_id, self.entity_id_width.unwrap_or(id_width))?,
+            variable_id(
+                self.sequence_number,
+                self.sequence_number_width.unwrap_or(id_width),
+            )?,
+        ))
+    }
+}
+impl fmt::Display for TransactionRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.entity_id, self.sequence_number)
+    }
+}
+impl FromStr for TransactionRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (entity_id, sequence_number) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected <entity_id>:<sequence_number>, found {s}"))?;
+        Ok(Self::new(
+            entity_id.parse().map_err(|err| format!("{err}"))?,
+            sequence_number.parse().map_err(|err| format!("{err}"))?,
+        ))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// A remote entity known to the Daemon.
+pub struct RemoteEntity {
+    pub entity_id: u64,
+    pub transport: TransportKind,
+    pub address: SocketAddr,
+}
+#[cfg(feature = "config")]
+impl From<&crate::config::RemoteConfig> for RemoteEntity {
+    fn from(config: &crate::config::RemoteConfig) -> Self {
+        Self {
+            entity_id: config.entity_id,
+            transport: config.transport,
+            address: config.address,
+        }
+    }
+}
+
+/// Milliseconds since the Unix epoch.
+fn millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// A [ContactWindow] in milliseconds since the Unix epoch.
+pub struct ContactWindowRef {
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+impl From<ContactWindowRef> for ContactWindow {
+    fn from(window: ContactWindowRef) -> Self {
+        Self {
+            start: UNIX_EPOCH + Duration::from_millis(window.start_ms),
+            end: UNIX_EPOCH + Duration::from_millis(window.end_ms),
+        }
+    }
+}
+impl From<&ContactWindow> for ContactWindowRef {
+    fn from(window: &ContactWindow) -> Self {
+        Self {
+            start_ms: millis(window.start),
+            end_ms: millis(window.end),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+/// A [ShutdownMode] as exchanged over the control interface.
+pub enum ShutdownModeRef {
+    Drain { timeout_ms: u64 },
+    SuspendAll,
+    Abort,
+}
+impl From<ShutdownModeRef> for ShutdownMode {
+    fn from(mode: ShutdownModeRef) -> Self {
+        match mode {
+            ShutdownModeRef::Drain { timeout_ms } => {
+                ShutdownMode::Drain(Duration::from_millis(timeout_ms))
+            }
+            ShutdownModeRef::SuspendAll => ShutdownMode::SuspendAll,
+            ShutdownModeRef::Abort => ShutdownMode::Abort,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "request", rename_all = "snake_case")]
+pub enum ControlRequest {
+    /// Announce the protocol version implemented by the client.
+    Hello {
+        version: u32,
+    },
+    Put {
+        source_filename: Utf8PathBuf,
+        destination_filename: Utf8PathBuf,
+        destination_entity_id: u64,
+        transmission_mode: TransmissionMode,
+        #[serde(default)]
+        filestore_requests: Vec<FileStoreRequest>,
+        #[serde(default)]
+        messages_to_user: Vec<MessageToUser>,
+        /// Scheduling priority, the configured default of the remote entity when omitted.
+        #[serde(default)]
+        priority: Option<Priority>,
+        /// Whether the receiver of an unacknowledged transaction sends a Finished PDU,
+        /// as configured for the remote entity when omitted.
+        #[serde(default)]
+        closure_requested: Option<bool>,
+        /// Stream the events of the new transaction until it finishes.
+        #[serde(default)]
+        follow: bool,
+    },
+    Cancel {
+        transaction: TransactionRef,
+    },
+    Suspend {
+        transaction: TransactionRef,
+    },
+    Resume {
+        transaction: TransactionRef,
+    },
+    Report {
+        transaction: TransactionRef,
+    },
+    Prompt {
+        transaction: TransactionRef,
+        option: NakOrKeepAlive,
+    },
+    SetContactPlan {
+        entity_id: u64,
+        windows: Vec<ContactWindowRef>,
+    },
+    SetLinkState {
+        entity_id: u64,
+        state: LinkState,
+    },
+    ClearLinkState {
+        entity_id: u64,
+    },
+    /// Stop the Daemon, answered once every transaction has stopped.
+    Shutdown {
+        #[serde(flatten)]
+        mode: ShutdownModeRef,
+    },
+    /// Report the transaction, then stream its events until it finishes.
+    Watch {
+        transaction: TransactionRef,
+    },
+    /// Stream the indications issued by the Daemon.
+    /// Each list left empty matches every indication, otherwise one of its entries must match.
+    Subscribe {
+        #[serde(default)]
+        transactions: Vec<TransactionRef>,
+        /// Remote entities, matching their transactions and link changes.
+        #[serde(default)]
+        entities: Vec<u64>,
+        #[serde(default)]
+        kinds: Vec<IndicationKind>,
+    },
+    /// Records of finished transactions.
+    /// Each list left empty matches every record, otherwise one of its entries must match.
+    History {
+        #[serde(default)]
+        transactions: Vec<TransactionRef>,
+        #[serde(default)]
+        entities: Vec<u64>,
+        /// Only transactions which finished at or after this time, in milliseconds since the Unix epoch.
+        #[serde(default)]
+        since_ms: Option<u64>,
+        /// Only transactions which finished before this time, in milliseconds since the Unix epoch.
+        #[serde(default)]
+        until_ms: Option<u64>,
+    },
+    ListRemote,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// Status of a transaction, see [Report].
+pub struct TransactionReport {
+    pub transaction: TransactionRef,
+    pub state: TransactionState,
+    pub status: TransactionStatus,
+    pub condition: Condition,
+}
+impl From<&Report> for TransactionReport {
+    fn from(report: &Report) -> Self {
+        Self {
+            transaction: TransactionRef::from(&report.id),
+            state: report.state,
+            status: report.status,
+            condition: report.condition,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// A [TransactionRecord] as exchanged over the control interface.
+/// Times are in milliseconds since the Unix epoch.
+pub struct HistoryRecord {
+    #[serde(flatten)]
+    pub report: TransactionReport,
+    pub remote_entity_id: u64,
+    pub transmission_mode: TransmissionMode,
+    pub source_filename: Utf8PathBuf,
+    pub destination_filename: Utf8PathBuf,
+    pub file_size: u64,
+    pub file_status: FileStatusCode,
+    pub delivery_code: DeliveryCode,
+    pub filestore_responses: Vec<FileStoreResponse>,
+    pub started_ms: u64,
+    pub finished_ms: u64,
+}
+impl From<&TransactionRecord> for HistoryRecord {
+    fn from(record: &TransactionRecord) -> Self {
+        Self {
+            report: TransactionReport::from(&record.report),
+            remote_entity_id: record.remote_entity_id.to_u64(),
+            transmission_mode: record.transmission_mode,
+            source_filename: record.source_filename.clone(),
+            destination_filename: record.destination_filename.clone(),
+            file_size: record.file_size,
+            file_status: record.file_status,
+            delivery_code: record.delivery_code,
+            filestore_responses: record.filestore_responses.clone(),
+            started_ms: millis(record.started),
+            finished_ms: millis(record.finished),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+/// A [PrimitiveError] as exchanged over the control interface.
+pub enum Refusal {
+    UnknownTransaction {
+        transaction: TransactionRef,
+    },
+    Finished {
+        transaction: TransactionRef,
+    },
+    NotSender {
+        transaction: TransactionRef,
+    },
+    NoRoute {
+        entity_id: u64,
+    },
+    FileStore {
+        message: String,
+    },
+    Unsupported {
+        entity_id: u64,
+        feature: String,
+    },
+    MetadataTooLarge {
+        entity_id: u64,
+        pdu_len: usize,
+        max_pdu_len: usize,
+    },
+    FieldTooLong {
+        entity_id: u64,
+        field: String,
+        len: usize,
+        max_len: usize,
+    },
+    ShuttingDown,
+    NoSuspendStore,
+}
+impl From<&PrimitiveError> for Refusal {
+    fn from(error: &PrimitiveError) -> Self {
+        match error {
+            PrimitiveError::UnknownTransaction(id) => Self::UnknownTransaction {
+                transaction: TransactionRef::from(id),
+            },
+            PrimitiveError::Finished(id) => Self::Finished {
+                transaction: TransactionRef::from(id),
+            },
+            PrimitiveError::NotSender(id) => Self::NotSender {
+                transaction: TransactionRef::from(id),
+            },
+            PrimitiveError::NoRoute(entity_id) => Self::NoRoute {
+                entity_id: entity_id.to_u64(),
+            },
+            PrimitiveError::FileStore(message) => Self::FileStore {
+                message: message.clone(),
+            },
+            PrimitiveError::Unsupported(entity_id, feature) => Self::Unsupported {
+                entity_id: entity_id.to_u64(),
+                feature: feature.clone(),
+            },
+            PrimitiveError::MetadataTooLarge(entity_id, pdu_len, max_pdu_len) => {
+                Self::MetadataTooLarge {
+                    entity_id: entity_id.to_u64(),
+                    pdu_len: *pdu_len,
+                    max_pdu_len: *max_pdu_len,
+                }
+            }
+            PrimitiveError::FieldTooLong(entity_id, field, len, max_len) => Self::FieldTooLong {
+                entity_id: entity_id.to_u64(),
+                field: field.clone(),
+                len: *len,
+                max_len: *max_len,
+            },
+            PrimitiveError::ShuttingDown => Self::ShuttingDown,
+            PrimitiveError::NoSuspendStore => Self::NoSuspendStore,
+        }
+    }
+}
+impl fmt::Display for Refusal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownTransaction { transaction } => {
+                write!(f, "No transaction {transaction} is known to the Daemon.")
+            }
+            Self::Finished { transaction } => {
+                write!(f, "Transaction {transaction} has already finished.")
+            }
+            Self::NotSender { transaction } => {
+                write!(f, "Transaction {transaction} is not a Send transaction.")
+            }
+            Self::NoRoute { entity_id } => {
+                write!(f, "No Transport available for EntityID: {entity_id}.")
+            }
+            Self::FileStore { message } => write!(f, "FileStore error: {message}."),
+            Self::Unsupported { entity_id, feature } => write!(
+                f,
+                "{feature} is not supported by the protocol version spoken with entity {entity_id}."
+            ),
+            Self::MetadataTooLarge {
+                entity_id,
+                pdu_len,
+                max_pdu_len,
+            } => write!(
+                f,
+                "The Metadata PDU to entity {entity_id} would be {pdu_len} bytes but PDUs are limited to {max_pdu_len}."
+            ),
+            Self::FieldTooLong {
+                entity_id,
+                field,
+                len,
+                max_len,
+            } => write!(
+                f,
+                "{field} of {len} bytes for entity {entity_id} is longer than the {max_len} bytes it may hold."
+            ),
+            Self::ShuttingDown => write!(f, "The Daemon is shutting down."),
+            Self::NoSuspendStore => write!(
+                f,
+                "No store is configured to save suspended transactions in."
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+/// Progress of a transaction, derived from the [Indication]s issued by the Daemon.
+pub enum TransactionEvent {
+    Initiated,
+    EofSent,
+    EofReceived,
+    MetadataReceived {
+        source_filename: Utf8PathBuf,
+        destination_filename: Utf8PathBuf,
+        file_size: u64,
+        transmission_mode: TransmissionMode,
+        messages_to_user: Vec<MessageToUser>,
+    },
+    FileSegmentReceived {
+        offset: u64,
+        length: u64,
+    },
+    Suspended {
+        condition: Condition,
+    },
+    Resumed {
+        progress: u64,
+    },
+    Fault {
+        condition: Condition,
+        progress: u64,
+    },
+    Abandoned {
+        condition: Condition,
+        progress: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "response", rename_all = "snake_case")]
+pub enum ControlResponse {
+    Hello {
+        version: u32,
+        entity_id: u64,
+        id_width: u8,
+    },
+    /// A new transaction was started by a Put request.
+    Started {
+        transaction: TransactionRef,
+    },
+    /// The Daemon carried out the request.
+    Accepted,
+    /// The Daemon did not carry out the request for the given reason.
+    Refused {
+        #[serde(flatten)]
+        refusal: Refusal,
+    },
+    Report(TransactionReport),
+    Event {
+        transaction: TransactionRef,
+        #[serde(flatten)]
+        event: TransactionEvent,
+    },
+    /// The transaction has finished, this is the last response while following it.
+    Finished {
+        transaction: TransactionRef,
+        status: TransactionStatus,
+        condition: Condition,
+        file_status: FileStatusCode,
+        delivery_code: DeliveryCode,
+        filestore_responses: Vec<FileStoreResponse>,
+    },
+    /// The link to a remote entity has gone up or down.
+    Link {
+        entity_id: u64,
+        state: LinkState,
+    },
+    /// Indications follow on this connection.
+    Subscribed,
+    /// The client fell behind and the given number of indications were dropped.
+    Lagged {
+        missed: u64,
+    },
+    Remotes {
+        remotes: Vec<RemoteEntity>,
+    },
+    /// The finished transactions matching a history request, in the order they finished.
+    History {
+        records: Vec<HistoryRecord>,
+    },
+    /// The Daemon has stopped, with the final state of every transaction which was running.
+    Stopped {
+        reports: Vec<TransactionReport>,
+    },
+    Error {
+        message: String,
+    },
+}
+impl From<Report> for ControlResponse {
+    fn from(report: Report) -> Self {
+        Self::Report(TransactionReport::from(&report))
+    }
+}
+impl ControlResponse {
+    fn error(message: impl ToString) -> Self {
+        Self::Error {
+            message: message.to_string(),
+        }
+    }
+
+    fn event(id: &TransactionID, event: TransactionEvent) -> Self {
+        Self::Event {
+            transaction: TransactionRef::from(id),
+            event,
+        }
+    }
+
+    /// The response describing an indication.
+    pub fn from_indication(indication: &Indication) -> Self {
+        match indication {
+            Indication::Transaction(id) => Self::event(id, TransactionEvent::Initiated),
+            Indication::EoFSent(id) => Self::event(id, TransactionEvent::EofSent),
+            Indication::EoFRecv(id) => Self::event(id, TransactionEvent::EofReceived),
+            Indication::MetadataRecv(metadata) => Self::event(
+                &metadata.id,
+                TransactionEvent::MetadataReceived {
+                    source_filename: metadata.source_filename.clone(),
+                    destination_filename: metadata.destination_filename.clone(),
+                    file_size: metadata.file_size,
+                    transmission_mode: metadata.transmission_mode,
+                    messages_to_user: metadata.user_messages.clone(),
+                },
+            ),
+            Indication::FileSegmentRecv(segment) => Self::event(
+                &segment.id,
+                TransactionEvent::FileSegmentReceived {
+                    offset: segment.offset,
+                    length: segment.length,
+                },
+            ),
+            Indication::Suspended(suspend) => Self::event(
+                &suspend.id,
+                TransactionEvent::Suspended {
+                    condition: suspend.condition,
+                },
+            ),
+            Indication::Resumed(resume) => Self::event(
+                &resume.id,
+                TransactionEvent::Resumed {
+                    progress: resume.progress,
+                },
+            ),
+            Indication::Fault(fault) => Self::event(
+                &fault.id,
+                TransactionEvent::Fault {
+                    condition: fault.condition,
+                    progress: fault.progress,
+                },
+            ),
+            Indication::Abandon(fault) => Self::event(
+                &fault.id,
+                TransactionEvent::Abandoned {
+                    condition: fault.condition,
+                    progress: fault.progress,
+                },
+            ),
+            Indication::Finished(finished) => Self::Finished {
+                transaction: TransactionRef::from(&finished.id),
+                status: finished.report.status,
+                condition: finished.report.condition,
+                file_status: finished.file_status,
+                delivery_code: finished.delivery_code,
+                filestore_responses: finished.filestore_responses.clone(),
+            },
+            Indication::Report(report) => Self::Report(TransactionReport::from(report)),
+            Indication::Link(link) => Self::Link {
+                entity_id: link.entity_id.to_u64(),
+                state: link.state,
+            },
+        }
+    }
+
+    /// The transaction this response describes, if any.
+    pub fn transaction(&self) -> Option<TransactionRef> {
+        match self {
+            Self::Started { transaction }
+            | Self::Event { transaction, .. }
+            | Self::Finished { transaction, .. } => Some(*transaction),
+            Self::Report(report) => Some(report.transaction),
+            _ => None,
+        }
+    }
+
+    /// Whether no further responses follow this one when following a transaction.
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            Self::Finished { .. }
+                | Self::Refused { .. }
+                | Self::Error { .. }
+                | Self::Event {
+                    event: TransactionEvent::Abandoned { .. },
+                    ..
+                }
+        )
+    }
+}
+
+/// Accepts clients on a Unix socket and forwards their requests to the Daemon.
+pub struct ControlServer {
+    listener: UnixListener,
+    context: ClientContext,
+}
+impl ControlServer {
+    /// Listen on the given path, replacing any stale socket left by a previous run.
+    ///
+    /// Only the owner of the Daemon may connect to the socket.
+    /// Fails with [AddrInUse](ErrorKind::AddrInUse) if another server still accepts
+    /// connections on the socket, or the path is not a socket at all.
+    pub fn bind(
+        path: &Utf8Path,
+        primitive_tx: Sender<UserPrimitive>,
+        entity_id: u64,
+        id_width: u8,
+    ) -> Result<Self, IoError> {
+        match std::fs::symlink_metadata(path) {
+            Ok(metadata) => {
+                // only a socket nobody is listening on any more is safe to replace.
+                if !metadata.file_type().is_socket() || StdUnixStream::connect(path).is_ok() {
+                    return Err(IoError::new(
+                        ErrorKind::AddrInUse,
+                        format!("{path} is in use"),
+                    ));
+                }
+                std::fs::remove_file(path)?;
+            }
+            Err(err) if err.kind() != ErrorKind::NotFound => return Err(err),
+            Err(_) => {}
+        }
+        let listener = UnixListener::bind(path)?;
+        // other users could otherwise issue requests with the rights of the Daemon.
+        std::fs::set_permissions(path, Permissions::from_mode(0o600))?;
+        Ok(Self {
+            listener,
+            context: ClientContext {
+                primitive_tx,
+                entity_id,
+                id_width,
+                remotes: Arc::new(vec![]),
+                indications: None,
+                history: None,
+            },
+        })
+    }
+
+    /// The remote entities reported to clients.
+    pub fn remotes(mut self, remotes: Vec<RemoteEntity>) -> Self {
+        self.context.remotes = Arc::new(remotes);
+        self
+    }
+
+    /// Indications issued by the Daemon, required for clients to follow transactions
+    /// or subscribe to indications.
+    pub fn indications(mut self, indications: IndicationBroadcast) -> Self {
+        self.context.indications = Some(indications);
+        self
+    }
+
+    /// Records of finished transactions, required for clients to query the history.
+    pub fn history(mut self, history: TransactionHistory) -> Self {
+        self.context.history = Some(history);
+        self
+    }
+
+    /// Serve clients until the Daemon stops accepting primitives.
+    pub async fn run(self) -> Result<(), IoError> {
+        loop {
+            let stream = tokio::select! {
+                accepted = self.listener.accept() => accepted?.0,
+                _ = self.context.primitive_tx.closed() => return Ok(()),
+            };
+            let context = self.context.clone();
+            tokio::task::spawn(async move {
+                if let Err(err) = handle_client(stream, context).await {
+                    error!("Control client error: {err}");
+                }
+            });
+        }
+    }
+}
+
+#[derive(Clone)]
+struct ClientContext {
+    primitive_tx: Sender<UserPrimitive>,
+    entity_id: u64,
+    id_width: u8,
+    remotes: Arc<Vec<RemoteEntity>>,
+    indications: Option<IndicationBroadcast>,
+    history: Option<TransactionHistory>,
+}
+
+/// Requests read from a client, each at most [MAX_REQUEST_LEN] bytes long.
+struct RequestLines {
+    reader: BufReader<OwnedReadHalf>,
+    line: Vec<u8>,
+}
+impl RequestLines {
+    fn new(read: OwnedReadHalf) -> Self {
+        Self {
+            reader: BufReader::new(read),
+            line: vec![],
+        }
+    }
+
+    /// The next request, without its line ending.
+    ///
+    /// Cancel safe, a partially read request is kept until the next call.
+    /// Fails with [InvalidData](ErrorKind::InvalidData) once a request grows too long.
+    async fn next_line(&mut self) -> Result<Option<String>, IoError> {
+        let limit = (MAX_REQUEST_LEN + 1).saturating_sub(self.line.len()) as u64;
+        (&mut self.reader)
+            .take(limit)
+            .read_until(b'\n', &mut self.line)
+            .await?;
+        if self.line.len() > MAX_REQUEST_LEN {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!("request longer than {MAX_REQUEST_LEN} bytes"),
+            ));
+        }
+        if self.line.is_empty() {
+            return Ok(None);
+        }
+        let mut line = std::mem::take(&mut self.line);
+        if line.last() == Some(&b'\n') {
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+        }
+        String::from_utf8(line)
+            .map(Some)
+            .map_err(|err| IoError::new(ErrorKind::InvalidData, err))
+    }
+}
+
+async fn handle_client(stream: UnixStream, context: ClientContext) -> Result<(), IoError> {
+    let (read, mut write) = stream.into_split();
+    let mut lines = RequestLines::new(read);
+    while let Some(line) = lines.next_line().await? {
+        let request = match serde_json::from_str::<ControlRequest>(&line) {
+            Ok(request) => request,
+            Err(err) => {
+                let response = ControlResponse::error(format!("Invalid request: {err}"));
+                write_line(&mut write, &response).await?;
+                continue;
+            }
+        };
+        info!("Control request: {request:?}");
+
+        // subscribe before the request is forwarded so no event of the transaction is missed.
+        let mut subscription = match &request {
+            ControlRequest::Put { follow: true, .. } | ControlRequest::Watch { .. } => {
+                context.subscribe(IndicationFilter::new())
+            }
+            ControlRequest::Subscribe {
+                transactions,
+                entities,
+                kinds,
+            } => match context.filter(transactions, entities, kinds) {
+                Ok(filter) => context.subscribe(filter),
+                Err(response) => {
+                    write_line(&mut write, &response).await?;
+                    continue;
+                }
+            },
+            _ => None,
+        };
+        let response = context.dispatch(request).await;
+        write_line(&mut write, &response).await?;
+
+        match (subscription.as_mut(), &response) {
+            (Some(subscription), ControlResponse::Subscribed) => {
+                return stream_indications(subscription, &mut lines, &mut write).await
+            }
+            (Some(subscription), _) if !response.is_final() => {
+                if let Some(transaction) = response.transaction() {
+                    follow(subscription, transaction, &mut write).await?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Forward the events of a transaction to the client until it finishes.
+async fn follow(
+    subscription: &mut Subscription,
+    transaction: TransactionRef,
+    write: &mut OwnedWriteHalf,
+) -> Result<(), IoError> {
+    loop {
+        let indication = match subscription.recv().await {
+            Ok(indication) => indication,
+            Err(SubscriptionError::Lagged(missed)) => {
+                warn!("Control client following {transaction} missed {missed} indications.");
+                write_line(write, &ControlResponse::Lagged { missed }).await?;
+                continue;
+            }
+            Err(SubscriptionError::Closed) => {
+                let response =
+                    ControlResponse::error("Daemon stopped before the transaction finished.");
+                return write_line(write, &response).await;
+            }
+        };
+        let response = ControlResponse::from_indication(&indication);
+        if response.transaction() == Some(transaction) {
+            write_line(write, &response).await?;
+            if response.is_final() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Forward the subscribed indications to the client until it disconnects.
+async fn stream_indications(
+    subscription: &mut Subscription,
+    lines: &mut RequestLines,
+    write: &mut OwnedWriteHalf,
+) -> Result<(), IoError> {
+    loop {
+        tokio::select! {
+            line = lines.next_line() => match line? {
+                Some(_) => {
+                    let response =
+                        ControlResponse::error("Requests are not accepted on a subscribed connection.");
+                    write_line(write, &response).await?;
+                }
+                None => return Ok(()),
+            },
+            indication = subscription.recv() => match indication {
+                Ok(indication) => {
+                    write_line(write, &ControlResponse::from_indication(&indication)).await?
+                }
+                Err(SubscriptionError::Lagged(missed)) => {
+                    warn!("Control client subscription missed {missed} indications.");
+                    write_line(write, &ControlResponse::Lagged { missed }).await?
+                }
+                Err(SubscriptionError::Closed) => return Ok(()),
+            },
+        }
+    }
+}
+
+impl ClientContext {
+    fn transaction_id(
+        &self,
+        transaction: TransactionRef,
+    ) -> Result<TransactionID, ControlResponse> {
+        transaction
+            .to_id(self.id_width)
+            .map_err(ControlResponse::error)
+    }
+
+    async fn send(&self, primitive: UserPrimitive) -> Result<(), ControlResponse> {
+        self.primitive_tx
+            .send(primitive)
+            .await
+            .map_err(|_| ControlResponse::error("Daemon is not running."))
+    }
+
+    /// Issue a primitive and wait for the outcome the Daemon replies with.
+    async fn request<R>(
+        &self,
+        primitive: impl FnOnce(oneshot::Sender<PrimitiveResult<R>>) -> UserPrimitive,
+    ) -> Result<R, ControlResponse> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.send(primitive(reply_tx)).await?;
+        reply_rx
+            .await
+            .map_err(|_| ControlResponse::error("Daemon stopped before replying."))?
+            .map_err(|err| ControlResponse::Refused {
+                refusal: Refusal::from(&err),
+            })
+    }
+
+    fn subscribe(&self, filter: IndicationFilter) -> Option<Subscription> {
+        self.indications
+            .as_ref()
+            .map(|indications| indications.subscribe(filter))
+    }
+
+    fn filter(
+        &self,
+        transactions: &[TransactionRef],
+        entities: &[u64],
+        kinds: &[IndicationKind],
+    ) -> Result<IndicationFilter, ControlResponse> {
+        let mut filter = IndicationFilter::new();
+        for transaction in transactions {
+            filter = filter.transaction(self.transaction_id(*transaction)?);
+        }
+        for entity in entities {
+            filter =
+                filter.entity(variable_id(*entity, self.id_width).map_err(ControlResponse::error)?);
+        }
+        for kind in kinds {
+            filter = filter.kind(*kind);
+        }
+        Ok(filter)
+    }
+
+    fn query(
+        &self,
+        transactions: &[TransactionRef],
+        entities: &[u64],
+        since_ms: Option<u64>,
+        until_ms: Option<u64>,
+    ) -> Result<HistoryQuery, ControlResponse> {
+        let mut query = HistoryQuery::new();
+        for transaction in transactions {
+            query = query.transaction(self.transaction_id(*transaction)?);
+        }
+        for entity in entities {
+            query =
+                query.entity(variable_id(*entity, self.id_width).map_err(ControlResponse::error)?);
+        }
+        if let Some(since_ms) = since_ms {
+            query = query.since(UNIX_EPOCH + Duration::from_millis(since_ms));
+        }
+        if let Some(until_ms) = until_ms {
+            query = query.until(UNIX_EPOCH + Duration::from_millis(until_ms));
+        }
+        Ok(query)
+    }
+
+    /// The outcome of a finished transaction being watched, if the history holds it.
+    async fn finished(
+        &self,
+        watch: bool,
+        id: TransactionID,
+    ) -> Result<Option<ControlResponse>, ControlResponse> {
+        let history = match (watch, self.history.as_ref()) {
+            (true, Some(history)) => history,
+            _ => return Ok(None),
+        };
+        Ok(history
+            .get(id)
+            .await
+            .map_err(ControlResponse::error)?
+            .map(|record| ControlResponse::Finished {
+                transaction: TransactionRef::from(&id),
+                status: record.report.status,
+                condition: record.report.condition,
+                file_status: record.file_status,
+                delivery_code: record.delivery_code,
+                filestore_responses: record.filestore_responses,
+            }))
+    }
+
+    fn require_indications(&self) -> Result<(), ControlResponse> {
+        match self.indications {
+            Some(_) => Ok(()),
+            None => Err(ControlResponse::error(
+                "Indications are not available from this daemon.",
+            )),
+        }
+    }
+
+    async fn dispatch(&self, request: ControlRequest) -> ControlResponse {
+        match self.try_dispatch(request).await {
+            Ok(response) | Err(response) => response,
+        }
+    }
+
+    async fn try_dispatch(
+        &self,
+        request: ControlRequest,
+    ) -> Result<ControlResponse, ControlResponse> {
+        match request {
+            ControlRequest::Hello { version } if version == PROTOCOL_VERSION => {
+                Ok(ControlResponse::Hello {
+                    version: PROTOCOL_VERSION,
+                    entity_id: self.entity_id,
+                    id_width: self.id_width,
+                })
+            }
+            ControlRequest::Hello { version } => Err(ControlResponse::error(format!(
+                "Unsupported protocol version {version}, this daemon implements version {PROTOCOL_VERSION}."
+            ))),
+            ControlRequest::Put {
+                source_filename,
+                destination_filename,
+                destination_entity_id,
+                transmission_mode,
+                filestore_requests,
+                messages_to_user,
+                priority,
+                closure_requested,
+                follow,
+            } => {
+                if follow {
+                    self.require_indications()?;
+                }
+                let destination_entity_id = variable_id(destination_entity_id, self.id_width)
+                    .map_err(ControlResponse::error)?;
+                let request = PutRequest {
+                    source_filename,
+                    destination_filename,
+                    destination_entity_id,
+                    transmission_mode,
+                    filestore_requests,
+                    message_to_user: messages_to_user,
+                    closure_requested,
+                    priority,
+                };
+                let id = self
+                    .request(|reply| UserPrimitive::Put(request, reply))
+                    .await?;
+                Ok(ControlResponse::Started {
+                    transaction: TransactionRef::from(&id),
+                })
+            }
+            ControlRequest::Report { transaction } | ControlRequest::Watch { transaction } => {
+                let watch = matches!(request, ControlRequest::Watch { .. });
+                if watch {
+                    self.require_indications()?;
+                }
+                let id = self.transaction_id(transaction)?;
+                // a transaction which already finished has nothing left to follow.
+                if let Some(finished) = self.finished(watch, id).await? {
+                    return Ok(finished);
+                }
+                match self.request(|reply| UserPrimitive::Report(id, reply)).await {
+                    Ok(report) => Ok(ControlResponse::from(report)),
+                    // it may have finished since the history was checked.
+                    Err(err) => Err(self.finished(watch, id).await?.unwrap_or(err)),
+                }
+            }
+            ControlRequest::Cancel { transaction }
+            | ControlRequest::Suspend { transaction }
+            | ControlRequest::Resume { transaction }
+            | ControlRequest::Prompt { transaction, .. } => {
+                let id = self.transaction_id(transaction)?;
+                self.request(|reply| match request {
+                    ControlRequest::Cancel { .. } => UserPrimitive::Cancel(id, reply),
+                    ControlRequest::Suspend { .. } => UserPrimitive::Suspend(id, reply),
+                    ControlRequest::Prompt { option, .. } => {
+                        UserPrimitive::Prompt(id, option, reply)
+                    }
+                    _ => UserPrimitive::Resume(id, reply),
+                })
+                .await?;
+                Ok(ControlResponse::Accepted)
+            }
+            ControlRequest::SetContactPlan { entity_id, .. }
+            | ControlRequest::SetLinkState { entity_id, .. }
+            | ControlRequest::ClearLinkState { entity_id } => {
+                let entity_id =
+                    variable_id(entity_id, self.id_width).map_err(ControlResponse::error)?;
+                let primitive = match request {
+                    ControlRequest::SetContactPlan { windows, .. } => UserPrimitive::SetContactPlan(
+                        entity_id,
+                        windows.into_iter().map(ContactWindow::from).collect(),
+                    ),
+                    ControlRequest::SetLinkState { state, .. } => {
+                        UserPrimitive::SetLinkState(entity_id, state)
+                    }
+                    _ => UserPrimitive::ClearLinkState(entity_id),
+                };
+                self.send(primitive).await?;
+                Ok(ControlResponse::Accepted)
+            }
+            ControlRequest::Shutdown { mode } => {
+                let reports = self
+                    .request(|reply| UserPrimitive::Shutdown(mode.into(), reply))
+                    .await?;
+                Ok(ControlResponse::Stopped {
+                    reports: reports.iter().map(TransactionReport::from).collect(),
+                })
+            }
+            ControlRequest::Subscribe { .. } => {
+                self.require_indications()?;
+                Ok(ControlResponse::Subscribed)
+            }
+            ControlRequest::History {
+                transactions,
+                entities,
+                since_ms,
+                until_ms,
+            } => {
+                let history = self.history.as_ref().ok_or_else(|| {
+                    ControlResponse::error("Transaction history is not available from this daemon.")
+                })?;
+                let query = self.query(&transactions, &entities, since_ms, until_ms)?;
+                let records = history.query(&query).await.map_err(ControlResponse::error)?;
+                Ok(ControlResponse::History {
+                    records: records.iter().map(HistoryRecord::from).collect(),
+                })
+            }
+            ControlRequest::ListRemote => Ok(ControlResponse::Remotes {
+                remotes: self.remotes.as_ref().clone(),
+            }),
+        }
+    }
+}
+
+async fn write_line<S: Serialize>(write: &mut OwnedWriteHalf, message: &S) -> Result<(), IoError> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    write.write_all(&line).await
+}
+
+/// Client side of the control interface.
+pub struct ControlClient {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    write: OwnedWriteHalf,
+}
+impl ControlClient {
+    pub async fn connect(path: &Utf8Path) -> Result<Self, IoError> {
+        let (read, write) = UnixStream::connect(path).await?.into_split();
+        Ok(Self {
+            lines: BufReader::new(read).lines(),
+            write,
+        })
+    }
+
+    /// Send a request and wait for its response.
+    pub async fn request(&mut self, request: &ControlRequest) -> Result<ControlResponse, IoError> {
+        write_line(&mut self.write, request).await?;
+        self.next_response().await
+    }
+
+    /// Wait for the next response streamed while following a transaction
+    /// or subscribed to indications.
+    pub async fn next_response(&mut self) -> Result<ControlResponse, IoError> {
+        let line = self
+            .lines
+            .next_line()
+            .await?
+            .ok_or_else(|| IoError::from(ErrorKind::UnexpectedEof))?;
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use cfdp_core::{
+        daemon::{FinishedIndication, LinkIndication, SuspendIndication},
+        pdu::{EntityID, TransactionSeqNum},
+    };
+    use tempfile::TempDir;
+    use tokio::sync::mpsc::channel;
+
+    fn socket_path(dir: &TempDir) -> Utf8PathBuf {
+        Utf8PathBuf::from_path_buf(dir.path().join("control.sock")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn bind_in_use() {
+        let dir = TempDir::new().unwrap();
+        let path = socket_path(&dir);
+        let (primitive_tx, _primitive_rx) = channel(1);
+
+        let server = ControlServer::bind(&path, primitive_tx.clone(), 1, 2).unwrap();
+        let err = ControlServer::bind(&path, primitive_tx.clone(), 1, 2)
+            .err()
+            .unwrap();
+        assert_eq!(ErrorKind::AddrInUse, err.kind());
+
+        // the socket left behind once the server is gone is replaced.
+        drop(server);
+        assert!(path.exists());
+        assert!(ControlServer::bind(&path, primitive_tx.clone(), 1, 2).is_ok());
+
+        let file = Utf8PathBuf::from_path_buf(dir.path().join("file")).unwrap();
+        std::fs::write(&file, b"keep").unwrap();
+        let err = ControlServer::bind(&file, primitive_tx, 1, 2)
+            .err()
+            .unwrap();
+        assert_eq!(ErrorKind::AddrInUse, err.kind());
+        assert_eq!(b"keep".to_vec(), std::fs::read(&file).unwrap());
+    }
+
+    #[tokio::test]
+    async fn owner_only() {
+        let dir = TempDir::new().unwrap();
+        let path = socket_path(&dir);
+        let (primitive_tx, _primitive_rx) = channel(1);
+
+        let _server = ControlServer::bind(&path, primitive_tx, 1, 2).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(0o600, mode & 0o777);
+    }
+
+    #[tokio::test]
+    async fn stops_with_daemon() {
+        let dir = TempDir::new().unwrap();
+        let path = socket_path(&dir);
+        let (primitive_tx, primitive_rx) = channel(1);
+        let server = ControlServer::bind(&path, primitive_tx, 1, 2).unwrap();
+        let run = tokio::task::spawn(server.run());
+
+        // no client needs to connect for the server to notice.
+        drop(primitive_rx);
+        tokio::time::timeout(Duration::from_secs(1), run)
+            .await
+            .expect("server kept running.")
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn request_too_long() {
+        let dir = TempDir::new().unwrap();
+        let path = socket_path(&dir);
+        let (primitive_tx, _primitive_rx) = channel(1);
+        let server = ControlServer::bind(&path, primitive_tx, 1, 2).unwrap();
+        tokio::task::spawn(server.run());
+
+        let mut client = ControlClient::connect(&path).await.unwrap();
+        let hello = ControlRequest::Hello {
+            version: PROTOCOL_VERSION,
+        };
+        assert!(matches!(
+            client.request(&hello).await.unwrap(),
+            ControlResponse::Hello { .. }
+        ));
+
+        // the connection is closed before the request ends.
+        let _ = client.write.write_all(&[b' '; MAX_REQUEST_LEN + 1]).await;
+        assert_eq!(
+            ErrorKind::UnexpectedEof,
+            client.next_response().await.unwrap_err().kind()
+        );
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let dir = TempDir::new().unwrap();
+        let path = socket_path(&dir);
+        let (primitive_tx, mut primitive_rx) = channel(1);
+        let server = ControlServer::bind(&path, primitive_tx, 1, 2).unwrap();
+        tokio::task::spawn(server.run());
+
+        // stand in for the daemon
+        let id = TransactionID(EntityID::from(1_u16), TransactionSeqNum::from(3_u16));
+        tokio::task::spawn(async move {
+            while let Some(primitive) = primitive_rx.recv().await {
+                match primitive {
+                    UserPrimitive::Put(request, sender) => {
+                        assert_eq!(EntityID::from(2_u16), request.destination_entity_id);
+                        assert_eq!(
+                            Some(Priority {
+                                class: 2,
+                                weight: 3
+                            }),
+                            request.priority
+                        );
+                        assert_eq!(Some(true), request.closure_requested);
+                        sender.send(Ok(id)).unwrap();
+                    }
+                    UserPrimitive::Report(report_id, sender) if report_id == id => sender
+                        .send(Ok(Report {
+                            id,
+                            state: TransactionState::Active,
+                            status: TransactionStatus::Active,
+                            condition: Condition::NoError,
+                        }))
+                        .unwrap(),
+                    UserPrimitive::Report(report_id, sender) => sender
+                        .send(Err(PrimitiveError::UnknownTransaction(report_id)))
+                        .unwrap(),
+                    UserPrimitive::Cancel(cancel_id, sender) => sender
+                        .send(Err(PrimitiveError::Finished(cancel_id)))
+                        .unwrap(),
+                    other => panic!("unexpected primitive {other:?}"),
+                }
+            }
+        });
+
+        let mut client = ControlClient::connect(&path).await.unwrap();
+        let transaction = TransactionRef::from(&id);
+        assert_eq!(
+            ControlResponse::Started { transaction },
+            client
+                .request(&ControlRequest::Put {
+                    source_filename: "local/small.txt".into(),
+                    destination_filename: "remote/small.txt".into(),
+                    destination_entity_id: 2,
+                    transmission_mode: TransmissionMode::Acknowledged,
+                    filestore_requests: vec![],
+                    messages_to_user: vec![],
+                    priority: Some(Priority {
+                        class: 2,
+                        weight: 3,
+                    }),
+                    closure_requested: Some(true),
+                    follow: false,
+                })
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            ControlResponse::Report(TransactionReport {
+                transaction,
+                state: TransactionState::Active,
+                status: TransactionStatus::Active,
+                condition: Condition::NoError
+            }),
+            client
+                .request(&ControlRequest::Report { transaction })
+                .await
+                .unwrap()
+        );
+        // IDs without widths have the width of the local entity.
+        let unknown = TransactionRef::new(1, 4);
+        assert_eq!(
+            ControlResponse::Refused {
+                refusal: Refusal::UnknownTransaction {
+                    transaction: TransactionRef {
+                        entity_id_width: Some(2),
+                        sequence_number_width: Some(2),
+                        ..unknown
+                    }
+                }
+            },
+            client
+                .request(&ControlRequest::Report {
+                    transaction: unknown
+                })
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            ControlResponse::Refused {
+                refusal: Refusal::Finished { transaction }
+            },
+            client
+                .request(&ControlRequest::Cancel { transaction })
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn follow_put() {
+        let dir = TempDir::new().unwrap();
+        let path = socket_path(&dir);
+        let (primitive_tx, mut primitive_rx) = channel(1);
+        let indications = IndicationBroadcast::new(8);
+        let server = ControlServer::bind(&path, primitive_tx, 1, 2)
+            .unwrap()
+            .indications(indications.clone());
+        tokio::task::spawn(server.run());
+
+        let id = TransactionID(EntityID::from(1_u16), TransactionSeqNum::from(3_u16));
+        let other = TransactionID(EntityID::from(1_u16), TransactionSeqNum::from(4_u16));
+        tokio::task::spawn(async move {
+            if let Some(UserPrimitive::Put(_, sender)) = primitive_rx.recv().await {
+                sender.send(Ok(id)).unwrap();
+            }
+            for indication in [
+                Indication::EoFSent(other),
+                Indication::Suspended(SuspendIndication {
+                    id,
+                    condition: Condition::NoError,
+                }),
+                Indication::EoFSent(id),
+                Indication::Finished(FinishedIndication {
+                    id,
+                    report: Report {
+                        id,
+                        state: TransactionState::Terminated,
+                        status: TransactionStatus::Unrecognized,
+                        condition: Condition::NoError,
+                    },
+                    file_status: FileStatusCode::Unreported,
+                    delivery_code: DeliveryCode::Complete,
+                    filestore_responses: vec![],
+                }),
+            ] {
+                indications.sender(EntityID::from(2_u16)).send(indication);
+            }
+        });
+
+        let mut client = ControlClient::connect(&path).await.unwrap();
+        let transaction = TransactionRef::from(&id);
+        let mut responses = vec![client
+            .request(&ControlRequest::Put {
+                source_filename: "local/small.txt".into(),
+                destination_filename: "remote/small.txt".into(),
+                destination_entity_id: 2,
+                transmission_mode: TransmissionMode::Unacknowledged,
+                filestore_requests: vec![],
+                messages_to_user: vec![],
+                priority: None,
+                closure_requested: None,
+                follow: true,
+            })
+            .await
+            .unwrap()];
+        while !responses.last().unwrap().is_final() {
+            responses.push(client.next_response().await.unwrap());
+        }
+        assert_eq!(
+            vec![
+                ControlResponse::Started { transaction },
+                ControlResponse::Event {
+                    transaction,
+                    event: TransactionEvent::Suspended {
+                        condition: Condition::NoError
+                    }
+                },
+                ControlResponse::Event {
+                    transaction,
+                    event: TransactionEvent::EofSent
+                },
+                ControlResponse::Finished {
+                    transaction,
+                    status: TransactionStatus::Unrecognized,
+                    condition: Condition::NoError,
+                    file_status: FileStatusCode::Unreported,
+                    delivery_code: DeliveryCode::Complete,
+                    filestore_responses: vec![]
+                }
+            ],
+            responses
+        );
+    }
+
+    #[tokio::test]
+    async fn list_remote_and_prompt() {
+        let dir = TempDir::new().unwrap();
+        let path = socket_path(&dir);
+        let (primitive_tx, mut primitive_rx) = channel(1);
+        let remotes = vec![RemoteEntity {
+            entity_id: 2,
+            transport: TransportKind::Udp,
+            address: "127.0.0.1:4560".parse().unwrap(),
+        }];
+        let server = ControlServer::bind(&path, primitive_tx, 1, 2)
+            .unwrap()
+            .remotes(remotes.clone());
+        tokio::task::spawn(server.run());
+
+        let mut client = ControlClient::connect(&path).await.unwrap();
+        assert_eq!(
+            ControlResponse::Remotes { remotes },
+            client.request(&ControlRequest::ListRemote).await.unwrap()
+        );
+
+        let transaction = TransactionRef::new(1, 3);
+        tokio::task::spawn(async move {
+            // a panic drops the reply, failing the request.
+            match primitive_rx.recv().await {
+                Some(UserPrimitive::Prompt(id, NakOrKeepAlive::KeepAlive, reply))
+                    if Ok(id) == transaction.to_id(2) =>
+                {
+                    reply.send(Ok(())).unwrap()
+                }
+                other => panic!("unexpected primitive {other:?}"),
+            }
+        });
+        assert_eq!(
+            ControlResponse::Accepted,
+            client
+                .request(&ControlRequest::Prompt {
+                    transaction,
+                    option: NakOrKeepAlive::KeepAlive
+                })
+                .await
+                .unwrap()
+        );
+
+        // following requires the indications of the daemon.
+        assert!(matches!(
+            client
+                .request(&ControlRequest::Watch { transaction })
+                .await
+                .unwrap(),
+            ControlResponse::Error { .. }
+        ));
+    }
+
+    #[test]
+    fn transaction_ref_widths() {
+        // a transaction started by a remote entity with wider IDs than the local ones.
+        let id = TransactionID(EntityID::new(5, 4).unwrap(), TransactionSeqNum::from(9_u64));
+        let transaction = TransactionRef::from(&id);
+        let json = serde_json::to_string(&transaction).unwrap();
+        assert_eq!(
+            r#"{"entity_id":5,"sequence_number":9,"entity_id_width":4,"sequence_number_width":8}"#,
+            json
+        );
+        let parsed: TransactionRef = serde_json::from_str(&json).unwrap();
+        assert_eq!(Ok(id), parsed.to_id(2));
+
+        let local = TransactionRef::new(5, 9).to_id(2).unwrap();
+        assert_ne!(id, local);
+        assert_eq!((2, 2), (local.0.width(), local.1.width()));
+    }
+
+    #[test]
+    fn transaction_ref_from_str() {
+        assert_eq!(Ok(TransactionRef::new(12, 7)), "12:7".parse());
+        assert!("12".parse::<TransactionRef>().is_err());
+        assert!("a:7".parse::<TransactionRef>().is_err());
+    }
+
+    #[test]
+    fn request_format() {
+        assert_eq!(
+            ControlRequest::Cancel {
+                transaction: TransactionRef::new(1, 2)
+            },
+            serde_json::from_str(
+                r#"{"request": "cancel", "transaction": {"entity_id": 1, "sequence_number": 2}}"#
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn documented_messages() {
+        // the exchange shown in the module documentation.
+        let requests = [
+            r#"{"request": "hello", "version": 1}"#,
+            r#"{"request": "put", "source_filename": "local.txt", "destination_filename": "remote.txt", "destination_entity_id": 2, "transmission_mode": "Acknowledged"}"#,
+            r#"{"request": "report", "transaction": {"entity_id": 1, "sequence_number": 0}}"#,
+        ];
+        for request in requests {
+            assert!(serde_json::from_str::<ControlRequest>(request).is_ok());
+        }
+        let responses = [
+            r#"{"response": "hello", "version": 1, "entity_id": 1, "id_width": 2}"#,
+            r#"{"response": "started", "transaction": {"entity_id": 1, "sequence_number": 0}}"#,
+            r#"{"response": "report", "transaction": {"entity_id": 1, "sequence_number": 0}, "state": "Active", "status": "Undefined", "condition": "NoError"}"#,
+        ];
+        for response in responses {
+            assert!(serde_json::from_str::<ControlResponse>(response).is_ok());
+        }
+    }
+
+    #[test]
+    fn put_with_filestore_requests() {
+        assert_eq!(
+            ControlRequest::Put {
+                source_filename: "a.txt".into(),
+                destination_filename: "b.txt".into(),
+                destination_entity_id: 2,
+                transmission_mode: TransmissionMode::Unacknowledged,
+                filestore_requests: vec![FileStoreRequest {
+                    action_code: cfdp_core::pdu::FileStoreAction::DeleteFile,
+                    first_filename: "old.txt".into(),
+                    second_filename: "".into(),
+                }],
+                messages_to_user: vec![MessageToUser {
+                    message_text: vec![1, 2]
+                }],
+                priority: Some(Priority {
+                    class: 1,
+                    weight: 1,
+                }),
+                closure_requested: None,
+                follow: false,
+            },
+            serde_json::from_str(
+                r#"{"request": "put", "source_filename": "a.txt", "destination_filename": "b.txt",
+                "destination_entity_id": 2, "transmission_mode": "Unacknowledged",
+                "filestore_requests": [{"action_code": "DeleteFile", "first_filename": "old.txt"}],
+                "messages_to_user": [{"message_text": [1, 2]}], "priority": {"class": 1}}"#
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            ControlRequest::Shutdown {
+                mode: ShutdownModeRef::Drain { timeout_ms: 500 }
+            },
+            serde_json::from_str(r#"{"request": "shutdown", "mode": "drain", "timeout_ms": 500}"#)
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn version_and_primitives() {
+        let dir = TempDir::new().unwrap();
+        let path = socket_path(&dir);
+        let (primitive_tx, mut primitive_rx) = channel(1);
+        let server = ControlServer::bind(&path, primitive_tx, 1, 2).unwrap();
+        tokio::task::spawn(server.run());
+
+        let mut client = ControlClient::connect(&path).await.unwrap();
+        assert!(matches!(
+            client
+                .request(&ControlRequest::Hello { version: 0 })
+                .await
+                .unwrap(),
+            ControlResponse::Error { .. }
+        ));
+        assert_eq!(
+            ControlResponse::Hello {
+                version: PROTOCOL_VERSION,
+                entity_id: 1,
+                id_width: 2
+            },
+            client
+                .request(&ControlRequest::Hello {
+                    version: PROTOCOL_VERSION
+                })
+                .await
+                .unwrap()
+        );
+
+        assert_eq!(
+            ControlResponse::Accepted,
+            client
+                .request(&ControlRequest::SetContactPlan {
+                    entity_id: 2,
+                    windows: vec![ContactWindowRef {
+                        start_ms: 1_000,
+                        end_ms: 2_500
+                    }]
+                })
+                .await
+                .unwrap()
+        );
+        match primitive_rx.recv().await {
+            Some(UserPrimitive::SetContactPlan(entity, windows)) => {
+                assert_eq!(EntityID::from(2_u16), entity);
+                assert_eq!(
+                    vec![ContactWindow {
+                        start: UNIX_EPOCH + Duration::from_millis(1_000),
+                        end: UNIX_EPOCH + Duration::from_millis(2_500)
+                    }],
+                    windows
+                );
+            }
+            other => panic!("unexpected primitive {other:?}"),
+        }
+
+        let id = TransactionID(EntityID::from(1_u16), TransactionSeqNum::from(3_u16));
+        tokio::task::spawn(async move {
+            if let Some(UserPrimitive::Shutdown(ShutdownMode::Abort, reply)) =
+                primitive_rx.recv().await
+            {
+                reply
+                    .send(Ok(vec![Report {
+                        id,
+                        state: TransactionState::Terminated,
+                        status: TransactionStatus::Undefined,
+                        condition: Condition::CancelReceived,
+                    }]))
+                    .unwrap();
+            }
+        });
+        assert_eq!(
+            ControlResponse::Stopped {
+                reports: vec![TransactionReport {
+                    transaction: TransactionRef::from(&id),
+                    state: TransactionState::Terminated,
+                    status: TransactionStatus::Undefined,
+                    condition: Condition::CancelReceived,
+                }]
+            },
+            client
+                .request(&ControlRequest::Shutdown {
+                    mode: ShutdownModeRef::Abort
+                })
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn subscribe() {
+        let dir = TempDir::new().unwrap();
+        let path = socket_path(&dir);
+        let (primitive_tx, _primitive_rx) = channel(1);
+        let indications = IndicationBroadcast::new(8);
+        let server = ControlServer::bind(&path, primitive_tx, 1, 2)
+            .unwrap()
+            .indications(indications.clone());
+        tokio::task::spawn(server.run());
+
+        let mut client = ControlClient::connect(&path).await.unwrap();
+        assert_eq!(
+            ControlResponse::Subscribed,
+            client.request(&subscribe_to(vec![])).await.unwrap()
+        );
+
+        let id = TransactionID(EntityID::from(1_u16), TransactionSeqNum::from(3_u16));
+        indications
+            .sender(EntityID::from(2_u16))
+            .send(Indication::Transaction(id));
+        indications.publish(Some(EntityID::from(2_u16)), link(2, LinkState::Down));
+        assert_eq!(
+            ControlResponse::Event {
+                transaction: TransactionRef::from(&id),
+                event: TransactionEvent::Initiated
+            },
+            client.next_response().await.unwrap()
+        );
+        assert_eq!(
+            ControlResponse::Link {
+                entity_id: 2,
+                state: LinkState::Down
+            },
+            client.next_response().await.unwrap()
+        );
+
+        // the connection only carries indications once subscribed.
+        assert!(matches!(
+            client.request(&ControlRequest::ListRemote).await.unwrap(),
+            ControlResponse::Error { .. }
+        ));
+    }
+
+    fn subscribe_to(kinds: Vec<IndicationKind>) -> ControlRequest {
+        ControlRequest::Subscribe {
+            transactions: vec![],
+            entities: vec![],
+            kinds,
+        }
+    }
+
+    fn link(entity: u16, state: LinkState) -> Indication {
+        Indication::Link(LinkIndication {
+            entity_id: EntityID::from(entity),
+            state,
+        })
+    }
+
+    #[tokio::test]
+    async fn subscribe_filtered_lagging() {
+        let dir = TempDir::new().unwrap();
+        let path = socket_path(&dir);
+        let (primitive_tx, _primitive_rx) = channel(1);
+        let indications = IndicationBroadcast::new(2);
+        let server = ControlServer::bind(&path, primitive_tx, 1, 2)
+            .unwrap()
+            .indications(indications.clone());
+        tokio::task::spawn(server.run());
+
+        let mut client = ControlClient::connect(&path).await.unwrap();
+        assert_eq!(
+            ControlResponse::Subscribed,
+            client
+                .request(&subscribe_to(vec![IndicationKind::Link]))
+                .await
+                .unwrap()
+        );
+
+        // published faster than the server can forward them.
+        let id = TransactionID(EntityID::from(1_u16), TransactionSeqNum::from(3_u16));
+        indications
+            .sender(EntityID::from(2_u16))
+            .send(Indication::Transaction(id));
+        for (entity, state) in [(2, LinkState::Up), (2, LinkState::Down), (3, LinkState::Up)] {
+            indications.publish(Some(EntityID::from(entity)), link(entity, state));
+        }
+
+        assert_eq!(
+            ControlResponse::Lagged { missed: 2 },
+            client.next_response().await.unwrap()
+        );
+        assert_eq!(
+            ControlResponse::Link {
+                entity_id: 2,
+                state: LinkState::Down
+            },
+            client.next_response().await.unwrap()
+        );
+        assert_eq!(
+            ControlResponse::Link {
+                entity_id: 3,
+                state: LinkState::Up
+            },
+            client.next_response().await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn history() {
+        let dir = TempDir::new().unwrap();
+        let path = socket_path(&dir);
+        let (primitive_tx, _primitive_rx) = channel(1);
+        let history = TransactionHistory::new(4);
+        for (sequence, entity) in [(3_u16, 2_u16), (4, 3)] {
+            let id = TransactionID(EntityID::from(1_u16), TransactionSeqNum::from(sequence));
+            history.insert(TransactionRecord {
+                report: Report {
+                    id,
+                    state: TransactionState::Terminated,
+                    status: TransactionStatus::Unrecognized,
+                    condition: Condition::NoError,
+                },
+                remote_entity_id: EntityID::from(entity),
+                transmission_mode: TransmissionMode::Acknowledged,
+                source_filename: "local.txt".into(),
+                destination_filename: "remote.txt".into(),
+                file_size: 10,
+                file_status: FileStatusCode::Retained,
+                delivery_code: DeliveryCode::Complete,
+                filestore_responses: vec![],
+                started: UNIX_EPOCH + Duration::from_millis(1_000),
+                finished: UNIX_EPOCH + Duration::from_millis(2_500),
+            });
+        }
+        let server = ControlServer::bind(&path, primitive_tx.clone(), 1, 2)
+            .unwrap()
+            .indications(IndicationBroadcast::new(8))
+            .history(history);
+        tokio::task::spawn(server.run());
+
+        let mut client = ControlClient::connect(&path).await.unwrap();
+        let request: ControlRequest =
+            serde_json::from_str(r#"{"request": "history", "entities": [3]}"#).unwrap();
+        let ControlResponse::History { records } = client.request(&request).await.unwrap() else {
+            panic!("expected a history response");
+        };
+        assert_eq!(1, records.len());
+        assert_eq!(4, records[0].report.transaction.sequence_number);
+        assert_eq!(3, records[0].remote_entity_id);
+        assert_eq!(
+            (1_000, 2_500),
+            (records[0].started_ms, records[0].finished_ms)
+        );
+
+        let ControlResponse::History { records } = client
+            .request(&ControlRequest::History {
+                transactions: vec![],
+                entities: vec![],
+                since_ms: Some(3_000),
+                until_ms: None,
+            })
+            .await
+            .unwrap()
+        else {
+            panic!("expected a history response");
+        };
+        assert!(records.is_empty());
+
+        // watching a transaction which already finished reports its outcome at once.
+        let ControlResponse::Finished {
+            transaction,
+            condition,
+            delivery_code,
+            ..
+        } = client
+            .request(&ControlRequest::Watch {
+                transaction: TransactionRef::new(1, 3),
+            })
+            .await
+            .unwrap()
+        else {
+            panic!("expected a finished response");
+        };
+        assert_eq!(3, transaction.sequence_number);
+        assert_eq!(Condition::NoError, condition);
+        assert_eq!(DeliveryCode::Complete, delivery_code);
+
+        // a server without the history of the daemon refuses the request.
+        let path = Utf8PathBuf::from_path_buf(dir.path().join("bare.sock")).unwrap();
+        let server = ControlServer::bind(&path, primitive_tx, 1, 2).unwrap();
+        tokio::task::spawn(server.run());
+        let mut client = ControlClient::connect(&path).await.unwrap();
+        assert!(matches!(
+            client.request(&request).await.unwrap(),
+            ControlResponse::Error { .. }
+        ));
+    }
+}
@@ -0,0 +1,42 @@
+//! Identification of remote entities shared by the configuration file and the control interface.
+use cfdp_core::pdu::{PDUError, VariableID};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ConfigError, ConfigResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// The available transports to reach a remote entity.
+pub enum TransportKind {
+    Udp,
+}
+
+/// Encode the ID with the given number of bytes.
+pub fn variable_id(id: u64, width: u8) -> ConfigResult<VariableID> {
+    VariableID::new(id, width).map_err(|err| match err {
+        PDUError::IDOutOfRange(..) => ConfigError::IDOutOfRange { id, width },
+        _ => ConfigError::InvalidIDWidth(width),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(300, 1, Err(ConfigError::IDOutOfRange { id: 300, width: 1 }))]
+    #[case(300, 2, Ok(VariableID::from(300_u16)))]
+    #[case(300, 3, Ok(VariableID::new(300, 3).unwrap()))]
+    #[case(300, 9, Err(ConfigError::InvalidIDWidth(9)))]
+    #[case(300, 0, Err(ConfigError::InvalidIDWidth(0)))]
+    #[case(u64::MAX, 8, Ok(VariableID::from(u64::MAX)))]
+    fn id_width(#[case] id: u64, #[case] width: u8, #[case] expected: ConfigResult<VariableID>) {
+        let found = variable_id(id, width);
+        assert_eq!(expected, found);
+        if let Ok(found) = found {
+            assert_eq!(width, found.width())
+        }
+    }
+}
@@ -1,6 +1,11 @@
+use camino::Utf8PathBuf;
 use thiserror::Error;
 
-use cfdp_core::{filestore::FileStoreError, transaction::TransactionID};
+use cfdp_core::{
+    daemon::{FaultIndication, PrimitiveError},
+    pdu::EntityID,
+    transaction::TransactionID,
+};
 use tokio::sync::mpsc::error::SendError;
 
 use crate::Command;
@@ -8,9 +13,6 @@ use crate::Command;
 pub type DaemonResult<T> = Result<T, DaemonError>;
 #[derive(Error, Debug)]
 pub enum DaemonError {
-    #[error("Error Spawning Send Transaction resulting from FileStore error: {0:}")]
-    SpawnSend(FileStoreError),
-
     #[error("Error sending Command to Transaction {0}: {1}")]
     TransactionCommuncation(TransactionID, Command),
 
@@ -26,3 +28,127 @@ impl From<(TransactionID, SendError<Command>)> for DaemonError {
         Self::TransactionCommuncation(value.0, value.1 .0)
     }
 }
+
+pub type ConfigResult<T> = Result<T, ConfigError>;
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    #[error("Missing required Daemon configuration: {0}.")]
+    Missing(&'static str),
+
+    #[error(
+        "The {timer} timeout for {entity} must be a positive number of seconds, found {value}."
+    )]
+    InvalidTimeout {
+        entity: String,
+        timer: &'static str,
+        value: i64,
+    },
+
+    #[error("The file size segment for {0} must be non-zero.")]
+    ZeroSegmentSize(String),
+
+    #[error(
+        "The {limit} rate limit for {entity} must allow a positive number of bytes per second."
+    )]
+    ZeroRate { entity: String, limit: &'static str },
+
+    #[error(
+        "File data PDUs to entity {entity} may be {pdu_len} bytes but PDUs are limited to {max_pdu_len}. Reduce the file size segment."
+    )]
+    SegmentTooLarge {
+        entity: EntityID,
+        pdu_len: usize,
+        max_pdu_len: usize,
+    },
+
+    #[error(
+        "NAK PDUs to entity {entity} need at least {nak_len} bytes but PDUs are limited to {max_pdu_len}."
+    )]
+    PDULengthTooShort {
+        entity: EntityID,
+        nak_len: usize,
+        max_pdu_len: usize,
+    },
+
+    #[error("The indication capacity must be non-zero.")]
+    ZeroIndicationCapacity,
+
+    #[error("Entity {0} is configured but no transport serves it.")]
+    NoTransport(EntityID),
+
+    #[error("Entity {0} is served by more than one transport.")]
+    DuplicateTransport(EntityID),
+
+    #[error("Entity IDs must be between 1 and 8 bytes wide, found {0}.")]
+    InvalidIDWidth(u8),
+
+    #[error("ID {id} does not fit in {width} byte(s).")]
+    IDOutOfRange { id: u64, width: u8 },
+
+    #[error("Unable to read configuration file {0}: {1}")]
+    Read(String, String),
+
+    #[error("Unable to parse configuration: {0}")]
+    Parse(String),
+
+    #[error("Unable to start transport: {0}")]
+    Transport(String),
+
+    #[error("Unable to load transaction history: {0}")]
+    History(String),
+
+    #[error("Unable to create PDU capture: {0}")]
+    Capture(String),
+
+    #[error("Unable to open the suspended transaction store: {0}")]
+    Suspend(String),
+}
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriptionError {
+    #[error("Subscriber fell behind and missed {0} indications.")]
+    Lagged(u64),
+
+    #[error("The Daemon is no longer issuing indications.")]
+    Closed,
+}
+
+#[derive(Error, Debug)]
+pub enum HistoryError {
+    #[error("Unable to access transaction history {0}: {1}")]
+    Io(Utf8PathBuf, std::io::Error),
+}
+
+#[derive(Error, Debug)]
+pub enum SuspendError {
+    #[error("Unable to access suspended transactions {0}: {1}")]
+    Io(Utf8PathBuf, std::io::Error),
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum CaptureError {
+    #[error("Not a pcap capture, unknown magic number 0x{0:08x}.")]
+    Magic(u32),
+
+    #[error("The capture is truncated at byte {0}.")]
+    Truncated(usize),
+
+    #[error("Unsupported capture link type {0}.")]
+    LinkType(u32),
+}
+
+pub type HandleResult<T> = Result<T, HandleError>;
+#[derive(Error, Debug, Clone)]
+pub enum HandleError {
+    #[error("The Daemon is no longer running.")]
+    DaemonStopped,
+
+    #[error("The Daemon refused the primitive. {0}")]
+    Refused(#[from] PrimitiveError),
+
+    #[error("Transaction {} was abandoned after {:?}.", .0.id, .0.condition)]
+    Abandoned(FaultIndication),
+
+    #[error("Fell behind and missed {0} indications.")]
+    Lagged(u64),
+}
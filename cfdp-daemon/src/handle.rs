@@ -0,0 +1,541 @@
+//! Typed async access to a running [Daemon](crate::Daemon).
+//!
+//! A [DaemonHandle] issues [UserPrimitive]s and waits for their replies,
+//! a [TransactionHandle] follows a single transaction through the [Indication]s it issues.
+//! Both report an error once the Daemon has stopped instead of silently dropping the request.
+use cfdp_core::{
+    daemon::{
+        ContactWindow, FinishedIndication, Indication, LinkState, PrimitiveResult, PutRequest,
+        Report, ShutdownMode, UserPrimitive,
+    },
+    pdu::{DeliveryCode, EntityID, FileStatusCode, NakOrKeepAlive},
+    transaction::{TransactionID, TransactionState},
+};
+use tokio::sync::{mpsc::Sender, oneshot};
+
+use crate::{
+    error::{HandleError, HandleResult, SubscriptionError},
+    history::{TransactionHistory, TransactionRecord},
+    indication::{IndicationBroadcast, IndicationFilter, Subscription},
+};
+
+impl From<&TransactionRecord> for FinishedIndication {
+    fn from(record: &TransactionRecord) -> Self {
+        Self {
+            id: record.id(),
+            report: record.report.clone(),
+            file_status: record.file_status,
+            delivery_code: record.delivery_code,
+            filestore_responses: record.filestore_responses.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Issues primitives to a running [Daemon](crate::Daemon).
+///
+/// Obtained from [DaemonBuilder::build_with_handle](crate::DaemonBuilder::build_with_handle),
+/// clones drive the same Daemon.
+pub struct DaemonHandle {
+    primitive_tx: Sender<UserPrimitive>,
+    indications: IndicationBroadcast,
+    history: TransactionHistory,
+}
+impl DaemonHandle {
+    /// Drive the Daemon receiving primitives from `primitive_tx`,
+    /// with its [indications](crate::Daemon::indications) and [history](crate::Daemon::history).
+    pub fn new(
+        primitive_tx: Sender<UserPrimitive>,
+        indications: IndicationBroadcast,
+        history: TransactionHistory,
+    ) -> Self {
+        Self {
+            primitive_tx,
+            indications,
+            history,
+        }
+    }
+
+    async fn send(&self, primitive: UserPrimitive) -> HandleResult<()> {
+        send(&self.primitive_tx, primitive).await
+    }
+
+    /// Start sending a file, see [UserPrimitive::Put].
+    pub async fn put(&self, put: PutRequest) -> HandleResult<TransactionHandle> {
+        // subscribe before the transaction starts so none of its indications are missed.
+        let subscription = self
+            .indications
+            .subscribe(IndicationFilter::new().entity(put.destination_entity_id));
+        let id = request(&self.primitive_tx, |reply| UserPrimitive::Put(put, reply)).await?;
+        Ok(self.follow(id, subscription))
+    }
+
+    /// Follow a transaction which is already running or has finished.
+    pub fn transaction(&self, id: TransactionID) -> TransactionHandle {
+        let subscription = self
+            .indications
+            .subscribe(IndicationFilter::new().transaction(id));
+        self.follow(id, subscription)
+    }
+
+    fn follow(&self, id: TransactionID, subscription: Subscription) -> TransactionHandle {
+        TransactionHandle {
+            id,
+            primitive_tx: self.primitive_tx.clone(),
+            subscription,
+            history: self.history.clone(),
+            final_report: None,
+        }
+    }
+
+    /// The current report of a transaction, or the final report if it has finished.
+    pub async fn report(&self, id: TransactionID) -> HandleResult<Report> {
+        report(&self.primitive_tx, id).await
+    }
+
+    pub async fn cancel(&self, id: TransactionID) -> HandleResult<()> {
+        request(&self.primitive_tx, |reply| UserPrimitive::Cancel(id, reply)).await
+    }
+
+    pub async fn suspend(&self, id: TransactionID) -> HandleResult<()> {
+        request(&self.primitive_tx, |reply| {
+            UserPrimitive::Suspend(id, reply)
+        })
+        .await
+    }
+
+    pub async fn resume(&self, id: TransactionID) -> HandleResult<()> {
+        request(&self.primitive_tx, |reply| UserPrimitive::Resume(id, reply)).await
+    }
+
+    pub async fn prompt(&self, id: TransactionID, option: NakOrKeepAlive) -> HandleResult<()> {
+        request(&self.primitive_tx, |reply| {
+            UserPrimitive::Prompt(id, option, reply)
+        })
+        .await
+    }
+
+    pub async fn set_contact_plan(
+        &self,
+        entity_id: EntityID,
+        windows: Vec<ContactWindow>,
+    ) -> HandleResult<()> {
+        self.send(UserPrimitive::SetContactPlan(entity_id, windows))
+            .await
+    }
+
+    pub async fn set_link_state(&self, entity_id: EntityID, state: LinkState) -> HandleResult<()> {
+        self.send(UserPrimitive::SetLinkState(entity_id, state))
+            .await
+    }
+
+    pub async fn clear_link_state(&self, entity_id: EntityID) -> HandleResult<()> {
+        self.send(UserPrimitive::ClearLinkState(entity_id)).await
+    }
+
+    /// Stop the Daemon, resolving to the final report of every transaction which was running.
+    pub async fn shutdown(&self, mode: ShutdownMode) -> HandleResult<Vec<Report>> {
+        request(&self.primitive_tx, |reply| {
+            UserPrimitive::Shutdown(mode, reply)
+        })
+        .await
+    }
+
+    /// Receive the indications matching the filter.
+    pub fn subscribe(&self, filter: IndicationFilter) -> Subscription {
+        self.indications.subscribe(filter)
+    }
+
+    /// Records of the finished transactions.
+    pub fn history(&self) -> &TransactionHistory {
+        &self.history
+    }
+}
+
+/// Follows a single transaction.
+///
+/// The transaction keeps running if the handle is dropped.
+pub struct TransactionHandle {
+    id: TransactionID,
+    primitive_tx: Sender<UserPrimitive>,
+    subscription: Subscription,
+    history: TransactionHistory,
+    // set once the final report of the transaction was received.
+    final_report: Option<Report>,
+}
+impl TransactionHandle {
+    pub fn id(&self) -> TransactionID {
+        self.id
+    }
+
+    /// The next indication issued for this transaction,
+    /// or `None` once the transaction has terminated.
+    ///
+    /// After a [Lagged](HandleError::Lagged) error the handle remains usable.
+    pub async fn next(&mut self) -> HandleResult<Option<Indication>> {
+        if self.final_report.is_some() {
+            return Ok(None);
+        }
+        loop {
+            let indication = tokio::select! {
+                // deliver any indications issued before the Daemon stopped.
+                biased;
+                indication = self.subscription.recv() => match indication {
+                    Ok(indication) => indication,
+                    Err(SubscriptionError::Lagged(missed)) => return Err(HandleError::Lagged(missed)),
+                    Err(SubscriptionError::Closed) => return Err(HandleError::DaemonStopped),
+                },
+                _ = self.primitive_tx.closed() => return Err(HandleError::DaemonStopped),
+            };
+            if indication.transaction_id() != Some(self.id) {
+                continue;
+            }
+            // every transaction issues a report as its final indication.
+            if let Indication::Report(report) = &indication {
+                if report.state == TransactionState::Terminated {
+                    self.final_report = Some(report.clone());
+                }
+            }
+            return Ok(Some(indication));
+        }
+    }
+
+    /// Wait for the transaction to finish.
+    ///
+    /// Resolves immediately if the transaction has already finished
+    /// and is still held in the [history](crate::history).
+    pub async fn finished(mut self) -> HandleResult<FinishedIndication> {
+        if let Some(finished) = self.recorded().await {
+            return Ok(finished);
+        }
+        loop {
+            match self.next().await {
+                Ok(Some(Indication::Finished(finished))) => return Ok(finished),
+                Ok(Some(Indication::Abandon(fault))) => return Err(HandleError::Abandoned(fault)),
+                Ok(Some(_)) => {}
+                // the transaction terminated without reaching the Finished state.
+                Ok(None) => {
+                    return Ok(self.recorded().await.unwrap_or_else(|| FinishedIndication {
+                        id: self.id,
+                        report: self.final_report.clone().expect("set once terminated"),
+                        file_status: FileStatusCode::Unreported,
+                        delivery_code: DeliveryCode::Incomplete,
+                        filestore_responses: vec![],
+                    }))
+                }
+                // the Finished indication may have been among those missed.
+                Err(HandleError::Lagged(_)) => {
+                    if let Some(finished) = self.recorded().await {
+                        return Ok(finished);
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    async fn recorded(&self) -> Option<FinishedIndication> {
+        self.history
+            .get(self.id)
+            .await
+            .ok()
+            .flatten()
+            .map(|record| FinishedIndication::from(&record))
+    }
+
+    /// The current report of the transaction, or the final report if it has finished.
+    pub async fn report(&self) -> HandleResult<Report> {
+        report(&self.primitive_tx, self.id).await
+    }
+
+    pub async fn cancel(&self) -> HandleResult<()> {
+        request(&self.primitive_tx, |reply| {
+            UserPrimitive::Cancel(self.id, reply)
+        })
+        .await
+    }
+
+    pub async fn suspend(&self) -> HandleResult<()> {
+        request(&self.primitive_tx, |reply| {
+            UserPrimitive::Suspend(self.id, reply)
+        })
+        .await
+    }
+
+    pub async fn resume(&self) -> HandleResult<()> {
+        request(&self.primitive_tx, |reply| {
+            UserPrimitive::Resume(self.id, reply)
+        })
+        .await
+    }
+
+    pub async fn prompt(&self, option: NakOrKeepAlive) -> HandleResult<()> {
+        request(&self.primitive_tx, |reply| {
+            UserPrimitive::Prompt(self.id, option, reply)
+        })
+        .await
+    }
+}
+
+async fn send(primitive_tx: &Sender<UserPrimitive>, primitive: UserPrimitive) -> HandleResult<()> {
+    primitive_tx
+        .send(primitive)
+        .await
+        .map_err(|_| HandleError::DaemonStopped)
+}
+
+/// Issue a primitive and wait for the outcome the Daemon replies with.
+async fn request<R>(
+    primitive_tx: &Sender<UserPrimitive>,
+    primitive: impl FnOnce(oneshot::Sender<PrimitiveResult<R>>) -> UserPrimitive,
+) -> HandleResult<R> {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    send(primitive_tx, primitive(reply_tx)).await?;
+    let outcome = reply_rx.await.map_err(|_| HandleError::DaemonStopped)?;
+    Ok(outcome?)
+}
+
+async fn report(primitive_tx: &Sender<UserPrimitive>, id: TransactionID) -> HandleResult<Report> {
+    request(primitive_tx, |reply| UserPrimitive::Report(id, reply)).await
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, SystemTime};
+
+    use cfdp_core::{
+        daemon::{FaultIndication, PrimitiveError},
+        pdu::{Condition, TransactionSeqNum, TransactionStatus, TransmissionMode},
+    };
+    use tokio::sync::mpsc::{channel, Receiver};
+
+    use super::*;
+
+    fn id(sequence: u16) -> TransactionID {
+        TransactionID(EntityID::from(1_u16), TransactionSeqNum::from(sequence))
+    }
+
+    fn request() -> PutRequest {
+        PutRequest {
+            source_filename: "local/small.txt".into(),
+            destination_filename: "remote/small.txt".into(),
+            destination_entity_id: EntityID::from(2_u16),
+            transmission_mode: TransmissionMode::Acknowledged,
+            filestore_requests: vec![],
+            message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
+        }
+    }
+
+    fn report(id: TransactionID, state: TransactionState) -> Report {
+        Report {
+            id,
+            state,
+            status: TransactionStatus::Active,
+            condition: Condition::NoError,
+        }
+    }
+
+    fn handle(capacity: usize) -> (DaemonHandle, Receiver<UserPrimitive>) {
+        let (primitive_tx, primitive_rx) = channel(1);
+        let handle = DaemonHandle::new(
+            primitive_tx,
+            IndicationBroadcast::new(capacity),
+            TransactionHistory::new(4),
+        );
+        (handle, primitive_rx)
+    }
+
+    /// Stand in for the Daemon, starting transaction 3 and issuing the given indications.
+    fn daemon(
+        mut primitive_rx: Receiver<UserPrimitive>,
+        indications: IndicationBroadcast,
+        issued: Vec<Indication>,
+    ) {
+        tokio::task::spawn(async move {
+            if let Some(UserPrimitive::Put(_, sender)) = primitive_rx.recv().await {
+                sender.send(Ok(id(3))).unwrap();
+            }
+            for indication in issued {
+                indications.sender(EntityID::from(2_u16)).send(indication);
+            }
+            // keep running until the handle is done.
+            while primitive_rx.recv().await.is_some() {}
+        });
+    }
+
+    #[tokio::test]
+    async fn put_until_finished() {
+        let (handle, primitive_rx) = handle(8);
+        let finished = FinishedIndication {
+            id: id(3),
+            report: report(id(3), TransactionState::Terminated),
+            file_status: FileStatusCode::Retained,
+            delivery_code: DeliveryCode::Complete,
+            filestore_responses: vec![],
+        };
+        daemon(
+            primitive_rx,
+            handle.indications.clone(),
+            vec![
+                Indication::Transaction(id(3)),
+                Indication::EoFSent(id(4)),
+                Indication::EoFSent(id(3)),
+                Indication::Finished(finished),
+                Indication::Report(report(id(3), TransactionState::Terminated)),
+            ],
+        );
+
+        let mut transaction = handle.put(request()).await.unwrap();
+        assert_eq!(id(3), transaction.id());
+        assert!(matches!(
+            transaction.next().await,
+            Ok(Some(Indication::Transaction(started))) if started == id(3)
+        ));
+        assert!(matches!(
+            transaction.next().await,
+            Ok(Some(Indication::EoFSent(sent))) if sent == id(3)
+        ));
+        let finished = transaction.finished().await.unwrap();
+        assert_eq!(id(3), finished.id);
+        assert_eq!(DeliveryCode::Complete, finished.delivery_code);
+    }
+
+    #[tokio::test]
+    async fn abandoned() {
+        let (handle, primitive_rx) = handle(8);
+        daemon(
+            primitive_rx,
+            handle.indications.clone(),
+            vec![Indication::Abandon(FaultIndication {
+                id: id(3),
+                condition: Condition::InactivityDetected,
+                progress: 0,
+            })],
+        );
+        let transaction = handle.put(request()).await.unwrap();
+        assert!(matches!(
+            transaction.finished().await,
+            Err(HandleError::Abandoned(fault)) if fault.condition == Condition::InactivityDetected
+        ));
+    }
+
+    #[tokio::test]
+    async fn terminated_from_history() {
+        let (handle, primitive_rx) = handle(8);
+        let record = TransactionRecord {
+            report: report(id(3), TransactionState::Terminated),
+            remote_entity_id: EntityID::from(2_u16),
+            transmission_mode: TransmissionMode::Acknowledged,
+            source_filename: "local/small.txt".into(),
+            destination_filename: "remote/small.txt".into(),
+            file_size: 10,
+            file_status: FileStatusCode::Retained,
+            delivery_code: DeliveryCode::Complete,
+            filestore_responses: vec![],
+            started: SystemTime::now(),
+            finished: SystemTime::now(),
+        };
+        // terminated without reaching the Finished state, e.g. abandoned at shutdown.
+        let history = handle.history.clone();
+        let indications = handle.indications.clone();
+        let mut primitive_rx = primitive_rx;
+        tokio::task::spawn(async move {
+            if let Some(UserPrimitive::Put(_, sender)) = primitive_rx.recv().await {
+                sender.send(Ok(id(3))).unwrap();
+            }
+            history.insert(record);
+            indications
+                .sender(EntityID::from(2_u16))
+                .send(Indication::Report(report(
+                    id(3),
+                    TransactionState::Terminated,
+                )));
+            while primitive_rx.recv().await.is_some() {}
+        });
+
+        let mut transaction = handle.put(request()).await.unwrap();
+        assert!(matches!(
+            transaction.next().await,
+            Ok(Some(Indication::Report(_)))
+        ));
+        assert!(matches!(transaction.next().await, Ok(None)));
+        assert_eq!(
+            FileStatusCode::Retained,
+            transaction.finished().await.unwrap().file_status
+        );
+
+        // a finished transaction resolves immediately.
+        let finished =
+            tokio::time::timeout(Duration::from_secs(1), handle.transaction(id(3)).finished())
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(DeliveryCode::Complete, finished.delivery_code);
+    }
+
+    #[tokio::test]
+    async fn daemon_stopped() {
+        let (handle, mut primitive_rx) = handle(8);
+        tokio::task::spawn(async move {
+            // refuse every Put and report, stop once asked to cancel.
+            while let Some(primitive) = primitive_rx.recv().await {
+                match primitive {
+                    UserPrimitive::Put(request, reply) => reply
+                        .send(Err(PrimitiveError::NoRoute(request.destination_entity_id)))
+                        .unwrap(),
+                    UserPrimitive::Report(id, reply) => reply
+                        .send(Err(PrimitiveError::UnknownTransaction(id)))
+                        .unwrap(),
+                    UserPrimitive::Cancel(_, reply) => {
+                        reply.send(Ok(())).unwrap();
+                        break;
+                    }
+                    other => panic!("unexpected primitive {other:?}"),
+                }
+            }
+        });
+        assert!(matches!(
+            handle.put(request()).await,
+            Err(HandleError::Refused(PrimitiveError::NoRoute(_)))
+        ));
+        assert!(matches!(
+            handle.report(id(5)).await,
+            Err(HandleError::Refused(PrimitiveError::UnknownTransaction(unknown))) if unknown == id(5)
+        ));
+
+        let mut transaction = handle.transaction(id(5));
+        transaction.cancel().await.unwrap();
+        assert!(matches!(
+            transaction.next().await,
+            Err(HandleError::DaemonStopped)
+        ));
+        assert!(matches!(
+            handle.cancel(id(5)).await,
+            Err(HandleError::DaemonStopped)
+        ));
+        assert!(matches!(
+            handle.put(request()).await,
+            Err(HandleError::DaemonStopped)
+        ));
+    }
+
+    #[tokio::test]
+    async fn lagged() {
+        let (handle, _primitive_rx) = handle(1);
+        let mut transaction = handle.transaction(id(3));
+        let sender = handle.indications.sender(EntityID::from(2_u16));
+        sender.send(Indication::EoFSent(id(3)));
+        sender.send(Indication::EoFRecv(id(3)));
+
+        assert!(matches!(
+            transaction.next().await,
+            Err(HandleError::Lagged(1))
+        ));
+        assert!(matches!(
+            transaction.next().await,
+            Ok(Some(Indication::EoFRecv(_)))
+        ));
+    }
+}
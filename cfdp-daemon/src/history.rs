@@ -0,0 +1,630 @@
+//! Records of completed transactions.
+//!
+//! Once a transaction ends its channel is removed from the [Daemon](crate::Daemon),
+//! the final state of the transaction is kept in a [TransactionHistory] instead.
+//! The most recent records are held in memory, bounded by the configured capacity.
+//! Optionally every record is also appended as a line of JSON to a log file,
+//! which outlives the Daemon and answers queries for records no longer held in memory.
+//! The log is only accessed from a dedicated thread, which keeps the offset of every record
+//! so records are read back without scanning the log from its start.
+//! Queries which only concern records still held in memory do not touch the log at all.
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use cfdp_core::{
+    daemon::Report,
+    filestore::FileStoreResponse,
+    pdu::{Condition, DeliveryCode, EntityID, FileStatusCode, TransmissionMode},
+    transaction::TransactionID,
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+use crate::error::HistoryError;
+
+/// Number of completed transactions held in memory unless configured otherwise.
+pub const DEFAULT_HISTORY_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The final state of a completed transaction.
+pub struct TransactionRecord {
+    /// The final report of the transaction.
+    pub report: Report,
+    /// The remote entity the transaction communicated with.
+    pub remote_entity_id: EntityID,
+    pub transmission_mode: TransmissionMode,
+    /// Empty if the transaction ended before its metadata was received.
+    pub source_filename: Utf8PathBuf,
+    /// Empty if the transaction ended before its metadata was received.
+    pub destination_filename: Utf8PathBuf,
+    pub file_size: u64,
+    pub file_status: FileStatusCode,
+    pub delivery_code: DeliveryCode,
+    pub filestore_responses: Vec<FileStoreResponse>,
+    /// When the transaction was initiated by or first became known to this entity.
+    pub started: SystemTime,
+    pub finished: SystemTime,
+}
+impl TransactionRecord {
+    pub fn id(&self) -> TransactionID {
+        self.report.id
+    }
+
+    pub fn condition(&self) -> Condition {
+        self.report.condition
+    }
+
+    /// Time from the start of the transaction until it finished.
+    pub fn duration(&self) -> Duration {
+        self.finished
+            .duration_since(self.started)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Selects the records returned by [TransactionHistory::query].
+///
+/// A criterion left empty matches every record,
+/// otherwise the record must match one of its entries.
+pub struct HistoryQuery {
+    transactions: Vec<TransactionID>,
+    entities: Vec<EntityID>,
+    since: Option<SystemTime>,
+    until: Option<SystemTime>,
+}
+impl HistoryQuery {
+    /// A query matching every record.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Match the record of the given transaction.
+    pub fn transaction(mut self, id: TransactionID) -> Self {
+        self.transactions.push(id);
+        self
+    }
+
+    /// Match records of transactions with the given remote entity.
+    pub fn entity(mut self, entity_id: EntityID) -> Self {
+        self.entities.push(entity_id);
+        self
+    }
+
+    /// Match transactions which finished at or after `time`.
+    pub fn since(mut self, time: SystemTime) -> Self {
+        self.since = Some(time);
+        self
+    }
+
+    /// Match transactions which finished before `time`.
+    pub fn until(mut self, time: SystemTime) -> Self {
+        self.until = Some(time);
+        self
+    }
+
+    fn matches(&self, record: &TransactionRecord) -> bool {
+        (self.transactions.is_empty() || self.transactions.contains(&record.id()))
+            && (self.entities.is_empty() || self.entities.contains(&record.remote_entity_id))
+            && self.since.is_none_or(|since| record.finished >= since)
+            && self.until.is_none_or(|until| record.finished < until)
+    }
+}
+
+#[derive(Debug)]
+struct Store {
+    capacity: usize,
+    // the most recent records, oldest first.
+    records: VecDeque<TransactionRecord>,
+    // set once a record was dropped from memory.
+    evicted: bool,
+    log: Option<mpsc::Sender<LogRequest>>,
+}
+impl Store {
+    fn push(&mut self, record: TransactionRecord) {
+        if self.capacity == 0 {
+            self.evicted = true;
+            return;
+        }
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+            self.evicted = true;
+        }
+        self.records.push_back(record);
+    }
+
+    /// Whether every record matching the query is held in memory.
+    ///
+    /// Records are stored in the order they finished, so any record older
+    /// than those in memory finished no later than the oldest of them.
+    fn holds(&self, query: &HistoryQuery) -> bool {
+        !self.evicted
+            || match (query.since, self.records.front()) {
+                (Some(since), Some(oldest)) => since > oldest.finished,
+                _ => false,
+            }
+    }
+
+    fn query(&self, query: &HistoryQuery) -> Vec<TransactionRecord> {
+        self.records
+            .iter()
+            .filter(|record| query.matches(record))
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+enum LogRequest {
+    Append(Box<TransactionRecord>),
+    Get(
+        TransactionID,
+        oneshot::Sender<Result<Option<TransactionRecord>, HistoryError>>,
+    ),
+    Query(
+        HistoryQuery,
+        oneshot::Sender<Result<Vec<TransactionRecord>, HistoryError>>,
+    ),
+}
+
+#[derive(Debug, Default)]
+/// Offsets of the records in the log.
+struct LogIndex {
+    // every record of each transaction, oldest first.
+    transactions: HashMap<TransactionID, Vec<u64>>,
+    // when each record finished, in the order of the log.
+    finished: Vec<(SystemTime, u64)>,
+}
+impl LogIndex {
+    fn insert(&mut self, offset: u64, record: &TransactionRecord) {
+        self.transactions
+            .entry(record.id())
+            .or_default()
+            .push(offset);
+        self.finished.push((record.finished, offset));
+    }
+
+    fn latest(&self, id: &TransactionID) -> Option<u64> {
+        self.transactions
+            .get(id)
+            .and_then(|offsets| offsets.last().copied())
+    }
+
+    /// Offsets of every record of the given transactions, in the order of the log.
+    fn records_of(&self, ids: &[TransactionID]) -> Vec<u64> {
+        let mut offsets: Vec<u64> = ids
+            .iter()
+            .filter_map(|id| self.transactions.get(id))
+            .flatten()
+            .copied()
+            .collect();
+        offsets.sort_unstable();
+        offsets.dedup();
+        offsets
+    }
+
+    /// Offset of the first record which finished at or after `time`,
+    /// [None] if every record finished before.
+    fn since(&self, time: SystemTime) -> Option<u64> {
+        let first = self
+            .finished
+            .partition_point(|(finished, _)| *finished < time);
+        self.finished.get(first).map(|(_, offset)| *offset)
+    }
+
+    /// Read the records matching the query, only visiting the part of the log they may be in.
+    fn query(
+        &self,
+        path: &Utf8Path,
+        query: &HistoryQuery,
+    ) -> Result<Vec<TransactionRecord>, HistoryError> {
+        let mut records = vec![];
+        if !query.transactions.is_empty() {
+            for offset in self.records_of(&query.transactions) {
+                match read_record(path, offset)? {
+                    Some(record) if query.matches(&record) => records.push(record),
+                    _ => {}
+                }
+            }
+            return Ok(records);
+        }
+
+        let start = match query.since {
+            Some(since) => match self.since(since) {
+                Some(offset) => offset,
+                None => return Ok(records),
+            },
+            None => 0,
+        };
+        scan_log(path, start, |_, record| {
+            if query.matches(&record) {
+                records.push(record)
+            }
+        })?;
+        Ok(records)
+    }
+}
+
+/// Serve requests for the log in order until every [TransactionHistory] is dropped.
+fn serve_log(path: Utf8PathBuf, mut index: LogIndex, requests: mpsc::Receiver<LogRequest>) {
+    for request in requests {
+        match request {
+            LogRequest::Append(record) => match append_log(&path, &record) {
+                Ok(offset) => index.insert(offset, &record),
+                Err(err) => warn!("Unable to record transaction {}: {err}", record.id()),
+            },
+            LogRequest::Get(id, reply) => {
+                let record = match index.latest(&id) {
+                    Some(offset) => read_record(&path, offset),
+                    None => Ok(None),
+                };
+                let _ = reply.send(record);
+            }
+            LogRequest::Query(query, reply) => {
+                let _ = reply.send(index.query(&path, &query));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A shared store of completed transactions.
+///
+/// Clones refer to the same store, so the history may be queried while the Daemon is running.
+pub struct TransactionHistory {
+    store: Arc<Mutex<Store>>,
+}
+impl TransactionHistory {
+    /// Hold up to `capacity` records in memory.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            store: Arc::new(Mutex::new(Store {
+                capacity,
+                records: VecDeque::new(),
+                evicted: false,
+                log: None,
+            })),
+        }
+    }
+
+    /// Hold up to `capacity` records in memory and append every record to the log at `path`.
+    ///
+    /// The most recent records already in the log are loaded into memory.
+    /// The log is created once the first transaction finishes if it does not exist yet.
+    pub fn open(capacity: usize, path: impl Into<Utf8PathBuf>) -> Result<Self, HistoryError> {
+        let path = path.into();
+        let history = Self::new(capacity);
+        terminate_log(&path)?;
+        let mut index = LogIndex::default();
+        {
+            let mut store = history.store.lock().unwrap();
+            scan_log(&path, 0, |offset, record| {
+                index.insert(offset, &record);
+                store.push(record);
+            })?;
+            let (log, requests) = mpsc::channel();
+            thread::Builder::new()
+                .name("cfdp-history".to_owned())
+                .spawn(move || serve_log(path, index, requests))
+                .map_err(|err| HistoryError::Io("history log thread".into(), err))?;
+            store.log = Some(log);
+        }
+        Ok(history)
+    }
+
+    /// Add the record of a transaction which just finished.
+    ///
+    /// The record is appended to the log in the background,
+    /// failing to do so is reported but does not affect the transaction.
+    pub(crate) fn insert(&self, record: TransactionRecord) {
+        let mut store = self.store.lock().unwrap();
+        if let Some(log) = store.log.as_ref() {
+            let _ = log.send(LogRequest::Append(Box::new(record.clone())));
+        }
+        store.push(record);
+    }
+
+    /// The record of a completed transaction.
+    pub async fn get(&self, id: TransactionID) -> Result<Option<TransactionRecord>, HistoryError> {
+        let reply = {
+            let store = self.store.lock().unwrap();
+            if let Some(record) = store.records.iter().rev().find(|record| record.id() == id) {
+                return Ok(Some(record.clone()));
+            }
+            let Some(log) = store.log.as_ref() else {
+                return Ok(None);
+            };
+            let (reply, response) = oneshot::channel();
+            log.send(LogRequest::Get(id, reply))
+                .map_err(|_| log_stopped())?;
+            response
+        };
+        reply.await.map_err(|_| log_stopped())?
+    }
+
+    /// Every record matching the query, in the order the transactions finished.
+    ///
+    /// Records no longer held in memory are read from the log, if there is one.
+    pub async fn query(
+        &self,
+        query: &HistoryQuery,
+    ) -> Result<Vec<TransactionRecord>, HistoryError> {
+        let reply = {
+            let store = self.store.lock().unwrap();
+            let log = match store.log.as_ref() {
+                Some(log) if !store.holds(query) => log,
+                _ => return Ok(store.query(query)),
+            };
+            let (reply, response) = oneshot::channel();
+            log.send(LogRequest::Query(query.clone(), reply))
+                .map_err(|_| log_stopped())?;
+            response
+        };
+        reply.await.map_err(|_| log_stopped())?
+    }
+}
+
+fn log_stopped() -> HistoryError {
+    HistoryError::Io(
+        "history log thread".into(),
+        std::io::Error::from(ErrorKind::BrokenPipe),
+    )
+}
+
+/// Append the record, returning the offset it was written at.
+fn append_log(path: &Utf8Path, record: &TransactionRecord) -> std::io::Result<u64> {
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let offset = file.metadata()?.len();
+    file.write_all(line.as_bytes())?;
+    Ok(offset)
+}
+
+fn read_record(path: &Utf8Path, offset: u64) -> Result<Option<TransactionRecord>, HistoryError> {
+    let error = |err| HistoryError::Io(path.to_owned(), err);
+    let mut file = BufReader::new(File::open(path).map_err(error)?);
+    file.seek(SeekFrom::Start(offset)).map_err(error)?;
+    let mut line = String::new();
+    file.read_line(&mut line).map_err(error)?;
+    match serde_json::from_str(&line) {
+        Ok(record) => Ok(Some(record)),
+        Err(err) => {
+            warn!("Skipping record at offset {offset} of {path}: {err}");
+            Ok(None)
+        }
+    }
+}
+
+/// Make sure the next record starts on its own line,
+/// even if the last one was cut short.
+fn terminate_log(path: &Utf8Path) -> Result<(), HistoryError> {
+    let error = |err| HistoryError::Io(path.to_owned(), err);
+    let mut file = match OpenOptions::new().read(true).append(true).open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(error(err)),
+    };
+    if file.metadata().map_err(error)?.len() == 0 {
+        return Ok(());
+    }
+    let mut last = [0_u8];
+    file.seek(SeekFrom::End(-1)).map_err(error)?;
+    file.read_exact(&mut last).map_err(error)?;
+    if last[0] != b'\n' {
+        file.write_all(b"\n").map_err(error)?;
+    }
+    Ok(())
+}
+
+/// Visit every record in the log from the record at `start`, along with the offset it starts at.
+fn scan_log(
+    path: &Utf8Path,
+    start: u64,
+    mut visit: impl FnMut(u64, TransactionRecord),
+) -> Result<(), HistoryError> {
+    let error = |err| HistoryError::Io(path.to_owned(), err);
+    let mut file = match File::open(path) {
+        Ok(file) => BufReader::new(file),
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(error(err)),
+    };
+    file.seek(SeekFrom::Start(start)).map_err(error)?;
+    let mut offset = start;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let len = file.read_line(&mut line).map_err(error)?;
+        if len == 0 {
+            break;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => visit(offset, record),
+            // most likely a record cut short when the process was killed.
+            Err(err) => warn!("Skipping record at offset {offset} of {path}: {err}"),
+        }
+        offset += len as u64;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use cfdp_core::{
+        pdu::{TransactionSeqNum, TransactionStatus},
+        transaction::TransactionState,
+    };
+    use rstest::rstest;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn record(sequence: u16, entity: u16, finished: u64) -> TransactionRecord {
+        TransactionRecord {
+            report: Report {
+                id: TransactionID(EntityID::from(1_u16), TransactionSeqNum::from(sequence)),
+                state: TransactionState::Terminated,
+                status: TransactionStatus::Terminated,
+                condition: Condition::NoError,
+            },
+            remote_entity_id: EntityID::from(entity),
+            transmission_mode: TransmissionMode::Acknowledged,
+            source_filename: "local.txt".into(),
+            destination_filename: "remote.txt".into(),
+            file_size: 10,
+            file_status: FileStatusCode::Retained,
+            delivery_code: DeliveryCode::Complete,
+            filestore_responses: vec![],
+            started: SystemTime::UNIX_EPOCH,
+            finished: SystemTime::UNIX_EPOCH + Duration::from_secs(finished),
+        }
+    }
+
+    fn sequences(records: Vec<TransactionRecord>) -> Vec<u16> {
+        records
+            .into_iter()
+            .map(|record| match record.id().1 {
+                sequence if sequence.width() == 2 => sequence.to_u64() as u16,
+                other => panic!("unexpected sequence number {other:?}"),
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case(HistoryQuery::new(), vec![0, 1, 2, 3])]
+    #[case(HistoryQuery::new().transaction(record(2, 0, 0).id()), vec![2])]
+    #[case(HistoryQuery::new().entity(EntityID::from(3_u16)), vec![1, 3])]
+    #[case(
+        HistoryQuery::new()
+            .since(SystemTime::UNIX_EPOCH + Duration::from_secs(20))
+            .until(SystemTime::UNIX_EPOCH + Duration::from_secs(40)),
+        vec![1, 2]
+    )]
+    #[case(
+        HistoryQuery::new()
+            .entity(EntityID::from(2_u16))
+            .since(SystemTime::UNIX_EPOCH + Duration::from_secs(20)),
+        vec![2]
+    )]
+    #[tokio::test]
+    async fn queries(#[case] query: HistoryQuery, #[case] expected: Vec<u16>) {
+        let history = TransactionHistory::new(10);
+        for (sequence, entity) in [(0, 2), (1, 3), (2, 2), (3, 3)] {
+            history.insert(record(sequence, entity, 10 * (sequence as u64 + 1)));
+        }
+        assert_eq!(expected, sequences(history.query(&query).await.unwrap()));
+    }
+
+    #[tokio::test]
+    async fn bounded() {
+        let history = TransactionHistory::new(2);
+        for sequence in 0..4 {
+            history.insert(record(sequence, 2, sequence as u64));
+        }
+        assert_eq!(
+            vec![2, 3],
+            sequences(history.query(&HistoryQuery::new()).await.unwrap())
+        );
+        assert!(history.get(record(0, 2, 0).id()).await.unwrap().is_none());
+        assert_eq!(
+            Duration::from_secs(3),
+            history
+                .get(record(3, 2, 0).id())
+                .await
+                .unwrap()
+                .unwrap()
+                .duration()
+        );
+    }
+
+    #[tokio::test]
+    async fn persisted() {
+        let dir = TempDir::new().unwrap();
+        let path = Utf8PathBuf::try_from(dir.path().join("history.jsonl")).unwrap();
+
+        let history = TransactionHistory::open(2, &path).unwrap();
+        for sequence in 0..4 {
+            history.insert(record(sequence, 2, sequence as u64));
+        }
+        // the log is complete even though memory only holds the latest records.
+        assert_eq!(
+            vec![0, 1, 2, 3],
+            sequences(history.query(&HistoryQuery::new()).await.unwrap())
+        );
+        assert!(history.get(record(0, 2, 0).id()).await.unwrap().is_some());
+        drop(history);
+
+        // a partial record from an interrupted write is skipped.
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"report\":")
+            .unwrap();
+        let reopened = TransactionHistory::open(2, &path).unwrap();
+        reopened.insert(record(4, 2, 4));
+        assert_eq!(
+            vec![0, 1, 2, 3, 4],
+            sequences(reopened.query(&HistoryQuery::new()).await.unwrap())
+        );
+        assert_eq!(
+            vec![3, 4],
+            sequences(
+                reopened
+                    .store
+                    .lock()
+                    .unwrap()
+                    .records
+                    .iter()
+                    .cloned()
+                    .collect()
+            )
+        );
+        let record = reopened.get(record(1, 2, 0).id()).await.unwrap().unwrap();
+        assert_eq!(Utf8PathBuf::from("remote.txt"), record.destination_filename);
+        assert_eq!(FileStatusCode::Retained, record.file_status);
+    }
+
+    #[tokio::test]
+    async fn indexed_queries() {
+        let dir = TempDir::new().unwrap();
+        let path = Utf8PathBuf::try_from(dir.path().join("history.jsonl")).unwrap();
+
+        let history = TransactionHistory::open(2, &path).unwrap();
+        for sequence in 0..4 {
+            history.insert(record(sequence, 2, 10 * sequence as u64));
+        }
+        // a sequence number reused after a restart.
+        history.insert(record(1, 3, 40));
+        assert_eq!(
+            vec![1, 1],
+            sequences(
+                history
+                    .query(&HistoryQuery::new().transaction(record(1, 0, 0).id()))
+                    .await
+                    .unwrap()
+            )
+        );
+        let since =
+            |secs| HistoryQuery::new().since(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+        assert_eq!(
+            vec![2, 3, 1],
+            sequences(history.query(&since(15)).await.unwrap())
+        );
+        assert!(history.query(&since(50)).await.unwrap().is_empty());
+
+        // a window within the records held in memory does not need the log.
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(vec![1], sequences(history.query(&since(35)).await.unwrap()));
+        // older records may share the time of the oldest one in memory.
+        assert!(history.query(&since(30)).await.unwrap().is_empty());
+    }
+}
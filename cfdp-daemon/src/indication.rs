@@ -0,0 +1,294 @@
+//! Delivery of [Indication]s to any number of independent subscribers.
+//!
+//! Every indication issued by the [Daemon](crate::Daemon) and its transactions is published
+//! to a bounded broadcast channel. Publishing never waits on a subscriber,
+//! instead a subscriber which falls more than the channel capacity behind is told how many
+//! indications it missed through [SubscriptionError::Lagged] and continues
+//! with the oldest indication still retained.
+//!
+//! The channel given to [IndicationBroadcast::with_relay] or
+//! [DaemonBuilder::user](crate::DaemonBuilder::user) is fed by a subscriber of its own,
+//! so the indications it misses when read too slowly are bounded by the channel capacity
+//! and logged as a warning, rather than queued in the Daemon without limit.
+use cfdp_core::{
+    daemon::{Indication, IndicationKind},
+    pdu::EntityID,
+    transaction::TransactionID,
+};
+use log::warn;
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    mpsc::Sender,
+};
+
+use crate::error::SubscriptionError;
+
+/// Number of indications retained for slow subscribers unless configured otherwise.
+pub const DEFAULT_INDICATION_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone)]
+struct Published {
+    // the remote entity the indication concerns, if known.
+    entity_id: Option<EntityID>,
+    indication: Indication,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Selects the indications delivered to a [Subscription].
+///
+/// A criterion left empty matches every indication,
+/// otherwise the indication must match one of its entries.
+pub struct IndicationFilter {
+    transactions: Vec<TransactionID>,
+    entities: Vec<EntityID>,
+    kinds: Vec<IndicationKind>,
+}
+impl IndicationFilter {
+    /// A filter matching every indication.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Match indications issued for the given transaction.
+    pub fn transaction(mut self, id: TransactionID) -> Self {
+        self.transactions.push(id);
+        self
+    }
+
+    /// Match indications concerning the given remote entity,
+    /// either from a transaction with the entity or a change of its link.
+    pub fn entity(mut self, entity_id: EntityID) -> Self {
+        self.entities.push(entity_id);
+        self
+    }
+
+    /// Match indications of the given kind.
+    pub fn kind(mut self, kind: IndicationKind) -> Self {
+        self.kinds.push(kind);
+        self
+    }
+
+    fn matches(&self, published: &Published) -> bool {
+        let indication = &published.indication;
+        (self.transactions.is_empty()
+            || indication
+                .transaction_id()
+                .is_some_and(|id| self.transactions.contains(&id)))
+            && (self.entities.is_empty()
+                || published
+                    .entity_id
+                    .is_some_and(|entity| self.entities.contains(&entity)))
+            && (self.kinds.is_empty() || self.kinds.contains(&indication.kind()))
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Publishes indications to every [Subscription].
+pub struct IndicationBroadcast {
+    tx: broadcast::Sender<Published>,
+}
+impl IndicationBroadcast {
+    /// Retain up to `capacity` indications for subscribers which have not yet received them.
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity);
+        Self { tx }
+    }
+
+    /// Like [new](Self::new), additionally delivering every indication to `indication_tx`.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn with_relay(capacity: usize, indication_tx: Sender<Indication>) -> Self {
+        let broadcast = Self::new(capacity);
+        tokio::task::spawn(forward(
+            broadcast.subscribe(IndicationFilter::new()),
+            indication_tx,
+        ));
+        broadcast
+    }
+
+    /// Receive every indication matching the filter from now on.
+    pub fn subscribe(&self, filter: IndicationFilter) -> Subscription {
+        Subscription {
+            rx: self.tx.subscribe(),
+            filter,
+        }
+    }
+
+    /// Publisher for the transactions with the given remote entity.
+    pub fn sender(&self, entity_id: EntityID) -> IndicationSender {
+        IndicationSender {
+            broadcast: self.clone(),
+            entity_id,
+        }
+    }
+
+    pub(crate) fn publish(&self, entity_id: Option<EntityID>, indication: Indication) {
+        // having no subscribers is not an error.
+        let _ = self.tx.send(Published {
+            entity_id,
+            indication,
+        });
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Publishes the indications of a transaction with a single remote entity.
+pub struct IndicationSender {
+    broadcast: IndicationBroadcast,
+    entity_id: EntityID,
+}
+impl IndicationSender {
+    pub fn send(&self, indication: Indication) {
+        self.broadcast.publish(Some(self.entity_id), indication)
+    }
+}
+
+#[derive(Debug)]
+/// A stream of the indications matching an [IndicationFilter].
+pub struct Subscription {
+    rx: broadcast::Receiver<Published>,
+    filter: IndicationFilter,
+}
+impl Subscription {
+    /// Wait for the next matching indication.
+    ///
+    /// After a [Lagged](SubscriptionError::Lagged) error the subscription remains usable.
+    pub async fn recv(&mut self) -> Result<Indication, SubscriptionError> {
+        loop {
+            match self.rx.recv().await {
+                Ok(published) if self.filter.matches(&published) => return Ok(published.indication),
+                Ok(_) => {}
+                Err(RecvError::Lagged(missed)) => return Err(SubscriptionError::Lagged(missed)),
+                Err(RecvError::Closed) => return Err(SubscriptionError::Closed),
+            }
+        }
+    }
+}
+
+/// Forward the indications to the user channel until either side is closed.
+async fn forward(mut subscription: Subscription, tx: Sender<Indication>) {
+    loop {
+        match subscription.recv().await {
+            Ok(indication) => {
+                if tx.send(indication).await.is_err() {
+                    return;
+                }
+            }
+            Err(SubscriptionError::Lagged(missed)) => {
+                warn!("The user channel is read too slowly and missed {missed} indications.")
+            }
+            Err(SubscriptionError::Closed) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cfdp_core::{
+        daemon::{LinkIndication, LinkState},
+        pdu::TransactionSeqNum,
+    };
+    use rstest::rstest;
+
+    use super::*;
+
+    fn id(sequence: u16) -> TransactionID {
+        TransactionID(EntityID::from(1_u16), TransactionSeqNum::from(sequence))
+    }
+
+    fn link(entity: u16) -> Indication {
+        Indication::Link(LinkIndication {
+            entity_id: EntityID::from(entity),
+            state: LinkState::Up,
+        })
+    }
+
+    #[rstest]
+    #[case(IndicationFilter::new(), vec![0, 1, 2, 3])]
+    #[case(IndicationFilter::new().transaction(id(1)), vec![0, 1])]
+    #[case(IndicationFilter::new().entity(EntityID::from(3_u16)), vec![2, 3])]
+    #[case(IndicationFilter::new().kind(IndicationKind::EoFSent), vec![1, 2])]
+    #[case(
+        IndicationFilter::new().entity(EntityID::from(2_u16)).kind(IndicationKind::Link),
+        vec![]
+    )]
+    #[tokio::test]
+    async fn filters(#[case] filter: IndicationFilter, #[case] expected: Vec<usize>) {
+        let indications = [
+            (2, Indication::Transaction(id(1))),
+            (2, Indication::EoFSent(id(1))),
+            (3, Indication::EoFSent(id(2))),
+            (3, link(3)),
+        ];
+        let broadcast = IndicationBroadcast::new(8);
+        let mut subscription = broadcast.subscribe(filter);
+        for (entity, indication) in indications.iter().cloned() {
+            match indication {
+                Indication::Link(_) => broadcast.publish(Some(EntityID::from(3_u16)), indication),
+                _ => broadcast
+                    .sender(EntityID::from(entity as u16))
+                    .send(indication),
+            }
+        }
+        drop(broadcast);
+
+        let mut received = vec![];
+        while let Ok(indication) = subscription.recv().await {
+            received.push(format!("{indication:?}"));
+        }
+        assert_eq!(
+            expected
+                .into_iter()
+                .map(|index| format!("{:?}", indications[index].1))
+                .collect::<Vec<_>>(),
+            received
+        );
+    }
+
+    #[tokio::test]
+    async fn lagged_subscriber_continues() {
+        let broadcast = IndicationBroadcast::new(2);
+        let mut slow = broadcast.subscribe(IndicationFilter::new());
+        let mut fast = broadcast.subscribe(IndicationFilter::new());
+        for sequence in 0..4 {
+            broadcast
+                .sender(EntityID::from(2_u16))
+                .send(Indication::EoFSent(id(sequence)));
+            assert!(matches!(fast.recv().await, Ok(Indication::EoFSent(_))));
+        }
+
+        assert_eq!(SubscriptionError::Lagged(2), slow.recv().await.unwrap_err());
+        assert!(matches!(
+            slow.recv().await,
+            Ok(Indication::EoFSent(received)) if received == id(2)
+        ));
+        drop(broadcast);
+        assert!(matches!(
+            slow.recv().await,
+            Ok(Indication::EoFSent(received)) if received == id(3)
+        ));
+        assert_eq!(SubscriptionError::Closed, slow.recv().await.unwrap_err());
+    }
+
+    #[tokio::test]
+    async fn relay_is_bounded() {
+        let (indication_tx, mut indication_rx) = tokio::sync::mpsc::channel(1);
+        let broadcast = IndicationBroadcast::with_relay(2, indication_tx);
+        // the user channel is read only after far more than either capacity was published.
+        for sequence in 0..8 {
+            broadcast
+                .sender(EntityID::from(2_u16))
+                .send(Indication::EoFSent(id(sequence)));
+        }
+        drop(broadcast);
+
+        // only the indications the broadcast still retains are delivered.
+        for sequence in 6..8 {
+            assert!(matches!(
+                indication_rx.recv().await,
+                Some(Indication::EoFSent(received)) if received == id(sequence)
+            ));
+        }
+        assert!(indication_rx.recv().await.is_none());
+    }
+}
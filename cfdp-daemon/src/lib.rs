@@ -1,16 +1,22 @@
 use std::{
-    collections::{hash_map::Entry, HashMap},
+    collections::{hash_map::Entry, HashMap, HashSet},
     fmt::Display,
+    fs::File,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Mutex,
     },
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
 use error::DaemonResult;
 use log::{error, info, warn};
-use tokio::{select, task::JoinHandle, time::MissedTickBehavior};
+use tokio::{
+    select,
+    sync::{mpsc::error::SendError, watch},
+    task::{JoinError, JoinHandle},
+    time::{Instant, MissedTickBehavior},
+};
 
 // Re-exported for convenience and compatibility.
 pub use tokio::sync::{
@@ -19,27 +25,55 @@ pub use tokio::sync::{
 };
 
 use cfdp_core::{
-    daemon::{EntityConfig, Indication, PutRequest, Report, UserPrimitive},
-    filestore::FileStore,
+    daemon::{
+        EntityConfig, Indication, LinkIndication, LinkState, PrimitiveError, PrimitiveResult,
+        Priority, PutRequest, Report, Scheduling, ShutdownMode, UserPrimitive,
+    },
+    filestore::{ChecksumType, FileStore, FileStoreResult},
     pdu::{
-        Direction, EntityID, FileSizeFlag, NakOrKeepAlive, PDUHeader, SegmentedData,
-        TransactionSeqNum, VariableID, PDU,
+        Direction, EntityID, FaultHandlerAction, FileSizeFlag, NakOrKeepAlive, PDUEncode, PDUError,
+        PDUHeader, ProtocolVersion, SegmentedData, TransactionSeqNum, VariableID, PDU,
     },
     transaction::{Metadata, TransactionConfig, TransactionID, TransactionState},
 };
 
+pub use builder::{DaemonBuilder, DaemonParts};
 pub use cfdp_core;
+pub use handle::{DaemonHandle, TransactionHandle};
 
+pub mod builder;
+pub mod capture;
+#[cfg(feature = "config")]
+pub mod config;
+pub(crate) mod contact;
+#[cfg(unix)]
+pub mod control;
+pub mod entity;
 pub mod error;
-pub(crate) mod segments;
+pub mod handle;
+pub mod history;
+pub mod indication;
+pub(crate) mod rate;
+pub(crate) mod scheduler;
+pub mod suspend;
 pub(crate) mod timer;
 pub mod transaction;
 pub mod transport;
 
 use self::error::DaemonError;
 
-use self::transport::PDUTransport;
-use transaction::{recv::RecvTransaction, send::SendTransaction, TransactionError};
+use self::contact::LinkSchedule;
+use self::history::TransactionHistory;
+use self::indication::{IndicationBroadcast, IndicationFilter, IndicationSender, Subscription};
+use self::rate::{Pacer, TokenBucket};
+use self::scheduler::{Scheduler, Slot};
+use self::suspend::{SavedTransaction, SuspendStore};
+use self::transport::{TransportEvent, TRANSPORT_MAX_RESTART_DELAY, TRANSPORT_RESTART_DELAY};
+use transaction::{
+    recv::{RecvTransaction, SavedRecv},
+    send::{metadata_pdu, SavedSend, SendTransaction},
+    TransactionError,
+};
 
 /// Lightweight commands the Daemon send to each Transaction
 #[derive(Debug)]
@@ -48,11 +82,23 @@ pub enum Command {
     Cancel,
     Suspend,
     Resume,
-    Report(oneshot::Sender<Report>),
+    Report(oneshot::Sender<PrimitiveResult<Report>>),
     Prompt(NakOrKeepAlive),
-    // may find a use for abandon in the future.
-    #[allow(unused)]
     Abandon,
+    /// Save the suspended transaction to the store and stop it without abandoning it.
+    Save(SuspendStore),
+}
+impl Command {
+    /// The command used to apply a fault handler action to a running transaction.
+    /// Returns [None] for [FaultHandlerAction::Ignore].
+    fn from_action(action: &FaultHandlerAction) -> Option<Self> {
+        match action {
+            FaultHandlerAction::Ignore => None,
+            FaultHandlerAction::Suspend => Some(Self::Suspend),
+            FaultHandlerAction::Cancel => Some(Self::Cancel),
+            FaultHandlerAction::Abandon => Some(Self::Abandon),
+        }
+    }
 }
 impl Display for Command {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -64,12 +110,10 @@ fn construct_metadata<T: FileStore + Send + 'static>(
     filestore: &Arc<T>,
     req: PutRequest,
     config: EntityConfig,
-) -> DaemonResult<Metadata> {
+) -> FileStoreResult<Metadata> {
     let file_size = match req.source_filename.file_name().is_none() {
         true => 0_u64,
-        false => filestore
-            .get_size(&req.source_filename)
-            .map_err(DaemonError::SpawnSend)?,
+        false => filestore.get_size(&req.source_filename)?,
     };
     Ok(Metadata {
         source_filename: req.source_filename,
@@ -77,44 +121,102 @@ fn construct_metadata<T: FileStore + Send + 'static>(
         file_size,
         filestore_requests: req.filestore_requests,
         message_to_user: req.message_to_user,
-        closure_requested: config.closure_requested,
+        closure_requested: req.closure_requested.unwrap_or(config.closure_requested),
         checksum_type: config.checksum_type,
     })
 }
 
-type RecvSpawnerTuple = (
-    TransactionID,
-    Sender<Command>,
-    JoinHandle<Result<TransactionID, TransactionError>>,
-);
+/// Metadata is never segmented, the whole of it must fit in a single PDU.
+fn check_metadata_len(
+    destination: EntityID,
+    config: &TransactionConfig,
+    metadata: &Metadata,
+) -> PrimitiveResult<()> {
+    let pdu = metadata_pdu(config, metadata);
+    let header_len = usize::from(pdu.header.encoded_len());
+    // the data field length bounds every PDU, the destination may bound it further.
+    let max_pdu_len = config
+        .max_pdu_len
+        .unwrap_or(usize::MAX)
+        .min(header_len + usize::from(u16::MAX));
+    let pdu_len = match pdu.checked_len() {
+        Ok(pdu_len) => pdu_len,
+        Err(PDUError::DataFieldTooLarge(data_field_len)) => header_len + data_field_len,
+        Err(PDUError::LengthValueTooLong(field, len, max)) => {
+            return Err(PrimitiveError::FieldTooLong(
+                destination,
+                field.to_owned(),
+                len,
+                max,
+            ))
+        }
+        Err(err) => return Err(PrimitiveError::Unsupported(destination, err.to_string())),
+    };
+    match pdu_len <= max_pdu_len {
+        true => Ok(()),
+        false => Err(PrimitiveError::MetadataTooLarge(
+            destination,
+            pdu_len,
+            max_pdu_len,
+        )),
+    }
+}
 
-type SendSpawnerTuple = (
+type TransactionSpawnerTuple = (
     Sender<Command>,
-    JoinHandle<Result<TransactionID, TransactionError>>,
+    JoinHandle<Result<Report, TransactionError>>,
 );
 
+/// Bookkeeping for a shutdown requested through [UserPrimitive::Shutdown].
+struct Shutdown {
+    mode: ShutdownMode,
+    // when any transactions still running are abandoned during a drain
+    deadline: Option<Instant>,
+    // latest known report of every transaction running when the shutdown was requested
+    reports: HashMap<TransactionID, Report>,
+    reply: oneshot::Sender<PrimitiveResult<Vec<Report>>>,
+}
+
 /// The CFDP Daemon is responsible for connecting [PDUTransport](crate::transport::PDUTransport) implementation
 /// with each individual [SendTransaction](crate::transaction::SendTransaction) and [RecvTransaction](crate::transaction::RecvTransaction).
 /// When a PDUTransport implementation
 /// sends a PDU through a channel, the Daemon distributes the PDU to the necessary Transaction.
 /// PDUs are sent from each Transaction directly to their respective PDUTransport implementations.
 pub struct Daemon<T: FileStore + Send + 'static> {
-    // The collection of all current transactions
-    transaction_handles: Vec<JoinHandle<Result<TransactionID, TransactionError>>>,
+    // The collection of all current transactions and their IDs
+    transaction_handles: Vec<(TransactionID, JoinHandle<Result<Report, TransactionError>>)>,
     // Mapping of unique transaction ids to channels used to talk to each transaction
     transaction_channels: HashMap<TransactionID, Sender<Command>>,
+    // the remote entity each running transaction is communicating with
+    transaction_entities: HashMap<TransactionID, EntityID>,
+    // running send transactions speaking the legacy protocol
+    legacy_transactions: HashSet<TransactionID>,
+    // the protocol version each remote entity was last heard using
+    peer_versions: HashMap<EntityID, ProtocolVersion>,
     // the vector of transportation tx channel connections
     transport_tx_map: HashMap<EntityID, Sender<(VariableID, PDU)>>,
     // the transport PDU rx channel connection
     transport_rx: Receiver<PDU>,
+    // health events from the tasks supervising each transport
+    health_rx: Receiver<TransportEvent>,
     // the underlying filestore used by this Daemon
     filestore: Arc<T>,
-    // message sender channel used to send Indications from Transactions to the User
-    indication_tx: Sender<Indication>,
+    // publishes Indications from the Daemon and its Transactions to every subscriber
+    indications: IndicationBroadcast,
+    // records of every finished transaction
+    history: TransactionHistory,
+    // where a SuspendAll shutdown saves the transactions, restored when the daemon starts
+    suspend_store: Option<SuspendStore>,
     // a mapping of individual fault handler actions per remote entity
     entity_configs: HashMap<VariableID, EntityConfig>,
     // the default fault handling configuration
     default_config: EntityConfig,
+    // bandwidth limits shared by all transactions with a remote entity
+    rate_limiters: HashMap<EntityID, Arc<Mutex<TokenBucket>>>,
+    // arbitrates the transport among the send transactions to each remote entity
+    schedulers: HashMap<EntityID, Scheduler>,
+    // contact plan and current link availability per remote entity
+    links: HashMap<EntityID, LinkSchedule>,
     // the entity ID of this daemon
     entity_id: EntityID,
     // current running count of the sequence numbers of transaction initiated by this entity
@@ -123,96 +225,270 @@ pub struct Daemon<T: FileStore + Send + 'static> {
     terminate: Arc<AtomicBool>,
     // channel to receive user primitives from the implemented User
     primitive_rx: Receiver<UserPrimitive>,
+    // set once the user has requested a shutdown
+    shutdown: Option<Shutdown>,
 }
 impl<T: FileStore + Send + Sync + 'static> Daemon<T> {
-    #[allow(clippy::too_many_arguments)]
-    pub fn new(
-        entity_id: EntityID,
-        sequence_num: TransactionSeqNum,
-        transport_map: HashMap<Vec<EntityID>, Box<dyn PDUTransport + Send>>,
-        filestore: Arc<T>,
-        entity_configs: HashMap<VariableID, EntityConfig>,
-        default_config: EntityConfig,
-        primitive_rx: Receiver<UserPrimitive>,
-        indication_tx: Sender<Indication>,
-    ) -> Self {
+    /// Construct a Daemon without validating the configuration.
+    /// Prefer the [DaemonBuilder] to catch configuration mistakes up front.
+    ///
+    /// Transports are started immediately, so this must be called from within a tokio runtime.
+    pub fn new(parts: DaemonParts<T>) -> Self {
+        let DaemonParts {
+            entity_id,
+            sequence_num,
+            transports,
+            filestore,
+            entity_configs,
+            default_config,
+            primitive_rx,
+            indications,
+            history,
+            suspend_store,
+        } = parts;
         let mut transport_tx_map: HashMap<EntityID, Sender<(VariableID, PDU)>> = HashMap::new();
         let (pdu_send, pdu_receive) = channel(100);
+        let (health_send, health_receive) = channel(10);
         let terminate = Arc::new(AtomicBool::new(false));
-        for (vec, mut transport) in transport_map.into_iter() {
+        for (vec, transport) in transports.into_iter() {
             let (remote_send, remote_receive) = channel(1);
 
             vec.iter().for_each(|id| {
                 transport_tx_map.insert(*id, remote_send.clone());
             });
 
-            let signal = terminate.clone();
-            let sender = pdu_send.clone();
-            tokio::task::spawn(async move {
-                transport.pdu_handler(signal, sender, remote_receive).await
-            });
+            tokio::task::spawn(transport::supervise(
+                transport,
+                vec,
+                terminate.clone(),
+                pdu_send.clone(),
+                remote_receive,
+                health_send.clone(),
+                TRANSPORT_RESTART_DELAY,
+                TRANSPORT_MAX_RESTART_DELAY,
+            ));
         }
         Self {
             transaction_handles: vec![],
             transaction_channels: HashMap::new(),
+            transaction_entities: HashMap::new(),
+            legacy_transactions: HashSet::new(),
+            peer_versions: HashMap::new(),
             transport_tx_map,
             transport_rx: pdu_receive,
+            health_rx: health_receive,
             filestore,
-            indication_tx,
+            indications,
+            history,
+            suspend_store,
             entity_configs,
             default_config,
+            rate_limiters: HashMap::new(),
+            schedulers: HashMap::new(),
+            links: HashMap::new(),
             entity_id,
             sequence_num,
             terminate,
             primitive_rx,
+            shutdown: None,
+        }
+    }
+
+    /// Receive the indications matching the filter, independently of any other consumer.
+    pub fn subscribe(&self, filter: IndicationFilter) -> Subscription {
+        self.indications.subscribe(filter)
+    }
+
+    /// A handle to subscribe to indications once the Daemon is running.
+    pub fn indications(&self) -> IndicationBroadcast {
+        self.indications.clone()
+    }
+
+    /// A handle to the records of finished transactions.
+    pub fn history(&self) -> TransactionHistory {
+        self.history.clone()
+    }
+
+    /// Build the [Pacer] for a new transaction with the remote entity.
+    /// The entity wide token bucket is created on first use and shared afterwards.
+    /// Takes the map of limiters directly so it can be used while a transaction entry is borrowed.
+    fn new_pacer(
+        rate_limiters: &mut HashMap<EntityID, Arc<Mutex<TokenBucket>>>,
+        entity: EntityID,
+        entity_config: &EntityConfig,
+    ) -> Pacer {
+        let shared = entity_config.rate_limit.map(|limit| {
+            rate_limiters
+                .entry(entity)
+                .or_insert_with(|| Arc::new(Mutex::new(TokenBucket::new(limit))))
+                .clone()
+        });
+        Pacer::new(entity_config.transaction_rate_limit, shared)
+    }
+
+    /// Register a send transaction with the scheduler of its destination.
+    fn new_slot(
+        schedulers: &mut HashMap<EntityID, Scheduler>,
+        entity: EntityID,
+        entity_config: &EntityConfig,
+        priority: Option<Priority>,
+    ) -> Slot {
+        match entity_config.scheduling {
+            Scheduling::Priority => schedulers
+                .entry(entity)
+                .or_default()
+                .slot(priority.unwrap_or(entity_config.default_priority)),
+            Scheduling::Unscheduled => Slot::unscheduled(),
         }
     }
 
-    fn spawn_receive_transaction(
+    /// Subscribe to the link state of the remote entity.
+    fn link_state(
+        links: &mut HashMap<EntityID, LinkSchedule>,
+        entity: EntityID,
+    ) -> watch::Receiver<LinkState> {
+        links
+            .entry(entity)
+            .or_insert_with(LinkSchedule::new)
+            .subscribe()
+    }
+
+    /// The time until the next contact window opens or closes on any link.
+    fn until_link_transition(&self) -> Duration {
+        let now = SystemTime::now();
+        self.links
+            .values()
+            .filter_map(|link| link.next_transition(now))
+            .min()
+            .map_or(Duration::MAX, |time| contact::until(time, now))
+    }
+
+    fn update_links(&mut self) {
+        let now = SystemTime::now();
+        let changes: Vec<(EntityID, LinkState)> = self
+            .links
+            .iter()
+            .filter_map(|(entity, link)| link.update(now).map(|state| (*entity, state)))
+            .collect();
+        for (entity, state) in changes {
+            self.link_changed(entity, state);
+        }
+    }
+
+    fn link_changed(&self, entity_id: EntityID, state: LinkState) {
+        info!("Link to entity {entity_id} is now {state:?}.");
+        self.indications.publish(
+            Some(entity_id),
+            Indication::Link(LinkIndication { entity_id, state }),
+        );
+    }
+
+    /// Update the link state of every entity served by the transport and apply the
+    /// configured [link_failure_action](EntityConfig::link_failure_action) when it fails.
+    async fn handle_transport_event(&mut self, event: TransportEvent) {
+        let now = SystemTime::now();
+        for entity in event.entities {
+            let change = self
+                .links
+                .entry(entity)
+                .or_insert_with(LinkSchedule::new)
+                .set_transport_state(event.state, now);
+            if let Some(state) = change {
+                self.link_changed(entity, state);
+            }
+
+            if event.state == LinkState::Up {
+                continue;
+            }
+            let action = self
+                .entity_configs
+                .get(&entity)
+                .unwrap_or(&self.default_config)
+                .link_failure_action
+                .clone();
+            for (id, _) in self
+                .transaction_entities
+                .iter()
+                .filter(|(_, remote)| **remote == entity)
+            {
+                if let (Some(command), Some(channel)) = (
+                    Command::from_action(&action),
+                    self.transaction_channels.get(id),
+                ) {
+                    warn!(
+                        "Transaction {id} affected by link failure to entity {entity}: {action:?}."
+                    );
+                    // the transaction may have finished in the meantime.
+                    let _ = channel.send(command).await;
+                }
+            }
+        }
+    }
+
+    fn new_receive_transaction(
         header: &PDUHeader,
-        transport_tx: Sender<(VariableID, PDU)>,
-        entity_config: EntityConfig,
+        entity_config: &EntityConfig,
         filestore: Arc<T>,
-        indication_tx: Sender<Indication>,
-    ) -> RecvSpawnerTuple {
-        let (transaction_tx, mut transaction_rx) = channel(100);
-
+        indication_tx: IndicationSender,
+    ) -> RecvTransaction<T> {
+        // widening to the configured width, IDs are never narrowed.
+        let id_width = entity_config.id_width.unwrap_or_default();
         let config = TransactionConfig {
-            source_entity_id: header.source_entity_id,
-            destination_entity_id: header.destination_entity_id,
+            source_entity_id: header.source_entity_id.widen(id_width),
+            destination_entity_id: header.destination_entity_id.widen(id_width),
             transmission_mode: header.transmission_mode,
             sequence_number: header.transaction_sequence_number,
             file_size_flag: header.large_file_flag,
             fault_handler_override: entity_config.fault_handler_override.clone(),
             file_size_segment: entity_config.file_size_segment,
+            max_pdu_len: entity_config.max_pdu_len,
             crc_flag: header.crc_flag,
             segment_metadata_flag: header.segment_metadata_flag,
             max_count: entity_config.default_transaction_max_count,
             inactivity_timeout: entity_config.inactivity_timeout,
             ack_timeout: entity_config.ack_timeout,
             nak_timeout: entity_config.nak_timeout,
+            protocol_version: entity_config
+                .protocol_version
+                .resolve(Some(header.protocol_version())),
         };
         /*  let name = format!(
             "({}, {})",
             &config.source_entity_id, &config.sequence_number
         );*/
-        let mut transaction = RecvTransaction::new(
+        RecvTransaction::new(
             config,
             entity_config.nak_procedure,
             filestore,
             indication_tx,
-        );
-        let id = transaction.id();
+        )
+    }
+
+    /// Drive a receive transaction in its own task until it terminates or is saved.
+    fn run_receive_transaction(
+        mut transaction: RecvTransaction<T>,
+        transport_tx: Sender<(VariableID, PDU)>,
+        history: TransactionHistory,
+        mut pacer: Pacer,
+        mut link: watch::Receiver<LinkState>,
+    ) -> TransactionSpawnerTuple {
+        let (transaction_tx, mut transaction_rx) = channel(100);
 
         // tokio tasks can have names but that seems an unsable feature
         let handle = tokio::task::spawn(async move {
             transaction.send_report(None)?;
+            if *link.borrow_and_update() == LinkState::Down {
+                transaction.freeze();
+            }
 
             while transaction.get_state() != TransactionState::Terminated {
                 let timeout = transaction.until_timeout();
+                let pacing = pacer.until_ready();
+                let link_up = *link.borrow() == LinkState::Up;
                 select! {
-                    Ok(permit) = transport_tx.reserve(), if transaction.has_pdu_to_send() => {
-                        transaction.send_pdu(permit)?
+                    Ok(permit) = transport_tx.reserve(), if link_up && transaction.has_pdu_to_send() && pacing.is_zero() => {
+                        transaction.send_pdu(permit)?;
+                        pacer.consume(transaction.take_bytes_sent());
                     },
                     Some(command) = transaction_rx.recv() => {
                         match command {
@@ -236,11 +512,26 @@ impl<T: FileStore + Send + Sync + 'static> Daemon<T> {
                             Command::Prompt(_) =>{
                                 // prompt is a no-op for a receive transaction.
                             }
+                            Command::Save(store) => {
+                                let (saved, data) = transaction.save()?;
+                                store.save(&SavedTransaction::Recv(saved), data)?;
+                                // not finished, so it is not recorded in the history.
+                                return Ok(transaction.generate_report());
+                            }
                         }
                     }
                     _ = tokio::time::sleep(timeout) => {
                         transaction.handle_timeout()?;
                     }
+                    // wake up once the bandwidth limit allows the next PDU out.
+                    _ = tokio::time::sleep(pacing), if link_up && transaction.has_pdu_to_send() && !pacing.is_zero() => {}
+                    Ok(()) = link.changed() => {
+                        // hold PDUs and timers while the remote entity is out of contact.
+                        match *link.borrow_and_update() {
+                            LinkState::Up => transaction.thaw(),
+                            LinkState::Down => transaction.freeze(),
+                        }
+                    }
                     else => {
                         if transport_tx.is_closed(){
                             log::error!("Channel to transport unexpectedly severed for transaction {}.", transaction.id());
@@ -251,57 +542,102 @@ impl<T: FileStore + Send + Sync + 'static> Daemon<T> {
                 };
             }
 
+            // recorded before the final report and before the channel closes,
+            // so whoever learns the transaction has terminated can find its record.
+            history.insert(transaction.generate_record());
             transaction.send_report(None)?;
-            Ok(transaction.id())
+            Ok(transaction.generate_report())
         });
 
-        (id, transaction_tx, handle)
+        (transaction_tx, handle)
     }
 
-    fn spawn_send_transaction(
+    /// Build the send transaction of a put request, refusing requests which cannot be sent.
+    fn new_send_transaction(
         request: PutRequest,
         transaction_id: TransactionID,
-        transport_tx: Sender<(EntityID, PDU)>,
         entity_config: EntityConfig,
         filestore: Arc<T>,
-        indication_tx: Sender<Indication>,
-    ) -> DaemonResult<SendSpawnerTuple> {
-        let (transaction_tx, mut transaction_rx) = channel(10);
-
+        indication_tx: IndicationSender,
+        protocol_version: ProtocolVersion,
+    ) -> PrimitiveResult<SendTransaction<T>> {
         let destination_entity_id = request.destination_entity_id;
         let transmission_mode = request.transmission_mode;
+        // widening to the configured width, IDs are never narrowed.
+        let id_width = entity_config.id_width.unwrap_or_default();
         let mut config = TransactionConfig {
-            source_entity_id: transaction_id.0,
-            destination_entity_id,
+            source_entity_id: transaction_id.0.widen(id_width),
+            destination_entity_id: destination_entity_id.widen(id_width),
             transmission_mode,
             sequence_number: transaction_id.1,
             file_size_flag: FileSizeFlag::Small,
             fault_handler_override: entity_config.fault_handler_override.clone(),
             file_size_segment: entity_config.file_size_segment,
+            max_pdu_len: entity_config.max_pdu_len,
             crc_flag: entity_config.crc_flag,
             segment_metadata_flag: SegmentedData::NotPresent,
             max_count: entity_config.default_transaction_max_count,
             inactivity_timeout: entity_config.inactivity_timeout,
             ack_timeout: entity_config.ack_timeout,
             nak_timeout: entity_config.nak_timeout,
+            protocol_version,
+        };
+        let mut metadata = construct_metadata(&filestore, request, entity_config)
+            .map_err(|err| PrimitiveError::FileStore(err.to_string()))?;
+        if protocol_version == ProtocolVersion::Legacy {
+            if metadata.file_size > u32::MAX.into() {
+                return Err(PrimitiveError::Unsupported(
+                    destination_entity_id,
+                    "A file larger than 4 GiB".to_owned(),
+                ));
+            }
+            // legacy Metadata can express neither, see Operations::to_legacy.
+            metadata.closure_requested = false;
+            metadata.checksum_type = ChecksumType::Modular;
+        }
+        config.file_size_flag = match metadata.file_size <= u32::MAX.into() {
+            true => FileSizeFlag::Small,
+            false => FileSizeFlag::Large,
         };
-        let metadata = construct_metadata(&filestore, request, entity_config)?;
+        check_metadata_len(destination_entity_id, &config, &metadata)?;
 
-        let handle = tokio::task::spawn(async move {
-            config.file_size_flag = match metadata.file_size <= u32::MAX.into() {
-                true => FileSizeFlag::Small,
-                false => FileSizeFlag::Large,
-            };
+        SendTransaction::new(config, metadata, filestore, indication_tx)
+            .map_err(|err| PrimitiveError::FileStore(err.to_string()))
+    }
 
-            let mut transaction = SendTransaction::new(config, metadata, filestore, indication_tx)?;
+    /// Drive a send transaction in its own task until it terminates or is saved.
+    fn run_send_transaction(
+        mut transaction: SendTransaction<T>,
+        priority: Option<Priority>,
+        transport_tx: Sender<(EntityID, PDU)>,
+        history: TransactionHistory,
+        mut pacer: Pacer,
+        slot: Slot,
+        mut link: watch::Receiver<LinkState>,
+    ) -> TransactionSpawnerTuple {
+        let (transaction_tx, mut transaction_rx) = channel(10);
+
+        let handle = tokio::task::spawn(async move {
             transaction.send_report(None)?;
+            if *link.borrow_and_update() == LinkState::Down {
+                transaction.freeze();
+            }
 
             while transaction.get_state() != TransactionState::Terminated {
                 let timeout = transaction.until_timeout();
+                let pacing = pacer.until_ready();
+                let link_up = *link.borrow() == LinkState::Up;
+                let ready = link_up && transaction.has_pdu_to_send() && pacing.is_zero();
+                if !ready {
+                    slot.withdraw();
+                }
 
                 select! {
-                    Ok(permit) = transport_tx.reserve(), if transaction.has_pdu_to_send()  => {
+                    Ok((turn, permit)) = slot.reserve(&transport_tx), if ready => {
                         transaction.send_pdu(permit)?;
+                        let sent = transaction.take_bytes_sent();
+                        pacer.consume(sent);
+                        turn.complete(sent);
                     },
 
                     Some(command) = transaction_rx.recv() => {
@@ -330,11 +666,29 @@ impl<T: FileStore + Send + Sync + 'static> Daemon<T> {
                             Command::Prompt(option) => {
                                 transaction.prepare_prompt(option)
                             }
+                            Command::Save(store) => {
+                                let saved = SavedTransaction::Send {
+                                    transaction: transaction.save()?,
+                                    priority,
+                                };
+                                store.save(&saved, None)?;
+                                // not finished, so it is not recorded in the history.
+                                return Ok(transaction.generate_report());
+                            }
                         }
                     },
                     _ = tokio::time::sleep(timeout) => {
                         transaction.handle_timeout()?;
                     },
+                    // wake up once the bandwidth limit allows the next PDU out.
+                    _ = tokio::time::sleep(pacing), if link_up && transaction.has_pdu_to_send() && !pacing.is_zero() => {},
+                    Ok(()) = link.changed() => {
+                        // hold PDUs and timers while the remote entity is out of contact.
+                        match *link.borrow_and_update() {
+                            LinkState::Up => transaction.thaw(),
+                            LinkState::Down => transaction.freeze(),
+                        }
+                    },
                     else => {
                         if transport_tx.is_closed(){
                             log::error!("Connection to transport unexpectedly severed for transaction {}.", transaction.id());
@@ -343,94 +697,208 @@ impl<T: FileStore + Send + Sync + 'static> Daemon<T> {
                     }
                 };
             }
+            // recorded before the final report and before the channel closes,
+            // so whoever learns the transaction has terminated can find its record.
+            history.insert(transaction.generate_record());
             transaction.send_report(None)?;
-            Ok(transaction_id)
+            Ok(transaction.generate_report())
         });
-        Ok((transaction_tx, handle))
+        (transaction_tx, handle)
+    }
+
+    /// Start a send transaction for the request.
+    fn put(&mut self, mut request: PutRequest) -> PrimitiveResult<TransactionID> {
+        if self.shutdown.is_some() {
+            return Err(PrimitiveError::ShuttingDown);
+        }
+        let destination = self.known_entity(request.destination_entity_id);
+        request.destination_entity_id = destination;
+        let transport_tx = self
+            .transport_tx_map
+            .get(&destination)
+            .cloned()
+            .ok_or(PrimitiveError::NoRoute(destination))?;
+
+        let entity_config = self
+            .entity_configs
+            .get(&destination)
+            .unwrap_or(&self.default_config)
+            .clone();
+        let id = TransactionID(self.entity_id, self.sequence_num);
+        let priority = request.priority;
+        let protocol_version = entity_config
+            .protocol_version
+            .resolve(self.peer_versions.get(&destination).copied());
+        let transaction = Self::new_send_transaction(
+            request,
+            id,
+            entity_config.clone(),
+            self.filestore.clone(),
+            self.indications.sender(destination),
+            protocol_version,
+        )?;
+        // only an accepted request uses up a sequence number.
+        self.sequence_num.increment();
+
+        let pacer = Self::new_pacer(&mut self.rate_limiters, destination, &entity_config);
+        let slot = Self::new_slot(&mut self.schedulers, destination, &entity_config, priority);
+        let link = Self::link_state(&mut self.links, destination);
+        let (sender, handle) = Self::run_send_transaction(
+            transaction,
+            priority,
+            transport_tx,
+            self.history.clone(),
+            pacer,
+            slot,
+            link,
+        );
+        self.transaction_handles.push((id, handle));
+        self.transaction_channels.insert(id, sender);
+        self.transaction_entities.insert(id, destination);
+        if protocol_version == ProtocolVersion::Legacy {
+            self.legacy_transactions.insert(id);
+        }
+        Ok(id)
+    }
+
+    /// Deliver a user command to a running transaction.
+    async fn command(&self, id: TransactionID, command: Command) -> PrimitiveResult<()> {
+        match self.transaction_channels.get(&id) {
+            // the transaction may have finished since the last cleanup.
+            Some(channel) => channel
+                .send(command)
+                .await
+                .map_err(|_| PrimitiveError::Finished(id)),
+            None => match self.history.get(id).await {
+                Ok(Some(_)) => Err(PrimitiveError::Finished(id)),
+                Ok(None) => Err(PrimitiveError::UnknownTransaction(id)),
+                Err(err) => {
+                    warn!("{err}");
+                    Err(PrimitiveError::UnknownTransaction(id))
+                }
+            },
+        }
     }
 
     async fn process_primitive(&mut self, primitive: UserPrimitive) -> DaemonResult<()> {
         match primitive {
             UserPrimitive::Put(request, put_sender) => {
-                let sequence_number = self.sequence_num.get_and_increment();
-
-                let entity_config = self
-                    .entity_configs
-                    .get(&request.destination_entity_id)
-                    .unwrap_or(&self.default_config)
-                    .clone();
-
-                if let Some(transport_tx) = self
-                    .transport_tx_map
-                    .get(&request.destination_entity_id)
-                    .cloned()
-                {
-                    let id = TransactionID(self.entity_id, sequence_number);
-                    let (sender, handle) = Self::spawn_send_transaction(
-                        request,
-                        id,
-                        transport_tx,
-                        entity_config,
-                        self.filestore.clone(),
-                        self.indication_tx.clone(),
-                    )?;
-                    self.transaction_handles.push(handle);
-                    self.transaction_channels.insert(id, sender);
-
-                    // ignore the possible error if the user disconnected;
-                    let _ = put_sender.send(id);
-                } else {
-                    warn!(
-                        "No Transport available for EntityID: {}. Skipping transaction creation.",
-                        request.destination_entity_id
-                    )
+                let result = self.put(request);
+                if let Err(err) = &result {
+                    warn!("Refusing Put request. {err}");
                 }
+                // ignore the possible error if the user disconnected;
+                let _ = put_sender.send(result);
             }
-            UserPrimitive::Cancel(id) => {
-                if let Some(channel) = self.transaction_channels.get(&id) {
-                    channel
-                        .send(Command::Cancel)
-                        .await
-                        .map_err(|err| DaemonError::from((id, err)))?;
-                }
+            UserPrimitive::Cancel(id, reply) => {
+                let _ = reply.send(self.command(id, Command::Cancel).await);
             }
-            UserPrimitive::Suspend(id) => {
-                if let Some(channel) = self.transaction_channels.get(&id) {
-                    channel
-                        .send(Command::Suspend)
-                        .await
-                        .map_err(|err| DaemonError::from((id, err)))?;
-                }
+            UserPrimitive::Suspend(id, reply) => {
+                let _ = reply.send(self.command(id, Command::Suspend).await);
             }
-            UserPrimitive::Resume(id) => {
-                if let Some(channel) = self.transaction_channels.get(&id) {
-                    channel
-                        .send(Command::Resume)
-                        .await
-                        .map_err(|err| DaemonError::from((id, err)))?;
-                }
+            UserPrimitive::Resume(id, reply) => {
+                let _ = reply.send(self.command(id, Command::Resume).await);
             }
             UserPrimitive::Report(id, report_sender) => {
-                if let Some(channel) = self.transaction_channels.get(&id) {
-                    channel
-                        .send(Command::Report(report_sender))
-                        .await
-                        .map_err(|err| DaemonError::from((id, err)))?;
+                let report_sender = match self.transaction_channels.get(&id) {
+                    Some(channel) => match channel.send(Command::Report(report_sender)).await {
+                        Ok(()) => return Ok(()),
+                        // finished since the last cleanup, the record is already kept.
+                        Err(SendError(Command::Report(report_sender))) => report_sender,
+                        Err(err) => return Err(DaemonError::from((id, err))),
+                    },
+                    None => report_sender,
+                };
+                let result = match self.history.get(id).await {
+                    Ok(Some(record)) => Ok(record.report),
+                    Ok(None) => Err(PrimitiveError::UnknownTransaction(id)),
+                    Err(err) => {
+                        warn!("{err}");
+                        Err(PrimitiveError::UnknownTransaction(id))
+                    }
+                };
+                let _ = report_sender.send(result);
+            }
+            UserPrimitive::Prompt(id, option, reply) => {
+                let result =
+                    if id.0 != self.entity_id && self.transaction_channels.contains_key(&id) {
+                        Err(PrimitiveError::NotSender(id))
+                    } else if self.legacy_transactions.contains(&id) {
+                        Err(PrimitiveError::Unsupported(
+                            self.transaction_entities[&id],
+                            "Prompt".to_owned(),
+                        ))
+                    } else {
+                        self.command(id, Command::Prompt(option)).await
+                    };
+                let _ = reply.send(result);
+            }
+            UserPrimitive::SetContactPlan(entity, windows) => {
+                let change = self
+                    .links
+                    .entry(entity)
+                    .or_insert_with(LinkSchedule::new)
+                    .set_plan(windows, SystemTime::now());
+                if let Some(state) = change {
+                    self.link_changed(entity, state);
                 }
             }
-            UserPrimitive::Prompt(id, option) => {
-                if let Some(channel) = self.transaction_channels.get(&id) {
-                    channel
-                        .send(Command::Prompt(option))
-                        .await
-                        .map_err(|err| DaemonError::from((id, err)))?;
+            UserPrimitive::SetLinkState(entity, state) => {
+                let change = self
+                    .links
+                    .entry(entity)
+                    .or_insert_with(LinkSchedule::new)
+                    .set_override(Some(state), SystemTime::now());
+                if let Some(state) = change {
+                    self.link_changed(entity, state);
                 }
             }
+            UserPrimitive::ClearLinkState(entity) => {
+                let change = self
+                    .links
+                    .get_mut(&entity)
+                    .and_then(|link| link.set_override(None, SystemTime::now()));
+                if let Some(state) = change {
+                    self.link_changed(entity, state);
+                }
+            }
+            UserPrimitive::Shutdown(mode, reply) => self.begin_shutdown(mode, reply).await,
         };
         Ok(())
     }
 
+    /// The ID an entity is known by locally.
+    ///
+    /// Peers may widen the IDs they encode, which are then matched by value.
+    fn known_entity(&self, id: EntityID) -> EntityID {
+        match id.same_value(&self.entity_id) {
+            true => self.entity_id,
+            false => self
+                .transport_tx_map
+                .keys()
+                .find(|known| known.same_value(&id))
+                .copied()
+                .unwrap_or(id),
+        }
+    }
+
+    /// Give the IDs of a received PDU the widths they are known by locally,
+    /// so that they key the same transactions and entities.
+    fn normalize_ids(&self, mut pdu: PDU) -> PDU {
+        let header = &mut pdu.header;
+        header.source_entity_id = self.known_entity(header.source_entity_id);
+        header.destination_entity_id = self.known_entity(header.destination_entity_id);
+        // sequence numbers of transactions started locally all share a width.
+        if header.source_entity_id == self.entity_id {
+            let sequence_number = &mut header.transaction_sequence_number;
+            *sequence_number = VariableID::new(sequence_number.to_u64(), self.sequence_num.width())
+                .unwrap_or(*sequence_number);
+        }
+        pdu
+    }
+
     async fn forward_pdu(&mut self, pdu: PDU) -> DaemonResult<()> {
+        let pdu = self.normalize_ids(pdu);
         if pdu.header.direction == Direction::ToReceiver
             && pdu.header.destination_entity_id != self.entity_id
         {
@@ -448,6 +916,8 @@ impl<T: FileStore + Send + Sync + 'static> Daemon<T> {
             Direction::ToSender => pdu.header.destination_entity_id,
             Direction::ToReceiver => pdu.header.source_entity_id,
         };
+        self.peer_versions
+            .insert(transport_entity, pdu.header.protocol_version());
 
         let key = TransactionID(
             pdu.header.source_entity_id,
@@ -456,6 +926,10 @@ impl<T: FileStore + Send + Sync + 'static> Daemon<T> {
         // hand pdu off to transaction
         let channel = match self.transaction_channels.entry(key) {
             Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(_) if self.shutdown.is_some() => {
+                info!("Daemon is shutting down. Discarding PDU for new transaction {key}.");
+                return Ok(());
+            }
             Entry::Vacant(entry) => {
                 if let Some(transport) = self.transport_tx_map.get(&transport_entity).cloned() {
                     // if this key is not in the channel list
@@ -467,15 +941,28 @@ impl<T: FileStore + Send + Sync + 'static> Daemon<T> {
                         .clone();
                     match &pdu.header.direction {
                         Direction::ToReceiver => {
-                            let (_id, channel, handle) = Self::spawn_receive_transaction(
+                            let pacer = Self::new_pacer(
+                                &mut self.rate_limiters,
+                                transport_entity,
+                                &entity_config,
+                            );
+                            let link = Self::link_state(&mut self.links, transport_entity);
+                            let transaction = Self::new_receive_transaction(
                                 &pdu.header,
-                                transport,
-                                entity_config,
+                                &entity_config,
                                 self.filestore.clone(),
-                                self.indication_tx.clone(),
+                                self.indications.sender(transport_entity),
+                            );
+                            let (channel, handle) = Self::run_receive_transaction(
+                                transaction,
+                                transport,
+                                self.history.clone(),
+                                pacer,
+                                link,
                             );
 
-                            self.transaction_handles.push(handle);
+                            self.transaction_handles.push((key, handle));
+                            self.transaction_entities.insert(key, transport_entity);
                             entry.insert(channel)
                         }
                         // This is a very unlikely scenario.
@@ -506,6 +993,10 @@ impl<T: FileStore + Send + Sync + 'static> Daemon<T> {
             // spawn a new one
             // this is very unlikely and only results
             // if a sender is re-using a transaction id
+            if self.shutdown.is_some() {
+                info!("Daemon is shutting down. Discarding PDU for finished transaction {key}.");
+                return Ok(());
+            }
             match pdu.header.direction {
                 Direction::ToReceiver => {
                     let entity_config = self
@@ -514,20 +1005,34 @@ impl<T: FileStore + Send + Sync + 'static> Daemon<T> {
                         .unwrap_or(&self.default_config)
                         .clone();
                     if let Some(transport) = self.transport_tx_map.get(&transport_entity).cloned() {
-                        let (id, new_channel, handle) = Self::spawn_receive_transaction(
+                        let pacer = Self::new_pacer(
+                            &mut self.rate_limiters,
+                            transport_entity,
+                            &entity_config,
+                        );
+                        let link = Self::link_state(&mut self.links, transport_entity);
+                        let transaction = Self::new_receive_transaction(
                             &pdu.header,
-                            transport,
-                            entity_config,
+                            &entity_config,
                             self.filestore.clone(),
-                            self.indication_tx.clone(),
+                            self.indications.sender(transport_entity),
+                        );
+                        let id = transaction.id();
+                        let (new_channel, handle) = Self::run_receive_transaction(
+                            transaction,
+                            transport,
+                            self.history.clone(),
+                            pacer,
+                            link,
                         );
-                        self.transaction_handles.push(handle);
+                        self.transaction_handles.push((id, handle));
                         new_channel
                             .send(Command::Pdu(pdu.clone()))
                             .await
                             .map_err(|err| DaemonError::from((id, err)))?;
                         // update the dict to have the new channel
                         self.transaction_channels.insert(key, new_channel);
+                        self.transaction_entities.insert(key, transport_entity);
                     }
                 }
                 Direction::ToSender => {
@@ -548,22 +1053,247 @@ impl<T: FileStore + Send + Sync + 'static> Daemon<T> {
         Ok(())
     }
 
+    /// Send a command to every running transaction.
+    async fn command_all(&self, command: impl Fn() -> Command) {
+        for channel in self.transaction_channels.values() {
+            // the transaction may have finished in the meantime.
+            let _ = channel.send(command()).await;
+        }
+    }
+
+    /// Request the current report of every running transaction.
+    async fn collect_reports(&self) -> HashMap<TransactionID, Report> {
+        let mut reports = HashMap::new();
+        for (id, channel) in self.transaction_channels.iter() {
+            let (report_tx, report_rx) = oneshot::channel();
+            if channel.send(Command::Report(report_tx)).await.is_ok() {
+                if let Ok(Ok(report)) = report_rx.await {
+                    reports.insert(*id, report);
+                }
+            }
+        }
+        reports
+    }
+
+    async fn begin_shutdown(
+        &mut self,
+        mode: ShutdownMode,
+        reply: oneshot::Sender<PrimitiveResult<Vec<Report>>>,
+    ) {
+        if self.shutdown.is_some() {
+            warn!("Daemon shutdown already in progress. Ignoring {mode:?} request.");
+            let _ = reply.send(Err(PrimitiveError::ShuttingDown));
+            return;
+        }
+        if mode == ShutdownMode::SuspendAll && self.suspend_store.is_none() {
+            warn!("Refusing {mode:?} shutdown without a store for suspended transactions.");
+            let _ = reply.send(Err(PrimitiveError::NoSuspendStore));
+            return;
+        }
+        info!("User requested daemon shutdown: {mode:?}.");
+
+        let deadline = match mode {
+            // a deadline too far in the future to represent is never reached.
+            ShutdownMode::Drain(timeout) => Instant::now().checked_add(timeout),
+            ShutdownMode::SuspendAll => {
+                self.command_all(|| Command::Suspend).await;
+                None
+            }
+            ShutdownMode::Abort => None,
+        };
+        // commands are processed in order, so suspended transactions report as such.
+        let reports = self.collect_reports().await;
+        match (mode, self.suspend_store.as_ref()) {
+            (ShutdownMode::Drain(_), _) => {}
+            (ShutdownMode::SuspendAll, Some(store)) => {
+                self.command_all(|| Command::Save(store.clone())).await
+            }
+            _ => self.command_all(|| Command::Abandon).await,
+        }
+
+        self.shutdown = Some(Shutdown {
+            mode,
+            deadline,
+            reports,
+            reply,
+        });
+    }
+
+    /// Abandon any transactions which did not finish before the drain deadline.
+    async fn drain_expired(&mut self) {
+        if let Some(shutdown) = self.shutdown.as_mut() {
+            shutdown.deadline = None;
+        }
+        warn!(
+            "Drain deadline reached. Abandoning {} transaction(s).",
+            self.transaction_channels.len()
+        );
+        self.command_all(|| Command::Abandon).await;
+    }
+
+    fn transaction_finished(
+        &mut self,
+        id: TransactionID,
+        result: Result<Result<Report, TransactionError>, JoinError>,
+    ) {
+        // forget the transaction however it ended,
+        // unless a new one has already taken over the ID.
+        if self
+            .transaction_channels
+            .get(&id)
+            .is_none_or(|channel| channel.is_closed())
+        {
+            let _ = self.transaction_channels.remove(&id);
+            let _ = self.transaction_entities.remove(&id);
+            let _ = self.legacy_transactions.remove(&id);
+        }
+        match result {
+            Ok(Ok(report)) => {
+                if let Some(shutdown) = self.shutdown.as_mut() {
+                    // suspended transactions are saved as they were when suspended.
+                    if shutdown.mode != ShutdownMode::SuspendAll {
+                        if let Some(last) = shutdown.reports.get_mut(&report.id) {
+                            *last = report;
+                        }
+                    }
+                }
+            }
+            Ok(Err(err)) => {
+                error!("Error occurred during transaction {id}: {err}")
+            }
+            Err(err) => error!("Unable to join transaction {id}: {err}"),
+        };
+    }
+
+    /// Restart the transactions saved by a previous [SuspendAll](ShutdownMode::SuspendAll)
+    /// shutdown. They remain suspended until resumed by the user.
+    fn restore_suspended(&mut self) {
+        let Some(store) = self.suspend_store.clone() else {
+            return;
+        };
+        let saved = match store.load() {
+            Ok(saved) => saved,
+            Err(err) => {
+                error!("{err}");
+                return;
+            }
+        };
+        for (transaction, data) in saved {
+            let id = transaction.id();
+            let result = match transaction {
+                SavedTransaction::Send {
+                    transaction,
+                    priority,
+                } => self.restore_send(transaction, priority),
+                SavedTransaction::Recv(transaction) => self.restore_receive(transaction, data),
+            };
+            match result {
+                Ok(()) => {
+                    info!("Restored suspended transaction {id}.");
+                    if let Err(err) = store.remove(id) {
+                        warn!("{err}");
+                    }
+                }
+                Err(err) => warn!("Unable to restore suspended transaction {id}. {err}"),
+            }
+        }
+    }
+
+    fn restore_send(
+        &mut self,
+        saved: SavedSend,
+        priority: Option<Priority>,
+    ) -> PrimitiveResult<()> {
+        let id = saved.id();
+        let destination = saved.destination();
+        let protocol_version = saved.protocol_version();
+        let transport_tx = self
+            .transport_tx_map
+            .get(&destination)
+            .cloned()
+            .ok_or(PrimitiveError::NoRoute(destination))?;
+        let entity_config = self
+            .entity_configs
+            .get(&destination)
+            .unwrap_or(&self.default_config)
+            .clone();
+        let transaction = SendTransaction::restore(
+            saved,
+            self.filestore.clone(),
+            self.indications.sender(destination),
+        )
+        .map_err(|err| PrimitiveError::FileStore(err.to_string()))?;
+
+        let pacer = Self::new_pacer(&mut self.rate_limiters, destination, &entity_config);
+        let slot = Self::new_slot(&mut self.schedulers, destination, &entity_config, priority);
+        let link = Self::link_state(&mut self.links, destination);
+        let (sender, handle) = Self::run_send_transaction(
+            transaction,
+            priority,
+            transport_tx,
+            self.history.clone(),
+            pacer,
+            slot,
+            link,
+        );
+        self.transaction_handles.push((id, handle));
+        self.transaction_channels.insert(id, sender);
+        self.transaction_entities.insert(id, destination);
+        if protocol_version == ProtocolVersion::Legacy {
+            self.legacy_transactions.insert(id);
+        }
+        // new transactions must not reuse the sequence number.
+        if id.0 == self.entity_id && id.1.to_u64() >= self.sequence_num.to_u64() {
+            self.sequence_num = id.1;
+            self.sequence_num.get_and_increment();
+        }
+        Ok(())
+    }
+
+    fn restore_receive(&mut self, saved: SavedRecv, data: Option<File>) -> PrimitiveResult<()> {
+        let id = saved.id();
+        // receive transactions exchange PDUs with the source entity.
+        let transport_tx = self
+            .transport_tx_map
+            .get(&id.0)
+            .cloned()
+            .ok_or(PrimitiveError::NoRoute(id.0))?;
+        let entity_config = self
+            .entity_configs
+            .get(&id.0)
+            .unwrap_or(&self.default_config)
+            .clone();
+        let transaction = RecvTransaction::restore(
+            saved,
+            data,
+            self.filestore.clone(),
+            self.indications.sender(id.0),
+        )
+        .map_err(|err| PrimitiveError::FileStore(err.to_string()))?;
+
+        let pacer = Self::new_pacer(&mut self.rate_limiters, id.0, &entity_config);
+        let link = Self::link_state(&mut self.links, id.0);
+        let (sender, handle) = Self::run_receive_transaction(
+            transaction,
+            transport_tx,
+            self.history.clone(),
+            pacer,
+            link,
+        );
+        self.transaction_handles.push((id, handle));
+        self.transaction_channels.insert(id, sender);
+        self.transaction_entities.insert(id, id.0);
+        Ok(())
+    }
+
     async fn cleanup_transactions(&mut self) {
         // join any handles that have completed
         let mut ind = 0;
         while ind < self.transaction_handles.len() {
-            if self.transaction_handles[ind].is_finished() {
-                let handle = self.transaction_handles.remove(ind);
-                match handle.await {
-                    Ok(Ok(id)) => {
-                        // remove the channel for this transaction if it is complete
-                        let _ = self.transaction_channels.remove(&id);
-                    }
-                    Ok(Err(err)) => {
-                        info!("Error occurred during transaction: {}", err)
-                    }
-                    Err(_) => error!("Unable to join handle!"),
-                };
+            if self.transaction_handles[ind].1.is_finished() {
+                let (id, handle) = self.transaction_handles.remove(ind);
+                let result = handle.await;
+                self.transaction_finished(id, result);
             } else {
                 ind += 1;
             }
@@ -572,6 +1302,8 @@ impl<T: FileStore + Send + Sync + 'static> Daemon<T> {
 
     /// This function will consist of the main logic loop in any daemon process.
     pub async fn manage_transactions(&mut self) -> DaemonResult<()> {
+        self.restore_suspended();
+
         let cleanup = {
             let mut interval = tokio::time::interval(Duration::from_secs(1));
             // Don't start counting another tick until the currrent one has been processed.
@@ -581,6 +1313,14 @@ impl<T: FileStore + Send + Sync + 'static> Daemon<T> {
         tokio::pin!(cleanup);
 
         loop {
+            // refresh every iteration so a window edge is never skipped
+            // when another branch wins the race against the transition timer.
+            self.update_links();
+            let link_transition = self.until_link_transition();
+            let drain_deadline = self
+                .shutdown
+                .as_ref()
+                .and_then(|shutdown| shutdown.deadline);
             select! {
                 pdu = self.transport_rx.recv() => match pdu {
                     Some(pdu) => match self.forward_pdu(pdu).await{
@@ -595,12 +1335,6 @@ impl<T: FileStore + Send + Sync + 'static> Daemon<T> {
                             // it has initiated but there is no active transaction.
                             warn!("{error}");
                         }
-                        Err(err) => {
-                            if !self.terminate.load(Ordering::Relaxed) {
-                                self.terminate.store(true, Ordering::Relaxed);
-                            }
-                            return Err(err);
-                        }
                     },
                     None => {
                         if !self.terminate.load(Ordering::Relaxed) {
@@ -613,12 +1347,6 @@ impl<T: FileStore + Send + Sync + 'static> Daemon<T> {
                 primitive = self.primitive_rx.recv() => match primitive {
                     Some(primitive) => match self.process_primitive(primitive).await{
                         Ok(_) => {},
-                        Err(error @ DaemonError::SpawnSend(_)) => {
-                            // Unable to spawn a send transaction.
-                            // There are lots of reasons this cound happen.
-                            // Mostly if a user asked for a file that doesn't exist.
-                            warn!("{error}");
-                        },
                         Err(error @ DaemonError::TransactionCommuncation(_, _)) => {
                             // This occcurs most likely if a user is attempting to
                             // interact with a transaction that is already finished.
@@ -639,22 +1367,35 @@ impl<T: FileStore + Send + Sync + 'static> Daemon<T> {
                         break;
                     }
                 },
+                Some(event) = self.health_rx.recv() => self.handle_transport_event(event).await,
                 _ = cleanup.tick() => self.cleanup_transactions().await,
+                // the link states are updated at the top of the loop.
+                _ = tokio::time::sleep(link_transition) => {},
+                _ = tokio::time::sleep_until(drain_deadline.unwrap_or_else(Instant::now)), if drain_deadline.is_some() => {
+                    self.drain_expired().await
+                },
             };
+
+            if self.shutdown.is_some() {
+                self.cleanup_transactions().await;
+                if self.transaction_handles.is_empty() {
+                    info!("All transactions stopped. Daemon shutting down.");
+                    self.terminate.store(true, Ordering::Relaxed);
+                    break;
+                }
+            }
         }
 
         // a final cleanup
-        while let Some(handle) = self.transaction_handles.pop() {
-            match handle.await {
-                Ok(Ok(id)) => {
-                    // remove the channel for this transaction if it is complete
-                    let _ = self.transaction_channels.remove(&id);
-                }
-                Ok(Err(err)) => {
-                    info!("Error occurred during transaction: {}", err)
-                }
-                Err(_) => error!("Unable to join handle!"),
-            };
+        while let Some((id, handle)) = self.transaction_handles.pop() {
+            let result = handle.await;
+            self.transaction_finished(id, result);
+        }
+        if let Some(shutdown) = self.shutdown.take() {
+            // ignore the possible error if the user disconnected;
+            let _ = shutdown
+                .reply
+                .send(Ok(shutdown.reports.into_values().collect()));
         }
         Ok(())
     }
@@ -662,15 +1403,26 @@ impl<T: FileStore + Send + Sync + 'static> Daemon<T> {
 
 #[cfg(test)]
 mod test {
+    use camino::Utf8Path;
     use cfdp_core::{
-        daemon::NakProcedure,
+        daemon::{NakProcedure, VersionNegotiation},
         filestore::{ChecksumType, NativeFileStore},
         pdu::{
-            self, CRCFlag, Condition, FaultHandlerAction, PDUPayload, PositiveAcknowledgePDU, U3,
+            self, CRCFlag, Condition, FaultHandlerAction, PDUEncode, PDUPayload,
+            PositiveAcknowledgePDU, U3,
         },
     };
+    use rstest::rstest;
+    use std::{
+        collections::BTreeMap,
+        io::{Error as IoError, ErrorKind},
+    };
 
     use super::*;
+    use crate::{
+        history::DEFAULT_HISTORY_CAPACITY, indication::DEFAULT_INDICATION_CAPACITY,
+        transport::PDUTransport,
+    };
 
     #[macro_export]
     macro_rules! assert_err{
@@ -685,8 +1437,8 @@ mod test {
     #[tokio::test]
     async fn pdu_to_sender_no_transaction() {
         let (_send, recv) = channel(1);
-        let (indication_tx, _indication_rx) = channel(1);
         let (_primitive_tx, primitive_rx) = channel(1);
+        let (_health_tx, health_rx) = channel(1);
         let filestore = Arc::new(NativeFileStore::new("."));
         let mut transport_tx_map = HashMap::<_, _>::new();
 
@@ -697,17 +1449,27 @@ mod test {
         let mut daemon = Daemon {
             transaction_handles: vec![],
             transaction_channels: HashMap::<_, _>::new(),
+            transaction_entities: HashMap::new(),
+            legacy_transactions: HashSet::new(),
+            peer_versions: HashMap::new(),
             transport_tx_map,
             transport_rx: recv,
+            health_rx,
             filestore,
-            indication_tx,
+            indications: IndicationBroadcast::new(1),
+            history: TransactionHistory::new(1),
+            suspend_store: None,
             entity_configs: HashMap::new(),
+            rate_limiters: HashMap::new(),
+            schedulers: HashMap::new(),
+            links: HashMap::new(),
             default_config: EntityConfig {
-                fault_handler_override: HashMap::from([(
+                fault_handler_override: BTreeMap::from([(
                     Condition::PositiveLimitReached,
                     FaultHandlerAction::Abandon,
                 )]),
                 file_size_segment: 1024,
+                max_pdu_len: None,
                 default_transaction_max_count: 2,
                 inactivity_timeout: 0,
                 ack_timeout: 1,
@@ -716,11 +1478,19 @@ mod test {
                 closure_requested: false,
                 checksum_type: ChecksumType::Modular,
                 nak_procedure: NakProcedure::Deferred(Duration::from_secs(0)),
+                link_failure_action: FaultHandlerAction::Ignore,
+                rate_limit: None,
+                transaction_rate_limit: None,
+                scheduling: Scheduling::Priority,
+                default_priority: Priority::default(),
+                protocol_version: VersionNegotiation::Auto,
+                id_width: None,
             },
             entity_id: 0_u64.into(),
             sequence_num: 0_u64.into(),
             terminate: Arc::new(AtomicBool::new(false)),
             primitive_rx,
+            shutdown: None,
         };
         let payload = PDUPayload::Directive(pdu::Operations::Ack(PositiveAcknowledgePDU {
             directive: pdu::PDUDirective::EoF,
@@ -749,4 +1519,376 @@ mod test {
         let res = daemon.forward_pdu(pdu).await;
         assert_err!(res, Err(DaemonError::UnableToResume(_)))
     }
+
+    /// Fails on the first receive, then receives a PDU for another entity and waits forever.
+    struct FlakyTransport {
+        receives: usize,
+    }
+    #[async_trait::async_trait]
+    impl PDUTransport for FlakyTransport {
+        async fn request(&mut self, _destination: VariableID, _pdu: PDU) -> Result<(), IoError> {
+            Ok(())
+        }
+
+        async fn receive(&mut self) -> Result<PDU, IoError> {
+            self.receives += 1;
+            match self.receives {
+                1 => Err(IoError::from(ErrorKind::BrokenPipe)),
+                2 => {
+                    let payload = PDUPayload::Directive(pdu::Operations::EoF(pdu::EndOfFile {
+                        condition: Condition::NoError,
+                        checksum: 0,
+                        file_size: 0,
+                        fault_location: None,
+                    }));
+                    Ok(PDU {
+                        header: PDUHeader {
+                            version: U3::One,
+                            pdu_type: pdu::PDUType::FileDirective,
+                            direction: Direction::ToReceiver,
+                            transmission_mode: pdu::TransmissionMode::Unacknowledged,
+                            crc_flag: CRCFlag::NotPresent,
+                            large_file_flag: FileSizeFlag::Small,
+                            pdu_data_field_length: payload.encoded_len(FileSizeFlag::Small),
+                            segmentation_control: pdu::SegmentationControl::NotPreserved,
+                            segment_metadata_flag: SegmentedData::NotPresent,
+                            source_entity_id: 1_u16.into(),
+                            transaction_sequence_number: 3_u16.into(),
+                            destination_entity_id: 5_u16.into(),
+                        },
+                        payload,
+                    })
+                }
+                _ => std::future::pending().await,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn transport_failure_indication() {
+        let (indication_tx, mut indication_rx) = channel(10);
+        let (primitive_tx, primitive_rx) = channel(1);
+        let filestore = Arc::new(NativeFileStore::new("."));
+        let transports = vec![(
+            vec![EntityID::from(1_u16)],
+            Box::new(FlakyTransport { receives: 0 }) as Box<dyn PDUTransport + Send>,
+        )];
+        let config = EntityConfig {
+            fault_handler_override: BTreeMap::new(),
+            file_size_segment: 1024,
+            max_pdu_len: None,
+            default_transaction_max_count: 2,
+            inactivity_timeout: 1,
+            ack_timeout: 1,
+            nak_timeout: 1,
+            crc_flag: CRCFlag::NotPresent,
+            closure_requested: false,
+            checksum_type: ChecksumType::Modular,
+            nak_procedure: NakProcedure::Deferred(Duration::ZERO),
+            link_failure_action: FaultHandlerAction::Ignore,
+            rate_limit: None,
+            transaction_rate_limit: None,
+            scheduling: Scheduling::Priority,
+            default_priority: Priority::default(),
+            protocol_version: VersionNegotiation::Auto,
+            id_width: None,
+        };
+
+        let mut daemon = Daemon::new(DaemonParts {
+            entity_id: EntityID::from(0_u16),
+            sequence_num: TransactionSeqNum::from(0_u16),
+            transports,
+            filestore,
+            entity_configs: HashMap::new(),
+            default_config: config,
+            primitive_rx,
+            indications: IndicationBroadcast::with_relay(
+                DEFAULT_INDICATION_CAPACITY,
+                indication_tx,
+            ),
+            history: TransactionHistory::new(DEFAULT_HISTORY_CAPACITY),
+            suspend_store: None,
+        });
+        let handle = tokio::spawn(async move { daemon.manage_transactions().await });
+
+        // the link is up again once the restarted transport receives a PDU.
+        for expected in [LinkState::Down, LinkState::Up] {
+            match indication_rx.recv().await {
+                Some(Indication::Link(link)) => assert_eq!(
+                    LinkIndication {
+                        entity_id: EntityID::from(1_u16),
+                        state: expected
+                    },
+                    link
+                ),
+                other => panic!("expected link indication but got {other:?}"),
+            }
+        }
+
+        drop(primitive_tx);
+        handle.await.unwrap().unwrap();
+    }
+
+    /// Hands every PDU sent to the test.
+    struct RecordingTransport {
+        sent: Sender<PDU>,
+    }
+    #[async_trait::async_trait]
+    impl PDUTransport for RecordingTransport {
+        async fn request(&mut self, _destination: VariableID, pdu: PDU) -> Result<(), IoError> {
+            self.sent
+                .send(pdu)
+                .await
+                .map_err(|_| IoError::from(ErrorKind::ConnectionReset))
+        }
+
+        async fn receive(&mut self) -> Result<PDU, IoError> {
+            std::future::pending().await
+        }
+    }
+
+    /// A Daemon sending to entity 1 over a [RecordingTransport].
+    ///
+    /// The returned directory holds its filestore with the file `sent.txt`.
+    fn recording_daemon(
+        config: EntityConfig,
+    ) -> (Daemon<NativeFileStore>, Receiver<PDU>, tempfile::TempDir) {
+        let directory = tempfile::tempdir().unwrap();
+        std::fs::write(directory.path().join("sent.txt"), b"an old ground station").unwrap();
+        let filestore = Arc::new(NativeFileStore::new(
+            Utf8Path::from_path(directory.path()).unwrap(),
+        ));
+
+        let (sent_tx, sent_rx) = channel(10);
+        let (indication_tx, _indication_rx) = channel(10);
+        let (_primitive_tx, primitive_rx) = channel(1);
+        let transports = vec![(
+            vec![EntityID::from(1_u16)],
+            Box::new(RecordingTransport { sent: sent_tx }) as Box<dyn PDUTransport + Send>,
+        )];
+        let daemon = Daemon::new(DaemonParts {
+            entity_id: EntityID::from(0_u16),
+            sequence_num: TransactionSeqNum::from(0_u16),
+            transports,
+            filestore,
+            entity_configs: HashMap::new(),
+            default_config: config,
+            primitive_rx,
+            indications: IndicationBroadcast::with_relay(
+                DEFAULT_INDICATION_CAPACITY,
+                indication_tx,
+            ),
+            history: TransactionHistory::new(DEFAULT_HISTORY_CAPACITY),
+            suspend_store: None,
+        });
+        (daemon, sent_rx, directory)
+    }
+
+    fn recording_config() -> EntityConfig {
+        EntityConfig {
+            fault_handler_override: BTreeMap::new(),
+            file_size_segment: 1024,
+            max_pdu_len: None,
+            default_transaction_max_count: 2,
+            inactivity_timeout: 1,
+            ack_timeout: 1,
+            nak_timeout: 1,
+            crc_flag: CRCFlag::NotPresent,
+            closure_requested: true,
+            checksum_type: ChecksumType::Null,
+            nak_procedure: NakProcedure::Deferred(Duration::ZERO),
+            link_failure_action: FaultHandlerAction::Ignore,
+            rate_limit: None,
+            transaction_rate_limit: None,
+            scheduling: Scheduling::Priority,
+            default_priority: Priority::default(),
+            protocol_version: VersionNegotiation::Auto,
+            id_width: None,
+        }
+    }
+
+    fn put_request() -> PutRequest {
+        PutRequest {
+            source_filename: "sent.txt".into(),
+            destination_filename: "received.txt".into(),
+            destination_entity_id: EntityID::from(1_u16),
+            transmission_mode: pdu::TransmissionMode::Unacknowledged,
+            filestore_requests: vec![],
+            message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
+        }
+    }
+
+    #[rstest]
+    #[case(VersionNegotiation::Legacy, None)]
+    #[case(VersionNegotiation::Auto, Some(ProtocolVersion::Legacy))]
+    #[tokio::test]
+    async fn legacy_peer(
+        #[case] negotiation: VersionNegotiation,
+        #[case] heard: Option<ProtocolVersion>,
+    ) {
+        let config = EntityConfig {
+            protocol_version: negotiation,
+            ..recording_config()
+        };
+        let (mut daemon, mut sent_rx, _directory) = recording_daemon(config);
+        if let Some(version) = heard {
+            daemon.peer_versions.insert(EntityID::from(1_u16), version);
+        }
+
+        let id = daemon.put(put_request()).unwrap();
+
+        let sent = sent_rx.recv().await.unwrap();
+        assert_eq!(U3::Zero, sent.header.version);
+        match sent.payload {
+            PDUPayload::Directive(pdu::Operations::Metadata(metadata)) => {
+                assert!(!metadata.closure_requested);
+                assert_eq!(ChecksumType::Modular, metadata.checksum_type);
+            }
+            other => panic!("expected metadata but got {other:?}"),
+        }
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        daemon
+            .process_primitive(UserPrimitive::Prompt(id, NakOrKeepAlive::Nak, reply_tx))
+            .await
+            .unwrap();
+        assert_eq!(
+            Err(PrimitiveError::Unsupported(
+                EntityID::from(1_u16),
+                "Prompt".to_owned()
+            )),
+            reply_rx.await.unwrap()
+        );
+    }
+
+    #[rstest]
+    #[case(None, true)]
+    #[case(Some(false), false)]
+    #[tokio::test]
+    async fn requested_closure(#[case] requested: Option<bool>, #[case] expected: bool) {
+        let (mut daemon, mut sent_rx, _directory) = recording_daemon(recording_config());
+        daemon
+            .put(PutRequest {
+                closure_requested: requested,
+                ..put_request()
+            })
+            .unwrap();
+
+        match sent_rx.recv().await.unwrap().payload {
+            PDUPayload::Directive(pdu::Operations::Metadata(metadata)) => {
+                assert_eq!(expected, metadata.closure_requested);
+            }
+            other => panic!("expected metadata but got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn configured_id_width() {
+        let config = EntityConfig {
+            id_width: Some(3),
+            ..recording_config()
+        };
+        let (mut daemon, mut sent_rx, _directory) = recording_daemon(config);
+        daemon.put(put_request()).unwrap();
+
+        let sent = sent_rx.recv().await.unwrap();
+        assert_eq!(3, sent.header.entity_id_width());
+        assert_eq!(2, sent.header.transaction_sequence_number.width());
+
+        let encoded = sent.encode();
+        // lengths minus one: 3 byte entity IDs and a 2 byte sequence number.
+        assert_eq!(0x21, encoded[3] & 0x77);
+        let decoded = PDU::decode(&mut encoded.as_slice()).unwrap();
+        assert_eq!(
+            EntityID::new(0, 3).unwrap(),
+            decoded.header.source_entity_id
+        );
+        assert_eq!(
+            EntityID::new(1, 3).unwrap(),
+            decoded.header.destination_entity_id
+        );
+
+        // received back, the IDs are known by their local widths again.
+        let normalized = daemon.normalize_ids(decoded);
+        assert_eq!(EntityID::from(0_u16), normalized.header.source_entity_id);
+        assert_eq!(
+            EntityID::from(1_u16),
+            normalized.header.destination_entity_id
+        );
+        assert_eq!(
+            TransactionSeqNum::from(0_u16),
+            normalized.header.transaction_sequence_number
+        );
+    }
+
+    #[rstest]
+    #[case(Some(400), 2, 400)]
+    // too long for the data field length of the 10 byte header to express.
+    #[case(None, 300, 10 + 65_535)]
+    #[tokio::test]
+    async fn oversize_metadata(
+        #[case] max_pdu_len: Option<usize>,
+        #[case] messages: usize,
+        #[case] limit: usize,
+    ) {
+        let config = EntityConfig {
+            max_pdu_len,
+            ..recording_config()
+        };
+        let (mut daemon, mut sent_rx, _directory) = recording_daemon(config);
+        let message = pdu::MessageToUser {
+            message_text: vec![0x5A; 250],
+        };
+        let result = daemon.put(PutRequest {
+            message_to_user: vec![message; messages],
+            ..put_request()
+        });
+        assert!(
+            matches!(
+                result,
+                Err(PrimitiveError::MetadataTooLarge(entity, pdu_len, max_pdu_len))
+                    if entity == EntityID::from(1_u16) && max_pdu_len == limit && pdu_len > limit
+            ),
+            "{result:?}"
+        );
+        assert!(sent_rx.try_recv().is_err());
+
+        // the refused request used up no sequence number.
+        let id = daemon.put(put_request()).unwrap();
+        assert_eq!(0, id.1.to_u64());
+    }
+
+    #[rstest]
+    #[case(
+        PutRequest {
+            destination_filename: "d".repeat(256).into(),
+            ..put_request()
+        },
+        "Destination filename"
+    )]
+    #[case(
+        PutRequest {
+            message_to_user: vec![pdu::MessageToUser {
+                message_text: vec![0x5A; 256],
+            }],
+            ..put_request()
+        },
+        "Message to user"
+    )]
+    #[tokio::test]
+    async fn field_too_long(#[case] request: PutRequest, #[case] name: &str) {
+        let (mut daemon, mut sent_rx, _directory) = recording_daemon(recording_config());
+        let result = daemon.put(request);
+        assert!(
+            matches!(
+                &result,
+                Err(PrimitiveError::FieldTooLong(entity, field, 256, 255))
+                    if *entity == EntityID::from(1_u16) && field == name
+            ),
+            "{result:?}"
+        );
+        assert!(sent_rx.try_recv().is_err());
+    }
 }
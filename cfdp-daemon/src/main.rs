@@ -0,0 +1,141 @@
+use std::{process::ExitCode, time::Duration};
+
+use camino::Utf8PathBuf;
+use cfdp_daemon::{
+    cfdp_core::daemon::{Indication, ShutdownMode, UserPrimitive},
+    channel,
+    config::DaemonConfig,
+    control::{default_socket, ControlServer, RemoteEntity},
+    error::SubscriptionError,
+    indication::IndicationFilter,
+    oneshot,
+};
+use clap::Parser;
+use log::{debug, error, info, warn};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Run a CFDP entity from a configuration file.")]
+struct Args {
+    /// TOML or JSON configuration of the local and remote entities.
+    #[arg(short, long)]
+    config: Utf8PathBuf,
+
+    /// Unix socket the control interface listens on.
+    #[arg(short, long, default_value_t = default_socket())]
+    socket: Utf8PathBuf,
+
+    /// Seconds active transactions are given to finish when the daemon is interrupted.
+    #[arg(long, default_value_t = 30)]
+    drain_timeout: u64,
+}
+
+fn log_indication(indication: &Indication) {
+    match indication {
+        Indication::Abandon(_) | Indication::Fault(_) => warn!("{indication:?}"),
+        Indication::FileSegmentRecv(_) | Indication::Report(_) => debug!("{indication:?}"),
+        _ => info!("{indication:?}"),
+    }
+}
+
+#[cfg(not(unix))]
+fn main() -> ExitCode {
+    eprintln!("The control interface requires Unix domain sockets.");
+    ExitCode::FAILURE
+}
+
+#[cfg(unix)]
+#[tokio::main]
+async fn main() -> ExitCode {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let args = Args::parse();
+
+    let config = match DaemonConfig::load(&args.config) {
+        Ok(config) => config,
+        Err(err) => {
+            error!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let (primitive_tx, primitive_rx) = channel(100);
+    let mut daemon = match config
+        .builder()
+        .await
+        .and_then(|builder| builder.primitives(primitive_rx).build())
+    {
+        Ok(daemon) => daemon,
+        Err(err) => {
+            error!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let server = match ControlServer::bind(
+        &args.socket,
+        primitive_tx.clone(),
+        config.local.entity_id,
+        config.local.id_width,
+    ) {
+        Ok(server) => server
+            .remotes(config.remotes.iter().map(RemoteEntity::from).collect())
+            .indications(daemon.indications())
+            .history(daemon.history()),
+        Err(err) => {
+            error!("Unable to listen on {}: {err}", args.socket);
+            return ExitCode::FAILURE;
+        }
+    };
+    tokio::task::spawn(async move {
+        if let Err(err) = server.run().await {
+            error!("Control interface stopped: {err}");
+        }
+    });
+
+    let mut subscription = daemon.subscribe(IndicationFilter::new());
+    tokio::task::spawn(async move {
+        loop {
+            match subscription.recv().await {
+                Ok(indication) => log_indication(&indication),
+                Err(err @ SubscriptionError::Lagged(_)) => warn!("{err}"),
+                Err(SubscriptionError::Closed) => break,
+            }
+        }
+    });
+
+    info!(
+        "Entity {} running, control interface on {}.",
+        config.local.entity_id, args.socket
+    );
+    let mut daemon_handle = tokio::task::spawn(async move { daemon.manage_transactions().await });
+
+    let result = tokio::select! {
+        result = &mut daemon_handle => result,
+        _ = tokio::signal::ctrl_c() => {
+            info!("Interrupted, draining transactions for up to {} seconds.", args.drain_timeout);
+            let (reply_tx, reply_rx) = oneshot::channel();
+            let mode = ShutdownMode::Drain(Duration::from_secs(args.drain_timeout));
+            if primitive_tx.send(UserPrimitive::Shutdown(mode, reply_tx)).await.is_ok() {
+                // a shutdown requested over the control interface reports to its client.
+                if let Ok(Ok(reports)) = reply_rx.await {
+                    for report in reports {
+                        info!("Final state: {report:?}");
+                    }
+                }
+            }
+            daemon_handle.await
+        }
+    };
+    let _ = std::fs::remove_file(&args.socket);
+
+    match result {
+        Ok(Ok(())) => ExitCode::SUCCESS,
+        Ok(Err(err)) => {
+            error!("{err}");
+            ExitCode::FAILURE
+        }
+        Err(err) => {
+            error!("Daemon task failed: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use cfdp_core::daemon::RateLimit;
+use tokio::time::Instant;
+
+/// A token bucket measured in bytes.
+///
+/// The bucket is allowed to go into debt when a PDU larger than the available
+/// tokens is sent, the next PDU is then held until the debt is repaid.
+/// This avoids needing to know the size of a PDU before it is built.
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last_update: Instant,
+}
+impl TokenBucket {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            rate: limit.bytes_per_second as f64,
+            burst: limit.burst as f64,
+            tokens: limit.burst as f64,
+            last_update: Instant::now(),
+        }
+    }
+
+    fn update(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_update).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.last_update = now;
+    }
+
+    /// Remove the number of bytes sent from the bucket.
+    pub fn consume(&mut self, bytes: u64) {
+        self.update();
+        self.tokens -= bytes as f64;
+    }
+
+    /// The amount of time until the bucket is out of debt.
+    pub fn until_ready(&mut self) -> Duration {
+        self.update();
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else if self.rate <= 0.0 {
+            // a zero rate never refills, the DaemonBuilder rejects it.
+            Duration::MAX
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+/// Combines the bandwidth limit of a single transaction with the limit
+/// shared between all transactions to the same remote entity.
+#[derive(Debug, Default)]
+pub struct Pacer {
+    transaction: Option<TokenBucket>,
+    entity: Option<Arc<Mutex<TokenBucket>>>,
+}
+impl Pacer {
+    pub fn new(
+        transaction_limit: Option<RateLimit>,
+        entity: Option<Arc<Mutex<TokenBucket>>>,
+    ) -> Self {
+        Self {
+            transaction: transaction_limit.map(TokenBucket::new),
+            entity,
+        }
+    }
+
+    /// The amount of time to wait before the next PDU may be sent.
+    pub fn until_ready(&mut self) -> Duration {
+        let transaction = self
+            .transaction
+            .as_mut()
+            .map_or(Duration::ZERO, TokenBucket::until_ready);
+        let entity = self.entity.as_ref().map_or(Duration::ZERO, |bucket| {
+            bucket
+                .lock()
+                .map_or(Duration::ZERO, |mut bucket| bucket.until_ready())
+        });
+        transaction.max(entity)
+    }
+
+    pub fn consume(&mut self, bytes: u64) {
+        if let Some(bucket) = self.transaction.as_mut() {
+            bucket.consume(bytes);
+        }
+        if let Some(Ok(mut bucket)) = self.entity.as_ref().map(|bucket| bucket.lock()) {
+            bucket.consume(bytes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::thread;
+
+    #[test]
+    fn burst_then_debt() {
+        let mut bucket = TokenBucket::new(RateLimit {
+            bytes_per_second: 1000,
+            burst: 100,
+        });
+        assert_eq!(Duration::ZERO, bucket.until_ready());
+
+        bucket.consume(100);
+        assert_eq!(Duration::ZERO, bucket.until_ready());
+
+        // 200 bytes in debt at 1000 bytes/s is ~200ms
+        bucket.consume(200);
+        let wait = bucket.until_ready();
+        assert!(wait > Duration::from_millis(150) && wait <= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn refill() {
+        let mut bucket = TokenBucket::new(RateLimit {
+            bytes_per_second: 1000,
+            burst: 100,
+        });
+        bucket.consume(150);
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(Duration::ZERO, bucket.until_ready());
+
+        // tokens never exceed the burst size
+        thread::sleep(Duration::from_millis(200));
+        bucket.consume(101);
+        assert!(bucket.until_ready() > Duration::ZERO);
+    }
+
+    #[test]
+    fn pacer_shared_entity() {
+        let entity = Arc::new(Mutex::new(TokenBucket::new(RateLimit {
+            bytes_per_second: 1000,
+            burst: 100,
+        })));
+        let mut first = Pacer::new(None, Some(entity.clone()));
+        let mut second = Pacer::new(
+            Some(RateLimit {
+                bytes_per_second: 10_000,
+                burst: 1000,
+            }),
+            Some(entity),
+        );
+
+        first.consume(200);
+        // the second transaction is within its own limit but the entity is in debt
+        assert!(second.until_ready() > Duration::ZERO);
+    }
+
+    #[test]
+    fn unlimited() {
+        let mut pacer = Pacer::default();
+        pacer.consume(u32::MAX.into());
+        assert_eq!(Duration::ZERO, pacer.until_ready());
+    }
+}
@@ -0,0 +1,310 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use cfdp_core::daemon::Priority;
+use tokio::sync::{
+    mpsc::{error::SendError, Permit, Sender},
+    Notify,
+};
+
+/// Arbitrates the transport among the transactions sending to a remote entity.
+///
+/// One transaction at a time holds the turn to send a PDU.
+/// The turn goes to the highest [class](Priority::class) with a PDU ready, and within
+/// the class to the transaction which has sent the fewest bytes relative to its weight.
+#[derive(Debug, Clone, Default)]
+pub struct Scheduler(Arc<Mutex<State>>);
+
+#[derive(Debug)]
+struct SlotState {
+    priority: Priority,
+    // bytes sent divided by the weight, the lowest is served next within a class.
+    virtual_time: f64,
+    waiting: bool,
+    notify: Arc<Notify>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    slots: HashMap<u64, SlotState>,
+    next_slot: u64,
+    holder: Option<u64>,
+    // virtual time of the last turn given in each class,
+    // new and idle transactions start from here so they cannot claim a backlog of turns.
+    clocks: HashMap<u8, f64>,
+}
+impl State {
+    /// Give the turn to the next waiting slot, unless it is already held.
+    fn dispatch(&mut self) {
+        if self.holder.is_some() {
+            return;
+        }
+        let next = self
+            .slots
+            .iter()
+            .filter(|(_, slot)| slot.waiting)
+            .min_by(|(a_id, a), (b_id, b)| {
+                b.priority
+                    .class
+                    .cmp(&a.priority.class)
+                    .then(a.virtual_time.total_cmp(&b.virtual_time))
+                    .then(a_id.cmp(b_id))
+            })
+            .map(|(id, _)| *id);
+        if let Some(id) = next {
+            let slot = &self.slots[&id];
+            self.clocks.insert(slot.priority.class, slot.virtual_time);
+            slot.notify.notify_one();
+            self.holder = Some(id);
+        }
+    }
+
+    /// Stop waiting for, or give up, the turn.
+    fn release(&mut self, id: u64) {
+        if let Some(slot) = self.slots.get_mut(&id) {
+            slot.waiting = false;
+        }
+        if self.holder == Some(id) {
+            self.holder = None;
+            self.dispatch();
+        }
+    }
+}
+
+impl Scheduler {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Register a transaction with the given priority.
+    pub fn slot(&self, priority: Priority) -> Slot {
+        let mut state = self.state();
+        let id = state.next_slot;
+        state.next_slot += 1;
+        let virtual_time = state.clocks.get(&priority.class).copied().unwrap_or(0.0);
+        state.slots.insert(
+            id,
+            SlotState {
+                priority,
+                virtual_time,
+                waiting: false,
+                notify: Arc::new(Notify::new()),
+            },
+        );
+        Slot {
+            scheduled: Some((self.clone(), id)),
+        }
+    }
+}
+
+/// The place of a single transaction in a [Scheduler].
+///
+/// Dropping the slot removes the transaction from the scheduler.
+#[derive(Debug, Default)]
+pub struct Slot {
+    scheduled: Option<(Scheduler, u64)>,
+}
+impl Slot {
+    /// A slot which never waits for its turn.
+    pub fn unscheduled() -> Self {
+        Self::default()
+    }
+
+    /// Leave the queue while the transaction has no PDU ready to send,
+    /// passing on a turn given to it in the meantime.
+    pub fn withdraw(&self) {
+        if let Some((scheduler, id)) = &self.scheduled {
+            scheduler.state().release(*id);
+        }
+    }
+
+    /// Wait for the turn of this transaction, then for room in the transport.
+    ///
+    /// The turn is given up if the future is dropped before it completes.
+    pub async fn reserve<'a, T>(
+        &self,
+        transport_tx: &'a Sender<T>,
+    ) -> Result<(Turn<'_>, Permit<'a, T>), SendError<()>> {
+        let turn = self.turn().await;
+        let permit = transport_tx.reserve().await?;
+        Ok((turn, permit))
+    }
+
+    async fn turn(&self) -> Turn<'_> {
+        let Some((scheduler, id)) = &self.scheduled else {
+            return Turn { scheduled: None };
+        };
+        let notify = {
+            let mut guard = scheduler.state();
+            let state = &mut *guard;
+            let slot = state.slots.get_mut(id).expect("registered until dropped");
+            if !slot.waiting {
+                slot.waiting = true;
+                if let Some(clock) = state.clocks.get(&slot.priority.class) {
+                    slot.virtual_time = slot.virtual_time.max(*clock);
+                }
+            }
+            let notify = slot.notify.clone();
+            state.dispatch();
+            notify
+        };
+        // withdraws from the scheduler if dropped while waiting.
+        let turn = Turn {
+            scheduled: Some((scheduler, *id)),
+        };
+        while scheduler.state().holder != Some(*id) {
+            notify.notified().await;
+        }
+        turn
+    }
+}
+impl Drop for Slot {
+    fn drop(&mut self) {
+        if let Some((scheduler, id)) = self.scheduled.take() {
+            let mut state = scheduler.state();
+            state.release(id);
+            state.slots.remove(&id);
+        }
+    }
+}
+
+/// The right to send the next PDU, passed on to the next transaction once dropped.
+#[derive(Debug)]
+pub struct Turn<'a> {
+    scheduled: Option<(&'a Scheduler, u64)>,
+}
+impl Turn<'_> {
+    /// Account for the bytes sent during this turn.
+    ///
+    /// The transaction stays queued for its next turn until it [withdraws](Slot::withdraw),
+    /// so a busy transaction competes for the next turn with the others already waiting.
+    pub fn complete(mut self, bytes: u64) {
+        if let Some((scheduler, id)) = self.scheduled.take() {
+            let mut state = scheduler.state();
+            if let Some(slot) = state.slots.get_mut(&id) {
+                slot.virtual_time += bytes as f64 / f64::from(slot.priority.weight.max(1));
+            }
+            if state.holder == Some(id) {
+                state.holder = None;
+                state.dispatch();
+            }
+        }
+    }
+}
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        if let Some((scheduler, id)) = self.scheduled {
+            scheduler.state().release(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    use tokio::sync::mpsc::channel;
+
+    fn priority(class: u8, weight: u16) -> Priority {
+        Priority { class, weight }
+    }
+
+    #[tokio::test]
+    async fn strict_priority() {
+        let scheduler = Scheduler::default();
+        let bulk = scheduler.slot(priority(0, 1));
+        let held = bulk.turn().await;
+
+        let (order_tx, mut order_rx) = channel(2);
+        for (slot, name) in [
+            (scheduler.slot(priority(0, 1)), "bulk"),
+            (scheduler.slot(priority(3, 1)), "urgent"),
+        ] {
+            let order_tx = order_tx.clone();
+            tokio::spawn(async move {
+                let turn = slot.turn().await;
+                order_tx.send(name).await.unwrap();
+                turn.complete(100);
+            });
+        }
+        // both are waiting before the turn is released.
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        held.complete(100);
+        bulk.withdraw();
+
+        assert_eq!(Some("urgent"), order_rx.recv().await);
+        assert_eq!(Some("bulk"), order_rx.recv().await);
+    }
+
+    #[tokio::test]
+    async fn weighted_share() {
+        let scheduler = Scheduler::default();
+        let light = Arc::new(scheduler.slot(priority(1, 1)));
+        let heavy = Arc::new(scheduler.slot(priority(1, 3)));
+        let turns = Arc::new(Mutex::new([0_u32; 2]));
+
+        let tasks = [light, heavy].into_iter().enumerate().map(|(index, slot)| {
+            let turns = turns.clone();
+            tokio::spawn(async move {
+                loop {
+                    let turn = slot.turn().await;
+                    {
+                        let mut turns = turns.lock().unwrap();
+                        if turns.iter().sum::<u32>() >= 400 {
+                            return;
+                        }
+                        turns[index] += 1;
+                    }
+                    turn.complete(100);
+                    // stand in for sending the PDU.
+                    tokio::task::yield_now().await;
+                }
+            })
+        });
+        for task in tasks.collect::<Vec<_>>() {
+            task.await.unwrap();
+        }
+
+        let [light, heavy] = *turns.lock().unwrap();
+        assert!((95..=105).contains(&light), "{light} {heavy}");
+        assert!((295..=305).contains(&heavy), "{light} {heavy}");
+    }
+
+    #[tokio::test]
+    async fn dropped_waiter() {
+        let scheduler = Scheduler::default();
+        let first = scheduler.slot(priority(0, 1));
+        let second = scheduler.slot(priority(0, 1));
+        let third = scheduler.slot(priority(0, 1));
+
+        let held = first.turn().await;
+        // gives up waiting while the turn is held elsewhere.
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), second.turn())
+                .await
+                .is_err()
+        );
+        let waiting = tokio::spawn(async move {
+            third.turn().await.complete(0);
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        drop(held);
+        tokio::time::timeout(Duration::from_millis(100), waiting)
+            .await
+            .expect("the abandoned waiter kept the turn")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn unscheduled() {
+        let (transport_tx, _transport_rx) = channel::<()>(1);
+        let slot = Slot::unscheduled();
+        let (turn, permit) = slot.reserve(&transport_tx).await.unwrap();
+        permit.send(());
+        turn.complete(100);
+    }
+}
@@ -0,0 +1,140 @@
+//! Transactions saved by a [SuspendAll](cfdp_core::daemon::ShutdownMode::SuspendAll) shutdown.
+//!
+//! Each suspended transaction is written to its own file in the directory of a [SuspendStore],
+//! a receiving transaction also keeps the file data received so far next to it.
+//! The next [Daemon](crate::Daemon) given the same store restores them once it starts
+//! managing transactions. They stay suspended until the user resumes them.
+use std::{
+    fs::{self, File},
+    io::{self, ErrorKind, Write},
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use cfdp_core::{daemon::Priority, transaction::TransactionID};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::SuspendError,
+    transaction::{recv::SavedRecv, send::SavedSend},
+};
+
+const STATE_EXTENSION: &str = "json";
+const DATA_EXTENSION: &str = "data";
+
+#[derive(Serialize, Deserialize)]
+pub(crate) enum SavedTransaction {
+    Send {
+        transaction: SavedSend,
+        // the priority the transaction was scheduled with, if not the default.
+        priority: Option<Priority>,
+    },
+    Recv(SavedRecv),
+}
+impl SavedTransaction {
+    pub(crate) fn id(&self) -> TransactionID {
+        match self {
+            Self::Send { transaction, .. } => transaction.id(),
+            Self::Recv(transaction) => transaction.id(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A directory holding the transactions suspended when a Daemon shut down.
+pub struct SuspendStore {
+    dir: Utf8PathBuf,
+}
+impl SuspendStore {
+    /// Keep suspended transactions in `dir`, which is created if it does not exist yet.
+    pub fn open(dir: impl Into<Utf8PathBuf>) -> Result<Self, SuspendError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).map_err(|err| SuspendError::Io(dir.clone(), err))?;
+        Ok(Self { dir })
+    }
+
+    /// The directory the transactions are kept in.
+    pub fn dir(&self) -> &Utf8Path {
+        &self.dir
+    }
+
+    fn path(&self, id: TransactionID, extension: &str) -> Utf8PathBuf {
+        self.dir.join(format!("{id}.{extension}"))
+    }
+
+    /// Save the transaction, replacing any previous state saved under its ID.
+    ///
+    /// The state is written last and moved into place,
+    /// so a transaction is only ever loaded complete with its data.
+    pub(crate) fn save(
+        &self,
+        transaction: &SavedTransaction,
+        data: Option<&mut File>,
+    ) -> Result<(), SuspendError> {
+        let id = transaction.id();
+        let data_path = self.path(id, DATA_EXTENSION);
+        match data {
+            Some(data) => {
+                let error = |err| SuspendError::Io(data_path.clone(), err);
+                let mut file = File::create(&data_path).map_err(error)?;
+                io::copy(data, &mut file).map_err(error)?;
+                file.sync_all().map_err(error)?;
+            }
+            None => remove(&data_path)?,
+        }
+
+        let state_path = self.path(id, STATE_EXTENSION);
+        let partial_path = self.path(id, "partial");
+        let error = |err| SuspendError::Io(partial_path.clone(), err);
+        let state = serde_json::to_vec(transaction).map_err(|err| error(err.into()))?;
+        let mut file = File::create(&partial_path).map_err(error)?;
+        file.write_all(&state).map_err(error)?;
+        file.sync_all().map_err(error)?;
+        fs::rename(&partial_path, &state_path).map_err(|err| SuspendError::Io(state_path, err))
+    }
+
+    /// Every saved transaction along with its file data, if any.
+    ///
+    /// Files which cannot be parsed are reported and left in place.
+    pub(crate) fn load(&self) -> Result<Vec<(SavedTransaction, Option<File>)>, SuspendError> {
+        let error = |err| SuspendError::Io(self.dir.clone(), err);
+        let mut saved = vec![];
+        for entry in self.dir.read_dir_utf8().map_err(error)? {
+            let path = entry.map_err(error)?.into_path();
+            if path.extension() != Some(STATE_EXTENSION) {
+                continue;
+            }
+            let state = fs::read(&path).map_err(|err| SuspendError::Io(path.clone(), err))?;
+            let transaction: SavedTransaction = match serde_json::from_slice(&state) {
+                Ok(transaction) => transaction,
+                Err(err) => {
+                    warn!("Skipping suspended transaction {path}: {err}");
+                    continue;
+                }
+            };
+            let data_path = self.path(transaction.id(), DATA_EXTENSION);
+            let data = match File::open(&data_path) {
+                Ok(file) => Some(file),
+                Err(err) if err.kind() == ErrorKind::NotFound => None,
+                Err(err) => return Err(SuspendError::Io(data_path, err)),
+            };
+            saved.push((transaction, data));
+        }
+        Ok(saved)
+    }
+
+    /// Forget the transaction once it has been restored.
+    pub(crate) fn remove(&self, id: TransactionID) -> Result<(), SuspendError> {
+        remove(&self.path(id, STATE_EXTENSION))?;
+        remove(&self.path(id, DATA_EXTENSION))
+    }
+}
+
+fn remove(path: &Utf8Path) -> Result<(), SuspendError> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => {
+            Err(SuspendError::Io(path.to_owned(), err))
+        }
+        _ => Ok(()),
+    }
+}
@@ -23,7 +23,7 @@ impl Counter {
     /// start the timer (if it was paused), setting the start_time
     /// clear the occurred flag
     /// the counter value is not reset
-    fn restart(&mut self) {
+    pub fn restart(&mut self) {
         self.update();
         self.start_time = Instant::now();
         self.paused = false;
@@ -97,6 +97,8 @@ pub struct Timer {
     pub inactivity: Counter,
     pub ack: Counter,
     pub nak: Counter,
+    // which of the (inactivity, ack, nak) counters were running when the timer was frozen.
+    frozen: Option<[bool; 3]>,
 }
 impl Timer {
     pub fn new(
@@ -114,31 +116,133 @@ impl Timer {
             ),
             ack: Counter::new(Duration::from_secs(ack_timeout as u64), ack_max_count),
             nak: Counter::new(Duration::from_secs(nak_timeout as u64), nak_max_count),
+            frozen: None,
+        }
+    }
+
+    /// Pause every running counter, remembering which ones were running.
+    /// Used while the link to the remote entity is unavailable.
+    pub fn freeze(&mut self) {
+        if self.frozen.is_some() {
+            return;
+        }
+        let running = [!self.inactivity.paused, !self.ack.paused, !self.nak.paused];
+        self.inactivity.pause();
+        self.ack.pause();
+        self.nak.pause();
+        self.frozen = Some(running);
+    }
+
+    /// Restart the counters which were running before [freeze](Self::freeze) was called.
+    /// Each counter begins a full timeout interval while keeping its count.
+    pub fn thaw(&mut self) {
+        if let Some([inactivity, ack, nak]) = self.frozen.take() {
+            if inactivity {
+                self.inactivity.restart();
+            }
+            if ack {
+                self.ack.restart();
+            }
+            if nak {
+                self.nak.restart();
+            }
+        }
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.is_some()
+    }
+
+    /// Start a counter, while frozen it is only marked to start on [thaw](Self::thaw).
+    fn start_counter(counter: &mut Counter, frozen: Option<&mut bool>, reset: bool) {
+        match frozen {
+            Some(running) => {
+                *running = true;
+                counter.occurred = false;
+                if reset {
+                    counter.count = 0;
+                }
+            }
+            None if reset => counter.reset(),
+            None => counter.restart(),
         }
     }
 
     pub fn restart_inactivity(&mut self) {
-        self.inactivity.restart()
+        Self::start_counter(
+            &mut self.inactivity,
+            self.frozen.as_mut().map(|frozen| &mut frozen[0]),
+            false,
+        )
     }
 
     pub fn reset_inactivity(&mut self) {
-        self.inactivity.reset()
+        Self::start_counter(
+            &mut self.inactivity,
+            self.frozen.as_mut().map(|frozen| &mut frozen[0]),
+            true,
+        )
     }
 
     pub fn restart_ack(&mut self) {
-        self.ack.restart()
+        Self::start_counter(
+            &mut self.ack,
+            self.frozen.as_mut().map(|frozen| &mut frozen[1]),
+            false,
+        )
     }
 
     pub fn reset_ack(&mut self) {
-        self.ack.reset()
+        Self::start_counter(
+            &mut self.ack,
+            self.frozen.as_mut().map(|frozen| &mut frozen[1]),
+            true,
+        )
     }
 
     pub fn restart_nak(&mut self) {
-        self.nak.restart()
+        Self::start_counter(
+            &mut self.nak,
+            self.frozen.as_mut().map(|frozen| &mut frozen[2]),
+            false,
+        )
     }
 
     pub fn reset_nak(&mut self) {
-        self.nak.reset()
+        Self::start_counter(
+            &mut self.nak,
+            self.frozen.as_mut().map(|frozen| &mut frozen[2]),
+            true,
+        )
+    }
+
+    /// Pause a counter, while frozen it is also kept from starting on [thaw](Self::thaw).
+    fn pause_counter(counter: &mut Counter, frozen: Option<&mut bool>) {
+        counter.pause();
+        if let Some(running) = frozen {
+            *running = false;
+        }
+    }
+
+    pub fn pause_inactivity(&mut self) {
+        Self::pause_counter(
+            &mut self.inactivity,
+            self.frozen.as_mut().map(|frozen| &mut frozen[0]),
+        )
+    }
+
+    pub fn pause_ack(&mut self) {
+        Self::pause_counter(
+            &mut self.ack,
+            self.frozen.as_mut().map(|frozen| &mut frozen[1]),
+        )
+    }
+
+    pub fn pause_nak(&mut self) {
+        Self::pause_counter(
+            &mut self.nak,
+            self.frozen.as_mut().map(|frozen| &mut frozen[2]),
+        )
     }
     /// returns the duration until one of the timers timeouts
     /// if all timers are paused, returns Duration::MAX
@@ -166,6 +270,60 @@ mod test {
 
     use std::{thread, time::Duration};
 
+    #[test]
+    fn freeze_thaw() {
+        let mut timer = Timer::new(1_i64, 5, 1_i64, 5, 1_i64, 5);
+        timer.restart_inactivity();
+        timer.freeze();
+        assert!(timer.is_frozen());
+        assert_eq!(Duration::MAX, timer.until_timeout());
+
+        thread::sleep(Duration::from_secs_f32(1.2_f32));
+        assert!(!timer.inactivity.timeout_occurred());
+
+        timer.thaw();
+        assert!(!timer.is_frozen());
+        assert!(timer.inactivity.is_ticking());
+        // only counters running before the freeze are restarted
+        assert!(!timer.ack.is_ticking());
+        assert!(!timer.nak.is_ticking());
+        assert!(timer.until_timeout() > Duration::from_millis(900));
+    }
+
+    #[test]
+    fn restart_while_frozen() {
+        let mut timer = Timer::new(1_i64, 5, 1_i64, 5, 1_i64, 5);
+        timer.freeze();
+        timer.restart_ack();
+        timer.reset_nak();
+        assert!(!timer.ack.is_ticking());
+        assert!(!timer.nak.is_ticking());
+        assert_eq!(Duration::MAX, timer.until_timeout());
+
+        // counters started while frozen run once thawed
+        timer.thaw();
+        assert!(timer.ack.is_ticking());
+        assert!(timer.nak.is_ticking());
+        assert!(!timer.inactivity.is_ticking());
+    }
+
+    #[test]
+    fn pause_while_frozen() {
+        let mut timer = Timer::new(1_i64, 5, 1_i64, 5, 1_i64, 5);
+        timer.restart_inactivity();
+        timer.restart_ack();
+        timer.freeze();
+        timer.pause_ack();
+        timer.restart_nak();
+        timer.pause_nak();
+
+        // counters paused while frozen stay paused once thawed
+        timer.thaw();
+        assert!(timer.inactivity.is_ticking());
+        assert!(!timer.ack.is_ticking());
+        assert!(!timer.nak.is_ticking());
+    }
+
     #[test]
     fn timeout() {
         let mut timer = Timer::new(1_i64, 5, 1_i64, 5, 1_i64, 5);
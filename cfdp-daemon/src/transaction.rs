@@ -11,10 +11,12 @@ pub type TransactionResult<T> = Result<T, TransactionError>;
 
 #[cfg(test)]
 pub(crate) mod test {
-    use std::collections::HashMap;
+    use std::collections::BTreeMap;
 
     use cfdp_core::{
-        pdu::{CRCFlag, FileSizeFlag, SegmentedData, TransmissionMode, VariableID},
+        pdu::{
+            CRCFlag, FileSizeFlag, ProtocolVersion, SegmentedData, TransmissionMode, VariableID,
+        },
         transaction::TransactionConfig,
     };
 
@@ -29,14 +31,16 @@ pub(crate) mod test {
             transmission_mode: TransmissionMode::Acknowledged,
             sequence_number: VariableID::from(3_u16),
             file_size_flag: FileSizeFlag::Small,
-            fault_handler_override: HashMap::new(),
+            fault_handler_override: BTreeMap::new(),
             file_size_segment: 16630_u16,
+            max_pdu_len: None,
             crc_flag: CRCFlag::NotPresent,
             segment_metadata_flag: SegmentedData::NotPresent,
             max_count: 5_u32,
             inactivity_timeout: 300_i64,
             ack_timeout: 300_i64,
             nak_timeout: 300_i64,
+            protocol_version: ProtocolVersion::Current,
         }
     }
 }
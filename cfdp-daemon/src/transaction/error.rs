@@ -11,6 +11,8 @@ use crate::cfdp_core::{
 
 use cfdp_core::transaction::TransactionID;
 
+use crate::error::SuspendError;
+
 #[derive(Error, Debug)]
 pub enum TransactionError {
     #[error("FileStore error during Transaction: {0}")]
@@ -48,6 +50,9 @@ pub enum TransactionError {
 
     #[error("No Transaction status for code {0}.")]
     InvalidStatus(u8),
+
+    #[error("Unable to save suspended transaction. {0}")]
+    Suspend(#[from] SuspendError),
 }
 impl From<FileStoreError> for TransactionError {
     fn from(error: FileStoreError) -> Self {
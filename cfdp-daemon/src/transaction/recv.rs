@@ -3,39 +3,40 @@ use std::{
     fs::File,
     io::{self, Seek, SeekFrom, Write},
     sync::Arc,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
 use camino::Utf8PathBuf;
 use log::{debug, warn};
-use tokio::sync::{
-    mpsc::{Permit, Sender},
-    oneshot,
-};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc::Permit, oneshot};
 
 use cfdp_core::{
     daemon::{
         FaultIndication, FileSegmentIndication, FinishedIndication, Indication,
-        MetadataRecvIndication, NakProcedure, Report, ResumeIndication, SuspendIndication,
+        MetadataRecvIndication, NakProcedure, PrimitiveResult, Report, ResumeIndication,
+        SuspendIndication,
     },
     filestore::{FileChecksum, FileStore, FileStoreError},
     pdu::{
         ACKSubDirective, Condition, DeliveryCode, Direction, FaultHandlerAction, FileDataPDU,
         FileStatusCode, FileStoreResponse, Finished, KeepAlivePDU, MetadataTLV, NakOrKeepAlive,
-        NegativeAcknowledgmentPDU, Operations, PDUDirective, PDUHeader, PDUPayload, PDUType,
-        PositiveAcknowledgePDU, PromptPDU, SegmentRequestForm, SegmentationControl,
-        TransactionStatus, TransmissionMode, VariableID, PDU, U3,
+        NegativeAcknowledgmentPDU, Operations, PDUDirective, PDUEncode, PDUHeader, PDUPayload,
+        PDUType, PositiveAcknowledgePDU, PromptPDU, SegmentRequestForm, SegmentationControl,
+        TransactionStatus, TransmissionMode, VariableID, PDU,
     },
+    segments::Segments,
     transaction::{Metadata, TransactionConfig, TransactionID, TransactionState},
 };
 
 use crate::{
-    segments::Segments,
+    history::TransactionRecord,
+    indication::IndicationSender,
     timer::{Counter, Timer},
     transaction::{TransactionError, TransactionResult},
 };
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
 enum RecvState {
     // initial state
     // received data, missing data and EOF
@@ -54,7 +55,7 @@ pub struct RecvTransaction<T: FileStore> {
     /// The [FileStore] implementation used to interact with files on disk.
     filestore: Arc<T>,
     /// Channel for Indications to propagate back up
-    indication_tx: Sender<Indication>,
+    indication_tx: IndicationSender,
     /// The current file being worked by this Transaction.
     file_handle: Option<File>,
     /// A sorted list of contiguous (start offset, end offset) non overlapping received segments to monitor progress and detect NAKs.
@@ -107,6 +108,41 @@ pub struct RecvTransaction<T: FileStore> {
     /// It is also used in case the NAK procedure is Deferred(delta) with delta>0
     /// In that case the start, stop offsets are the whole file
     delayed_nack_timers: Vec<(Counter, u64, u64)>,
+    /// Number of bytes handed to the transport since the last call to
+    /// [take_bytes_sent](Self::take_bytes_sent). Used to pace outgoing PDUs.
+    bytes_sent: u64,
+    /// When the first PDU of this Transaction was received.
+    started: SystemTime,
+}
+
+#[derive(Serialize, Deserialize)]
+/// The state of a suspended [RecvTransaction] kept while no Daemon runs it.
+///
+/// The file data received so far is kept alongside,
+/// PDUs waiting to be sent and the timers are prepared again once the Transaction resumes.
+pub(crate) struct SavedRecv {
+    status: TransactionStatus,
+    config: TransactionConfig,
+    nak_procedure: NakProcedure,
+    metadata: Option<Metadata>,
+    saved_segments: Segments,
+    received_file_size: u64,
+    condition: Condition,
+    delivery_code: DeliveryCode,
+    file_status: FileStatusCode,
+    filestore_response: Vec<FileStoreResponse>,
+    checksum: Option<u32>,
+    recv_state: RecvState,
+    file_size: Option<u64>,
+    finished: Option<(Finished, bool)>,
+    naks: VecDeque<SegmentRequestForm>,
+    nak_received_file_size: u64,
+    started: SystemTime,
+}
+impl SavedRecv {
+    pub(crate) fn id(&self) -> TransactionID {
+        TransactionID(self.config.source_entity_id, self.config.sequence_number)
+    }
 }
 
 impl<T: FileStore> RecvTransaction<T> {
@@ -122,7 +158,7 @@ impl<T: FileStore> RecvTransaction<T> {
         // Connection to the local FileStore implementation.
         filestore: Arc<T>,
         // Sender channel used to propagate Message To User back up to the Daemon Thread.
-        indication_tx: Sender<Indication>,
+        indication_tx: IndicationSender,
     ) -> Self {
         let received_file_size = 0_u64;
         let timer = Timer::new(
@@ -160,11 +196,136 @@ impl<T: FileStore> RecvTransaction<T> {
             naks: VecDeque::new(),
             nak_received_file_size: received_file_size,
             delayed_nack_timers: Vec::new(),
+            bytes_sent: 0,
+            started: SystemTime::now(),
         };
         transaction.timer.restart_inactivity();
         transaction
     }
 
+    /// Rebuild a suspended Transaction from the state [saved](Self::save) by a previous Daemon,
+    /// along with the file data received before it was saved.
+    /// Timers stay stopped until the Transaction is resumed.
+    pub(crate) fn restore(
+        saved: SavedRecv,
+        data: Option<File>,
+        filestore: Arc<T>,
+        indication_tx: IndicationSender,
+    ) -> TransactionResult<Self> {
+        let timer = Timer::new(
+            saved.config.inactivity_timeout,
+            saved.config.max_count,
+            saved.config.ack_timeout,
+            saved.config.max_count,
+            saved.config.nak_timeout,
+            saved.config.max_count,
+        );
+
+        let file_handle = match data {
+            Some(mut data) => {
+                let mut handle = filestore.open_tempfile()?;
+                io::copy(&mut data, &mut handle).map_err(FileStoreError::IO)?;
+                Some(handle)
+            }
+            None => None,
+        };
+        Ok(Self {
+            status: saved.status,
+            config: saved.config,
+            filestore,
+            indication_tx,
+            file_handle,
+            saved_segments: saved.saved_segments,
+            nak_procedure: saved.nak_procedure,
+            metadata: saved.metadata,
+            received_file_size: saved.received_file_size,
+            header: None,
+            condition: saved.condition,
+            delivery_code: saved.delivery_code,
+            file_status: saved.file_status,
+            filestore_response: saved.filestore_response,
+            timer,
+            checksum: saved.checksum,
+            state: TransactionState::Suspended,
+            recv_state: saved.recv_state,
+            ack: None,
+            finished: saved.finished,
+            file_size: saved.file_size,
+            prompt: None,
+            naks: saved.naks,
+            nak_received_file_size: saved.nak_received_file_size,
+            delayed_nack_timers: Vec::new(),
+            bytes_sent: 0,
+            started: saved.started,
+        })
+    }
+
+    /// The state needed to [restore](Self::restore) this Transaction in another Daemon,
+    /// along with the file data received so far if any.
+    pub(crate) fn save(&mut self) -> TransactionResult<(SavedRecv, Option<&mut File>)> {
+        let saved = SavedRecv {
+            status: self.status,
+            config: self.config.clone(),
+            nak_procedure: self.nak_procedure,
+            metadata: self.metadata.clone(),
+            saved_segments: self.saved_segments.clone(),
+            received_file_size: self.received_file_size,
+            condition: self.condition,
+            delivery_code: self.delivery_code,
+            file_status: self.file_status,
+            filestore_response: self.filestore_response.clone(),
+            checksum: self.checksum,
+            recv_state: self.recv_state,
+            file_size: self.file_size,
+            finished: self.finished.clone(),
+            naks: self.naks.clone(),
+            nak_received_file_size: self.nak_received_file_size,
+            started: self.started,
+        };
+        let data = match self.file_handle.as_mut() {
+            Some(handle) => {
+                handle.rewind().map_err(FileStoreError::IO)?;
+                Some(handle)
+            }
+            None => None,
+        };
+        Ok((saved, data))
+    }
+
+    /// Freeze all timers while the link to the remote entity is unavailable.
+    pub(crate) fn freeze(&mut self) {
+        self.timer.freeze();
+        self.delayed_nack_timers
+            .iter_mut()
+            .for_each(|(counter, _, _)| counter.pause());
+    }
+
+    /// Restart any timers stopped by [freeze](Self::freeze).
+    /// Delayed NAKs wait out their full delay again rather than all firing at once.
+    pub(crate) fn thaw(&mut self) {
+        if self.timer.is_frozen() {
+            self.delayed_nack_timers
+                .iter_mut()
+                .for_each(|(counter, _, _)| counter.restart());
+        }
+        self.timer.thaw();
+    }
+
+    /// Check whether the gap from `start` to `end` persists once the delay has passed.
+    /// While frozen the delay only starts on [thaw](Self::thaw).
+    fn delay_nak(&mut self, delay: Duration, start: u64, end: u64) {
+        let mut counter = Counter::new(delay, 1);
+        if !self.timer.is_frozen() {
+            counter.start();
+        }
+        self.delayed_nack_timers.push((counter, start, end));
+    }
+
+    /// Returns the number of bytes sent since the previous call and resets the count.
+    pub(crate) fn take_bytes_sent(&mut self) -> u64 {
+        std::mem::take(&mut self.bytes_sent)
+    }
+
     pub(crate) fn has_pdu_to_send(&self) -> bool {
         match self.recv_state {
             RecvState::ReceiveData => {
@@ -180,6 +341,10 @@ impl<T: FileStore> RecvTransaction<T> {
 
     // returns the time until the first timeout
     pub(crate) fn until_timeout(&self) -> Duration {
+        // delayed NAKs are held along with everything else while frozen.
+        if self.timer.is_frozen() {
+            return Duration::MAX;
+        }
         let d = self.timer.until_timeout();
         // we only check the first delayed nack timer (if any)
         // because they are ordered chronological
@@ -311,6 +476,7 @@ impl<T: FileStore> RecvTransaction<T> {
                     let destination = header.source_entity_id;
 
                     let pdu = PDU { header, payload };
+                    self.bytes_sent += u64::from(pdu.encoded_len());
                     permit.send((destination, pdu));
                 }
             }
@@ -347,7 +513,7 @@ impl<T: FileStore> RecvTransaction<T> {
         self.state
     }
 
-    fn generate_report(&self) -> Report {
+    pub(crate) fn generate_report(&self) -> Report {
         Report {
             id: self.id(),
             state: self.get_state(),
@@ -356,10 +522,42 @@ impl<T: FileStore> RecvTransaction<T> {
         }
     }
 
-    pub fn send_report(&self, sender: Option<oneshot::Sender<Report>>) -> TransactionResult<()> {
+    /// The record of this Transaction kept once it has finished.
+    pub(crate) fn generate_record(&self) -> TransactionRecord {
+        let (source_filename, destination_filename) = match self.metadata.as_ref() {
+            Some(metadata) => (
+                metadata.source_filename.clone(),
+                metadata.destination_filename.clone(),
+            ),
+            None => Default::default(),
+        };
+        TransactionRecord {
+            report: self.generate_report(),
+            remote_entity_id: self.config.source_entity_id,
+            transmission_mode: self.config.transmission_mode,
+            source_filename,
+            destination_filename,
+            file_size: self
+                .metadata
+                .as_ref()
+                .map(|metadata| metadata.file_size)
+                .or(self.file_size)
+                .unwrap_or(self.received_file_size),
+            file_status: self.file_status,
+            delivery_code: self.delivery_code,
+            filestore_responses: self.filestore_response.clone(),
+            started: self.started,
+            finished: SystemTime::now(),
+        }
+    }
+
+    pub fn send_report(
+        &self,
+        sender: Option<oneshot::Sender<PrimitiveResult<Report>>>,
+    ) -> TransactionResult<()> {
         let report = self.generate_report();
         if let Some(channel) = sender {
-            let _ = channel.send(report.clone());
+            let _ = channel.send(Ok(report.clone()));
         }
         self.send_indication(Indication::Report(report));
 
@@ -383,7 +581,7 @@ impl<T: FileStore> RecvTransaction<T> {
             }
         } else {
             let header = PDUHeader {
-                version: U3::One,
+                version: self.config.protocol_version.into(),
                 pdu_type,
                 direction,
                 transmission_mode: self.config.transmission_mode,
@@ -495,9 +693,9 @@ impl<T: FileStore> RecvTransaction<T> {
         debug!("Transaction {0} shutting down.", self.id());
 
         self.state = TransactionState::Terminated;
-        self.timer.ack.pause();
-        self.timer.nak.pause();
-        self.timer.inactivity.pause();
+        self.timer.pause_ack();
+        self.timer.pause_nak();
+        self.timer.pause_inactivity();
     }
 
     pub fn cancel(&mut self) -> TransactionResult<()> {
@@ -509,7 +707,7 @@ impl<T: FileStore> RecvTransaction<T> {
 
     fn _cancel(&mut self) {
         self.recv_state = RecvState::Cancelled;
-        self.timer.nak.pause();
+        self.timer.pause_nak();
 
         match &self.config.transmission_mode {
             TransmissionMode::Acknowledged => self.prepare_finished(None),
@@ -538,9 +736,9 @@ impl<T: FileStore> RecvTransaction<T> {
     }
 
     pub fn suspend(&mut self) -> TransactionResult<()> {
-        self.timer.ack.pause();
-        self.timer.nak.pause();
-        self.timer.inactivity.pause();
+        self.timer.pause_ack();
+        self.timer.pause_nak();
+        self.timer.pause_inactivity();
         self.state = TransactionState::Suspended;
 
         self.send_indication(Indication::Suspended(SuspendIndication {
@@ -631,6 +829,7 @@ impl<T: FileStore> RecvTransaction<T> {
 
             let destination = header.source_entity_id;
             let pdu = PDU { header, payload };
+            self.bytes_sent += u64::from(pdu.encoded_len());
             permit.send((destination, pdu));
         }
         Ok(())
@@ -665,23 +864,43 @@ impl<T: FileStore> RecvTransaction<T> {
         ));
     }
 
-    fn send_finished(&mut self, permit: Permit<(VariableID, PDU)>) -> TransactionResult<()> {
-        self.timer.restart_ack();
-        if let Some((finished, true)) = &self.finished {
+    /// The Finished PDU, less any filestore responses which would make it too long to send.
+    ///
+    /// The receiving user still learns of every response through the Finished indication
+    /// issued by this entity.
+    fn finished_pdu(&mut self, mut finished: Finished) -> PDU {
+        loop {
             let payload = PDUPayload::Directive(Operations::Finished(finished.clone()));
-
-            let payload_len = payload.encoded_len(self.config.file_size_flag);
-
             let header = self.get_header(
                 Direction::ToSender,
                 PDUType::FileDirective,
-                payload_len,
+                payload.encoded_len(self.config.file_size_flag),
                 SegmentationControl::NotPreserved,
             );
-
-            let destination = header.source_entity_id;
             let pdu = PDU { header, payload };
 
+            let fits = matches!(
+                pdu.checked_len(),
+                Ok(len) if self.config.max_pdu_len.is_none_or(|max_pdu_len| len <= max_pdu_len)
+            );
+            if fits || finished.filestore_response.pop().is_none() {
+                return pdu;
+            }
+            warn!(
+                "Transaction {}: dropping a filestore response which does not fit in the Finished PDU.",
+                self.id()
+            );
+        }
+    }
+
+    fn send_finished(&mut self, permit: Permit<(VariableID, PDU)>) -> TransactionResult<()> {
+        self.timer.restart_ack();
+        if let Some((finished, true)) = &self.finished {
+            let pdu = self.finished_pdu(finished.clone());
+            let destination = pdu.header.source_entity_id;
+
+            self.bytes_sent += u64::from(pdu.encoded_len());
+
             permit.send((destination, pdu));
             debug!("Transaction {0} sent Finished", self.id());
             self.set_finished_flag(false);
@@ -716,6 +935,29 @@ impl<T: FileStore> RecvTransaction<T> {
         naks
     }
 
+    /// The most segment requests sent in a single NAK.
+    ///
+    /// NAKs are limited to the file size segment and, when configured, the maximum PDU length.
+    /// At least one request is always sent so the transfer can make progress.
+    fn max_nak_num(&mut self) -> usize {
+        let mut payload_len = u32::from(self.config.file_size_segment);
+        if let Some(max_pdu_len) = self.config.max_pdu_len {
+            let header = self.get_header(
+                Direction::ToSender,
+                PDUType::FileDirective,
+                0,
+                SegmentationControl::NotPreserved,
+            );
+            // the directive code precedes the NAK.
+            let available = max_pdu_len.saturating_sub(
+                usize::from(header.encoded_len()) + 1 + usize::from(header.crc_flag.encoded_len()),
+            );
+            payload_len = payload_len.min(u32::try_from(available).unwrap_or(u32::MAX));
+        }
+        NegativeAcknowledgmentPDU::max_nak_num(self.config.file_size_flag, payload_len).max(1)
+            as usize
+    }
+
     fn send_naks(&mut self, permit: Permit<(VariableID, PDU)>) -> TransactionResult<()> {
         if self.nak_received_file_size == self.received_file_size {
             if self.timer.nak.limit_reached() && !self.handle_fault(Condition::NakLimitReached)? {
@@ -728,13 +970,7 @@ impl<T: FileStore> RecvTransaction<T> {
             self.nak_received_file_size = self.received_file_size;
         }
 
-        let n = usize::min(
-            self.naks.len(),
-            NegativeAcknowledgmentPDU::max_nak_num(
-                self.config.file_size_flag,
-                self.config.file_size_segment as u32,
-            ) as usize,
-        );
+        let n = usize::min(self.naks.len(), self.max_nak_num());
 
         let segment_requests: Vec<SegmentRequestForm> = self.naks.drain(..n).collect();
         let scope_start = segment_requests
@@ -765,6 +1001,8 @@ impl<T: FileStore> RecvTransaction<T> {
         let destination = header.source_entity_id;
         let pdu = PDU { header, payload };
 
+        self.bytes_sent += u64::from(pdu.encoded_len());
+
         permit.send((destination, pdu));
 
         Ok(())
@@ -873,9 +1111,7 @@ impl<T: FileStore> RecvTransaction<T> {
                                         });
                                     } else {
                                         // start a timer to check if the gap still persists after the delta delay
-                                        let mut timer = Counter::new(delay, 1);
-                                        timer.start();
-                                        self.delayed_nack_timers.push((timer, prev_end, offset));
+                                        self.delay_nak(delay, prev_end, offset);
                                     }
                                 }
                             }
@@ -912,13 +1148,7 @@ impl<T: FileStore> RecvTransaction<T> {
                                         } else {
                                             // we need to check/send the gaps only after this delta has expired
                                             // start a counter for that
-                                            let mut counter = Counter::new(delay, 1);
-                                            counter.start();
-                                            self.delayed_nack_timers.push((
-                                                counter,
-                                                0,
-                                                eof.file_size,
-                                            ));
+                                            self.delay_nak(delay, 0, eof.file_size);
                                         }
                                     }
                                 } else {
@@ -946,7 +1176,7 @@ impl<T: FileStore> RecvTransaction<T> {
                                         self.id(),
                                         ack.condition
                                     );
-                                    self.timer.ack.pause();
+                                    self.timer.pause_ack();
                                     self.shutdown();
                                     Ok(())
                                 } else {
@@ -1189,15 +1419,13 @@ impl<T: FileStore> RecvTransaction<T> {
             self.finalize_receive()?;
             self.recv_state = RecvState::Finished;
             self.prepare_finished(None);
-            self.timer.nak.pause();
+            self.timer.pause_nak();
         }
         Ok(())
     }
 
-    //send the indication in another task, no need to wait for it
     fn send_indication(&self, indication: Indication) {
-        let tx = self.indication_tx.clone();
-        tokio::task::spawn(async move { tx.send(indication).await });
+        self.indication_tx.send(indication)
     }
 }
 
@@ -1210,13 +1438,15 @@ mod test {
     use cfdp_core::{
         filestore::{ChecksumType, NativeFileStore},
         pdu::{
-            CRCFlag, EndOfFile, FileSizeFlag, FileStoreAction, FileStoreRequest, FileStoreStatus,
-            MessageToUser, MetadataPDU, PromptPDU, RenameStatus, SegmentedData,
-            UnsegmentedFileData,
+            CRCFlag, EndOfFile, EntityID, FileSizeFlag, FileStoreAction, FileStoreRequest,
+            FileStoreStatus, MessageToUser, MetadataPDU, PromptPDU, RenameStatus, SegmentedData,
+            UnsegmentedFileData, U3,
         },
     };
 
     use super::*;
+    use crate::indication::{IndicationBroadcast, IndicationFilter};
+    use crate::suspend::{SavedTransaction, SuspendStore};
     use crate::transaction::test::default_config;
 
     use camino::{Utf8Path, Utf8PathBuf};
@@ -1232,7 +1462,7 @@ mod test {
 
     #[rstest]
     fn header(default_config: &TransactionConfig, tempdir_fixture: &TempDir) {
-        let (indication_tx, _) = channel(1);
+        let indication_tx = IndicationBroadcast::new(1).sender(EntityID::from(0_u16));
         let config = default_config.clone();
         let filestore = Arc::new(NativeFileStore::new(
             Utf8Path::from_path(tempdir_fixture.path()).expect("Unable to make utf8 tempdir"),
@@ -1272,7 +1502,7 @@ mod test {
 
     #[rstest]
     fn test_if_file_transfer(default_config: &TransactionConfig, tempdir_fixture: &TempDir) {
-        let (indication_tx, _) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let config = default_config.clone();
         let filestore = Arc::new(NativeFileStore::new(
             Utf8Path::from_path(tempdir_fixture.path()).expect("Unable to make utf8 tempdir"),
@@ -1284,10 +1514,7 @@ mod test {
             filestore,
             indication_tx,
         );
-        assert_eq!(
-            TransactionStatus::Undefined,
-            transaction.get_status().clone()
-        );
+        assert_eq!(TransactionStatus::Active, transaction.get_status().clone());
         let mut path = Utf8PathBuf::new();
         path.push("a");
 
@@ -1306,7 +1533,7 @@ mod test {
 
     #[rstest]
     fn store_filedata(default_config: &TransactionConfig, tempdir_fixture: &TempDir) {
-        let (indication_tx, _) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let config = default_config.clone();
         let filestore = Arc::new(NativeFileStore::new(
             Utf8Path::from_path(tempdir_fixture.path()).expect("Unable to make utf8 tempdir"),
@@ -1341,7 +1568,7 @@ mod test {
 
     #[rstest]
     fn finalize_file(default_config: &TransactionConfig, tempdir_fixture: &TempDir) {
-        let (indication_tx, _) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let config = default_config.clone();
         let filestore = Arc::new(NativeFileStore::new(
             Utf8Path::from_path(tempdir_fixture.path()).expect("Unable to make utf8 tempdir"),
@@ -1398,6 +1625,61 @@ mod test {
         assert_eq!(input, out_string)
     }
 
+    #[rstest]
+    fn save_restore(default_config: &TransactionConfig, tempdir_fixture: &TempDir) {
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
+        let config = default_config.clone();
+        let filestore = Arc::new(NativeFileStore::new(
+            Utf8Path::from_path(tempdir_fixture.path()).expect("Unable to make utf8 tempdir"),
+        ));
+        let store_dir = TempDir::new().unwrap();
+        let store = SuspendStore::open(Utf8Path::from_path(store_dir.path()).unwrap()).unwrap();
+
+        let mut transaction = RecvTransaction::new(
+            config,
+            NakProcedure::Deferred(Duration::ZERO),
+            filestore.clone(),
+            indication_tx.clone(),
+        );
+        for offset in [0, 10] {
+            transaction
+                .store_file_data(FileDataPDU::Unsegmented(UnsegmentedFileData {
+                    offset,
+                    file_data: vec![offset as u8 + 1; 4],
+                }))
+                .expect("Error saving file data");
+        }
+        transaction.suspend().unwrap();
+        let (saved, data) = transaction.save().unwrap();
+        store
+            .save(&SavedTransaction::Recv(saved), data)
+            .expect("Unable to save transaction.");
+        drop(transaction);
+
+        let (saved, data) = store.load().unwrap().pop().unwrap();
+        let SavedTransaction::Recv(saved) = saved else {
+            panic!("expected a receive transaction.")
+        };
+        let mut restored = RecvTransaction::restore(saved, data, filestore, indication_tx).unwrap();
+        store.remove(restored.id()).unwrap();
+        assert!(store.load().unwrap().is_empty());
+
+        assert_eq!(TransactionState::Suspended, restored.get_state());
+        assert_eq!(8, restored.received_file_size);
+        assert_eq!(Some(14), restored.saved_segments.end());
+        // the metadata is still missing along with the gap between the segments.
+        assert_eq!(
+            VecDeque::<SegmentRequestForm>::from([(0_u64, 0_u64).into(), (4_u64, 10_u64).into()]),
+            restored.get_all_naks()
+        );
+
+        let handle = restored.get_handle().unwrap();
+        handle.rewind().unwrap();
+        let mut buff = vec![];
+        handle.read_to_end(&mut buff).unwrap();
+        assert_eq!(vec![1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 11, 11, 11, 11], buff);
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_naks(
@@ -1406,7 +1688,7 @@ mod test {
         #[values(FileSizeFlag::Small, FileSizeFlag::Large)] file_size_flag: FileSizeFlag,
     ) {
         let (transport_tx, mut transport_rx) = channel(10);
-        let (indication_tx, _) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let mut config = default_config.clone();
         config.file_size_flag = file_size_flag;
         let file_size = match &file_size_flag {
@@ -1485,7 +1767,7 @@ mod test {
         transmission_mode: TransmissionMode,
     ) {
         let (transport_tx, mut transport_rx) = channel(1);
-        let (indication_tx, _) = channel(1);
+        let indication_tx = IndicationBroadcast::new(1).sender(EntityID::from(0_u16));
         let mut config = default_config.clone();
         config.transmission_mode = transmission_mode;
 
@@ -1555,7 +1837,7 @@ mod test {
     #[rstest]
     #[tokio::test]
     async fn suspend(default_config: &TransactionConfig, tempdir_fixture: &TempDir) {
-        let (indication_tx, _indication_rx) = channel(1);
+        let indication_tx = IndicationBroadcast::new(1).sender(EntityID::from(0_u16));
         let config = default_config.clone();
 
         let filestore = Arc::new(NativeFileStore::new(
@@ -1594,10 +1876,77 @@ mod test {
         assert_eq!(TransactionState::Suspended, transaction.state)
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn suspend_frozen(default_config: &TransactionConfig, tempdir_fixture: &TempDir) {
+        let indication_tx = IndicationBroadcast::new(1).sender(EntityID::from(0_u16));
+        let config = default_config.clone();
+
+        let filestore = Arc::new(NativeFileStore::new(
+            Utf8Path::from_path(tempdir_fixture.path()).expect("Unable to make utf8 tempdir"),
+        ));
+        let mut transaction = RecvTransaction::new(
+            config,
+            NakProcedure::Deferred(Duration::ZERO),
+            filestore,
+            indication_tx,
+        );
+
+        transaction.timer.restart_ack();
+        transaction.timer.restart_nak();
+        transaction.freeze();
+        transaction.suspend().unwrap();
+
+        // a transaction suspended during a contact gap does not time out once it ends.
+        transaction.thaw();
+        let timers = [
+            &transaction.timer.ack,
+            &transaction.timer.inactivity,
+            &transaction.timer.nak,
+        ];
+        timers.iter().for_each(|timer| assert!(!timer.is_ticking()));
+        assert_eq!(Duration::MAX, transaction.until_timeout());
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn frozen_delayed_nak(default_config: &TransactionConfig, tempdir_fixture: &TempDir) {
+        let indication_tx = IndicationBroadcast::new(1).sender(EntityID::from(0_u16));
+        let config = default_config.clone();
+
+        let filestore = Arc::new(NativeFileStore::new(
+            Utf8Path::from_path(tempdir_fixture.path()).expect("Unable to make utf8 tempdir"),
+        ));
+        let delay = Duration::from_millis(200);
+        let mut transaction = RecvTransaction::new(
+            config,
+            NakProcedure::Deferred(delay),
+            filestore,
+            indication_tx,
+        );
+
+        transaction.delay_nak(delay, 0, 10);
+        transaction.freeze();
+        transaction.delay_nak(delay, 10, 20);
+        tokio::time::sleep(delay * 2).await;
+        assert!(transaction
+            .delayed_nack_timers
+            .iter_mut()
+            .all(|(counter, _, _)| !counter.timeout_occurred()));
+
+        // the delayed NAKs wait out their delay once the gap ends instead of firing at once.
+        transaction.thaw();
+        assert!(transaction.until_timeout() > delay / 2);
+        assert!(transaction
+            .delayed_nack_timers
+            .iter()
+            .all(|(counter, _, _)| counter.is_ticking()));
+    }
+
     #[rstest]
     #[tokio::test]
     async fn resume(default_config: &TransactionConfig, tempdir_fixture: &TempDir) {
-        let (indication_tx, _indication_rx) = channel(1);
+        let indication_tx = IndicationBroadcast::new(1).sender(EntityID::from(0_u16));
         let config = default_config.clone();
 
         let filestore = Arc::new(NativeFileStore::new(
@@ -1655,7 +2004,7 @@ mod test {
     #[tokio::test]
     async fn send_ack_eof(default_config: &TransactionConfig, tempdir_fixture: &TempDir) {
         let (transport_tx, mut transport_rx) = channel(10);
-        let (indication_tx, _) = channel(1);
+        let indication_tx = IndicationBroadcast::new(1).sender(EntityID::from(0_u16));
         let config = default_config.clone();
 
         let filestore = Arc::new(NativeFileStore::new(
@@ -1720,7 +2069,7 @@ mod test {
     #[rstest]
     #[tokio::test]
     async fn finalize_receive(default_config: &TransactionConfig, tempdir_fixture: &TempDir) {
-        let (indication_tx, _indication_rx) = channel(1);
+        let indication_tx = IndicationBroadcast::new(1).sender(EntityID::from(0_u16));
         let config = default_config.clone();
 
         let filestore = Arc::new(NativeFileStore::new(
@@ -1777,6 +2126,47 @@ mod test {
         assert!(!filestore.get_native_path(path).exists());
     }
 
+    #[rstest]
+    fn finished_drops_oversize_responses(
+        default_config: &TransactionConfig,
+        tempdir_fixture: &TempDir,
+    ) {
+        let indication_tx = IndicationBroadcast::new(1).sender(EntityID::from(0_u16));
+        let mut config = default_config.clone();
+        // 10 byte header, directive code, status byte and a single 43 byte response.
+        config.max_pdu_len = Some(60);
+
+        let filestore = Arc::new(NativeFileStore::new(
+            Utf8Path::from_path(tempdir_fixture.path()).expect("Unable to make utf8 tempdir"),
+        ));
+        let mut transaction = RecvTransaction::new(
+            config,
+            NakProcedure::Deferred(Duration::ZERO),
+            filestore,
+            indication_tx,
+        );
+
+        let response = FileStoreResponse {
+            action_and_status: FileStoreStatus::RenameFile(RenameStatus::NewFilenameAlreadyExists),
+            first_filename: "/path/to/a/file".into(),
+            second_filename: "/path/to/a/new/file".into(),
+            filestore_message: vec![1_u8, 3, 58],
+        };
+        let pdu = transaction.finished_pdu(Finished {
+            condition: Condition::NoError,
+            delivery_code: DeliveryCode::Complete,
+            file_status: FileStatusCode::Retained,
+            filestore_response: vec![response.clone(); 3],
+            fault_location: None,
+        });
+        assert_eq!(55, pdu.checked_len().unwrap());
+
+        let PDUPayload::Directive(Operations::Finished(finished)) = pdu.payload else {
+            panic!("expected Finished, got {:?}", pdu.payload)
+        };
+        assert_eq!(vec![response], finished.filestore_response);
+    }
+
     #[rstest]
     fn pdu_error_unack(
         default_config: &TransactionConfig,
@@ -1823,7 +2213,7 @@ mod test {
         )]
         operation: Operations,
     ) {
-        let (indication_tx, _) = channel(1);
+        let indication_tx = IndicationBroadcast::new(1).sender(EntityID::from(0_u16));
         let mut config = default_config.clone();
         config.transmission_mode = TransmissionMode::Unacknowledged;
 
@@ -1913,7 +2303,7 @@ mod test {
         )]
         operation: Operations,
     ) {
-        let (indication_tx, _) = channel(1);
+        let indication_tx = IndicationBroadcast::new(1).sender(EntityID::from(0_u16));
         let mut config = default_config.clone();
         config.transmission_mode = TransmissionMode::Acknowledged;
 
@@ -1974,7 +2364,7 @@ mod test {
         #[values(TransmissionMode::Unacknowledged, TransmissionMode::Acknowledged)]
         transmission_mode: TransmissionMode,
     ) {
-        let (indication_tx, _indication_rx) = channel(1);
+        let indication_tx = IndicationBroadcast::new(1).sender(EntityID::from(0_u16));
         let mut config = default_config.clone();
         config.transmission_mode = transmission_mode;
 
@@ -2042,7 +2432,9 @@ mod test {
         #[values(TransmissionMode::Unacknowledged, TransmissionMode::Acknowledged)]
         transmission_mode: TransmissionMode,
     ) {
-        let (indication_tx, mut indication_rx) = channel(1);
+        let indications = IndicationBroadcast::new(10);
+        let mut indication_rx = indications.subscribe(IndicationFilter::new());
+        let indication_tx = indications.sender(EntityID::from(0_u16));
         let mut config = default_config.clone();
         config.transmission_mode = transmission_mode;
 
@@ -2127,7 +2519,7 @@ mod test {
         transmission_mode: TransmissionMode,
     ) {
         let (transport_tx, mut transport_rx) = channel(10);
-        let (indication_tx, _indication_rx) = channel(1);
+        let indication_tx = IndicationBroadcast::new(1).sender(EntityID::from(0_u16));
         let mut config = default_config.clone();
         config.transmission_mode = transmission_mode;
 
@@ -2275,7 +2667,7 @@ mod test {
                     directive: PDUDirective::EoF,
                     directive_subtype_code: ACKSubDirective::Other,
                     condition: Condition::NoError,
-                    transaction_status: TransactionStatus::Undefined,
+                    transaction_status: TransactionStatus::Active,
                 }));
 
                 let payload_len = payload.encoded_len(expected_pdu.header.large_file_flag);
@@ -2308,7 +2700,7 @@ mod test {
         #[values(NakOrKeepAlive::Nak, NakOrKeepAlive::KeepAlive)] nak_or_keep_alive: NakOrKeepAlive,
     ) {
         let (transport_tx, mut transport_rx) = channel(10);
-        let (indication_tx, _indication_rx) = channel(1);
+        let indication_tx = IndicationBroadcast::new(1).sender(EntityID::from(0_u16));
         let mut config = default_config.clone();
         config.transmission_mode = TransmissionMode::Acknowledged;
 
@@ -2407,12 +2799,21 @@ mod test {
     }
 
     #[rstest]
+    #[case(16, None)]
+    // 10 byte header, directive code and room for a single segment request.
+    #[case(1024, Some(10 + 1 + 16))]
     #[tokio::test]
-    async fn nak_split(default_config: &TransactionConfig, tempdir_fixture: &TempDir) {
+    async fn nak_split(
+        default_config: &TransactionConfig,
+        tempdir_fixture: &TempDir,
+        #[case] file_size_segment: u16,
+        #[case] max_pdu_len: Option<usize>,
+    ) {
         let (transport_tx, mut transport_rx) = channel(10);
-        let (indication_tx, _indication_rx) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let mut config = default_config.clone();
-        config.file_size_segment = 16;
+        config.file_size_segment = file_size_segment;
+        config.max_pdu_len = max_pdu_len;
         config.transmission_mode = TransmissionMode::Acknowledged;
 
         let expected_id = config.source_entity_id;
@@ -2525,7 +2926,7 @@ mod test {
     #[tokio::test]
     async fn delayed_nak(default_config: &TransactionConfig, tempdir_fixture: &TempDir) {
         let (transport_tx, mut transport_rx) = channel(10);
-        let (indication_tx, _indication_rx) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let mut config = default_config.clone();
         config.file_size_segment = 32;
         config.transmission_mode = TransmissionMode::Acknowledged;
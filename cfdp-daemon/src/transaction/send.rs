@@ -3,37 +3,94 @@ use std::{
     fs::{File, OpenOptions},
     io::{Read, Seek, SeekFrom},
     sync::Arc,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
 use log::{debug, info};
-use tokio::sync::{
-    mpsc::{Permit, Sender},
-    oneshot,
-};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc::Permit, oneshot};
 
 use cfdp_core::{
     daemon::{
-        FaultIndication, FinishedIndication, Indication, Report, ResumeIndication,
+        FaultIndication, FinishedIndication, Indication, PrimitiveResult, Report, ResumeIndication,
         SuspendIndication,
     },
     filestore::{FileChecksum, FileStore, FileStoreError},
     pdu::{
-        ACKSubDirective, Condition, DeliveryCode, Direction, EndOfFile, FaultHandlerAction,
-        FileDataPDU, FileStatusCode, MetadataPDU, MetadataTLV, NakOrKeepAlive, Operations,
-        PDUDirective, PDUHeader, PDUPayload, PDUType, PositiveAcknowledgePDU, PromptPDU,
-        SegmentRequestForm, SegmentationControl, SegmentedData, TransactionStatus,
-        TransmissionMode, UnsegmentedFileData, VariableID, PDU, U3,
+        ACKSubDirective, Condition, DeliveryCode, Direction, EndOfFile, EntityID,
+        FaultHandlerAction, FileDataPDU, FileStatusCode, FileStoreResponse, MetadataPDU,
+        MetadataTLV, NakOrKeepAlive, Operations, PDUDirective, PDUEncode, PDUHeader, PDUPayload,
+        PDUType, PositiveAcknowledgePDU, PromptPDU, ProtocolVersion, SegmentRequestForm,
+        SegmentationControl, SegmentedData, TransactionStatus, TransmissionMode,
+        UnsegmentedFileData, VariableID, PDU,
     },
     transaction::{Metadata, TransactionConfig, TransactionID, TransactionState},
 };
 
 use crate::{
+    history::TransactionRecord,
+    indication::IndicationSender,
     timer::Timer,
     transaction::{TransactionError, TransactionResult},
 };
 
-#[derive(PartialEq, Debug)]
+fn new_header(
+    config: &TransactionConfig,
+    direction: Direction,
+    pdu_type: PDUType,
+    pdu_data_field_length: u16,
+    segmentation_control: SegmentationControl,
+) -> PDUHeader {
+    PDUHeader {
+        version: config.protocol_version.into(),
+        pdu_type,
+        direction,
+        transmission_mode: config.transmission_mode,
+        crc_flag: config.crc_flag,
+        large_file_flag: config.file_size_flag,
+        pdu_data_field_length,
+        segmentation_control,
+        segment_metadata_flag: config.segment_metadata_flag,
+        source_entity_id: config.source_entity_id,
+        transaction_sequence_number: config.sequence_number,
+        destination_entity_id: config.destination_entity_id,
+    }
+}
+
+/// The Metadata PDU opening a transaction with the given configuration.
+pub(crate) fn metadata_pdu(config: &TransactionConfig, metadata: &Metadata) -> PDU {
+    let metadata = MetadataPDU {
+        closure_requested: metadata.closure_requested,
+        checksum_type: metadata.checksum_type,
+        file_size: metadata.file_size,
+        source_filename: metadata.source_filename.clone(),
+        destination_filename: metadata.destination_filename.clone(),
+        options: metadata
+            .filestore_requests
+            .iter()
+            .map(|fs| MetadataTLV::FileStoreRequest(fs.clone()))
+            .chain(
+                metadata
+                    .message_to_user
+                    .iter()
+                    .map(|msg| MetadataTLV::MessageToUser(msg.clone())),
+            )
+            .collect(),
+    };
+
+    let payload = PDUPayload::Directive(Operations::Metadata(metadata));
+    let header = new_header(
+        config,
+        Direction::ToReceiver,
+        PDUType::FileDirective,
+        payload.encoded_len(config.file_size_flag),
+        // TODO add semgentation Control ability
+        SegmentationControl::NotPreserved,
+    );
+    PDU { header, payload }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
 enum SendState {
     // initial state
     // send the metadata and then go to SendData (if file transfer) or directly to SendEof
@@ -73,6 +130,8 @@ pub struct SendTransaction<T: FileStore> {
     delivery_code: DeliveryCode,
     // Status of the current File
     file_status: FileStatusCode,
+    /// The responses to the filestore requests reported by the receiver.
+    filestore_responses: Vec<FileStoreResponse>,
     /// Timer used to track if the Nak limit has been reached
     /// inactivity has occurred
     /// or the ACK limit is reached
@@ -93,11 +152,54 @@ pub struct SendTransaction<T: FileStore> {
     /// A PromptPDU to be sent at the next available opportunity.
     prompt: Option<PromptPDU>,
     /// Channel for Indications to propagate back up
-    indication_tx: Sender<Indication>,
+    indication_tx: IndicationSender,
     /// flag to track if the initial EoFSent Indication has been sent.
     /// This indication only needs to be delivered for the initial EoF transmission
     send_eof_indication: bool,
+    /// Number of bytes handed to the transport since the last call to
+    /// [take_bytes_sent](Self::take_bytes_sent). Used to pace outgoing PDUs.
+    bytes_sent: u64,
+    /// When this Transaction was initiated.
+    started: SystemTime,
 }
+#[derive(Serialize, Deserialize)]
+/// The state of a suspended [SendTransaction] kept while no Daemon runs it.
+///
+/// PDUs waiting to be sent and the timers are not kept,
+/// they are prepared again once the Transaction resumes.
+pub(crate) struct SavedSend {
+    status: TransactionStatus,
+    config: TransactionConfig,
+    metadata: Metadata,
+    naks: VecDeque<SegmentRequestForm>,
+    sent_file_size: u64,
+    received_file_size: u64,
+    condition: Condition,
+    delivery_code: DeliveryCode,
+    file_status: FileStatusCode,
+    filestore_responses: Vec<FileStoreResponse>,
+    checksum: Option<u32>,
+    send_state: SendState,
+    eof: Option<(EndOfFile, bool)>,
+    send_eof_indication: bool,
+    // where the next file segment is read from, if the file was opened.
+    file_position: Option<u64>,
+    started: SystemTime,
+}
+impl SavedSend {
+    pub(crate) fn id(&self) -> TransactionID {
+        TransactionID(self.config.source_entity_id, self.config.sequence_number)
+    }
+
+    pub(crate) fn destination(&self) -> EntityID {
+        self.config.destination_entity_id
+    }
+
+    pub(crate) fn protocol_version(&self) -> ProtocolVersion {
+        self.config.protocol_version
+    }
+}
+
 impl<T: FileStore> SendTransaction<T> {
     /// Start a new SendTransaction with the given [configuration](TransactionConfig)
     /// and [Filestore Implementation](FileStore)
@@ -109,7 +211,7 @@ impl<T: FileStore> SendTransaction<T> {
         // Connection to the local FileStore implementation.
         filestore: Arc<T>,
         // Sender channel used to propagate Message To User back up to the Daemon Thread.
-        indication_tx: Sender<Indication>,
+        indication_tx: IndicationSender,
     ) -> TransactionResult<Self> {
         let timer = Timer::new(
             config.inactivity_timeout,
@@ -133,6 +235,7 @@ impl<T: FileStore> SendTransaction<T> {
             condition: Condition::NoError,
             delivery_code: DeliveryCode::Incomplete,
             file_status: FileStatusCode::Unreported,
+            filestore_responses: vec![],
             timer,
             checksum: None,
             send_state: SendState::SendMetadata,
@@ -142,11 +245,104 @@ impl<T: FileStore> SendTransaction<T> {
             prompt: None,
             indication_tx,
             send_eof_indication: true,
+            bytes_sent: 0,
+            started: SystemTime::now(),
         };
         me.send_indication(Indication::Transaction(me.id()));
         Ok(me)
     }
 
+    /// Rebuild a suspended Transaction from the state [saved](Self::save) by a previous Daemon.
+    /// Timers stay stopped until the Transaction is resumed.
+    pub(crate) fn restore(
+        saved: SavedSend,
+        filestore: Arc<T>,
+        indication_tx: IndicationSender,
+    ) -> TransactionResult<Self> {
+        let timer = Timer::new(
+            saved.config.inactivity_timeout,
+            saved.config.max_count,
+            saved.config.ack_timeout,
+            saved.config.max_count,
+            saved.config.nak_timeout,
+            saved.config.max_count,
+        );
+
+        let mut me = Self {
+            status: saved.status,
+            config: saved.config,
+            filestore,
+            file_handle: None,
+            naks: saved.naks,
+            metadata: saved.metadata,
+            sent_file_size: saved.sent_file_size,
+            received_file_size: saved.received_file_size,
+            header: None,
+            condition: saved.condition,
+            delivery_code: saved.delivery_code,
+            file_status: saved.file_status,
+            filestore_responses: saved.filestore_responses,
+            timer,
+            checksum: saved.checksum,
+            send_state: saved.send_state,
+            state: TransactionState::Suspended,
+            eof: saved.eof,
+            ack: None,
+            prompt: None,
+            indication_tx,
+            send_eof_indication: saved.send_eof_indication,
+            bytes_sent: 0,
+            started: saved.started,
+        };
+        if let Some(position) = saved.file_position {
+            me.get_handle()?
+                .seek(SeekFrom::Start(position))
+                .map_err(FileStoreError::IO)?;
+        }
+        Ok(me)
+    }
+
+    /// The state needed to [restore](Self::restore) this Transaction in another Daemon.
+    pub(crate) fn save(&mut self) -> TransactionResult<SavedSend> {
+        let file_position = match self.file_handle.as_mut() {
+            Some(handle) => Some(handle.stream_position().map_err(FileStoreError::IO)?),
+            None => None,
+        };
+        Ok(SavedSend {
+            status: self.status,
+            config: self.config.clone(),
+            metadata: self.metadata.clone(),
+            naks: self.naks.clone(),
+            sent_file_size: self.sent_file_size,
+            received_file_size: self.received_file_size,
+            condition: self.condition,
+            delivery_code: self.delivery_code,
+            file_status: self.file_status,
+            filestore_responses: self.filestore_responses.clone(),
+            checksum: self.checksum,
+            send_state: self.send_state,
+            eof: self.eof.clone(),
+            send_eof_indication: self.send_eof_indication,
+            file_position,
+            started: self.started,
+        })
+    }
+
+    /// Freeze all timers while the link to the remote entity is unavailable.
+    pub(crate) fn freeze(&mut self) {
+        self.timer.freeze();
+    }
+
+    /// Restart any timers stopped by [freeze](Self::freeze).
+    pub(crate) fn thaw(&mut self) {
+        self.timer.thaw();
+    }
+
+    /// Returns the number of bytes sent since the previous call and resets the count.
+    pub(crate) fn take_bytes_sent(&mut self) -> u64 {
+        std::mem::take(&mut self.bytes_sent)
+    }
+
     #[allow(clippy::unnecessary_map_or)]
     // we can revmoe this if update MSRV >= 1.70
     pub(crate) fn has_pdu_to_send(&self) -> bool {
@@ -163,6 +359,10 @@ impl<T: FileStore> SendTransaction<T> {
 
     // returns the time until the first timeout (inactivity or ack)
     pub(crate) fn until_timeout(&self) -> Duration {
+        // timers are held along with everything else while frozen.
+        if self.timer.is_frozen() {
+            return Duration::MAX;
+        }
         match self.send_state {
             SendState::SendEof | SendState::Cancelled => self.timer.until_timeout(),
             _ => Duration::MAX,
@@ -285,7 +485,7 @@ impl<T: FileStore> SendTransaction<T> {
     pub fn get_mode(&self) -> TransmissionMode {
         self.config.transmission_mode
     }
-    fn generate_report(&self) -> Report {
+    pub(crate) fn generate_report(&self) -> Report {
         Report {
             id: self.id(),
             state: self.get_state(),
@@ -294,11 +494,31 @@ impl<T: FileStore> SendTransaction<T> {
         }
     }
 
-    pub fn send_report(&self, sender: Option<oneshot::Sender<Report>>) -> TransactionResult<()> {
+    /// The record of this Transaction kept once it has finished.
+    pub(crate) fn generate_record(&self) -> TransactionRecord {
+        TransactionRecord {
+            report: self.generate_report(),
+            remote_entity_id: self.config.destination_entity_id,
+            transmission_mode: self.config.transmission_mode,
+            source_filename: self.metadata.source_filename.clone(),
+            destination_filename: self.metadata.destination_filename.clone(),
+            file_size: self.metadata.file_size,
+            file_status: self.file_status,
+            delivery_code: self.delivery_code,
+            filestore_responses: self.filestore_responses.clone(),
+            started: self.started,
+            finished: SystemTime::now(),
+        }
+    }
+
+    pub fn send_report(
+        &self,
+        sender: Option<oneshot::Sender<PrimitiveResult<Report>>>,
+    ) -> TransactionResult<()> {
         let report = self.generate_report();
 
         if let Some(channel) = sender {
-            let _ = channel.send(report.clone());
+            let _ = channel.send(Ok(report.clone()));
         }
         self.send_indication(Indication::Report(report));
 
@@ -321,20 +541,13 @@ impl<T: FileStore> SendTransaction<T> {
                 ..header.clone()
             }
         } else {
-            let header = PDUHeader {
-                version: U3::One,
-                pdu_type,
+            let header = new_header(
+                &self.config,
                 direction,
-                transmission_mode: self.config.transmission_mode,
-                crc_flag: self.config.crc_flag,
-                large_file_flag: self.config.file_size_flag,
+                pdu_type,
                 pdu_data_field_length,
                 segmentation_control,
-                segment_metadata_flag: self.config.segment_metadata_flag,
-                source_entity_id: self.config.source_entity_id,
-                transaction_sequence_number: self.config.sequence_number,
-                destination_entity_id: self.config.destination_entity_id,
-            };
+            );
             self.header = Some(header.clone());
             header
         }
@@ -460,6 +673,8 @@ impl<T: FileStore> SendTransaction<T> {
         );
         let pdu = PDU { header, payload };
 
+        self.bytes_sent += u64::from(pdu.encoded_len());
+
         permit.send((destination, pdu));
 
         Ok(())
@@ -531,6 +746,8 @@ impl<T: FileStore> SendTransaction<T> {
             let destination = header.destination_entity_id;
             let pdu = PDU { header, payload };
 
+            self.bytes_sent += u64::from(pdu.encoded_len());
+
             permit.send((destination, pdu));
             debug!("Transaction {0} sent EndOfFile.", self.id());
             self.set_eof_flag(false);
@@ -567,6 +784,8 @@ impl<T: FileStore> SendTransaction<T> {
             let destination = header.destination_entity_id;
             let pdu = PDU { header, payload };
 
+            self.bytes_sent += u64::from(pdu.encoded_len());
+
             permit.send((destination, pdu));
             debug!("Transaction {0} sent PromptPdu.", self.id());
         }
@@ -591,8 +810,8 @@ impl<T: FileStore> SendTransaction<T> {
     pub fn shutdown(&mut self) {
         debug!("Transaction {0} shutting down.", self.id());
         self.state = TransactionState::Terminated;
-        self.timer.ack.pause();
-        self.timer.inactivity.pause();
+        self.timer.pause_ack();
+        self.timer.pause_inactivity();
     }
 
     pub fn cancel(&mut self) -> TransactionResult<()> {
@@ -600,15 +819,15 @@ impl<T: FileStore> SendTransaction<T> {
     }
 
     fn _cancel(&mut self, condition: Condition) -> TransactionResult<()> {
-        self.timer.inactivity.pause();
+        self.timer.pause_inactivity();
         self.condition = condition;
         self.send_state = SendState::Cancelled;
         self.prepare_eof(Some(self.config.source_entity_id))
     }
 
     pub fn suspend(&mut self) -> TransactionResult<()> {
-        self.timer.ack.pause();
-        self.timer.inactivity.pause();
+        self.timer.pause_ack();
+        self.timer.pause_inactivity();
         self.state = TransactionState::Suspended;
 
         self.send_indication(Indication::Suspended(SuspendIndication {
@@ -706,6 +925,8 @@ impl<T: FileStore> SendTransaction<T> {
             let destination = header.destination_entity_id;
             let pdu = PDU { header, payload };
 
+            self.bytes_sent += u64::from(pdu.encoded_len());
+
             permit.send((destination, pdu));
             debug!("Transaction {0} sent Ack(Finished).", self.id());
             self.shutdown();
@@ -748,6 +969,7 @@ impl<T: FileStore> SendTransaction<T> {
                         Operations::Finished(finished) => {
                             self.delivery_code = finished.delivery_code;
                             self.file_status = finished.file_status;
+                            self.filestore_responses = finished.filestore_response;
                             self.prepare_ack();
                             self.send_state = SendState::Finished;
                             self.condition = finished.condition;
@@ -760,7 +982,7 @@ impl<T: FileStore> SendTransaction<T> {
                             self.send_indication(Indication::Finished(FinishedIndication {
                                 id: self.id(),
                                 report: self.generate_report(),
-                                filestore_responses: finished.filestore_response,
+                                filestore_responses: self.filestore_responses.clone(),
                                 file_status: self.file_status,
                                 delivery_code: self.delivery_code,
                             }));
@@ -821,7 +1043,7 @@ impl<T: FileStore> SendTransaction<T> {
                         }
                         Operations::Ack(ack) => {
                             if ack.directive == PDUDirective::EoF {
-                                self.timer.ack.pause();
+                                self.timer.pause_ack();
                                 // all good
                                 Ok(())
                             } else {
@@ -882,11 +1104,12 @@ impl<T: FileStore> SendTransaction<T> {
                             }
                             self.condition = finished.condition;
                             self.delivery_code = finished.delivery_code;
+                            self.filestore_responses = finished.filestore_response;
 
                             self.send_indication(Indication::Finished(FinishedIndication {
                                 id: self.id(),
                                 report: self.generate_report(),
-                                filestore_responses: finished.filestore_response,
+                                filestore_responses: self.filestore_responses.clone(),
                                 file_status: self.file_status,
                                 delivery_code: self.delivery_code,
                             }));
@@ -912,48 +1135,17 @@ impl<T: FileStore> SendTransaction<T> {
 
     fn send_metadata(&mut self, permit: Permit<(VariableID, PDU)>) -> TransactionResult<()> {
         let destination = self.config.destination_entity_id;
-        let metadata = MetadataPDU {
-            closure_requested: self.metadata.closure_requested,
-            checksum_type: self.metadata.checksum_type,
-            file_size: self.metadata.file_size,
-            source_filename: self.metadata.source_filename.clone(),
-            destination_filename: self.metadata.destination_filename.clone(),
-            options: self
-                .metadata
-                .filestore_requests
-                .iter()
-                .map(|fs| MetadataTLV::FileStoreRequest(fs.clone()))
-                .chain(
-                    self.metadata
-                        .message_to_user
-                        .iter()
-                        .map(|msg| MetadataTLV::MessageToUser(msg.clone())),
-                )
-                .collect(),
-        };
+        let pdu = metadata_pdu(&self.config, &self.metadata);
 
-        let payload = PDUPayload::Directive(Operations::Metadata(metadata));
-        let payload_len: u16 = payload.encoded_len(self.config.file_size_flag);
-
-        let header = self.get_header(
-            Direction::ToReceiver,
-            PDUType::FileDirective,
-            payload_len,
-            // TODO add semgentation Control ability
-            SegmentationControl::NotPreserved,
-        );
-
-        let pdu = PDU { header, payload };
+        self.bytes_sent += u64::from(pdu.encoded_len());
 
         permit.send((destination, pdu));
         debug!("Transaction {0} sent Metadata.", self.id());
         Ok(())
     }
 
-    //send the indication in another task, no need to wait for it
     fn send_indication(&self, indication: Indication) {
-        let tx = self.indication_tx.clone();
-        tokio::task::spawn(async move { tx.send(indication).await });
+        self.indication_tx.send(indication)
     }
 }
 
@@ -963,15 +1155,16 @@ mod test {
     use cfdp_core::{
         filestore::{ChecksumType, NativeFileStore},
         pdu::{
-            CRCFlag, FileSizeFlag, FileStoreResponse, FileStoreStatus, Finished, KeepAlivePDU,
-            NakOrKeepAlive, NegativeAcknowledgmentPDU, PromptPDU, RenameStatus, SegmentedData,
-            UnsegmentedFileData,
+            CRCFlag, EntityID, FileSizeFlag, FileStoreResponse, FileStoreStatus, Finished,
+            KeepAlivePDU, NakOrKeepAlive, NegativeAcknowledgmentPDU, PromptPDU, RenameStatus,
+            SegmentedData, UnsegmentedFileData, U3,
         },
     };
 
     use std::io::Write;
 
     use super::*;
+    use crate::indication::IndicationBroadcast;
     use crate::transaction::test::default_config;
 
     use camino::{Utf8Path, Utf8PathBuf};
@@ -992,7 +1185,7 @@ mod test {
             Utf8Path::from_path(tempdir_fixture.path()).expect("Unable to make utf8 tempdir"),
         ));
 
-        let (indication_tx, _indication_rx) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let metadata = test_metadata(10, Utf8PathBuf::from(""));
         let mut transaction = SendTransaction::new(config, metadata, filestore, indication_tx)
             .expect("unable to start transaction.");
@@ -1031,7 +1224,7 @@ mod test {
             Utf8Path::from_path(tempdir_fixture.path()).expect("Unable to make utf8 tempdir"),
         ));
 
-        let (indication_tx, _indication_rx) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let metadata = test_metadata(600_u64, Utf8PathBuf::from("a"));
         let transaction = SendTransaction::new(config, metadata, filestore, indication_tx).unwrap();
 
@@ -1054,7 +1247,7 @@ mod test {
 
         let path = Utf8PathBuf::from("testfile");
 
-        let (indication_tx, _indication_rx) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let metadata = test_metadata(10, path.clone());
         let mut transaction =
             SendTransaction::new(config, metadata, filestore.clone(), indication_tx).unwrap();
@@ -1137,7 +1330,7 @@ mod test {
         let path = Utf8PathBuf::from(filename);
         let metadata = test_metadata(10, path.clone());
 
-        let (indication_tx, _indication_rx) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let mut transaction =
             SendTransaction::new(config, metadata, filestore, indication_tx).unwrap();
 
@@ -1226,7 +1419,7 @@ mod test {
             Utf8Path::from_path(tempdir_fixture.path()).expect("Unable to make utf8 tempdir"),
         ));
 
-        let (indication_tx, _indication_rx) = channel(1);
+        let indication_tx = IndicationBroadcast::new(1).sender(EntityID::from(0_u16));
         let metadata = test_metadata(10, Utf8PathBuf::from(""));
         let mut transaction =
             SendTransaction::new(config, metadata, filestore, indication_tx).unwrap();
@@ -1253,7 +1446,7 @@ mod test {
 
         let input = "Here is some test data to write!$*#*.\n";
 
-        let (indication_tx, _indication_rx) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let path = Utf8PathBuf::from("test_eof.dat");
         let metadata = test_metadata(input.len() as u64, path.clone());
         let mut transaction =
@@ -1315,6 +1508,69 @@ mod test {
         filestore.delete_file(path).expect("cannot remove file");
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn frozen_eof(default_config: &TransactionConfig, tempdir_fixture: &TempDir) {
+        let (transport_tx, _transport_rx) = channel(1);
+        let config = default_config.clone();
+        let filestore = Arc::new(NativeFileStore::new(
+            Utf8Path::from_path(tempdir_fixture.path()).expect("Unable to make utf8 tempdir"),
+        ));
+
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
+        let path = Utf8PathBuf::from("test_frozen_eof.dat");
+        filestore
+            .open(&path, OpenOptions::new().create_new(true).write(true))
+            .unwrap();
+        let metadata = test_metadata(0, path.clone());
+        let mut transaction =
+            SendTransaction::new(config, metadata, filestore.clone(), indication_tx).unwrap();
+
+        // the EoF is sent the moment the window closes, its timers wait for the next one.
+        transaction.send_state = SendState::SendEof;
+        transaction.freeze();
+        transaction.prepare_eof(None).unwrap();
+        transaction
+            .send_eof(transport_tx.reserve().await.unwrap())
+            .unwrap();
+        assert_eq!(Duration::MAX, transaction.until_timeout());
+
+        transaction.thaw();
+        assert!(transaction.until_timeout() < Duration::MAX);
+
+        filestore.delete_file(path).expect("cannot remove file");
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn suspend_frozen(default_config: &TransactionConfig, tempdir_fixture: &TempDir) {
+        let config = default_config.clone();
+        let filestore = Arc::new(NativeFileStore::new(
+            Utf8Path::from_path(tempdir_fixture.path()).expect("Unable to make utf8 tempdir"),
+        ));
+
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
+        let path = Utf8PathBuf::from("test_suspend_frozen.dat");
+        filestore
+            .open(&path, OpenOptions::new().create_new(true).write(true))
+            .unwrap();
+        let metadata = test_metadata(0, path.clone());
+        let mut transaction =
+            SendTransaction::new(config, metadata, filestore.clone(), indication_tx).unwrap();
+
+        transaction.timer.restart_ack();
+        transaction.timer.restart_inactivity();
+        transaction.freeze();
+        transaction.suspend().unwrap();
+
+        // a transaction suspended during a contact gap does not time out once it ends.
+        transaction.thaw();
+        assert!(!transaction.timer.ack.is_ticking());
+        assert!(!transaction.timer.inactivity.is_ticking());
+
+        filestore.delete_file(path).expect("cannot remove file");
+    }
+
     #[rstest]
     #[tokio::test]
     async fn cancel_send(
@@ -1333,7 +1589,7 @@ mod test {
 
         let input = "Here is some test data to write!$*#*.\n";
 
-        let (indication_tx, _indication_rx) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let path = Utf8PathBuf::from(format!("test_eof_{:}.dat", config.transmission_mode as u8));
         let metadata = test_metadata(input.len() as u64, path.clone());
         let mut transaction =
@@ -1412,7 +1668,7 @@ mod test {
         let filestore = Arc::new(NativeFileStore::new(
             Utf8Path::from_path(tempdir_fixture.path()).expect("Unable to make utf8 tempdir"),
         ));
-        let (indication_tx, _indication_rx) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let metadata = test_metadata(0, Utf8PathBuf::from(""));
         let mut transaction =
             SendTransaction::new(config, metadata, filestore, indication_tx).unwrap();
@@ -1443,7 +1699,7 @@ mod test {
         let filestore = Arc::new(NativeFileStore::new(
             Utf8Path::from_path(tempdir_fixture.path()).expect("Unable to make utf8 tempdir"),
         ));
-        let (indication_tx, _indication_rx) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let metadata = test_metadata(0, Utf8PathBuf::from(""));
         let mut transaction =
             SendTransaction::new(config, metadata, filestore, indication_tx).unwrap();
@@ -1486,7 +1742,7 @@ mod test {
         let path = Utf8PathBuf::from(format!("test_eof_{:}.dat", config.transmission_mode as u8));
         let input = "Here is some test data to write!$*#*.\n";
 
-        let (indication_tx, _indication_rx) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let metadata = test_metadata(input.len() as u64, path);
         let mut transaction =
             SendTransaction::new(config.clone(), metadata, filestore, indication_tx).unwrap();
@@ -1584,7 +1840,7 @@ mod test {
             Utf8Path::from_path(tempdir_fixture.path()).expect("Unable to make utf8 tempdir"),
         ));
 
-        let (indication_tx, _indication_rx) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let metadata = test_metadata(600, Utf8PathBuf::from("Test_file.txt"));
         let mut transaction =
             SendTransaction::new(config, metadata, filestore, indication_tx).unwrap();
@@ -1650,7 +1906,7 @@ mod test {
             Utf8Path::from_path(tempdir_fixture.path()).expect("Unable to make utf8 tempdir"),
         ));
 
-        let (indication_tx, _indication_rx) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let metadata = test_metadata(600, Utf8PathBuf::from("Test_file.txt"));
         let mut transaction =
             SendTransaction::new(config, metadata, filestore, indication_tx).unwrap();
@@ -1692,7 +1948,7 @@ mod test {
             Utf8Path::from_path(tempdir_fixture.path()).expect("Unable to make utf8 tempdir"),
         ));
 
-        let (indication_tx, _indication_rx) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let metadata = test_metadata(600, Utf8PathBuf::from("Test_file.txt"));
         let mut transaction =
             SendTransaction::new(config, metadata, filestore, indication_tx).unwrap();
@@ -1727,7 +1983,7 @@ mod test {
             Utf8Path::from_path(tempdir_fixture.path()).expect("Unable to make utf8 tempdir"),
         ));
 
-        let (indication_tx, _indication_rx) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let path = Utf8PathBuf::from("test_file");
         let metadata = test_metadata(600, path);
         let mut transaction =
@@ -1804,7 +2060,7 @@ mod test {
             Utf8Path::from_path(tempdir_fixture.path()).expect("Unable to make utf8 tempdir"),
         ));
 
-        let (indication_tx, _indication_rx) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let metadata = test_metadata(total_size, Utf8PathBuf::from("test_file"));
         let mut transaction =
             SendTransaction::new(config, metadata, filestore, indication_tx).unwrap();
@@ -1876,7 +2132,7 @@ mod test {
             checksum_type: ChecksumType::Null,
         };
 
-        let (indication_tx, _indication_rx) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let mut transaction =
             SendTransaction::new(config, metadata, filestore, indication_tx).unwrap();
 
@@ -1956,7 +2212,7 @@ mod test {
             checksum_type: ChecksumType::Null,
         };
 
-        let (indication_tx, _indication_rx) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let mut transaction =
             SendTransaction::new(config, metadata, filestore, indication_tx).unwrap();
         transaction.checksum = Some(0);
@@ -2010,7 +2266,7 @@ mod test {
         let path = Utf8PathBuf::from(format!("test_eof_{:}.dat", config.transmission_mode as u8));
         let input = "Here is some test data to write!$*#*.\n";
 
-        let (indication_tx, _indication_rx) = channel(10);
+        let indication_tx = IndicationBroadcast::new(10).sender(EntityID::from(0_u16));
         let metadata = test_metadata(input.len() as u64, path);
         let mut transaction =
             SendTransaction::new(config.clone(), metadata, filestore, indication_tx).unwrap();
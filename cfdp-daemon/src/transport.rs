@@ -1,22 +1,30 @@
 use std::{
     collections::HashMap,
     fmt::Debug,
+    future::{poll_fn, Future},
     io::{Error as IoError, ErrorKind},
     net::SocketAddr,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
+    task::Poll,
+    time::Duration,
 };
 
 use async_trait::async_trait;
-use log::error;
+use log::{error, info, warn};
 use tokio::{
     net::{ToSocketAddrs, UdpSocket},
-    sync::mpsc::{Receiver, Sender},
+    select,
+    sync::mpsc::{channel, Receiver, Sender},
+    time::{sleep_until, Instant},
 };
 
-use crate::cfdp_core::pdu::{PDUEncode, VariableID, PDU};
+use crate::cfdp_core::{
+    daemon::LinkState,
+    pdu::{EntityID, PDUPayload, PDUView, VariableID, PDU},
+};
 
 /// Transports are designed to run in a thread in the background
 /// inside a [Daemon](crate::Daemon) process
@@ -27,8 +35,17 @@ pub trait PDUTransport {
     async fn request(&mut self, destination: VariableID, pdu: PDU) -> Result<(), IoError>;
 
     /// Recieves a PDU from the associated communication protocol.
+    ///
+    /// Must be cancel safe: dropping the future before it completes may not lose a PDU.
+    /// Both [pdu_handler](PDUTransport::pdu_handler) and [MultiPathTransport] rely on it.
     async fn receive(&mut self) -> Result<PDU, IoError>;
 
+    /// The largest encoded PDU this transport can carry in a single request.
+    /// Returns [None] if the transport imposes no limit.
+    fn max_pdu_len(&self) -> Option<usize> {
+        None
+    }
+
     /// Provides logic for listening for incoming PDUs and sending any outbound PDUs
     /// A default implementeation is provided for convenience.
     ///
@@ -40,6 +57,10 @@ pub trait PDUTransport {
     /// The [Daemon](crate::Daemon) is responsible for receiving messages and distribute them to each
     /// transaction [Send](crate::transaction::SendTransaction) or [Recv](crate::transaction::RecvTransaction)
     /// The signal is used to indicate a shutdown operation was requested.
+    ///
+    /// Undecodable PDUs are logged and skipped, any other error is returned so the
+    /// [Daemon](crate::Daemon) can report the link as down and restart the handler
+    /// on a new channel once the transport works again.
     async fn pdu_handler(
         &mut self,
         signal: Arc<AtomicBool>,
@@ -57,10 +78,10 @@ pub trait PDUTransport {
                                 return Err(IoError::from(ErrorKind::ConnectionAborted));
                             }
                         },
-                        Err(err) => {
+                        Err(err) if err.kind() == ErrorKind::InvalidData => {
                             error!("Error decoding PDU: {}", err);
-
                         }
+                        Err(err) => return Err(err),
                     };
                 },
                 Some((entity, pdu)) = recv.recv() => {
@@ -76,12 +97,164 @@ pub trait PDUTransport {
     }
 }
 
+/// Delay before a failed transport is first probed, doubled after every failed probe.
+pub(crate) const TRANSPORT_RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// Longest delay between probes of a failed transport.
+pub(crate) const TRANSPORT_MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+
+/// A change in health of a transport, reported to the [Daemon](crate::Daemon).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TransportEvent {
+    /// All entities served by the transport.
+    pub entities: Vec<EntityID>,
+    pub state: LinkState,
+}
+
+/// Outcome of probing a failed transport.
+enum Probe {
+    Up,
+    Failed(IoError),
+    /// The Daemon is no longer exchanging PDUs.
+    Closed,
+}
+
+/// Run the [pdu_handler](PDUTransport::pdu_handler) of a transport until shutdown is signaled.
+///
+/// Whenever the handler fails the link is reported down through the health channel.
+/// The transport is then probed, first after `restart_delay` and with the delay doubling
+/// up to `max_restart_delay` after every failed probe. A probe waits to receive a PDU
+/// or to send the next one. Only once a probe succeeds is the link reported up and the handler restarted.
+/// The delay is reset once the handler has stayed up for `max_restart_delay`,
+/// so a transport failing again right after recovering backs off further.
+/// Supervision ends if the Daemon stops listening for health events or PDUs.
+///
+/// PDUs are delivered at most once: those handed to a failing handler or probe are dropped,
+/// not sent again, and the transactions recover them through their NAK and ACK timers.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn supervise(
+    mut transport: Box<dyn PDUTransport + Send>,
+    entities: Vec<EntityID>,
+    signal: Arc<AtomicBool>,
+    sender: Sender<PDU>,
+    mut recv: Receiver<(VariableID, PDU)>,
+    health: Sender<TransportEvent>,
+    restart_delay: Duration,
+    max_restart_delay: Duration,
+) {
+    let mut delay = restart_delay;
+    while !signal.load(Ordering::Relaxed) {
+        let started = tokio::time::Instant::now();
+        let err = match run_handler(transport.as_mut(), &signal, &sender, &mut recv).await {
+            Ok(()) => break,
+            Err(err) => err,
+        };
+
+        error!("Transport for entities {entities:?} failed: {err}");
+        if started.elapsed() >= max_restart_delay {
+            delay = restart_delay;
+        }
+        if !report(&health, &entities, LinkState::Down).await {
+            break;
+        }
+
+        loop {
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(max_restart_delay);
+            if signal.load(Ordering::Relaxed) {
+                return;
+            }
+            match probe(transport.as_mut(), &sender, &mut recv).await {
+                Probe::Up => break,
+                Probe::Failed(err) => {
+                    warn!("Transport for entities {entities:?} is still failing: {err}")
+                }
+                Probe::Closed => return,
+            }
+        }
+
+        info!("Transport for entities {entities:?} recovered, restarting it.");
+        if !report(&health, &entities, LinkState::Up).await {
+            break;
+        }
+    }
+}
+
+/// Returns false once the Daemon stops listening.
+async fn report(health: &Sender<TransportEvent>, entities: &[EntityID], state: LinkState) -> bool {
+    let event = TransportEvent {
+        entities: entities.to_vec(),
+        state,
+    };
+    health.send(event).await.is_ok()
+}
+
+/// Run the handler on a channel of its own, relaying the PDUs from the Daemon to it,
+/// so it can be restarted on a fresh channel after a failure.
+async fn run_handler(
+    transport: &mut (dyn PDUTransport + Send),
+    signal: &Arc<AtomicBool>,
+    sender: &Sender<PDU>,
+    recv: &mut Receiver<(VariableID, PDU)>,
+) -> Result<(), IoError> {
+    let (relay_tx, relay_rx) = channel(1);
+    let handler = transport.pdu_handler(signal.clone(), sender.clone(), relay_rx);
+    tokio::pin!(handler);
+
+    let relay = async move {
+        while let Some(outbound) = recv.recv().await {
+            if relay_tx.send(outbound).await.is_err() {
+                break;
+            }
+        }
+        // closing the channel lets the handler finish.
+        drop(relay_tx);
+    };
+
+    select! {
+        result = &mut handler => result,
+        _ = relay => handler.await,
+    }
+}
+
+/// Check whether a failed transport works again.
+///
+/// A PDU which fails to send is dropped.
+async fn probe(
+    transport: &mut (dyn PDUTransport + Send),
+    sender: &Sender<PDU>,
+    recv: &mut Receiver<(VariableID, PDU)>,
+) -> Probe {
+    select! {
+        received = transport.receive() => match received {
+            Ok(pdu) => match sender.send(pdu).await {
+                Ok(()) => Probe::Up,
+                Err(_) => Probe::Closed,
+            },
+            // data arriving, even if undecodable, means the transport works.
+            Err(err) if err.kind() == ErrorKind::InvalidData => Probe::Up,
+            Err(err) => Probe::Failed(err),
+        },
+        outbound = recv.recv() => match outbound {
+            Some((destination, pdu)) => match transport.request(destination, pdu).await {
+                Ok(()) => Probe::Up,
+                Err(err) => Probe::Failed(err),
+            },
+            None => Probe::Closed,
+        },
+    }
+}
+
+/// Largest payload of a single UDP datagram over IPv4.
+const UDP_MAX_PAYLOAD: usize = 65_507;
+
 /// A wrapper struct around a [UdpSocket] and a Mapping from
 /// EntityIDs to [SocketAddr] instances.
 pub struct UdpTransport {
     socket: UdpSocket,
 
     buffer: Vec<u8>,
+    send_buffer: Vec<u8>,
     entity_map: HashMap<VariableID, SocketAddr>,
 }
 impl UdpTransport {
@@ -93,6 +266,7 @@ impl UdpTransport {
         Ok(Self {
             socket,
             buffer: vec![0_u8; u16::MAX as usize],
+            send_buffer: vec![0_u8; UDP_MAX_PAYLOAD],
             entity_map,
         })
     }
@@ -104,6 +278,7 @@ impl TryFrom<(UdpSocket, HashMap<VariableID, SocketAddr>)> for UdpTransport {
             socket: inputs.0,
             // this buffer will be 511 KiB, should be sufficiently small;
             buffer: vec![0_u8; u16::MAX as usize],
+            send_buffer: vec![0_u8; UDP_MAX_PAYLOAD],
             entity_map: inputs.1,
         };
         Ok(me)
@@ -117,15 +292,24 @@ impl PDUTransport for UdpTransport {
             .entity_map
             .get(&destination)
             .ok_or_else(|| IoError::from(ErrorKind::AddrNotAvailable))?;
-        self.socket.send_to(pdu.encode().as_slice(), addr).await?;
+        let length = pdu
+            .encode_into(&mut self.send_buffer)
+            .map_err(|err| IoError::new(ErrorKind::InvalidInput, err.to_string()))?;
+        self.socket
+            .send_to(&self.send_buffer[..length], addr)
+            .await?;
         Ok(())
     }
 
+    fn max_pdu_len(&self) -> Option<usize> {
+        Some(UDP_MAX_PAYLOAD)
+    }
+
     async fn receive(&mut self) -> Result<PDU, IoError> {
-        let (_n, _addr) = self.socket.recv_from(&mut self.buffer).await?;
+        let (n, _addr) = self.socket.recv_from(&mut self.buffer).await?;
 
-        match PDU::decode(&mut self.buffer.as_slice()) {
-            Ok(pdu) => Ok(pdu),
+        match PDUView::decode(&self.buffer[..n]) {
+            Ok((pdu, _)) => Ok(pdu.into()),
             Err(err) => {
                 // might need to stop depending on the error.
                 // some are recoverable though
@@ -134,3 +318,592 @@ impl PDUTransport for UdpTransport {
         }
     }
 }
+
+/// A single path to one or more remote entities managed by a [MultiPathTransport].
+struct Link {
+    transport: Box<dyn PDUTransport + Send>,
+    /// Lower values are preferred when selecting a link.
+    priority: u8,
+    entities: Vec<EntityID>,
+    /// Last time any traffic was received over this link.
+    last_received: Instant,
+    /// Set when a request over this link fails, cleared on the next received PDU.
+    failed: bool,
+    /// Set when receiving over this link fails, it is not listened to again
+    /// until the health window has passed, cleared on the next received PDU.
+    down_since: Option<Instant>,
+}
+impl Link {
+    fn is_healthy(&self, window: Duration) -> bool {
+        !self.failed && self.down_since.is_none() && self.last_received.elapsed() <= window
+    }
+
+    /// When a down link is listened to again, [None] if it is not down.
+    fn retry_at(&self, window: Duration) -> Option<Instant> {
+        self.down_since.map(|since| since + window)
+    }
+}
+
+/// A composite transport holding several prioritized links per remote entity.
+///
+/// Outbound PDUs are sent over the highest priority healthy link to the destination.
+/// A link is considered healthy as long as traffic has been received on it within the
+/// configured health window and its last request did not fail.
+/// When a request fails the next link for the entity is tried automatically.
+/// If no link to an entity is healthy the links are still attempted in priority order.
+///
+/// Directive PDUs may optionally be duplicated across every healthy link to the destination.
+/// Similar to the reasoning behind [NakProcedure](cfdp_core::daemon::NakProcedure),
+/// a lost directive (NAK, EoF, Finished, ACK) costs at least a full timer cycle to recover from,
+/// while a duplicate is discarded cheaply by the receiving transaction.
+/// File data PDUs are never duplicated.
+///
+/// Links are listened to in turn so a busy link cannot starve the others.
+/// A link whose receive fails is marked down and only listened to again once the
+/// health window has passed, it recovers as soon as a PDU is received over it.
+/// [receive](PDUTransport::receive) only fails once every link is down.
+/// Every link transport must have a cancel safe [receive](PDUTransport::receive),
+/// pending receives on the other links are dropped whenever one completes.
+pub struct MultiPathTransport {
+    links: Vec<Link>,
+    health_window: Duration,
+    duplicate_directives: bool,
+    /// Index of the link polled first on the next receive.
+    next_receive: usize,
+}
+impl MultiPathTransport {
+    pub fn new(health_window: Duration, duplicate_directives: bool) -> Self {
+        Self {
+            links: vec![],
+            health_window,
+            duplicate_directives,
+            next_receive: 0,
+        }
+    }
+
+    /// Add a link used to reach the given entities.
+    /// Links with a lower priority value are preferred.
+    pub fn with_link(
+        mut self,
+        priority: u8,
+        entities: Vec<EntityID>,
+        transport: Box<dyn PDUTransport + Send>,
+    ) -> Self {
+        self.links.push(Link {
+            transport,
+            priority,
+            entities,
+            last_received: Instant::now(),
+            failed: false,
+            down_since: None,
+        });
+        // stable sort keeps insertion order between links of equal priority
+        self.links.sort_by_key(|link| link.priority);
+        self
+    }
+
+    /// Indices of all links able to reach the destination, healthy links first,
+    /// each group ordered by priority.
+    fn route(&self, destination: &VariableID) -> Vec<usize> {
+        let (healthy, unhealthy): (Vec<usize>, Vec<usize>) = self
+            .links
+            .iter()
+            .enumerate()
+            .filter(|(_, link)| link.entities.contains(destination))
+            .map(|(index, _)| index)
+            .partition(|index| self.links[*index].is_healthy(self.health_window));
+        healthy.into_iter().chain(unhealthy).collect()
+    }
+
+    async fn request_on(
+        &mut self,
+        index: usize,
+        destination: VariableID,
+        pdu: PDU,
+    ) -> Result<(), IoError> {
+        let link = &mut self.links[index];
+        match link.transport.request(destination, pdu).await {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                warn!(
+                    "Link with priority {} to entity {} failed: {}",
+                    link.priority, destination, err
+                );
+                link.failed = true;
+                Err(err)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl PDUTransport for MultiPathTransport {
+    async fn request(&mut self, destination: VariableID, pdu: PDU) -> Result<(), IoError> {
+        let route = self.route(&destination);
+        if route.is_empty() {
+            return Err(IoError::from(ErrorKind::AddrNotAvailable));
+        }
+
+        let healthy: Vec<usize> = route
+            .iter()
+            .copied()
+            .filter(|index| self.links[*index].is_healthy(self.health_window))
+            .collect();
+
+        if self.duplicate_directives
+            && matches!(pdu.payload, PDUPayload::Directive(_))
+            && healthy.len() > 1
+        {
+            let mut sent = false;
+            for index in healthy {
+                sent |= self
+                    .request_on(index, destination, pdu.clone())
+                    .await
+                    .is_ok();
+            }
+            if sent {
+                return Ok(());
+            }
+        }
+
+        let mut last_error = IoError::from(ErrorKind::NotConnected);
+        for index in route {
+            match self.request_on(index, destination, pdu.clone()).await {
+                Ok(()) => return Ok(()),
+                Err(err) => last_error = err,
+            }
+        }
+        Err(last_error)
+    }
+
+    async fn receive(&mut self) -> Result<PDU, IoError> {
+        if self.links.is_empty() {
+            return Err(IoError::from(ErrorKind::NotConnected));
+        }
+        loop {
+            let now = Instant::now();
+            let window = self.health_window;
+            let first = self.next_receive % self.links.len();
+            // the soonest a link which is down is listened to again.
+            let retry = self
+                .links
+                .iter()
+                .filter_map(|link| link.retry_at(window))
+                .filter(|retry| *retry > now)
+                .min();
+
+            let (received, failures) = {
+                // pending receives in polling order, starting after the link which last delivered.
+                let mut pending: Vec<_> = self
+                    .links
+                    .iter_mut()
+                    .enumerate()
+                    .filter(|(_, link)| link.retry_at(window).is_none_or(|retry| retry <= now))
+                    .map(|(index, link)| (index, Some(link.transport.receive())))
+                    .collect();
+                let skipped = pending
+                    .iter()
+                    .take_while(|(index, _)| *index < first)
+                    .count();
+                pending.rotate_left(skipped);
+                let mut retry = retry.map(|retry| Box::pin(sleep_until(retry)));
+
+                let mut failures = vec![];
+                let received = poll_fn(|cx| {
+                    for (index, slot) in pending.iter_mut() {
+                        let Some(future) = slot else { continue };
+                        match future.as_mut().poll(cx) {
+                            Poll::Pending => {}
+                            // Data arriving on the link, even if undecodable, means the path is alive.
+                            Poll::Ready(Err(err)) if err.kind() != ErrorKind::InvalidData => {
+                                failures.push((*index, err));
+                                *slot = None;
+                            }
+                            Poll::Ready(result) => return Poll::Ready(Some((*index, result))),
+                        }
+                    }
+                    // a down link is due to be listened to again.
+                    if let Some(Poll::Ready(())) =
+                        retry.as_mut().map(|sleep| sleep.as_mut().poll(cx))
+                    {
+                        return Poll::Ready(None);
+                    }
+                    match pending.iter().all(|(_, slot)| slot.is_none()) {
+                        true => Poll::Ready(None),
+                        false => Poll::Pending,
+                    }
+                })
+                .await;
+                (received, failures)
+            };
+
+            let mut last_error = IoError::from(ErrorKind::NotConnected);
+            for (index, err) in failures {
+                let link = &mut self.links[index];
+                warn!(
+                    "Link with priority {} to entities {:?} stopped receiving: {}",
+                    link.priority, link.entities, err
+                );
+                link.down_since = Some(Instant::now());
+                last_error = err;
+            }
+
+            if let Some((index, result)) = received {
+                let link = &mut self.links[index];
+                link.last_received = Instant::now();
+                link.failed = false;
+                link.down_since = None;
+                self.next_receive = index + 1;
+                return result;
+            }
+            if self.links.iter().all(|link| link.down_since.is_some()) {
+                error!("Every link of the multi-path transport has failed.");
+                // listen to every link again should the transport be restarted.
+                self.links
+                    .iter_mut()
+                    .for_each(|link| link.down_since = None);
+                return Err(last_error);
+            }
+        }
+    }
+
+    /// Any link may carry a PDU so the smallest limit applies.
+    fn max_pdu_len(&self) -> Option<usize> {
+        self.links
+            .iter()
+            .filter_map(|link| link.transport.max_pdu_len())
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cfdp_core::pdu::{
+        CRCFlag, Direction, EndOfFile, FileDataPDU, FileSizeFlag, Operations, PDUHeader, PDUType,
+        SegmentationControl, SegmentedData, TransmissionMode, UnsegmentedFileData, U3,
+    };
+    use tokio::{task::JoinHandle, time::Instant};
+
+    use super::*;
+
+    struct ChannelTransport {
+        outbound: Sender<(VariableID, PDU)>,
+        inbound: Receiver<PDU>,
+        // number of requests which fail before any succeeds.
+        failures: usize,
+        // number of receives which fail before any succeeds.
+        receive_failures: usize,
+    }
+    #[async_trait]
+    impl PDUTransport for ChannelTransport {
+        async fn request(&mut self, destination: VariableID, pdu: PDU) -> Result<(), IoError> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(IoError::from(ErrorKind::BrokenPipe));
+            }
+            self.outbound
+                .send((destination, pdu))
+                .await
+                .map_err(|_| IoError::from(ErrorKind::ConnectionReset))
+        }
+
+        async fn receive(&mut self) -> Result<PDU, IoError> {
+            if self.receive_failures > 0 {
+                self.receive_failures -= 1;
+                return Err(IoError::from(ErrorKind::ConnectionRefused));
+            }
+            self.inbound
+                .recv()
+                .await
+                .ok_or_else(|| IoError::from(ErrorKind::ConnectionReset))
+        }
+    }
+
+    type LinkEnds = (
+        Box<dyn PDUTransport + Send>,
+        Receiver<(VariableID, PDU)>,
+        Sender<PDU>,
+    );
+    fn failing_link(failures: usize) -> LinkEnds {
+        channel_link(failures, 0)
+    }
+
+    fn channel_link(failures: usize, receive_failures: usize) -> LinkEnds {
+        let (outbound, outbound_rx) = channel(10);
+        let (inbound_tx, inbound) = channel(10);
+        (
+            Box::new(ChannelTransport {
+                outbound,
+                inbound,
+                failures,
+                receive_failures,
+            }),
+            outbound_rx,
+            inbound_tx,
+        )
+    }
+
+    fn link(fail: bool) -> LinkEnds {
+        failing_link(if fail { usize::MAX } else { 0 })
+    }
+
+    fn make_pdu(payload: PDUPayload) -> PDU {
+        let pdu_type = match &payload {
+            PDUPayload::Directive(_) => PDUType::FileDirective,
+            PDUPayload::FileData(_) => PDUType::FileData,
+        };
+        PDU {
+            header: PDUHeader {
+                version: U3::One,
+                pdu_type,
+                direction: Direction::ToReceiver,
+                transmission_mode: TransmissionMode::Acknowledged,
+                crc_flag: CRCFlag::NotPresent,
+                large_file_flag: FileSizeFlag::Small,
+                pdu_data_field_length: payload.encoded_len(FileSizeFlag::Small),
+                segmentation_control: SegmentationControl::NotPreserved,
+                segment_metadata_flag: SegmentedData::NotPresent,
+                source_entity_id: 0_u16.into(),
+                transaction_sequence_number: 3_u16.into(),
+                destination_entity_id: 1_u16.into(),
+            },
+            payload,
+        }
+    }
+
+    fn directive() -> PDU {
+        make_pdu(PDUPayload::Directive(Operations::EoF(EndOfFile {
+            condition: cfdp_core::pdu::Condition::NoError,
+            checksum: 0,
+            file_size: 12,
+            fault_location: None,
+        })))
+    }
+
+    fn file_data() -> PDU {
+        make_pdu(PDUPayload::FileData(FileDataPDU::Unsegmented(
+            UnsegmentedFileData {
+                offset: 0,
+                file_data: vec![1_u8, 2, 3],
+            },
+        )))
+    }
+
+    fn supervised(
+        transport: Box<dyn PDUTransport + Send>,
+    ) -> (
+        JoinHandle<()>,
+        Sender<(VariableID, PDU)>,
+        Receiver<TransportEvent>,
+    ) {
+        let (pdu_tx, _pdu_rx) = channel(1);
+        let (remote_tx, remote_rx) = channel(1);
+        let (health_tx, health_rx) = channel(1);
+        let handle = tokio::spawn(supervise(
+            transport,
+            vec![1_u16.into()],
+            Arc::new(AtomicBool::new(false)),
+            pdu_tx,
+            remote_rx,
+            health_tx,
+            Duration::from_millis(10),
+            Duration::from_millis(100),
+        ));
+        (handle, remote_tx, health_rx)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn supervise_restart() {
+        let (transport, mut outbound_rx, inbound_tx) = failing_link(2);
+        let (handle, remote_tx, mut health_rx) = supervised(transport);
+
+        remote_tx.send((1_u16.into(), directive())).await.unwrap();
+        assert_eq!(
+            TransportEvent {
+                entities: vec![1_u16.into()],
+                state: LinkState::Down
+            },
+            health_rx.recv().await.unwrap()
+        );
+        // the first probe fails as well, the second one sends the next PDU.
+        let down = Instant::now();
+        remote_tx.send((1_u16.into(), file_data())).await.unwrap();
+        remote_tx.send((1_u16.into(), directive())).await.unwrap();
+        assert_eq!(LinkState::Up, health_rx.recv().await.unwrap().state);
+        assert_eq!(Duration::from_millis(30), down.elapsed());
+        // PDUs lost in the failures are not sent again.
+        assert_eq!(outbound_rx.try_recv().unwrap().1, directive());
+        assert!(outbound_rx.try_recv().is_err());
+
+        // the restarted handler carries on with the next PDU.
+        remote_tx.send((1_u16.into(), file_data())).await.unwrap();
+        assert_eq!(outbound_rx.recv().await.unwrap().1, file_data());
+
+        // a failed receive after the daemon stopped listening ends supervision.
+        drop(health_rx);
+        drop(inbound_tx);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn supervise_no_flapping() {
+        let (transport, _outbound_rx, _inbound_tx) = link(true);
+        let (_handle, remote_tx, mut health_rx) = supervised(transport);
+
+        remote_tx.send((1_u16.into(), directive())).await.unwrap();
+        assert_eq!(LinkState::Down, health_rx.recv().await.unwrap().state);
+        // a transport which keeps failing stays down.
+        assert!(
+            tokio::time::timeout(Duration::from_secs(10), health_rx.recv())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn prefer_priority() {
+        let (primary, mut primary_rx, _primary_tx) = link(false);
+        let (backup, mut backup_rx, _backup_tx) = link(false);
+        let mut transport = MultiPathTransport::new(Duration::from_secs(10), false)
+            .with_link(1, vec![1_u16.into()], backup)
+            .with_link(0, vec![1_u16.into()], primary);
+
+        transport.request(1_u16.into(), directive()).await.unwrap();
+        assert_eq!(primary_rx.try_recv().unwrap().1, directive());
+        assert!(backup_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn unknown_entity() {
+        let (primary, _primary_rx, _primary_tx) = link(false);
+        let mut transport = MultiPathTransport::new(Duration::from_secs(10), false).with_link(
+            0,
+            vec![1_u16.into()],
+            primary,
+        );
+
+        let err = transport
+            .request(2_u16.into(), directive())
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AddrNotAvailable);
+    }
+
+    #[tokio::test]
+    async fn failover_on_error() {
+        let (primary, _primary_rx, primary_tx) = link(true);
+        let (backup, mut backup_rx, _backup_tx) = link(false);
+        let mut transport = MultiPathTransport::new(Duration::from_secs(10), false)
+            .with_link(0, vec![1_u16.into()], primary)
+            .with_link(1, vec![1_u16.into()], backup);
+
+        transport.request(1_u16.into(), file_data()).await.unwrap();
+        assert_eq!(backup_rx.try_recv().unwrap().1, file_data());
+        assert!(transport.links[0].failed);
+
+        // receiving over the primary restores it
+        primary_tx.send(directive()).await.unwrap();
+        assert_eq!(transport.receive().await.unwrap(), directive());
+        assert!(!transport.links[0].failed);
+    }
+
+    #[tokio::test]
+    async fn failover_on_silence() {
+        let (primary, mut primary_rx, _primary_tx) = link(false);
+        let (backup, mut backup_rx, backup_tx) = link(false);
+        let mut transport = MultiPathTransport::new(Duration::from_millis(50), false)
+            .with_link(0, vec![1_u16.into()], primary)
+            .with_link(1, vec![1_u16.into()], backup);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        backup_tx.send(directive()).await.unwrap();
+        transport.receive().await.unwrap();
+
+        transport.request(1_u16.into(), file_data()).await.unwrap();
+        assert_eq!(backup_rx.try_recv().unwrap().1, file_data());
+        assert!(primary_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn receive_round_robin() {
+        let (primary, _primary_rx, primary_tx) = link(false);
+        let (backup, _backup_rx, backup_tx) = link(false);
+        let mut transport = MultiPathTransport::new(Duration::from_secs(10), false)
+            .with_link(0, vec![1_u16.into()], primary)
+            .with_link(1, vec![1_u16.into()], backup);
+
+        for _ in 0..2 {
+            primary_tx.send(directive()).await.unwrap();
+            backup_tx.send(file_data()).await.unwrap();
+        }
+        for expected in [directive(), file_data(), directive(), file_data()] {
+            assert_eq!(transport.receive().await.unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn receive_skips_failed_link() {
+        let (primary, _primary_rx, primary_tx) = link(false);
+        let (backup, _backup_rx, backup_tx) = link(false);
+        let mut transport = MultiPathTransport::new(Duration::from_secs(10), false)
+            .with_link(0, vec![1_u16.into()], primary)
+            .with_link(1, vec![1_u16.into()], backup);
+
+        // the primary fails on every receive once its sender is gone.
+        drop(primary_tx);
+        backup_tx.send(directive()).await.unwrap();
+        assert_eq!(transport.receive().await.unwrap(), directive());
+        assert!(transport.links[0].down_since.is_some());
+
+        backup_tx.send(file_data()).await.unwrap();
+        assert_eq!(transport.receive().await.unwrap(), file_data());
+
+        // only once every link failed is the error returned.
+        drop(backup_tx);
+        let err = transport.receive().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ConnectionReset);
+        assert!(transport.links.iter().all(|link| link.down_since.is_none()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn receive_recovers_link() {
+        // the primary is refused once, e.g. after an ICMP unreachable.
+        let (primary, mut primary_rx, primary_tx) = channel_link(0, 1);
+        let (backup, _backup_rx, backup_tx) = link(false);
+        let mut transport = MultiPathTransport::new(Duration::from_millis(50), false)
+            .with_link(0, vec![1_u16.into()], primary)
+            .with_link(1, vec![1_u16.into()], backup);
+
+        backup_tx.send(directive()).await.unwrap();
+        assert_eq!(transport.receive().await.unwrap(), directive());
+        assert!(transport.links[0].down_since.is_some());
+
+        // the primary is listened to again once the health window has passed.
+        primary_tx.send(file_data()).await.unwrap();
+        let down = Instant::now();
+        assert_eq!(transport.receive().await.unwrap(), file_data());
+        assert_eq!(Duration::from_millis(50), down.elapsed());
+        assert!(transport.links[0].down_since.is_none());
+
+        // and is preferred for sending again.
+        transport.request(1_u16.into(), directive()).await.unwrap();
+        assert_eq!(primary_rx.try_recv().unwrap().1, directive());
+    }
+
+    #[tokio::test]
+    async fn duplicate_directives() {
+        let (primary, mut primary_rx, _primary_tx) = link(false);
+        let (backup, mut backup_rx, _backup_tx) = link(false);
+        let mut transport = MultiPathTransport::new(Duration::from_secs(10), true)
+            .with_link(0, vec![1_u16.into()], primary)
+            .with_link(1, vec![1_u16.into()], backup);
+
+        transport.request(1_u16.into(), directive()).await.unwrap();
+        assert_eq!(primary_rx.try_recv().unwrap().1, directive());
+        assert_eq!(backup_rx.try_recv().unwrap().1, directive());
+
+        transport.request(1_u16.into(), file_data()).await.unwrap();
+        assert_eq!(primary_rx.try_recv().unwrap().1, file_data());
+        assert!(backup_rx.try_recv().is_err());
+    }
+}
@@ -1,11 +1,11 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fs::{self, OpenOptions},
     io::{Error as IoError, ErrorKind, Write},
     marker::PhantomData,
     net::SocketAddr,
     path::Path,
-    sync::{Arc, RwLock},
+    sync::Arc,
     time::Duration,
 };
 
@@ -13,8 +13,9 @@ use async_trait::async_trait;
 use camino::Utf8PathBuf;
 use cfdp_core::{
     daemon::{
-        EntityConfig, FinishedIndication, Indication, MetadataRecvIndication, NakProcedure,
-        PutRequest, Report, UserPrimitive,
+        ContactWindow, EntityConfig, FinishedIndication, Indication, LinkState,
+        MetadataRecvIndication, NakProcedure, Priority, PutRequest, Report, Scheduling,
+        ShutdownMode, UserPrimitive, VersionNegotiation,
     },
     filestore::{ChecksumType, FileStore, NativeFileStore},
     pdu::{
@@ -44,8 +45,10 @@ use tokio::{
 };
 
 use cfdp_daemon::{
+    history::{HistoryQuery, TransactionHistory, TransactionRecord, DEFAULT_HISTORY_CAPACITY},
+    suspend::SuspendStore,
     transport::{PDUTransport, UdpTransport},
-    Daemon,
+    DaemonBuilder,
 };
 
 #[derive(Debug)]
@@ -84,6 +87,11 @@ pub(crate) fn get_proxy_request(
             })
             .unwrap_or(TransmissionMode::Unacknowledged);
 
+        let closure_requested = messages.iter().find_map(|msg| match msg {
+            ProxyOperation::ProxyClosureRequest(req) => Some(req.closure_requested),
+            _ => None,
+        });
+
         let filestore_requests = messages
             .iter()
             .filter_map(|msg| match msg {
@@ -116,6 +124,8 @@ pub(crate) fn get_proxy_request(
             transmission_mode,
             filestore_requests,
             message_to_user,
+            closure_requested,
+            priority: None,
         };
         out.push(req)
     }
@@ -199,7 +209,12 @@ pub(crate) fn categorize_user_msg(
     (proxy_reqs, other_reqs, responses, cancel_id, other_messages)
 }
 
-type UserSplit = (TestUserHalf, Receiver<UserPrimitive>, Sender<Indication>);
+type UserSplit = (
+    TestUserHalf,
+    Receiver<UserPrimitive>,
+    Sender<Indication>,
+    TransactionHistory,
+);
 
 pub(crate) struct TestUser {
     internal_tx: Sender<UserPrimitive>,
@@ -208,16 +223,15 @@ pub(crate) struct TestUser {
     indication_tx: Sender<Indication>,
     // Indication listener thread
     indication_handle: JoinHandle<()>,
-    history: Arc<RwLock<HashMap<TransactionID, Report>>>,
+    history: TransactionHistory,
     tokio_handle: tokio::runtime::Handle,
 }
 impl TestUser {
     pub(crate) fn new<T: FileStore + Send + Sync + 'static>(filestore: Arc<T>) -> Self {
         let (internal_tx, internal_rx) = mpsc::channel::<UserPrimitive>(1);
         let (indication_tx, mut indication_rx) = mpsc::channel::<Indication>(1000);
-        let history = Arc::new(RwLock::new(HashMap::<TransactionID, Report>::new()));
+        let history = TransactionHistory::new(DEFAULT_HISTORY_CAPACITY);
 
-        let auto_history = history.clone();
         let auto_sender = internal_tx.clone();
 
         let indication_handle = tokio::task::spawn(async move {
@@ -242,12 +256,17 @@ impl TestUser {
                                 .await
                                 .expect("Unable to send auto request");
 
-                            let id = put_recv.await.expect("Recv channel disconnected: ");
-                            proxy_map.insert(id, origin_id);
+                            match put_recv.await.expect("Recv channel disconnected: ") {
+                                Ok(id) => {
+                                    proxy_map.insert(id, origin_id);
+                                }
+                                Err(err) => info!("Proxy Put refused: {err}"),
+                            }
                         }
 
                         if let Some(id) = cancel_id {
-                            let primitive = UserPrimitive::Cancel(id);
+                            let (cancel_tx, _cancel_rx) = oneshot::channel();
+                            let primitive = UserPrimitive::Cancel(id, cancel_tx);
                             auto_sender
                                 .send(primitive)
                                 .await
@@ -314,6 +333,8 @@ impl TestUser {
                                                     ),
                                                 )),
                                             ],
+                                            closure_requested: None,
+                                            priority: None,
                                         }
                                     }
                                     Err(_) => PutRequest {
@@ -344,6 +365,8 @@ impl TestUser {
                                                 ),
                                             )),
                                         ],
+                                        closure_requested: None,
+                                        priority: None,
                                     },
                                 },
                                 UserRequest::RemoteStatusReport(report_request) => {
@@ -365,16 +388,8 @@ impl TestUser {
                                         })
                                         .expect("error asking for report.");
 
-                                    let report = match report_rx.await.map_err(|_| {
-                                        IoError::new(
-                                            ErrorKind::ConnectionReset,
-                                            "Daemon Half of User disconnected.",
-                                        )
-                                    }) {
-                                        Ok(report) => Some(report),
-                                        // try to get from history here
-                                        Err(_) => auto_history.read().unwrap().get(&id).cloned(),
-                                    };
+                                    // finished transactions are reported from the history.
+                                    let report = report_rx.await.ok().and_then(Result::ok);
                                     let response = {
                                         match report {
                                             Some(data) => RemoteStatusReportResponse {
@@ -411,15 +426,21 @@ impl TestUser {
                                                 UserResponse::RemoteStatusReport(response),
                                             )),
                                         ],
+                                        closure_requested: None,
+                                        priority: None,
                                     }
                                 }
                                 UserRequest::RemoteSuspend(suspend_req) => {
-                                    let primitive = UserPrimitive::Suspend(TransactionID(
-                                        suspend_req.source_entity_id,
-                                        suspend_req.transaction_sequence_number,
-                                    ));
+                                    let (suspend_tx, suspend_rx) = oneshot::channel();
+                                    let primitive = UserPrimitive::Suspend(
+                                        TransactionID(
+                                            suspend_req.source_entity_id,
+                                            suspend_req.transaction_sequence_number,
+                                        ),
+                                        suspend_tx,
+                                    );
 
-                                    let suspend_indication = auto_sender
+                                    auto_sender
                                         .send(primitive)
                                         .await
                                         .map_err(|_| {
@@ -428,7 +449,8 @@ impl TestUser {
                                                 "Daemon Half of User disconnected.",
                                             )
                                         })
-                                        .is_ok();
+                                        .expect("error asking for suspend.");
+                                    let suspend_indication = matches!(suspend_rx.await, Ok(Ok(())));
 
                                     PutRequest {
                                         source_filename: "".into(),
@@ -459,15 +481,21 @@ impl TestUser {
                                                 ),
                                             )),
                                         ],
+                                        closure_requested: None,
+                                        priority: None,
                                     }
                                 }
                                 UserRequest::RemoteResume(resume_request) => {
-                                    let primitive = UserPrimitive::Resume(TransactionID(
-                                        resume_request.source_entity_id,
-                                        resume_request.transaction_sequence_number,
-                                    ));
+                                    let (resume_tx, resume_rx) = oneshot::channel();
+                                    let primitive = UserPrimitive::Resume(
+                                        TransactionID(
+                                            resume_request.source_entity_id,
+                                            resume_request.transaction_sequence_number,
+                                        ),
+                                        resume_tx,
+                                    );
 
-                                    let suspend_indication = auto_sender
+                                    auto_sender
                                         .send(primitive)
                                         .await
                                         .map_err(|_| {
@@ -476,7 +504,8 @@ impl TestUser {
                                                 "Daemon Half of User disconnected.",
                                             )
                                         })
-                                        .is_ok();
+                                        .expect("error asking for resume.");
+                                    let suspend_indication = matches!(resume_rx.await, Ok(Ok(())));
 
                                     PutRequest {
                                         source_filename: "".into(),
@@ -507,6 +536,8 @@ impl TestUser {
                                                 ),
                                             )),
                                         ],
+                                        closure_requested: None,
+                                        priority: None,
                                     }
                                 }
                             };
@@ -563,6 +594,8 @@ impl TestUser {
                                 transmission_mode: TransmissionMode::Unacknowledged,
                                 filestore_requests: vec![],
                                 message_to_user,
+                                closure_requested: None,
+                                priority: None,
                             };
                             // we should be able to connect to the socket we are running
                             // just fine. but we can ignore errors per
@@ -579,9 +612,6 @@ impl TestUser {
                                 });
                         }
                     }
-                    Indication::Report(report) => {
-                        auto_history.write().unwrap().insert(report.id, report);
-                    }
                     Indication::FileSegmentRecv(filesegment) => {
                         debug!(
                             "Transaction {} Received file data offset {} length {}",
@@ -617,11 +647,12 @@ impl TestUser {
             TestUserHalf {
                 internal_tx,
                 _indication_handle: indication_handle,
-                history,
+                history: history.clone(),
                 tokio_handle,
             },
             internal_rx,
             indication_tx,
+            history,
         )
     }
 }
@@ -661,7 +692,7 @@ impl TestUser {
 pub struct TestUserHalf {
     internal_tx: Sender<UserPrimitive>,
     _indication_handle: JoinHandle<()>,
-    history: Arc<RwLock<HashMap<TransactionID, Report>>>,
+    history: TransactionHistory,
     tokio_handle: tokio::runtime::Handle,
 }
 impl TestUserHalf {
@@ -677,21 +708,64 @@ impl TestUserHalf {
                     " 1 Daemon Half of User disconnected.",
                 )
             })?;
-            put_recv.await.map_err(|_| {
+            put_recv
+                .await
+                .map_err(|_| {
+                    IoError::new(
+                        ErrorKind::ConnectionReset,
+                        "Daemon Half of User disconnected.",
+                    )
+                })?
+                .map_err(IoError::other)
+        })
+    }
+
+    // this function is actually used in series_f1 but series_f2 and f3 generate an unused warning
+    // apparently related https://github.com/rust-lang/rust/issues/46379
+    #[allow(unused)]
+    pub fn cancel(&self, transaction: TransactionID) -> Result<(), IoError> {
+        self.tokio_handle.block_on(async {
+            let (cancel_tx, cancel_rx) = oneshot::channel();
+            let primitive = UserPrimitive::Cancel(transaction, cancel_tx);
+            self.internal_tx.send(primitive).await.map_err(|_| {
                 IoError::new(
                     ErrorKind::ConnectionReset,
                     "Daemon Half of User disconnected.",
                 )
-            })
+            })?;
+            cancel_rx
+                .await
+                .map_err(|_| {
+                    IoError::new(
+                        ErrorKind::ConnectionReset,
+                        "Daemon Half of User disconnected.",
+                    )
+                })?
+                .map_err(IoError::other)
         })
     }
 
-    // this function is actually used in series_f1 but series_f2 and f3 generate an unused warning
-    // apparently related https://github.com/rust-lang/rust/issues/46379
     #[allow(unused)]
-    pub fn cancel(&self, transaction: TransactionID) -> Result<(), IoError> {
+    pub fn set_contact_plan(
+        &self,
+        entity: EntityID,
+        windows: Vec<ContactWindow>,
+    ) -> Result<(), IoError> {
+        self.send_primitive(UserPrimitive::SetContactPlan(entity, windows))
+    }
+
+    #[allow(unused)]
+    pub fn set_link_state(&self, entity: EntityID, state: LinkState) -> Result<(), IoError> {
+        self.send_primitive(UserPrimitive::SetLinkState(entity, state))
+    }
+
+    #[allow(unused)]
+    pub fn clear_link_state(&self, entity: EntityID) -> Result<(), IoError> {
+        self.send_primitive(UserPrimitive::ClearLinkState(entity))
+    }
+
+    fn send_primitive(&self, primitive: UserPrimitive) -> Result<(), IoError> {
         self.tokio_handle.block_on(async {
-            let primitive = UserPrimitive::Cancel(transaction);
             self.internal_tx.send(primitive).await.map_err(|_| {
                 IoError::new(
                     ErrorKind::ConnectionReset,
@@ -701,6 +775,38 @@ impl TestUserHalf {
         })
     }
 
+    #[allow(unused)]
+    pub fn shutdown(&self, mode: ShutdownMode) -> Result<Vec<Report>, IoError> {
+        self.tokio_handle.block_on(async {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            self.internal_tx
+                .send(UserPrimitive::Shutdown(mode, reply_tx))
+                .await
+                .map_err(|_| {
+                    IoError::new(
+                        ErrorKind::ConnectionReset,
+                        "Daemon Half of User disconnected.",
+                    )
+                })?;
+            reply_rx
+                .await
+                .map_err(|_| {
+                    IoError::new(
+                        ErrorKind::ConnectionReset,
+                        "Daemon Half of User disconnected.",
+                    )
+                })?
+                .map_err(IoError::other)
+        })
+    }
+
+    #[allow(unused)]
+    pub fn history(&self, query: &HistoryQuery) -> Vec<TransactionRecord> {
+        self.tokio_handle
+            .block_on(self.history.query(query))
+            .expect("unable to query the transaction history.")
+    }
+
     #[allow(unused)]
     pub fn report(&self, transaction: TransactionID) -> Result<Option<Report>, IoError> {
         self.tokio_handle.block_on(async {
@@ -713,12 +819,15 @@ impl TestUserHalf {
                     format!("Daemon Half of User disconnected on send: {err}"),
                 )
             })?;
-            let response = match report_rx.await {
-                Ok(report) => Some(report),
-                // if the channel disconnects because the transaction is finished then just get from history.
-                Err(_) => self.history.read().unwrap().get(&transaction).cloned(),
-            };
-            Ok(response)
+            // finished transactions are reported from the history by the daemon.
+            match report_rx.await {
+                Ok(Ok(report)) => Ok(Some(report)),
+                Ok(Err(_)) => Ok(None),
+                Err(_) => Err(IoError::new(
+                    ErrorKind::ConnectionReset,
+                    "Daemon Half of User disconnected.",
+                )),
+            }
         })
     }
 }
@@ -751,11 +860,12 @@ pub(crate) async fn create_daemons<T: FileStore + Sync + Send + 'static>(
     nak_procedure: NakProcedure,
 ) -> DaemonType {
     let config = EntityConfig {
-        fault_handler_override: HashMap::from([(
+        fault_handler_override: BTreeMap::from([(
             Condition::PositiveLimitReached,
             FaultHandlerAction::Abandon,
         )]),
         file_size_segment: 1024,
+        max_pdu_len: None,
         default_transaction_max_count: 2,
         inactivity_timeout: timeouts[0].unwrap_or(1),
         ack_timeout: timeouts[1].unwrap_or(1),
@@ -764,6 +874,13 @@ pub(crate) async fn create_daemons<T: FileStore + Sync + Send + 'static>(
         closure_requested: false,
         checksum_type: ChecksumType::Modular,
         nak_procedure,
+        link_failure_action: FaultHandlerAction::Ignore,
+        rate_limit: None,
+        transaction_rate_limit: None,
+        scheduling: Scheduling::Priority,
+        default_priority: Priority::default(),
+        protocol_version: VersionNegotiation::Auto,
+        id_width: None,
     };
 
     let remote_config = HashMap::from([
@@ -774,18 +891,30 @@ pub(crate) async fn create_daemons<T: FileStore + Sync + Send + 'static>(
     let local_filestore = filestore.clone();
 
     let local_user = TestUser::new(local_filestore.clone());
-    let (local_userhalf, local_daemonhalf, indication_tx) = local_user.split();
-
-    let mut local_daemon = Daemon::new(
-        EntityID::from(0_u16),
-        TransactionSeqNum::from(0_u16),
-        local_transport_map,
-        local_filestore,
-        remote_config.clone(),
-        config.clone(),
-        local_daemonhalf,
-        indication_tx,
-    );
+    let (local_userhalf, local_daemonhalf, indication_tx, local_history) = local_user.split();
+
+    // kept with the filestore, so it is removed along with it.
+    let suspended = TempDir::new_in(local_filestore.get_native_path(""))
+        .expect("Unable to create suspend directory.")
+        .into_path();
+    let mut builder = DaemonBuilder::new(EntityID::from(0_u16), local_filestore)
+        .sequence_num(TransactionSeqNum::from(0_u16))
+        .default_config(config.clone())
+        .user(local_daemonhalf, indication_tx)
+        .history(local_history)
+        .suspend_store(
+            SuspendStore::open(Utf8PathBuf::try_from(suspended).expect("Non UTF-8 path."))
+                .expect("Unable to open suspend store."),
+        );
+    for (entity, entity_config) in remote_config.iter() {
+        builder = builder.entity_config(*entity, entity_config.clone());
+    }
+    for (entities, transport) in local_transport_map {
+        builder = builder.transport(entities, transport);
+    }
+    let mut local_daemon = builder
+        .build()
+        .expect("Invalid local daemon configuration.");
 
     let local_handle = tokio::task::spawn(async move {
         local_daemon
@@ -798,18 +927,23 @@ pub(crate) async fn create_daemons<T: FileStore + Sync + Send + 'static>(
 
     let remote_filestore = filestore;
     let remote_user = TestUser::new(remote_filestore.clone());
-    let (remote_userhalf, remote_daemonhalf, remote_indication_tx) = remote_user.split();
-
-    let mut remote_daemon = Daemon::new(
-        EntityID::from(1_u16),
-        TransactionSeqNum::from(0_u16),
-        remote_transport_map,
-        remote_filestore,
-        remote_config,
-        config,
-        remote_daemonhalf,
-        remote_indication_tx,
-    );
+    let (remote_userhalf, remote_daemonhalf, remote_indication_tx, remote_history) =
+        remote_user.split();
+
+    let mut builder = DaemonBuilder::new(EntityID::from(1_u16), remote_filestore)
+        .sequence_num(TransactionSeqNum::from(0_u16))
+        .default_config(config)
+        .user(remote_daemonhalf, remote_indication_tx)
+        .history(remote_history);
+    for (entity, entity_config) in remote_config.iter() {
+        builder = builder.entity_config(*entity, entity_config.clone());
+    }
+    for (entities, transport) in remote_transport_map {
+        builder = builder.transport(entities, transport);
+    }
+    let mut remote_daemon = builder
+        .build()
+        .expect("Invalid remote daemon configuration.");
 
     let remote_handle = tokio::task::spawn(async move {
         remote_daemon
@@ -1152,22 +1286,20 @@ impl PDUTransport for LossyTransport {
                 match &pdu.payload {
                     PDUPayload::Directive(operation) => {
                         match operation.get_directive() {
-                            PDUDirective::Metadata => {
-                                if self.every_counter[0] == 0 {
+                            PDUDirective::Metadata
+                                if self.every_counter[0] == 0 => {
                                     self.every_counter[0] += 1;
                                     debug!("skipping {pdu:?}");
                                     return Ok(());
                                 }
-                            }
-                            PDUDirective::Nak => {
+                            PDUDirective::Nak
                                 // naks are sent after EoF and and AckEoF
                                 // only thing after this is to send the Fin
-                                if self.every_counter[1] == 0 {
+                                if self.every_counter[1] == 0 => {
                                     self.every_counter[1] += 1;
                                     debug!("skipping {pdu:?}");
                                     return Ok(());
                                 }
-                            }
                             PDUDirective::Ack => {
                                 if let PDUPayload::Directive(Operations::Ack(
                                     PositiveAcknowledgePDU {
@@ -1187,14 +1319,13 @@ impl PDUTransport for LossyTransport {
                                     return Ok(());
                                 }
                             }
-                            PDUDirective::Finished => {
+                            PDUDirective::Finished
                                 // increment counter but still don't send it
-                                if self.every_counter[3] == 0 {
+                                if self.every_counter[3] == 0 => {
                                     self.every_counter[3] += 1;
                                     debug!("skipping {pdu:?}");
                                     return Ok(());
                                 }
-                            }
                             // others can be sent no problem or are not being used in this test
                             _ => {}
                         }
@@ -9,8 +9,9 @@ use cfdp_core::{
     pdu::{
         Condition, DeliveryCode, DirectoryListingRequest, EntityID, FileStatusCode,
         FileStoreAction, FileStoreRequest, MessageToUser, OriginatingTransactionIDMessage,
-        PDUEncode, ProxyOperation, ProxyPutRequest, ProxyPutResponse, RemoteResumeRequest,
-        TransactionSeqNum, TransmissionMode, UserOperation, UserRequest, UserResponse,
+        PDUEncode, ProxyClosureRequest, ProxyOperation, ProxyPutRequest, ProxyPutResponse,
+        RemoteResumeRequest, TransactionSeqNum, TransmissionMode, UserOperation, UserRequest,
+        UserResponse,
     },
     transaction::TransactionID,
 };
@@ -20,7 +21,7 @@ use rstest::rstest;
 #[test]
 fn categorize_user_message() {
     let origin_id = TransactionID(EntityID::from(55_u16), TransactionSeqNum::from(12_u16));
-    let proxy_ops = vec![
+    let proxy_ops = [
         ProxyOperation::ProxyFileStoreRequest(FileStoreRequest {
             action_code: FileStoreAction::CreateDirectory,
             first_filename: "/tmp".into(),
@@ -40,6 +41,9 @@ fn categorize_user_message() {
             message_text: "help".as_bytes().to_vec(),
         }),
         ProxyOperation::ProxyTransmissionMode(TransmissionMode::Acknowledged),
+        ProxyOperation::ProxyClosureRequest(ProxyClosureRequest {
+            closure_requested: true,
+        }),
     ];
 
     let put_requests = vec![PutRequest {
@@ -70,6 +74,8 @@ fn categorize_user_message() {
                 },
             )),
         ],
+        closure_requested: Some(true),
+        priority: None,
     }];
 
     let requests = vec![
@@ -199,6 +205,8 @@ fn proxy_req(#[values(true, false)] use_mode: bool) {
                 .encode(),
             },
         ],
+        closure_requested: None,
+        priority: None,
     };
     assert_eq!(1, recovered.len());
     assert_eq!(expected, recovered[0])
@@ -0,0 +1,124 @@
+use std::{
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use camino::Utf8PathBuf;
+use cfdp_core::{
+    daemon::{ContactWindow, LinkState, NakProcedure, PutRequest},
+    filestore::FileStore,
+    pdu::{EntityID, TransmissionMode},
+};
+use rstest::rstest;
+
+mod common;
+use common::{new_entities, static_assets, StaticAssets};
+
+#[rstest]
+#[timeout(Duration::from_secs(10))]
+// Test goal:
+//  - Hold a transfer while the link is forced down
+//  - Timers are frozen, so no inactivity fault is raised before the link returns
+// Configuration:
+//  - Acknowledged
+//  - File Size: Medium
+fn link_down_holds_transfer(static_assets: &StaticAssets) {
+    let (local_user, _remote_user, filestore, _local, _remote) = new_entities(
+        static_assets,
+        None,
+        None,
+        [Some(1), Some(1), Some(1)],
+        NakProcedure::Deferred(Duration::ZERO),
+    );
+
+    let out_file: Utf8PathBuf = "remote/medium_link_down.txt".into();
+    let path_to_out = filestore.get_native_path(&out_file);
+
+    local_user
+        .set_link_state(EntityID::from(1_u16), LinkState::Down)
+        .expect("unable to set link state.");
+
+    let id = local_user
+        .put(PutRequest {
+            source_filename: "local/medium.txt".into(),
+            destination_filename: out_file,
+            destination_entity_id: EntityID::from(1_u16),
+            transmission_mode: TransmissionMode::Acknowledged,
+            filestore_requests: vec![],
+            message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
+        })
+        .expect("unable to send put request.");
+
+    // long enough for the inactivity limit to be reached if the timers were running
+    thread::sleep(Duration::from_secs(3));
+    assert!(!path_to_out.exists());
+
+    local_user
+        .clear_link_state(EntityID::from(1_u16))
+        .expect("unable to clear link state.");
+
+    while !path_to_out.exists() {
+        thread::sleep(Duration::from_millis(100))
+    }
+    assert!(path_to_out.exists());
+
+    let report = local_user
+        .report(id)
+        .expect("Unable to send report request.")
+        .expect("No report received.");
+    assert_eq!(cfdp_core::pdu::Condition::NoError, report.condition);
+}
+
+#[rstest]
+#[timeout(Duration::from_secs(10))]
+// Test goal:
+//  - Wait for the next contact window before transmitting
+// Configuration:
+//  - Unacknowledged
+//  - File Size: Small
+fn transfer_waits_for_window(static_assets: &StaticAssets) {
+    let (local_user, _remote_user, filestore, _local, _remote) = new_entities(
+        static_assets,
+        None,
+        None,
+        [None; 3],
+        NakProcedure::Deferred(Duration::ZERO),
+    );
+
+    let out_file: Utf8PathBuf = "remote/small_contact_window.txt".into();
+    let path_to_out = filestore.get_native_path(&out_file);
+
+    let start = SystemTime::now() + Duration::from_secs(2);
+    local_user
+        .set_contact_plan(
+            EntityID::from(1_u16),
+            vec![ContactWindow {
+                start,
+                end: start + Duration::from_secs(60),
+            }],
+        )
+        .expect("unable to set contact plan.");
+
+    local_user
+        .put(PutRequest {
+            source_filename: "local/small.txt".into(),
+            destination_filename: out_file,
+            destination_entity_id: EntityID::from(1_u16),
+            transmission_mode: TransmissionMode::Unacknowledged,
+            filestore_requests: vec![],
+            message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
+        })
+        .expect("unable to send put request.");
+
+    thread::sleep(Duration::from_secs(1));
+    assert!(!path_to_out.exists());
+
+    while !path_to_out.exists() {
+        thread::sleep(Duration::from_millis(100))
+    }
+    assert!(SystemTime::now() >= start);
+}
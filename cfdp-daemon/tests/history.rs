@@ -0,0 +1,84 @@
+use std::{thread, time::Duration};
+
+use cfdp_core::{
+    daemon::{NakProcedure, PutRequest},
+    filestore::FileStore,
+    pdu::{Condition, DeliveryCode, EntityID, FileStatusCode, TransmissionMode},
+    transaction::TransactionState,
+};
+use cfdp_daemon::history::{HistoryQuery, TransactionRecord};
+use rstest::rstest;
+
+mod common;
+use common::{new_entities, static_assets, StaticAssets, TestUserHalf};
+
+fn wait_for_record(user: &TestUserHalf, query: &HistoryQuery) -> TransactionRecord {
+    loop {
+        if let Some(record) = user.history(query).pop() {
+            return record;
+        }
+        thread::sleep(Duration::from_millis(100))
+    }
+}
+
+#[rstest]
+#[timeout(Duration::from_secs(10))]
+// Test goal:
+//  - Both entities keep a record of a finished transaction
+//  - The final report is still available once the transaction is gone
+// Configuration:
+//  - Acknowledged
+//  - File Size: Small
+fn records_finished_transaction(static_assets: &StaticAssets) {
+    let (local_user, remote_user, filestore, _local, _remote) = new_entities(
+        static_assets,
+        None,
+        None,
+        [None; 3],
+        NakProcedure::Deferred(Duration::ZERO),
+    );
+
+    let id = local_user
+        .put(PutRequest {
+            source_filename: "local/small.txt".into(),
+            destination_filename: "remote/small_history.txt".into(),
+            destination_entity_id: EntityID::from(1_u16),
+            transmission_mode: TransmissionMode::Acknowledged,
+            filestore_requests: vec![],
+            message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
+        })
+        .expect("unable to send put request.");
+    let file_size = filestore
+        .get_size("local/small.txt")
+        .expect("unable to read source file size.");
+
+    let sent = wait_for_record(&local_user, &HistoryQuery::new().transaction(id));
+    assert_eq!(EntityID::from(1_u16), sent.remote_entity_id);
+    assert_eq!("local/small.txt", sent.source_filename);
+    assert_eq!("remote/small_history.txt", sent.destination_filename);
+    assert_eq!(file_size, sent.file_size);
+    assert_eq!(Condition::NoError, sent.condition());
+    assert_eq!(DeliveryCode::Complete, sent.delivery_code);
+    assert_eq!(FileStatusCode::Retained, sent.file_status);
+    assert!(sent.finished >= sent.started);
+
+    let received = wait_for_record(
+        &remote_user,
+        &HistoryQuery::new().entity(EntityID::from(0_u16)),
+    );
+    assert_eq!(id, received.id());
+    assert_eq!("remote/small_history.txt", received.destination_filename);
+    assert_eq!(file_size, received.file_size);
+    assert_eq!(DeliveryCode::Complete, received.delivery_code);
+
+    let report = local_user
+        .report(id)
+        .expect("unable to request report.")
+        .expect("no report of the finished transaction.");
+    assert_eq!(TransactionState::Terminated, report.state);
+    assert!(local_user
+        .history(&HistoryQuery::new().entity(EntityID::from(7_u16)))
+        .is_empty());
+}
@@ -0,0 +1,79 @@
+use std::{io::Error as IoError, thread, time::Duration};
+
+use cfdp_core::{
+    daemon::{NakProcedure, PrimitiveError, PutRequest},
+    pdu::{EntityID, TransactionSeqNum, TransmissionMode},
+    transaction::TransactionID,
+};
+use cfdp_daemon::history::HistoryQuery;
+use rstest::rstest;
+
+mod common;
+use common::{new_entities, static_assets, StaticAssets};
+
+fn refusal(err: IoError) -> PrimitiveError {
+    err.get_ref()
+        .and_then(|inner| inner.downcast_ref::<PrimitiveError>())
+        .cloned()
+        .expect("not refused by the daemon.")
+}
+
+fn request(source: &str, destination_entity_id: u16) -> PutRequest {
+    PutRequest {
+        source_filename: source.into(),
+        destination_filename: "remote/small_primitives.txt".into(),
+        destination_entity_id: EntityID::from(destination_entity_id),
+        transmission_mode: TransmissionMode::Unacknowledged,
+        filestore_requests: vec![],
+        message_to_user: vec![],
+        closure_requested: None,
+        priority: None,
+    }
+}
+
+#[rstest]
+#[timeout(Duration::from_secs(10))]
+// Test goal:
+//  - Every refused primitive is answered with the reason
+// Configuration:
+//  - Unacknowledged
+//  - File Size: Small
+fn refusals(static_assets: &StaticAssets) {
+    let (local_user, _remote_user, _filestore, _local, _remote) = new_entities(
+        static_assets,
+        None,
+        None,
+        [None; 3],
+        NakProcedure::Deferred(Duration::ZERO),
+    );
+
+    assert_eq!(
+        PrimitiveError::NoRoute(EntityID::from(7_u16)),
+        refusal(local_user.put(request("local/small.txt", 7)).unwrap_err())
+    );
+    assert!(matches!(
+        refusal(local_user.put(request("local/missing.txt", 1)).unwrap_err()),
+        PrimitiveError::FileStore(_)
+    ));
+
+    let unknown = TransactionID(EntityID::from(0_u16), TransactionSeqNum::from(99_u16));
+    assert_eq!(
+        PrimitiveError::UnknownTransaction(unknown),
+        refusal(local_user.cancel(unknown).unwrap_err())
+    );
+    assert!(local_user.report(unknown).unwrap().is_none());
+
+    let id = local_user
+        .put(request("local/small.txt", 1))
+        .expect("unable to send put request.");
+    while local_user
+        .history(&HistoryQuery::new().transaction(id))
+        .is_empty()
+    {
+        thread::sleep(Duration::from_millis(100))
+    }
+    assert_eq!(
+        PrimitiveError::Finished(id),
+        refusal(local_user.cancel(id).unwrap_err())
+    );
+}
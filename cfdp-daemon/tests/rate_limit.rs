@@ -0,0 +1,168 @@
+use std::{
+    collections::BTreeMap,
+    io::{Error as IoError, ErrorKind},
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use cfdp_core::{
+    daemon::{
+        EntityConfig, NakProcedure, Priority, PutRequest, RateLimit, Scheduling, ShutdownMode,
+        VersionNegotiation,
+    },
+    filestore::{ChecksumType, NativeFileStore},
+    pdu::{
+        CRCFlag, EntityID, FaultHandlerAction, Operations, PDUPayload, TransmissionMode,
+        VariableID, PDU,
+    },
+};
+use cfdp_daemon::{transport::PDUTransport, DaemonBuilder};
+use tempfile::TempDir;
+use tokio::{
+    sync::mpsc::{unbounded_channel, UnboundedSender},
+    time::Instant,
+};
+
+/// Records when each PDU was handed to the transport, nothing is ever received.
+struct TimedTransport(UnboundedSender<(Instant, PDU)>);
+#[async_trait]
+impl PDUTransport for TimedTransport {
+    async fn request(&mut self, _destination: VariableID, pdu: PDU) -> Result<(), IoError> {
+        self.0
+            .send((Instant::now(), pdu))
+            .map_err(|_| IoError::from(ErrorKind::ConnectionAborted))
+    }
+
+    async fn receive(&mut self) -> Result<PDU, IoError> {
+        std::future::pending::<()>().await;
+        Err(IoError::from(ErrorKind::NotConnected))
+    }
+}
+
+fn config(rate_limit: Option<RateLimit>) -> EntityConfig {
+    EntityConfig {
+        fault_handler_override: BTreeMap::new(),
+        file_size_segment: 1000,
+        max_pdu_len: None,
+        default_transaction_max_count: 2,
+        inactivity_timeout: 10,
+        ack_timeout: 10,
+        nak_timeout: 10,
+        crc_flag: CRCFlag::NotPresent,
+        closure_requested: false,
+        checksum_type: ChecksumType::Modular,
+        nak_procedure: NakProcedure::Deferred(Duration::ZERO),
+        link_failure_action: FaultHandlerAction::Ignore,
+        rate_limit,
+        transaction_rate_limit: None,
+        scheduling: Scheduling::Priority,
+        default_priority: Priority::default(),
+        protocol_version: VersionNegotiation::Auto,
+        id_width: None,
+    }
+}
+
+/// Send a 4000 byte file in 1000 byte segments and return when each PDU left the daemon.
+async fn transfer(rate_limit: Option<RateLimit>) -> Vec<(Instant, PDU)> {
+    let tempdir = TempDir::new().unwrap();
+    std::fs::write(tempdir.path().join("paced.txt"), vec![0x5a_u8; 4000]).unwrap();
+    let filestore = Arc::new(NativeFileStore::new(
+        tempdir.path().to_str().expect("temporary path is UTF-8."),
+    ));
+
+    let (pdu_tx, mut pdu_rx) = unbounded_channel();
+    let (mut daemon, handle) = DaemonBuilder::new(EntityID::from(0_u16), filestore)
+        .transport(
+            vec![EntityID::from(1_u16)],
+            Box::new(TimedTransport(pdu_tx)),
+        )
+        .default_config(config(rate_limit))
+        .build_with_handle()
+        .unwrap();
+    let running = tokio::task::spawn(async move { daemon.manage_transactions().await });
+
+    handle
+        .put(PutRequest {
+            source_filename: "paced.txt".into(),
+            destination_filename: "received.txt".into(),
+            destination_entity_id: EntityID::from(1_u16),
+            transmission_mode: TransmissionMode::Unacknowledged,
+            filestore_requests: vec![],
+            message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
+        })
+        .await
+        .unwrap()
+        .finished()
+        .await
+        .unwrap();
+    handle.shutdown(ShutdownMode::Abort).await.unwrap();
+    running.await.unwrap().unwrap();
+
+    let mut sent = vec![];
+    while let Ok(pdu) = pdu_rx.try_recv() {
+        sent.push(pdu);
+    }
+    sent
+}
+
+fn file_data_times(sent: &[(Instant, PDU)]) -> Vec<Instant> {
+    sent.iter()
+        .filter(|(_, pdu)| matches!(pdu.payload, PDUPayload::FileData(_)))
+        .map(|(time, _)| *time)
+        .collect()
+}
+
+fn eof_time(sent: &[(Instant, PDU)]) -> Instant {
+    sent.iter()
+        .find(|(_, pdu)| matches!(pdu.payload, PDUPayload::Directive(Operations::EoF(_))))
+        .map(|(time, _)| *time)
+        .expect("no EoF was sent.")
+}
+
+#[tokio::test(start_paused = true)]
+// Test goal:
+//  - A rate limit spreads file data PDUs out at the configured rate
+// Configuration:
+//  - Unacknowledged
+//  - 1000 bytes per second with a 1000 byte burst
+async fn rate_limit_paces_transfer() {
+    let sent = transfer(Some(RateLimit {
+        bytes_per_second: 1000,
+        burst: 1000,
+    }))
+    .await;
+    let start = sent.first().expect("nothing was sent.").0;
+
+    let file_data = file_data_times(&sent);
+    assert_eq!(4, file_data.len());
+    // the burst covers the Metadata and the first segment,
+    // every following segment waits the second it takes to repay its own length.
+    for pair in file_data[1..].windows(2) {
+        let gap = pair[1] - pair[0];
+        assert!(
+            gap >= Duration::from_secs(1) && gap < Duration::from_millis(1100),
+            "segments were {gap:?} apart."
+        );
+    }
+    let elapsed = eof_time(&sent) - start;
+    assert!(
+        elapsed >= Duration::from_secs(3) && elapsed < Duration::from_secs(4),
+        "transfer took {elapsed:?}."
+    );
+}
+
+#[tokio::test(start_paused = true)]
+// Test goal:
+//  - Without a rate limit the whole file is sent at once
+// Configuration:
+//  - Unacknowledged
+async fn unlimited_transfer() {
+    let sent = transfer(None).await;
+    let start = sent.first().expect("nothing was sent.").0;
+
+    assert_eq!(4, file_data_times(&sent).len());
+    assert_eq!(Duration::ZERO, eof_time(&sent) - start);
+}
@@ -41,6 +41,8 @@ fn f1s01(get_filestore: &UsersAndFilestore) {
             transmission_mode: TransmissionMode::Unacknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -73,6 +75,8 @@ fn f1s02(get_filestore: &UsersAndFilestore) {
             transmission_mode: TransmissionMode::Unacknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -105,6 +109,8 @@ fn f1s03(get_filestore: &UsersAndFilestore) {
             transmission_mode: TransmissionMode::Acknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -150,6 +156,8 @@ fn f1s04(fixture_f1s04: &'static EntityConstructorReturn) {
             transmission_mode: TransmissionMode::Acknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -196,6 +204,8 @@ fn f1s05(fixture_f1s05: &'static EntityConstructorReturn) {
             transmission_mode: TransmissionMode::Acknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -243,6 +253,8 @@ fn f1s06(fixture_f1s06: &'static EntityConstructorReturn) {
             transmission_mode: TransmissionMode::Acknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -291,6 +303,8 @@ fn f1s07(get_filestore: &UsersAndFilestore) {
                     ProxyOperation::ProxyTransmissionMode(TransmissionMode::Acknowledged),
                 )),
             ],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
     while !path_interim.exists() {
@@ -332,6 +346,8 @@ fn f1s07(get_filestore: &UsersAndFilestore) {
                     )),
                 )),
             ],
+            closure_requested: None,
+            priority: None,
         })
         .expect("Unable to send put request.");
 
@@ -364,6 +380,8 @@ fn f1s08(get_filestore: &UsersAndFilestore) {
             transmission_mode: TransmissionMode::Acknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -412,6 +430,8 @@ fn f1s09(get_filestore: &UsersAndFilestore) {
             transmission_mode: TransmissionMode::Acknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
     while remote_user
@@ -464,6 +484,8 @@ fn f1s10(get_filestore: &UsersAndFilestore) {
             transmission_mode: TransmissionMode::Unacknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
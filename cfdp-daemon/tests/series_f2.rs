@@ -49,6 +49,8 @@ fn f2s01(fixture_f2s01: &'static EntityConstructorReturn) {
             transmission_mode: TransmissionMode::Acknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -95,6 +97,8 @@ fn f2s02(fixture_f2s02: &'static EntityConstructorReturn) {
             transmission_mode: TransmissionMode::Acknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -141,6 +145,8 @@ fn f2s03(fixture_f2s03: &'static EntityConstructorReturn) {
             transmission_mode: TransmissionMode::Acknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -187,6 +193,8 @@ fn f2s04(fixture_f2s04: &'static EntityConstructorReturn) {
             transmission_mode: TransmissionMode::Acknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -233,6 +241,8 @@ fn f2s05(fixture_f2s05: &'static EntityConstructorReturn) {
             transmission_mode: TransmissionMode::Acknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -280,6 +290,8 @@ fn f2s06(fixture_f2s06: &'static EntityConstructorReturn) {
             transmission_mode: TransmissionMode::Acknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -329,6 +341,8 @@ fn f2s07(fixture_f2s07: &'static EntityConstructorReturn) {
             transmission_mode: TransmissionMode::Acknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -390,6 +404,8 @@ fn f2s08(fixture_f2s08: &'static EntityConstructorReturn) {
             transmission_mode: TransmissionMode::Acknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -448,6 +464,8 @@ fn f2s09(fixture_f2s09: &'static EntityConstructorReturn) {
             transmission_mode: TransmissionMode::Acknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -507,6 +525,8 @@ fn f2s10(fixture_f2s10: &'static EntityConstructorReturn) {
             transmission_mode: TransmissionMode::Acknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -50,6 +50,8 @@ fn f3s01(get_filestore: &UsersAndFilestore) {
                     ProxyOperation::ProxyTransmissionMode(TransmissionMode::Acknowledged),
                 )),
             ],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -86,6 +88,8 @@ fn f3s02(get_filestore: &UsersAndFilestore) {
                 second_filename: "".into(),
             }],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -122,6 +126,8 @@ fn f3s03(get_filestore: &UsersAndFilestore) {
                 second_filename: "".into(),
             }],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -142,6 +148,8 @@ fn f3s03(get_filestore: &UsersAndFilestore) {
                 second_filename: "".into(),
             }],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
     while local_user
@@ -196,6 +204,8 @@ fn f3s04(get_filestore: &UsersAndFilestore) {
                 second_filename: "".into(),
             }],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -216,6 +226,8 @@ fn f3s04(get_filestore: &UsersAndFilestore) {
                 second_filename: new_file,
             }],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -282,6 +294,8 @@ fn f3s05(get_filestore: &UsersAndFilestore) {
                 },
             ],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -348,6 +362,8 @@ fn f3s06(get_filestore: &UsersAndFilestore) {
             transmission_mode: TransmissionMode::Acknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -368,6 +384,8 @@ fn f3s06(get_filestore: &UsersAndFilestore) {
                 second_filename: new_file.clone(),
             }],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -435,6 +453,8 @@ fn f3s07(get_filestore: &UsersAndFilestore) {
                 second_filename: "".into(),
             }],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -474,6 +494,8 @@ fn f3s08(get_filestore: &UsersAndFilestore) {
                 second_filename: "".into(),
             }],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -496,6 +518,8 @@ fn f3s08(get_filestore: &UsersAndFilestore) {
                 second_filename: "".into(),
             }],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -530,6 +554,8 @@ fn f3s09(get_filestore: &UsersAndFilestore) {
             transmission_mode: TransmissionMode::Acknowledged,
             filestore_requests: vec![],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -552,6 +578,8 @@ fn f3s09(get_filestore: &UsersAndFilestore) {
                 second_filename: "".into(),
             }],
             message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -591,6 +619,8 @@ fn f3s10(get_filestore: &UsersAndFilestore) {
                     directory_filename: "/local/remote.listing".into(),
                 }),
             ))],
+            closure_requested: None,
+            priority: None,
         })
         .expect("unable to send put request.");
 
@@ -0,0 +1,145 @@
+use std::{
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use camino::Utf8PathBuf;
+use cfdp_core::{
+    daemon::{ContactWindow, LinkState, NakProcedure, PutRequest, ShutdownMode},
+    filestore::FileStore,
+    pdu::{Condition, EntityID, TransmissionMode},
+    transaction::TransactionState,
+};
+use rstest::rstest;
+
+mod common;
+use common::{new_entities, static_assets, StaticAssets};
+
+#[rstest]
+#[timeout(Duration::from_secs(10))]
+// Test goal:
+//  - Drain lets an active transfer complete before the daemon stops
+//  - The transfer is held until a contact window opens so it is still active at shutdown
+// Configuration:
+//  - Unacknowledged
+//  - File Size: Small
+fn drain_completes_transfer(static_assets: &StaticAssets) {
+    let (local_user, _remote_user, filestore, _local, _remote) = new_entities(
+        static_assets,
+        None,
+        None,
+        [None; 3],
+        NakProcedure::Deferred(Duration::ZERO),
+    );
+
+    let out_file: Utf8PathBuf = "remote/small_drain.txt".into();
+    let path_to_out = filestore.get_native_path(&out_file);
+
+    let start = SystemTime::now() + Duration::from_secs(1);
+    local_user
+        .set_contact_plan(
+            EntityID::from(1_u16),
+            vec![ContactWindow {
+                start,
+                end: start + Duration::from_secs(60),
+            }],
+        )
+        .expect("unable to set contact plan.");
+
+    let id = local_user
+        .put(PutRequest {
+            source_filename: "local/small.txt".into(),
+            destination_filename: out_file,
+            destination_entity_id: EntityID::from(1_u16),
+            transmission_mode: TransmissionMode::Unacknowledged,
+            filestore_requests: vec![],
+            message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
+        })
+        .expect("unable to send put request.");
+
+    let reports = local_user
+        .shutdown(ShutdownMode::Drain(Duration::from_secs(5)))
+        .expect("unable to shutdown daemon.");
+
+    assert_eq!(1, reports.len());
+    assert_eq!(id, reports[0].id);
+    assert_eq!(TransactionState::Terminated, reports[0].state);
+    assert_eq!(Condition::NoError, reports[0].condition);
+
+    // the receiving entity is still running and finishes writing the file.
+    while !path_to_out.exists() {
+        thread::sleep(Duration::from_millis(100))
+    }
+}
+
+#[rstest]
+#[case::drain_deadline(
+    ShutdownMode::Drain(Duration::from_millis(500)),
+    TransactionState::Terminated
+)]
+#[case::suspend_all(ShutdownMode::SuspendAll, TransactionState::Suspended)]
+#[case::abort(ShutdownMode::Abort, TransactionState::Terminated)]
+#[timeout(Duration::from_secs(10))]
+// Test goal:
+//  - Stop a transaction which cannot make progress and report its final state
+// Configuration:
+//  - Acknowledged
+//  - File Size: Medium
+fn shutdown_stalled_transfer(
+    static_assets: &StaticAssets,
+    #[case] mode: ShutdownMode,
+    #[case] expected: TransactionState,
+) {
+    let (local_user, _remote_user, filestore, _local, _remote) = new_entities(
+        static_assets,
+        None,
+        None,
+        [None; 3],
+        NakProcedure::Deferred(Duration::ZERO),
+    );
+
+    let out_file: Utf8PathBuf = format!("remote/medium_shutdown_{mode:?}.txt").into();
+    let path_to_out = filestore.get_native_path(&out_file);
+
+    local_user
+        .set_link_state(EntityID::from(1_u16), LinkState::Down)
+        .expect("unable to set link state.");
+
+    let id = local_user
+        .put(PutRequest {
+            source_filename: "local/medium.txt".into(),
+            destination_filename: out_file,
+            destination_entity_id: EntityID::from(1_u16),
+            transmission_mode: TransmissionMode::Acknowledged,
+            filestore_requests: vec![],
+            message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
+        })
+        .expect("unable to send put request.");
+
+    let reports = local_user
+        .shutdown(mode)
+        .expect("unable to shutdown daemon.");
+
+    assert_eq!(1, reports.len());
+    assert_eq!(id, reports[0].id);
+    assert_eq!(expected, reports[0].state);
+    assert!(!path_to_out.exists());
+
+    // no new transactions are accepted after a shutdown.
+    assert!(local_user
+        .put(PutRequest {
+            source_filename: "local/small.txt".into(),
+            destination_filename: "remote/small_after_shutdown.txt".into(),
+            destination_entity_id: EntityID::from(1_u16),
+            transmission_mode: TransmissionMode::Unacknowledged,
+            filestore_requests: vec![],
+            message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
+        })
+        .is_err());
+}
@@ -0,0 +1,253 @@
+use std::{
+    collections::BTreeMap,
+    io::{Error as IoError, ErrorKind},
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use camino::Utf8PathBuf;
+use cfdp_core::{
+    daemon::{
+        EntityConfig, Indication, IndicationKind, NakProcedure, PrimitiveError, Priority,
+        PutRequest, Scheduling, ShutdownMode, VersionNegotiation,
+    },
+    filestore::{ChecksumType, NativeFileStore},
+    pdu::{CRCFlag, Condition, EntityID, FaultHandlerAction, TransmissionMode, VariableID, PDU},
+    transaction::TransactionState,
+};
+use cfdp_daemon::{
+    error::HandleError, indication::IndicationFilter, suspend::SuspendStore,
+    transport::PDUTransport, DaemonBuilder, DaemonHandle,
+};
+use tempfile::TempDir;
+use tokio::{
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    task::JoinHandle,
+};
+
+/// Connects two daemons, delivering at most `limit` PDUs to the other side.
+struct ChannelTransport {
+    outbox: UnboundedSender<PDU>,
+    inbox: UnboundedReceiver<PDU>,
+    limit: usize,
+}
+#[async_trait]
+impl PDUTransport for ChannelTransport {
+    async fn request(&mut self, _destination: VariableID, pdu: PDU) -> Result<(), IoError> {
+        if self.limit == 0 {
+            return Ok(());
+        }
+        self.limit -= 1;
+        self.outbox
+            .send(pdu)
+            .map_err(|_| IoError::from(ErrorKind::ConnectionAborted))
+    }
+
+    async fn receive(&mut self) -> Result<PDU, IoError> {
+        match self.inbox.recv().await {
+            Some(pdu) => Ok(pdu),
+            // the other daemon stopped first.
+            None => std::future::pending().await,
+        }
+    }
+}
+
+fn transports(limit: usize) -> (ChannelTransport, ChannelTransport) {
+    let (local_tx, remote_rx) = unbounded_channel();
+    let (remote_tx, local_rx) = unbounded_channel();
+    (
+        ChannelTransport {
+            outbox: local_tx,
+            inbox: local_rx,
+            limit,
+        },
+        ChannelTransport {
+            outbox: remote_tx,
+            inbox: remote_rx,
+            limit,
+        },
+    )
+}
+
+fn config() -> EntityConfig {
+    EntityConfig {
+        fault_handler_override: BTreeMap::from([(
+            Condition::PositiveLimitReached,
+            FaultHandlerAction::Abandon,
+        )]),
+        file_size_segment: 1000,
+        max_pdu_len: None,
+        default_transaction_max_count: 5,
+        inactivity_timeout: 30,
+        ack_timeout: 1,
+        nak_timeout: 1,
+        crc_flag: CRCFlag::NotPresent,
+        closure_requested: false,
+        checksum_type: ChecksumType::Modular,
+        nak_procedure: NakProcedure::Deferred(Duration::ZERO),
+        link_failure_action: FaultHandlerAction::Ignore,
+        rate_limit: None,
+        transaction_rate_limit: None,
+        scheduling: Scheduling::Priority,
+        default_priority: Priority::default(),
+        protocol_version: VersionNegotiation::Auto,
+        id_width: None,
+    }
+}
+
+fn start(
+    entity: u16,
+    remote: u16,
+    filestore: Arc<NativeFileStore>,
+    transport: ChannelTransport,
+    store: Option<SuspendStore>,
+) -> (DaemonHandle, JoinHandle<()>) {
+    let mut builder = DaemonBuilder::new(EntityID::from(entity), filestore)
+        .transport(vec![EntityID::from(remote)], Box::new(transport))
+        .default_config(config());
+    if let Some(store) = store {
+        builder = builder.suspend_store(store);
+    }
+    let (mut daemon, handle) = builder.build_with_handle().unwrap();
+    let running = tokio::task::spawn(async move { daemon.manage_transactions().await.unwrap() });
+    (handle, running)
+}
+
+#[tokio::test]
+// Test goal:
+//  - Transactions suspended at shutdown are restored by the next daemon and finish once resumed
+// Configuration:
+//  - Acknowledged
+//  - Both entities are shut down while the file is partially delivered
+async fn suspend_all_and_resume() {
+    let tempdir = TempDir::new().unwrap();
+    let root = Utf8PathBuf::try_from(tempdir.path().to_owned()).unwrap();
+    let input: Vec<u8> = (0..3500_u32).map(|byte| byte as u8).collect();
+    std::fs::write(root.join("local.txt"), &input).unwrap();
+    let filestore = Arc::new(NativeFileStore::new(&root));
+    let local_store = SuspendStore::open(root.join("suspended/local")).unwrap();
+    let remote_store = SuspendStore::open(root.join("suspended/remote")).unwrap();
+
+    // only the Metadata and the first file segment arrive.
+    let (local_transport, remote_transport) = transports(2);
+    let (local, local_running) = start(
+        0,
+        1,
+        filestore.clone(),
+        local_transport,
+        Some(local_store.clone()),
+    );
+    let (remote, remote_running) = start(
+        1,
+        0,
+        filestore.clone(),
+        remote_transport,
+        Some(remote_store.clone()),
+    );
+    let mut segments =
+        remote.subscribe(IndicationFilter::new().kind(IndicationKind::FileSegmentRecv));
+    let mut transaction = local
+        .put(PutRequest {
+            source_filename: "local.txt".into(),
+            destination_filename: "received.txt".into(),
+            destination_entity_id: EntityID::from(1_u16),
+            transmission_mode: TransmissionMode::Acknowledged,
+            filestore_requests: vec![],
+            message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
+        })
+        .await
+        .unwrap();
+    let id = transaction.id();
+    segments.recv().await.unwrap();
+    while !matches!(transaction.next().await, Ok(Some(Indication::EoFSent(_)))) {}
+
+    for (handle, running) in [(local, local_running), (remote, remote_running)] {
+        let reports = handle.shutdown(ShutdownMode::SuspendAll).await.unwrap();
+        assert_eq!(1, reports.len());
+        assert_eq!(id, reports[0].id);
+        assert_eq!(TransactionState::Suspended, reports[0].state);
+        running.await.unwrap();
+    }
+    assert!(!root.join("received.txt").exists());
+    // both transactions were saved rather than abandoned.
+    for store in [&local_store, &remote_store] {
+        assert!(store.dir().join(format!("{id}.json")).exists());
+    }
+
+    let (local_transport, remote_transport) = transports(usize::MAX);
+    let (local, local_running) = start(
+        0,
+        1,
+        filestore.clone(),
+        local_transport,
+        Some(local_store.clone()),
+    );
+    let (remote, remote_running) = start(
+        1,
+        0,
+        filestore.clone(),
+        remote_transport,
+        Some(remote_store.clone()),
+    );
+    for handle in [&local, &remote] {
+        assert_eq!(
+            TransactionState::Suspended,
+            handle.report(id).await.unwrap().state
+        );
+    }
+    let transaction = local.transaction(id);
+    remote.resume(id).await.unwrap();
+    local.resume(id).await.unwrap();
+    let finished = tokio::time::timeout(Duration::from_secs(10), transaction.finished())
+        .await
+        .expect("transaction did not finish.")
+        .unwrap();
+    assert_eq!(Condition::NoError, finished.report.condition);
+    assert_eq!(input, std::fs::read(root.join("received.txt")).unwrap());
+    for store in [&local_store, &remote_store] {
+        assert!(!store.dir().join(format!("{id}.json")).exists());
+    }
+
+    // the restored sequence number is not reused.
+    let next = local
+        .put(PutRequest {
+            source_filename: "".into(),
+            destination_filename: "".into(),
+            destination_entity_id: EntityID::from(1_u16),
+            transmission_mode: TransmissionMode::Unacknowledged,
+            filestore_requests: vec![],
+            message_to_user: vec![],
+            closure_requested: None,
+            priority: None,
+        })
+        .await
+        .unwrap();
+    assert_ne!(id, next.id());
+
+    for (handle, running) in [(local, local_running), (remote, remote_running)] {
+        handle.shutdown(ShutdownMode::Abort).await.unwrap();
+        running.await.unwrap();
+    }
+}
+
+#[tokio::test]
+// Test goal:
+//  - A suspending shutdown is refused when there is nowhere to save the transactions
+async fn suspend_all_without_store() {
+    let tempdir = TempDir::new().unwrap();
+    let filestore = Arc::new(NativeFileStore::new(
+        tempdir.path().to_str().expect("temporary path is UTF-8."),
+    ));
+    let (transport, _remote) = transports(0);
+    let (handle, running) = start(0, 1, filestore, transport, None);
+
+    assert!(matches!(
+        handle.shutdown(ShutdownMode::SuspendAll).await,
+        Err(HandleError::Refused(PrimitiveError::NoSuspendStore))
+    ));
+    handle.shutdown(ShutdownMode::Abort).await.unwrap();
+    running.await.unwrap();
+}